@@ -1,13 +1,25 @@
 use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
 use folo::criterion::{ComparativeAdapter, FoloAdapter};
+use folo::io::PinnedBuffer;
+use folo::mem::{DropPolicy, NumaPlacement, PinnedSlabChain};
+use folo::net::{RegisteredUdpSocket, UdpSocket};
 use std::{
-    cell::LazyCell,
+    cell::{LazyCell, RefCell, UnsafeCell},
     fs::File,
     io::Read,
+    net::{Ipv4Addr, SocketAddr},
     path::{Path, PathBuf},
+    thread::LocalKey,
 };
 
-criterion_group!(benches, file_io, scan_many_files);
+criterion_group!(
+    benches,
+    file_io,
+    read_many_tiny_files,
+    write_many_tiny_files,
+    scan_many_files,
+    udp_send
+);
 criterion_main!(benches);
 
 const FILE_SIZE: usize = 10 * 1024 * 1024 * 1024;
@@ -56,7 +68,118 @@ fn file_io(c: &mut Criterion) {
                             let file = folo::fs::read(FILE_PATH).await.unwrap();
                             assert_eq!(file.len(), FILE_SIZE);
                         })
-                        .await;
+                        .await
+                        .unwrap();
+                    })
+                }))
+            },
+            |prepared| prepared.run(),
+            BatchSize::PerIteration,
+        );
+    });
+
+    group.bench_function("folo_read_file_to_vec_parallel", |b| {
+        b.iter_batched(
+            || {
+                comparison_adapter.begin_folo(Box::new(|| {
+                    Box::pin(async move {
+                        folo::rt::spawn_on_any(|| async {
+                            let file = folo::fs::read_parallel(
+                                FILE_PATH,
+                                folo::fs::ParallelReadOptions::default(),
+                            )
+                            .await
+                            .unwrap();
+                            assert_eq!(file.len(), FILE_SIZE);
+                        })
+                        .await
+                        .unwrap();
+                    })
+                }))
+            },
+            |prepared| prepared.run(),
+            BatchSize::PerIteration,
+        );
+    });
+
+    group.bench_function("folo_read_file_chunks", |b| {
+        b.iter_batched(
+            || {
+                comparison_adapter.begin_folo(Box::new(|| {
+                    Box::pin(async move {
+                        folo::rt::spawn_on_any(|| async {
+                            let file = folo::fs::File::open(SMALL_FILE_PATH).await.unwrap();
+                            let len = consume_chunks(file.chunks(CHUNK_SIZE)).await;
+                            assert_eq!(len, SMALL_FILE_SIZE);
+                        })
+                        .await
+                        .unwrap();
+                    })
+                }))
+            },
+            |prepared| prepared.run(),
+            BatchSize::PerIteration,
+        );
+    });
+
+    group.bench_function("folo_read_file_chunks_with_readahead", |b| {
+        b.iter_batched(
+            || {
+                comparison_adapter.begin_folo(Box::new(|| {
+                    Box::pin(async move {
+                        folo::rt::spawn_on_any(|| async {
+                            let file = folo::fs::File::open(SMALL_FILE_PATH).await.unwrap();
+                            let len = consume_chunks(
+                                file.chunks_with_readahead(CHUNK_SIZE, READAHEAD_WINDOW),
+                            )
+                            .await;
+                            assert_eq!(len, SMALL_FILE_SIZE);
+                        })
+                        .await
+                        .unwrap();
+                    })
+                }))
+            },
+            |prepared| prepared.run(),
+            BatchSize::PerIteration,
+        );
+    });
+
+    // The first iteration on a worker thread allocates the buffer pool and later iterations reuse
+    // it, so the iterations are not all alike.
+    group.bench_function("folo_read_file_pooled_buffers", |b| {
+        b.iter_batched(
+            || {
+                comparison_adapter.begin_folo(Box::new(|| {
+                    Box::pin(async move {
+                        folo::rt::spawn_on_any(|| async {
+                            let len = read_with_pooled_buffers(SMALL_FILE_PATH).await;
+                            assert_eq!(len, SMALL_FILE_SIZE);
+                        })
+                        .await
+                        .unwrap();
+                    })
+                }))
+            },
+            |prepared| prepared.run(),
+            BatchSize::PerIteration,
+        );
+    });
+
+    // Same as above but every iteration starts with the buffer pool in the same warm state.
+    group.bench_function("folo_read_file_pooled_buffers_reset_pool", |b| {
+        b.iter_batched(
+            || {
+                comparison_adapter.begin_folo(Box::new(|| {
+                    Box::pin(async move {
+                        folo::rt::spawn_on_any(|| async {
+                            folo::io::PinnedBuffer::reset_pool(POOLED_READS_IN_FLIGHT);
+
+                            let len = read_with_pooled_buffers(SMALL_FILE_PATH).await;
+                            assert_eq!(len, SMALL_FILE_SIZE);
+                        })
+                        .await
+                        .unwrap();
                     })
                 }))
             },
@@ -136,8 +259,284 @@ fn file_io(c: &mut Criterion) {
     std::fs::remove_file(SMALL_FILE_PATH).unwrap();
 }
 
+const CHUNK_SIZE: usize = 1024 * 1024;
+const READAHEAD_WINDOW: usize = 8;
+
+// Processes each chunk the way a consumer would, so there is some work to overlap the reads with.
+async fn consume_chunks(
+    chunks: impl futures::Stream<Item = folo::io::Result<folo::io::PinnedBuffer>>,
+) -> usize {
+    let mut chunks = std::pin::pin!(chunks);
+    let mut len = 0;
+
+    while let Some(chunk) = futures::StreamExt::next(&mut chunks).await {
+        let chunk = chunk.unwrap();
+        std::hint::black_box(chunk.as_slice().iter().fold(0_u8, |acc, b| acc ^ b));
+        len += chunk.len();
+    }
+
+    len
+}
+
+const POOLED_READS_IN_FLIGHT: usize = 64;
+
+// Reads the file with many reads in flight at the same time, each into a buffer from the pool.
+async fn read_with_pooled_buffers(path: impl AsRef<Path>) -> usize {
+    use futures::StreamExt;
+
+    let file = folo::fs::File::open(path).await.unwrap();
+    let file_len = file.len().await.unwrap() as usize;
+    let buffer_len = folo::io::PinnedBuffer::from_pool().len();
+
+    futures::stream::iter((0..file_len).step_by(buffer_len))
+        .map(|offset| file.read_at(offset, folo::io::PinnedBuffer::from_pool()))
+        .buffer_unordered(POOLED_READS_IN_FLIGHT)
+        .fold(0, |len, buffer| async move { len + buffer.unwrap().len() })
+        .await
+}
+
+const TINY_FILE_SIZE: usize = 4 * 1024;
+const TINY_FILE_COUNT: usize = 5000;
+const TINY_FILES_DIR: &str = "testdata_tiny";
+
+// Compares reading with a size probe against reading into a growable buffer, for the case where
+// the per-file overhead dominates over the time spent reading data.
+fn read_many_tiny_files(c: &mut Criterion) {
+    let comparison_adapter =
+        ComparativeAdapter::new(|| tokio::runtime::Builder::new_multi_thread().build().unwrap());
+
+    std::fs::create_dir_all(TINY_FILES_DIR).unwrap();
+
+    let files = (0..TINY_FILE_COUNT)
+        .map(|i| {
+            let path = Path::new(TINY_FILES_DIR).join(format!("{i}.bin"));
+            std::fs::write(&path, vec![0xAB; TINY_FILE_SIZE]).unwrap();
+            path
+        })
+        .collect::<Box<[_]>>();
+
+    let mut group = c.benchmark_group("read_many_tiny_files");
+
+    group.bench_function("folo_read", |b| {
+        b.iter_batched(
+            || {
+                let files = files.clone();
+
+                comparison_adapter.begin_folo(Box::new(move || {
+                    Box::pin(async move {
+                        folo::rt::spawn_on_any(move || async move {
+                            for file in files.iter() {
+                                let file = folo::fs::read(file).await.unwrap();
+                                assert_eq!(file.len(), TINY_FILE_SIZE);
+                            }
+                        })
+                        .await
+                        .unwrap();
+                    })
+                }))
+            },
+            |prepared| prepared.run(),
+            BatchSize::PerIteration,
+        );
+    });
+
+    group.bench_function("folo_read_growable", |b| {
+        b.iter_batched(
+            || {
+                let files = files.clone();
+
+                comparison_adapter.begin_folo(Box::new(move || {
+                    Box::pin(async move {
+                        folo::rt::spawn_on_any(move || async move {
+                            for file in files.iter() {
+                                let file = folo::fs::read_growable(file).await.unwrap();
+                                assert_eq!(file.len(), TINY_FILE_SIZE);
+                            }
+                        })
+                        .await
+                        .unwrap();
+                    })
+                }))
+            },
+            |prepared| prepared.run(),
+            BatchSize::PerIteration,
+        );
+    });
+
+    group.finish();
+
+    std::fs::remove_dir_all(TINY_FILES_DIR).unwrap();
+}
+
+const WRITE_FILES_DIR: &str = "testdata_write";
+const WRITE_CONCURRENCY: usize = 32;
+
+// Compares writing many tiny files durably with batched flushes against flushing every file on its
+// own, for bulk export workloads where the per-file durability cost dominates.
+fn write_many_tiny_files(c: &mut Criterion) {
+    let comparison_adapter =
+        ComparativeAdapter::new(|| tokio::runtime::Builder::new_multi_thread().build().unwrap());
+
+    std::fs::create_dir_all(WRITE_FILES_DIR).unwrap();
+
+    let files = (0..TINY_FILE_COUNT)
+        .map(|i| Path::new(WRITE_FILES_DIR).join(format!("{i}.bin")))
+        .collect::<Box<[_]>>();
+
+    let mut group = c.benchmark_group("write_many_tiny_files");
+
+    // Durable writes of thousands of files are slow, so only repeat a few times.
+    group.sample_size(10);
+
+    group.bench_function("folo_write_many", |b| {
+        b.iter_batched(
+            || {
+                let files = files.clone();
+
+                comparison_adapter.begin_folo(Box::new(move || {
+                    Box::pin(async move {
+                        folo::rt::spawn_on_any(move || async move {
+                            let items = files.iter().map(|path| (path, vec![0xAB; TINY_FILE_SIZE]));
+
+                            let options = folo::fs::WriteManyOptions {
+                                concurrency: WRITE_CONCURRENCY,
+                                ..Default::default()
+                            };
+
+                            for result in folo::fs::write_many(items, options).await {
+                                assert_eq!(result.unwrap(), TINY_FILE_SIZE);
+                            }
+                        })
+                        .await
+                        .unwrap();
+                    })
+                }))
+            },
+            |prepared| prepared.run(),
+            BatchSize::PerIteration,
+        );
+    });
+
+    group.bench_function("folo_write_each_flushed", |b| {
+        b.iter_batched(
+            || {
+                let files = files.clone();
+
+                comparison_adapter.begin_folo(Box::new(move || {
+                    Box::pin(async move {
+                        folo::rt::spawn_on_any(move || async move {
+                            use futures::StreamExt;
+
+                            // Same amount of concurrency but every file is flushed on its own.
+                            let mut writes = futures::stream::iter(files.iter())
+                                .map(|path| {
+                                    folo::fs::write_many(
+                                        [(path, vec![0xAB; TINY_FILE_SIZE])],
+                                        folo::fs::WriteManyOptions::default(),
+                                    )
+                                })
+                                .buffer_unordered(WRITE_CONCURRENCY);
+
+                            while let Some(results) = writes.next().await {
+                                for result in results {
+                                    assert_eq!(result.unwrap(), TINY_FILE_SIZE);
+                                }
+                            }
+                        })
+                        .await
+                        .unwrap();
+                    })
+                }))
+            },
+            |prepared| prepared.run(),
+            BatchSize::PerIteration,
+        );
+    });
+
+    group.finish();
+
+    std::fs::remove_dir_all(WRITE_FILES_DIR).unwrap();
+}
+
 const SCAN_PATH: &str = "c:\\Source";
 
+const SCAN_BUFFER_SIZE: usize = 64 * 1024;
+const SCAN_BUFFERS_CACHED: usize = 256;
+
+thread_local! {
+    static SCAN_BUFFERS: folo::io::PinnedBufferPool =
+        folo::io::PinnedBufferPool::new(SCAN_BUFFER_SIZE, SCAN_BUFFERS_CACHED);
+}
+
+// Reads the file piece by piece into a buffer from the pool of the current thread, so scanning
+// many files reuses the same few buffers instead of allocating a fresh buffer per file.
+async fn read_with_scan_buffers(path: impl AsRef<Path>) -> folo::io::Result<usize> {
+    let file = folo::fs::File::open(path).await?;
+    let mut buffer = SCAN_BUFFERS.with(folo::io::PinnedBufferPool::acquire);
+    let mut offset = 0;
+
+    loop {
+        buffer = file.read_at(offset, buffer.use_all()).await?;
+
+        if buffer.is_empty() {
+            return Ok(offset);
+        }
+
+        offset += buffer.len();
+    }
+}
+
+// Each slab holds a single buffer, so with interleaved placement consecutive buffers come from
+// consecutive NUMA nodes instead of all buffers of a slab coming from the same node.
+type NumaScanBuffers = RefCell<PinnedSlabChain<UnsafeCell<[u8; SCAN_BUFFER_SIZE]>, 1>>;
+
+thread_local! {
+    static LOCAL_SCAN_BUFFERS: NumaScanBuffers = RefCell::new(
+        PinnedSlabChain::with_numa_placement(DropPolicy::MayDropItems, NumaPlacement::Local),
+    );
+
+    static INTERLEAVED_SCAN_BUFFERS: NumaScanBuffers = RefCell::new(
+        PinnedSlabChain::with_numa_placement(DropPolicy::MayDropItems, NumaPlacement::Interleaved),
+    );
+}
+
+// Same as `read_with_scan_buffers()` but the buffer comes from one of the pools above, so we can
+// compare the effect of the NUMA placement of the buffers on the scan. The difference is only
+// visible on a system with multiple NUMA nodes - on other systems, both placements are the same.
+async fn read_with_numa_scan_buffers(
+    path: impl AsRef<Path>,
+    buffers: &'static LocalKey<NumaScanBuffers>,
+) -> folo::io::Result<usize> {
+    let file = folo::fs::File::open(path).await?;
+
+    let (index, ptr) = buffers.with(|buffers| {
+        let mut buffers = buffers.borrow_mut();
+        let inserter = buffers.begin_insert();
+        (inserter.index(), inserter.insert_uninit() as *mut u8)
+    });
+
+    // SAFETY: The storage is pinned and we only release it below, after the last read completes.
+    let mut buffer = unsafe { folo::io::PinnedBuffer::from_ptr(ptr, SCAN_BUFFER_SIZE) };
+    let mut offset = 0;
+
+    let result = loop {
+        buffer = match file.read_at(offset, buffer.use_all()).await {
+            Ok(buffer) => buffer,
+            Err(e) => break Err(e),
+        };
+
+        if buffer.is_empty() {
+            break Ok(offset);
+        }
+
+        offset += buffer.len();
+    };
+
+    buffers.with(|buffers| buffers.borrow_mut().remove(index));
+
+    result
+}
+
 // We read in every file in the target directory, recursively, concurrently.
 fn scan_many_files(c: &mut Criterion) {
     let file_list = LazyCell::new(|| {
@@ -164,25 +563,67 @@ fn scan_many_files(c: &mut Criterion) {
             || file_list.clone(),
             |files| {
                 folo::rt::spawn_on_any(move || async move {
-                    let tasks = files
-                        .iter()
-                        .cloned()
-                        .map(|file| {
-                            folo::rt::spawn_on_any(|| async {
-                                let _ = folo::fs::read(file).await;
-                            })
+                    let tasks = files.iter().cloned().map(|file| {
+                        folo::rt::spawn_on_any(|| async {
+                            let _ = folo::fs::read(file).await;
                         })
-                        .collect::<Vec<_>>();
+                    });
 
-                    for task in tasks {
-                        task.await;
-                    }
+                    folo::rt::join_all(tasks).await;
+                })
+            },
+            criterion::BatchSize::LargeInput,
+        );
+    });
+
+    group.bench_function("folo_scan_many_files_pooled", |b| {
+        _ = &*file_list;
+
+        b.to_async(FoloAdapter::default()).iter_batched(
+            || file_list.clone(),
+            |files| {
+                folo::rt::spawn_on_any(move || async move {
+                    let tasks = files.iter().cloned().map(|file| {
+                        folo::rt::spawn_on_any(|| async {
+                            let _ = read_with_scan_buffers(file).await;
+                        })
+                    });
+
+                    folo::rt::join_all(tasks).await;
                 })
             },
             criterion::BatchSize::LargeInput,
         );
     });
 
+    for (name, buffers) in [
+        ("folo_scan_many_files_numa_local", &LOCAL_SCAN_BUFFERS),
+        (
+            "folo_scan_many_files_numa_interleaved",
+            &INTERLEAVED_SCAN_BUFFERS,
+        ),
+    ] {
+        group.bench_function(name, |b| {
+            _ = &*file_list;
+
+            b.to_async(FoloAdapter::default()).iter_batched(
+                || file_list.clone(),
+                |files| {
+                    folo::rt::spawn_on_any(move || async move {
+                        let tasks = files.iter().cloned().map(|file| {
+                            folo::rt::spawn_on_any(move || async move {
+                                let _ = read_with_numa_scan_buffers(file, buffers).await;
+                            })
+                        });
+
+                        folo::rt::join_all(tasks).await;
+                    })
+                },
+                criterion::BatchSize::LargeInput,
+            );
+        });
+    }
+
     group.bench_function("tokio_scan_many_files", |b| {
         b.to_async(&tokio).iter_batched(
             || file_list.clone(),
@@ -210,6 +651,93 @@ fn scan_many_files(c: &mut Criterion) {
     group.finish();
 }
 
+// Small datagrams make the per-operation overhead dominate, which is what registered buffers save.
+const DATAGRAM_SIZE: usize = 512;
+const DATAGRAMS_PER_ITERATION: usize = 16 * 1024;
+const SENDS_IN_FLIGHT: usize = 64;
+
+fn udp_send(c: &mut Criterion) {
+    // The datagrams go to a socket that nobody receives from. Once its receive buffer is full, the
+    // OS drops the datagrams, which does not affect the sender.
+    let sink = std::net::UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let sink_addr = sink.local_addr().unwrap();
+
+    let mut group = c.benchmark_group("udp_send");
+
+    // Buffers from the pool are covered by registrations that the pool makes once per slab, so
+    // this only pays for the registration in the first iteration on each worker thread.
+    group.bench_function("folo_rio_send_registered_buffers", |b| {
+        b.to_async(FoloAdapter::default()).iter(|| {
+            folo::rt::spawn_on_any(move || async move {
+                let socket = RegisteredUdpSocket::connect(sink_addr).await.unwrap();
+
+                for _ in 0..DATAGRAMS_PER_ITERATION / SENDS_IN_FLIGHT {
+                    let sends = (0..SENDS_IN_FLIGHT).map(|_| {
+                        let mut buffer = PinnedBuffer::from_pool();
+                        buffer.set_len(DATAGRAM_SIZE);
+                        socket.send(buffer)
+                    });
+
+                    for result in futures::future::join_all(sends).await {
+                        result.unwrap();
+                    }
+                }
+            })
+        });
+    });
+
+    // Buffers that do not come from the pool are registered for every send. We reuse the same
+    // storage for every round of sends, so the registration is the only difference to the above.
+    group.bench_function("folo_rio_send_unregistered_buffers", |b| {
+        b.to_async(FoloAdapter::default()).iter(|| {
+            folo::rt::spawn_on_any(move || async move {
+                let socket = RegisteredUdpSocket::connect(sink_addr).await.unwrap();
+
+                let mut storage = (0..SENDS_IN_FLIGHT)
+                    .map(|_| vec![0; DATAGRAM_SIZE].into_boxed_slice())
+                    .collect::<Vec<_>>();
+
+                for _ in 0..DATAGRAMS_PER_ITERATION / SENDS_IN_FLIGHT {
+                    let sends = storage
+                        .drain(..)
+                        .map(|slice| socket.send(PinnedBuffer::from_boxed_slice(slice)));
+
+                    storage = futures::future::join_all(sends)
+                        .await
+                        .into_iter()
+                        .map(|result| result.unwrap().into_inner_boxed_slice())
+                        .collect();
+                }
+            })
+        });
+    });
+
+    // Overlapped I/O with buffers from the pool, for reference.
+    group.bench_function("folo_udp_send_to", |b| {
+        b.to_async(FoloAdapter::default()).iter(|| {
+            folo::rt::spawn_on_any(move || async move {
+                let socket = UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+                    .await
+                    .unwrap();
+
+                for _ in 0..DATAGRAMS_PER_ITERATION / SENDS_IN_FLIGHT {
+                    let sends = (0..SENDS_IN_FLIGHT).map(|_| {
+                        let mut buffer = PinnedBuffer::from_pool();
+                        buffer.set_len(DATAGRAM_SIZE);
+                        socket.send_to(buffer, sink_addr)
+                    });
+
+                    for result in futures::future::join_all(sends).await {
+                        result.unwrap();
+                    }
+                }
+            })
+        });
+    });
+
+    group.finish();
+}
+
 /// Generate a list of all files in SCAN_PATH and all subdirectories recursively,
 /// returning a boxed slice with their absolute paths.
 fn generate_file_list(path: impl AsRef<Path>) -> Box<[PathBuf]> {
@@ -2,7 +2,7 @@ use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
 use folo::{criterion::ComparativeAdapter, rt::RemoteJoinHandle};
 use std::thread;
 
-criterion_group!(benches, spawn_and_await);
+criterion_group!(benches, spawn_and_await, yield_now);
 criterion_main!(benches);
 
 // We spawn this many top level tasks.
@@ -199,3 +199,50 @@ fn spawn_and_await(c: &mut Criterion) {
 
     group.finish();
 }
+
+// Number of times a task yields in one iteration of the yield benchmarks.
+const YIELD_COUNT: usize = 1000;
+
+// Every yield wakes up the yielding task from the thread that owns it, so this measures the cost
+// of a self-wakeup round trip through the task engine.
+fn yield_now(c: &mut Criterion) {
+    let comparison_adapter = ComparativeAdapter::new(|| {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+    });
+
+    let mut group = c.benchmark_group("yield_now");
+
+    group.bench_function("folo", |b| {
+        b.iter_batched(
+            || {
+                comparison_adapter.begin_folo(Box::new(|| {
+                    Box::pin(async move {
+                        for _ in 0..YIELD_COUNT {
+                            folo::rt::yield_now().await;
+                        }
+                    })
+                }))
+            },
+            |prepared| prepared.run(),
+            BatchSize::PerIteration,
+        );
+    });
+
+    group.bench_function("tokio", |b| {
+        b.iter_batched(
+            || {
+                comparison_adapter.begin_competitor(Box::pin(async move {
+                    for _ in 0..YIELD_COUNT {
+                        tokio::task::yield_now().await;
+                    }
+                }))
+            },
+            |prepared| prepared.run(),
+            BatchSize::PerIteration,
+        );
+    });
+
+    group.finish();
+}
@@ -1,3 +1,37 @@
+mod buf_writer;
+mod copy_dir;
+mod disk_space;
+mod encoding;
+mod file;
 mod functions;
+mod metadata;
+mod mmap;
+mod periodic_durability;
+mod read_dir;
+mod records;
+mod resilient;
+mod rotating_log;
+mod streams;
+mod temp_dir;
+mod walk_dir;
+mod warm_cache;
+mod write_many;
 
+pub use buf_writer::*;
+pub use copy_dir::*;
+pub use disk_space::*;
+pub use encoding::*;
+pub use file::*;
 pub use functions::*;
+pub use metadata::*;
+pub use mmap::*;
+pub use periodic_durability::*;
+pub use read_dir::*;
+pub use records::*;
+pub use resilient::*;
+pub use rotating_log::*;
+pub use streams::*;
+pub use temp_dir::*;
+pub use walk_dir::*;
+pub use warm_cache::*;
+pub use write_many::*;
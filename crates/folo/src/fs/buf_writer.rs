@@ -0,0 +1,200 @@
+use crate::{
+    fs::functions::{open_for_write, write_buffer_to_file},
+    io,
+    rt::current_runtime,
+    windows::OwnedHandle,
+};
+use negative_impl::negative_impl;
+use std::{
+    mem,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use windows::Win32::Foundation::HANDLE;
+
+/// Writes to a file through an in-memory buffer, submitting the buffered data to the file in
+/// larger writes once the buffer fills up or `flush()` is called.
+///
+/// All the buffered writers of a runtime share a budget of buffered bytes, set via
+/// `RuntimeBuilder::max_buffered_write_bytes()`. A write that would exceed the budget first
+/// flushes the buffer of the writer and if that does not free up enough of the budget, the data is
+/// written to the file directly instead of being buffered.
+///
+/// Data still in the buffer when the writer is dropped is discarded - call `flush()` first.
+#[derive(Debug)]
+pub struct BufWriter {
+    // This is an Arc because flushing the file to storage (see `PeriodicDurability`) happens on a
+    // synchronous worker thread, so we must share the handle with that thread.
+    file: Arc<OwnedHandle<HANDLE>>,
+
+    buffer: Vec<u8>,
+    capacity: usize,
+
+    // Offset in the file at which the buffered data is to be written.
+    offset: usize,
+
+    // How many bytes of the budget we hold. This is normally the length of the buffer but remains
+    // held while a flush is in progress, as the data is not in the file until the flush completes.
+    reserved: usize,
+    budget: Arc<WriteBufferBudget>,
+}
+
+impl BufWriter {
+    /// Creates a new file or truncates an existing one and returns a writer that buffers up to
+    /// `capacity` bytes before writing them to the file.
+    pub async fn create(path: impl AsRef<Path>, capacity: usize) -> io::Result<Self> {
+        let file = open_for_write(path).await?;
+        let budget = current_runtime::with(|runtime| Arc::clone(runtime.write_buffer_budget()));
+
+        Ok(Self {
+            file: Arc::new(file),
+            buffer: Vec::with_capacity(capacity),
+            capacity,
+            offset: 0,
+            reserved: 0,
+            budget,
+        })
+    }
+
+    /// Number of bytes written into the buffer that have not yet been written to the file.
+    pub fn buffered_len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Appends data to the file. The data is buffered if it fits into the buffer and the budget of
+    /// buffered bytes of the runtime, otherwise it is written to the file before returning.
+    pub async fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        if self.buffer.len() + data.len() > self.capacity {
+            self.flush().await?;
+        }
+
+        if data.len() > self.capacity {
+            return self.write_direct(data).await;
+        }
+
+        if !self.budget.try_reserve(data.len()) {
+            // Other writers may be holding the rest of the budget, so even after flushing our own
+            // buffer there may not be enough space. We do not wait for space to free up, as that
+            // depends on when the other writers are flushed, which might be never.
+            self.flush().await?;
+
+            if !self.budget.try_reserve(data.len()) {
+                return self.write_direct(data).await;
+            }
+        }
+
+        self.reserved += data.len();
+        self.buffer.extend_from_slice(data);
+
+        Ok(())
+    }
+
+    /// Writes any buffered data to the file. This does not wait for the data to be durably stored.
+    pub async fn flush(&mut self) -> io::Result<()> {
+        let buffer = mem::replace(&mut self.buffer, Vec::with_capacity(self.capacity));
+
+        let result = if buffer.is_empty() {
+            Ok(())
+        } else {
+            let len = buffer.len();
+            let result = write_buffer_to_file(&self.file, self.offset, buffer.into()).await;
+            self.offset += len;
+            result.map(|_| ()).map_err(io::OperationError::into_inner)
+        };
+
+        // If the write failed, the data is lost either way, so the budget is released regardless.
+        self.budget.release(mem::take(&mut self.reserved));
+
+        result
+    }
+
+    pub(super) fn file(&self) -> &Arc<OwnedHandle<HANDLE>> {
+        &self.file
+    }
+
+    async fn write_direct(&mut self, data: &[u8]) -> io::Result<()> {
+        write_buffer_to_file(&self.file, self.offset, data.to_vec().into())
+            .await
+            .map_err(io::OperationError::into_inner)?;
+        self.offset += data.len();
+
+        Ok(())
+    }
+}
+
+impl Drop for BufWriter {
+    fn drop(&mut self) {
+        self.budget.release(self.reserved);
+    }
+}
+
+#[negative_impl]
+impl !Send for BufWriter {}
+#[negative_impl]
+impl !Sync for BufWriter {}
+
+/// Total number of bytes currently buffered by all the `BufWriter`s of the current runtime,
+/// including data that is being written to files by flushes that have not yet completed.
+///
+/// # Panics
+///
+/// Panics if the current thread is not owned by the Folo runtime.
+pub fn buffered_write_bytes() -> usize {
+    current_runtime::with(|runtime| runtime.write_buffer_budget().used())
+}
+
+/// The budget of bytes buffered by all the `BufWriter`s of a runtime, shared between all threads.
+#[derive(Debug)]
+pub(crate) struct WriteBufferBudget {
+    limit: usize,
+    used: AtomicUsize,
+}
+
+impl WriteBufferBudget {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            used: AtomicUsize::new(0),
+        }
+    }
+
+    /// Takes `bytes` from the budget if there is enough left.
+    pub fn try_reserve(&self, bytes: usize) -> bool {
+        self.used
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                used.checked_add(bytes).filter(|&total| total <= self.limit)
+            })
+            .is_ok()
+    }
+
+    pub fn release(&self, bytes: usize) {
+        self.used.fetch_sub(bytes, Ordering::AcqRel);
+    }
+
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::Acquire)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn budget_rejects_over_limit() {
+        let budget = WriteBufferBudget::new(100);
+
+        assert!(budget.try_reserve(60));
+        assert!(!budget.try_reserve(41));
+        assert!(budget.try_reserve(40));
+        assert_eq!(budget.used(), 100);
+
+        budget.release(60);
+
+        assert!(budget.try_reserve(50));
+        assert_eq!(budget.used(), 90);
+    }
+}
@@ -0,0 +1,283 @@
+use crate::{
+    fs::{copy, functions::create_dir_all_blocking},
+    io,
+    rt::{spawn_sync, SynchronousTaskType},
+};
+use futures::StreamExt;
+use std::{
+    collections::HashSet,
+    fs::{FileTimes, OpenOptions},
+    os::windows::fs::{FileTimesExt, OpenOptionsExt},
+    path::{Path, PathBuf},
+};
+use windows::Win32::Storage::FileSystem::{FILE_FLAG_BACKUP_SEMANTICS, FILE_WRITE_ATTRIBUTES};
+
+/// Determines how `copy_dir()` treats symbolic links found in the source directory tree.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SymlinkBehavior {
+    /// The target of the link is copied as if it were located where the link is. Directories
+    /// reachable via multiple links are only copied once, to avoid infinite recursion.
+    #[default]
+    Follow,
+
+    /// The link itself is recreated in the destination tree, pointing to the same target.
+    /// Creating symbolic links may require elevated privileges or Developer Mode on Windows.
+    CopyAsLink,
+}
+
+/// Options for `copy_dir()`.
+#[derive(Clone, Copy, Debug)]
+pub struct CopyDirOptions {
+    /// The maximum number of files to copy at the same time.
+    pub concurrency: usize,
+
+    /// Whether to apply the creation and last access times of the source files and directories to
+    /// their copies. The last write time of files is always preserved.
+    pub preserve_timestamps: bool,
+
+    pub symlinks: SymlinkBehavior,
+}
+
+impl Default for CopyDirOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 8,
+            preserve_timestamps: false,
+            symlinks: SymlinkBehavior::default(),
+        }
+    }
+}
+
+/// Failure to copy one item of the directory tree in `copy_dir()`.
+#[derive(Debug)]
+pub struct CopyDirError {
+    /// Path of the source item that could not be copied.
+    pub path: PathBuf,
+    pub error: io::Error,
+}
+
+/// The outcome of `copy_dir()`.
+#[derive(Debug, Default)]
+pub struct CopyDirReport {
+    pub files_copied: usize,
+    pub bytes_copied: u64,
+
+    /// Items of the directory tree that could not be copied. If a directory could not be copied,
+    /// nothing inside it was copied either.
+    pub errors: Vec<CopyDirError>,
+}
+
+/// Recursively copies the directory `from` to `to`, creating the destination directory and any
+/// missing parents. Existing files in the destination are replaced.
+///
+/// Failures to copy individual items of the tree do not stop the copy - they are collected in the
+/// returned report instead. An error is only returned if the copy cannot be started at all (e.g.
+/// because the source directory does not exist).
+pub async fn copy_dir(
+    from: impl AsRef<Path>,
+    to: impl AsRef<Path>,
+    options: CopyDirOptions,
+) -> io::Result<CopyDirReport> {
+    let from = from.as_ref().to_path_buf();
+    let to = to.as_ref().to_path_buf();
+
+    // Walking the tree and creating directories are blocking operations, so we kick them off to a
+    // synchronous worker thread to avoid blocking the async workers with these slow calls. This
+    // only touches directory metadata - the heavy lifting of copying files happens afterwards.
+    let plan = spawn_sync(SynchronousTaskType::Syscall, move || {
+        plan_blocking(&from, &to, options.symlinks)
+    })
+    .await?;
+
+    let mut report = CopyDirReport {
+        errors: plan.errors,
+        ..Default::default()
+    };
+
+    let mut copies = futures::stream::iter(plan.files)
+        .map(|(from, to)| async move {
+            let result = copy_file(&from, &to, options.preserve_timestamps).await;
+            (from, result)
+        })
+        .buffer_unordered(options.concurrency.max(1));
+
+    while let Some((path, result)) = copies.next().await {
+        match result {
+            Ok(bytes_copied) => {
+                report.files_copied += 1;
+                report.bytes_copied += bytes_copied;
+            }
+            Err(error) => report.errors.push(CopyDirError { path, error }),
+        }
+    }
+
+    // Copying the contents of a directory updates its timestamps, so we can only apply the
+    // timestamps of directories once everything is copied. The directories were planned parents
+    // first, so we go in reverse to get to the children before their parents.
+    if options.preserve_timestamps {
+        let errors = spawn_sync(SynchronousTaskType::Syscall, move || {
+            plan.directories
+                .iter()
+                .rev()
+                .filter_map(|(from, to)| {
+                    copy_timestamps_blocking(from, to)
+                        .err()
+                        .map(|error| CopyDirError {
+                            path: from.clone(),
+                            error: error.into(),
+                        })
+                })
+                .collect::<Vec<_>>()
+        })
+        .await;
+
+        report.errors.extend(errors);
+    }
+
+    Ok(report)
+}
+
+async fn copy_file(from: &Path, to: &Path, preserve_timestamps: bool) -> io::Result<u64> {
+    let bytes_copied = copy(from, to).await?;
+
+    if preserve_timestamps {
+        let from = from.to_path_buf();
+        let to = to.to_path_buf();
+
+        spawn_sync(SynchronousTaskType::Syscall, move || {
+            copy_timestamps_blocking(&from, &to)
+        })
+        .await?;
+    }
+
+    Ok(bytes_copied)
+}
+
+#[derive(Debug, Default)]
+struct CopyPlan {
+    // Source and destination of every file to copy.
+    files: Vec<(PathBuf, PathBuf)>,
+
+    // Source and destination of every directory that was created, parents before children.
+    directories: Vec<(PathBuf, PathBuf)>,
+
+    errors: Vec<CopyDirError>,
+}
+
+/// Walks the source tree, creating the destination directories and symbolic links on the way and
+/// collecting the files to copy.
+fn plan_blocking(from: &Path, to: &Path, symlinks: SymlinkBehavior) -> io::Result<CopyPlan> {
+    if !std::fs::metadata(from)?.is_dir() {
+        return Err(std::io::Error::from(std::io::ErrorKind::NotADirectory).into());
+    }
+
+    create_dir_all_blocking(to)?;
+
+    let mut plan = CopyPlan::default();
+
+    // Canonical paths of the directories we have visited, to avoid copying a directory reachable
+    // via symbolic links more than once (or infinitely many times if the links form a cycle).
+    let mut visited = HashSet::new();
+    visited.insert(std::fs::canonicalize(from)?);
+
+    let mut pending = vec![(from.to_path_buf(), to.to_path_buf())];
+
+    while let Some((from_dir, to_dir)) = pending.pop() {
+        let entries = match std::fs::read_dir(&from_dir) {
+            Ok(entries) => entries,
+            Err(error) => {
+                plan.errors.push(CopyDirError {
+                    path: from_dir,
+                    error: error.into(),
+                });
+                continue;
+            }
+        };
+
+        plan.directories.push((from_dir, to_dir.clone()));
+
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(error) => {
+                    plan.errors.push(CopyDirError {
+                        path: plan.directories.last().unwrap().0.clone(),
+                        error: error.into(),
+                    });
+                    continue;
+                }
+            };
+
+            let from_path = entry.path();
+            let to_path = to_dir.join(entry.file_name());
+
+            match plan_entry(&from_path, &to_path, symlinks, &mut visited) {
+                Ok(Some(PlannedEntry::File)) => plan.files.push((from_path, to_path)),
+                Ok(Some(PlannedEntry::Directory)) => pending.push((from_path, to_path)),
+                Ok(None) => {}
+                Err(error) => plan.errors.push(CopyDirError {
+                    path: from_path,
+                    error,
+                }),
+            }
+        }
+    }
+
+    Ok(plan)
+}
+
+enum PlannedEntry {
+    File,
+    Directory,
+}
+
+fn plan_entry(
+    from: &Path,
+    to: &Path,
+    symlinks: SymlinkBehavior,
+    visited: &mut HashSet<PathBuf>,
+) -> io::Result<Option<PlannedEntry>> {
+    let metadata = std::fs::symlink_metadata(from)?;
+
+    if metadata.is_symlink() && symlinks == SymlinkBehavior::CopyAsLink {
+        let target = std::fs::read_link(from)?;
+
+        // On Windows, a link knows whether it points to a file or a directory.
+        if metadata.is_dir() {
+            std::os::windows::fs::symlink_dir(target, to)?;
+        } else {
+            std::os::windows::fs::symlink_file(target, to)?;
+        }
+
+        return Ok(None);
+    }
+
+    // This follows symbolic links, which we only get here with if we are to follow them.
+    if !std::fs::metadata(from)?.is_dir() {
+        return Ok(Some(PlannedEntry::File));
+    }
+
+    if !visited.insert(std::fs::canonicalize(from)?) {
+        return Ok(None);
+    }
+
+    create_dir_all_blocking(to)?;
+
+    Ok(Some(PlannedEntry::Directory))
+}
+
+fn copy_timestamps_blocking(from: &Path, to: &Path) -> std::io::Result<()> {
+    let metadata = std::fs::metadata(from)?;
+
+    let times = FileTimes::new()
+        .set_accessed(metadata.accessed()?)
+        .set_modified(metadata.modified()?)
+        .set_created(metadata.created()?);
+
+    // Backup semantics are required to open directories.
+    OpenOptions::new()
+        .access_mode(FILE_WRITE_ATTRIBUTES.0)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS.0)
+        .open(to)?
+        .set_times(times)
+}
@@ -0,0 +1,59 @@
+use crate::{
+    io,
+    rt::{spawn_sync, SynchronousTaskType},
+};
+use std::path::Path;
+use windows::{core::HSTRING, Win32::Storage::FileSystem::GetDiskFreeSpaceExW};
+
+/// Space on the volume that holds some path, as reported by the operating system.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DiskSpace {
+    /// Total size of the volume in bytes.
+    pub total_bytes: u64,
+
+    /// Free space on the volume in bytes.
+    pub free_bytes: u64,
+
+    /// Free space on the volume that the current user can make use of, in bytes. This may be less
+    /// than `free_bytes` if disk quotas are in effect.
+    pub available_bytes: u64,
+}
+
+/// Queries the space on the volume that holds the given file or directory. This is useful to
+/// fail fast before writing a large amount of data, instead of running out of space midway.
+///
+/// For network shares, the values are whatever the server reports, which may not reflect the
+/// actual state of the underlying storage (e.g. if the share is backed by multiple volumes or
+/// the server applies its own quotas). Treat the values as a hint, not a guarantee.
+pub async fn disk_space(path: impl AsRef<Path>) -> io::Result<DiskSpace> {
+    let path = path.as_ref().to_path_buf();
+
+    // Querying the volume is a blocking operation that may involve a network round trip, so we
+    // kick it off to a synchronous worker thread to avoid blocking the async workers.
+    spawn_sync(SynchronousTaskType::Syscall, move || -> io::Result<_> {
+        // The OS only accepts directories, so for files we ask about the directory they are in.
+        let directory = match path.parent() {
+            Some(parent) if path.is_file() => parent,
+            _ => path.as_path(),
+        };
+
+        let mut space = DiskSpace {
+            total_bytes: 0,
+            free_bytes: 0,
+            available_bytes: 0,
+        };
+
+        // SAFETY: The path outlives the call and we pass valid pointers to locals.
+        unsafe {
+            GetDiskFreeSpaceExW(
+                &HSTRING::from(directory),
+                Some(&mut space.available_bytes),
+                Some(&mut space.total_bytes),
+                Some(&mut space.free_bytes),
+            )?;
+        }
+
+        Ok(space)
+    })
+    .await
+}
@@ -0,0 +1,169 @@
+use crate::{fs::read, io};
+use std::path::Path;
+use windows::Win32::Globalization::{MultiByteToWideChar, MB_ERR_INVALID_CHARS};
+
+/// The text encoding of a file read via `read_to_string_with_encoding()`.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Encoding {
+    /// Detects the encoding from the byte order mark at the start of the file, falling back to
+    /// UTF-8 if there is none.
+    #[default]
+    Auto,
+
+    Utf8,
+
+    /// UTF-16, little-endian. This is what Windows typically means by "Unicode".
+    Utf16Le,
+
+    /// UTF-16, big-endian.
+    Utf16Be,
+
+    /// A Windows code page identifier, such as 1252 (Western European) or `CP_ACP` for the active
+    /// code page of the system.
+    CodePage(u32),
+}
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+/// Reads the contents of a UTF-8 encoded file into a string.
+///
+/// Returns an error if the contents are not valid UTF-8.
+pub async fn read_to_string(path: impl AsRef<Path>) -> io::Result<String> {
+    read_to_string_with_encoding(path, Encoding::Utf8).await
+}
+
+/// Reads the contents of a text file into a string, converting from the specified encoding.
+///
+/// A byte order mark at the start of the file is not included in the string if it matches the
+/// encoding. With `Encoding::Auto`, the byte order mark selects the encoding.
+///
+/// Returns an error if the contents are not valid in the specified encoding.
+pub async fn read_to_string_with_encoding(
+    path: impl AsRef<Path>,
+    encoding: Encoding,
+) -> io::Result<String> {
+    let bytes = read(path).await?;
+
+    decode(&bytes, encoding)
+}
+
+fn decode(bytes: &[u8], encoding: Encoding) -> io::Result<String> {
+    let encoding = match encoding {
+        Encoding::Auto if bytes.starts_with(UTF16LE_BOM) => Encoding::Utf16Le,
+        Encoding::Auto if bytes.starts_with(UTF16BE_BOM) => Encoding::Utf16Be,
+        Encoding::Auto => Encoding::Utf8,
+        encoding => encoding,
+    };
+
+    match encoding {
+        Encoding::Auto => unreachable!("auto-detection is resolved above"),
+        Encoding::Utf8 => {
+            let bytes = bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes);
+
+            String::from_utf8(bytes.to_vec()).map_err(invalid_data)
+        }
+        Encoding::Utf16Le => {
+            let bytes = bytes.strip_prefix(UTF16LE_BOM).unwrap_or(bytes);
+
+            decode_utf16(bytes, u16::from_le_bytes)
+        }
+        Encoding::Utf16Be => {
+            let bytes = bytes.strip_prefix(UTF16BE_BOM).unwrap_or(bytes);
+
+            decode_utf16(bytes, u16::from_be_bytes)
+        }
+        Encoding::CodePage(code_page) => decode_code_page(bytes, code_page),
+    }
+}
+
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> io::Result<String> {
+    let pairs = bytes.chunks_exact(2);
+
+    if !pairs.remainder().is_empty() {
+        return Err(invalid_data("UTF-16 data has an odd number of bytes"));
+    }
+
+    let units = pairs
+        .map(|pair| from_bytes([pair[0], pair[1]]))
+        .collect::<Vec<_>>();
+
+    String::from_utf16(&units).map_err(invalid_data)
+}
+
+fn decode_code_page(bytes: &[u8], code_page: u32) -> io::Result<String> {
+    // The conversion API does not accept empty input.
+    if bytes.is_empty() {
+        return Ok(String::new());
+    }
+
+    // We first ask how many UTF-16 code units we need, then do the actual conversion.
+    // SAFETY: No safety requirements beyond passing valid slices.
+    let len = unsafe { MultiByteToWideChar(code_page, MB_ERR_INVALID_CHARS, bytes, None) };
+
+    if len == 0 {
+        return Err(windows::core::Error::from_win32().into());
+    }
+
+    let mut units = vec![0; len as usize];
+
+    // SAFETY: No safety requirements beyond passing valid slices.
+    let len =
+        unsafe { MultiByteToWideChar(code_page, MB_ERR_INVALID_CHARS, bytes, Some(&mut units)) };
+
+    if len == 0 {
+        return Err(windows::core::Error::from_win32().into());
+    }
+
+    units.truncate(len as usize);
+
+    String::from_utf16(&units).map_err(invalid_data)
+}
+
+fn invalid_data<E>(error: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    std::io::Error::new(std::io::ErrorKind::InvalidData, error).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auto_detects_bom() {
+        assert_eq!(
+            decode(&[0xEF, 0xBB, 0xBF, b'h', b'i'], Encoding::Auto).unwrap(),
+            "hi"
+        );
+        assert_eq!(
+            decode(&[0xFF, 0xFE, b'h', 0, b'i', 0], Encoding::Auto).unwrap(),
+            "hi"
+        );
+        assert_eq!(
+            decode(&[0xFE, 0xFF, 0, b'h', 0, b'i'], Encoding::Auto).unwrap(),
+            "hi"
+        );
+        assert_eq!(decode(b"hi", Encoding::Auto).unwrap(), "hi");
+    }
+
+    #[test]
+    fn invalid_utf16_is_error() {
+        assert!(decode(&[b'h', 0, b'i'], Encoding::Utf16Le).is_err());
+
+        // An unpaired surrogate.
+        assert!(decode(&[0x00, 0xD8], Encoding::Utf16Le).is_err());
+    }
+
+    #[test]
+    fn code_page() {
+        // "café" in Windows-1252.
+        assert_eq!(
+            decode(&[b'c', b'a', b'f', 0xE9], Encoding::CodePage(1252)).unwrap(),
+            "café"
+        );
+        assert_eq!(decode(&[], Encoding::CodePage(1252)).unwrap(), "");
+    }
+}
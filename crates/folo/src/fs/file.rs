@@ -0,0 +1,1111 @@
+use crate::{
+    fs::{
+        functions::{
+            open_for_write, read_buffer_from_file_with_priority, write_buffer_to_file,
+            APPEND_OFFSET,
+        },
+        Metadata,
+    },
+    io::{self, IoClass, IoPriority, PendingOperations, PinnedBuffer},
+    process::InheritableHandle,
+    rt::{current_async_agent, spawn_sync, SynchronousTaskType},
+    windows::OwnedHandle,
+};
+use futures::{future, stream, Stream, StreamExt, TryStreamExt};
+use negative_impl::negative_impl;
+use std::{
+    ffi::CString,
+    mem::{self, ManuallyDrop},
+    os::windows::io::FromRawHandle,
+    path::Path,
+    ptr,
+    rc::Rc,
+    slice,
+    sync::Arc,
+};
+use windows::{
+    core::PCSTR,
+    Win32::{
+        Foundation::{
+            ERROR_ACCESS_DENIED, ERROR_INVALID_FUNCTION, ERROR_LOCK_VIOLATION, ERROR_MORE_DATA,
+            HANDLE,
+        },
+        Storage::FileSystem::{
+            CreateFileA, FileCompressionInfo, FlushFileBuffers, GetFileInformationByHandleEx,
+            GetFileSizeEx, LockFileEx, ReOpenFile, UnlockFileEx, COMPRESSION_FORMAT_DEFAULT,
+            COMPRESSION_FORMAT_NONE, FILE_APPEND_DATA, FILE_COMPRESSION_INFO,
+            FILE_FLAGS_AND_ATTRIBUTES, FILE_FLAG_NO_BUFFERING, FILE_FLAG_OVERLAPPED,
+            FILE_GENERIC_READ, FILE_READ_ATTRIBUTES, FILE_SHARE_DELETE, FILE_SHARE_READ,
+            FILE_SHARE_WRITE, FILE_WRITE_ATTRIBUTES, LOCKFILE_EXCLUSIVE_LOCK,
+            LOCKFILE_FAIL_IMMEDIATELY, LOCK_FILE_FLAGS, OPEN_ALWAYS, OPEN_EXISTING,
+        },
+        System::{
+            Ioctl::{
+                DISK_GEOMETRY, FILE_ALLOCATED_RANGE_BUFFER, FSCTL_QUERY_ALLOCATED_RANGES,
+                FSCTL_SET_COMPRESSION, GET_LENGTH_INFORMATION, IOCTL_DISK_GET_DRIVE_GEOMETRY,
+                IOCTL_DISK_GET_LENGTH_INFO,
+            },
+            IO::{DeviceIoControl, OVERLAPPED},
+        },
+    },
+};
+
+/// A region of a file that has storage allocated for it. See `File::read_allocated_ranges()`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AllocatedRange {
+    /// Offset of the first byte of the range, from the start of the file.
+    pub offset: u64,
+
+    /// Length of the range in bytes.
+    pub len: u64,
+}
+
+impl AllocatedRange {
+    fn end(&self) -> u64 {
+        self.offset + self.len
+    }
+}
+
+/// An open file on which asynchronous I/O operations can be performed.
+///
+/// The file is bound to the I/O driver of the async worker thread that opened it, so all I/O on
+/// the file must happen on that same thread.
+#[derive(Debug)]
+pub struct File {
+    // Shared with the synchronous worker threads that perform blocking calls on the file, so the
+    // handle stays open until they are done, even if the file is dropped in the meantime.
+    handle: Arc<OwnedHandle<HANDLE>>,
+
+    // Only set for devices opened via `open_device()`, which require all reads to be aligned to
+    // the sector size of the device.
+    sector_size: Option<usize>,
+
+    // Reads and locks in flight on the file, which `cancel_all()` waits for.
+    pending: PendingOperations,
+
+    // The reactor whose I/O driver the file is bound to.
+    reactor_id: usize,
+
+    // Where the next sequential read or write via `io::AsyncRead` or `io::AsyncWrite` starts.
+    position: usize,
+
+    // Set for files opened via `open_append()`, all writes to which go to the end of the file.
+    append: bool,
+}
+
+impl File {
+    /// Opens an existing file for reading.
+    ///
+    /// To open a named data stream of the file (see `streams()`), append `:` and the stream name
+    /// to the path.
+    pub async fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path_cstr = CString::new(path.as_ref().to_str().unwrap()).unwrap();
+
+        // Opening the file is a blocking operation, so we kick it off to a synchronous worker
+        // thread to avoid blocking the async workers with this slow call.
+        let handle = spawn_sync(SynchronousTaskType::Syscall, move || -> io::Result<_> {
+            // SAFETY: The path is a valid null-terminated string that outlives the call and we
+            // take ownership of the returned handle, closing it when dropped.
+            Ok(unsafe {
+                OwnedHandle::new(CreateFileA(
+                    PCSTR::from_raw(path_cstr.as_ptr() as *const u8),
+                    FILE_GENERIC_READ.0,
+                    FILE_SHARE_READ,
+                    None,
+                    OPEN_EXISTING,
+                    FILE_FLAG_OVERLAPPED,
+                    None,
+                )?)
+            })
+        })
+        .await?;
+
+        let reactor_id = current_async_agent::with_io(|io| {
+            io.bind_io_primitive(&*handle, IoClass::Disk)
+                .map(|()| io.reactor_id())
+        })?;
+
+        Ok(Self {
+            handle: Arc::new(handle),
+            sector_size: None,
+            pending: PendingOperations::new(),
+            reactor_id,
+            position: 0,
+            append: false,
+        })
+    }
+
+    /// Creates a new file or truncates an existing one and opens it for writing.
+    pub async fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let handle = open_for_write(path).await?;
+
+        Ok(Self {
+            handle: Arc::new(handle),
+            sector_size: None,
+            pending: PendingOperations::new(),
+            reactor_id: current_async_agent::with_io(|io| io.reactor_id()),
+            position: 0,
+            append: false,
+        })
+    }
+
+    /// Opens a file for appending, creating it if it does not exist. Every write to the file goes
+    /// to the end of the file at the time of the write, regardless of the offset it was requested
+    /// at, and is appended as a whole even if other handles (including ones in other processes)
+    /// are appending to the same file at the same time. Writes larger than 10 MiB are split into
+    /// multiple appends, which other appends may come between.
+    pub async fn open_append(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path_cstr = CString::new(path.as_ref().to_str().unwrap()).unwrap();
+
+        // Opening the file is a blocking operation, so we kick it off to a synchronous worker
+        // thread to avoid blocking the async workers with this slow call.
+        let handle = spawn_sync(SynchronousTaskType::Syscall, move || -> io::Result<_> {
+            // SAFETY: The path is a valid null-terminated string that outlives the call and we
+            // take ownership of the returned handle, closing it when dropped.
+            Ok(unsafe {
+                OwnedHandle::new(CreateFileA(
+                    PCSTR::from_raw(path_cstr.as_ptr() as *const u8),
+                    // Without FILE_WRITE_DATA, the OS only allows the handle to append.
+                    (FILE_APPEND_DATA | FILE_READ_ATTRIBUTES).0,
+                    // Other appenders may have the file open at the same time.
+                    FILE_SHARE_READ | FILE_SHARE_WRITE,
+                    None,
+                    OPEN_ALWAYS,
+                    FILE_FLAG_OVERLAPPED,
+                    None,
+                )?)
+            })
+        })
+        .await?;
+
+        let reactor_id = current_async_agent::with_io(|io| {
+            io.bind_io_primitive(&*handle, IoClass::Disk)
+                .map(|()| io.reactor_id())
+        })?;
+
+        Ok(Self {
+            handle: Arc::new(handle),
+            sector_size: None,
+            pending: PendingOperations::new(),
+            reactor_id,
+            position: 0,
+            append: true,
+        })
+    }
+
+    /// Opens a raw volume (e.g. `\\.\C:`) or physical drive (e.g. `\\.\PhysicalDrive0`) for
+    /// reading, bypassing the file system and the OS cache.
+    ///
+    /// All reads from a device must start at an offset that is a multiple of the sector size of
+    /// the device (see `sector_size()`) and read into a buffer whose active region starts at a
+    /// memory address that is a multiple of the sector size and has a length that is a multiple of
+    /// the sector size. Reads that violate these constraints fail with
+    /// `io::Error::InvalidOptions`.
+    ///
+    /// Opening a device requires administrator privileges. Without them, this fails with a
+    /// `std::io::ErrorKind::PermissionDenied` error.
+    pub async fn open_device(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path_str = path.as_ref().to_str().unwrap().to_string();
+        let path_cstr = CString::new(path_str.as_str()).unwrap();
+
+        // Opening the device is a blocking operation, so we kick it off to a synchronous worker
+        // thread to avoid blocking the async workers with this slow call.
+        let handle = spawn_sync(SynchronousTaskType::Syscall, move || -> io::Result<_> {
+            // SAFETY: The path is a valid null-terminated string that outlives the call.
+            let result = unsafe {
+                CreateFileA(
+                    PCSTR::from_raw(path_cstr.as_ptr() as *const u8),
+                    FILE_GENERIC_READ.0,
+                    // Devices are in use by the rest of the system, so we must share everything.
+                    FILE_SHARE_READ | FILE_SHARE_WRITE,
+                    None,
+                    OPEN_EXISTING,
+                    // Devices can only be opened for unbuffered I/O.
+                    FILE_FLAG_OVERLAPPED | FILE_FLAG_NO_BUFFERING,
+                    None,
+                )
+            };
+
+            match result {
+                // SAFETY: We take ownership of the returned handle, closing it when dropped.
+                Ok(handle) => Ok(unsafe { OwnedHandle::new(handle) }),
+                Err(e) if e.code() == ERROR_ACCESS_DENIED.into() => {
+                    Err(io::Error::StdIo(std::io::Error::new(
+                        std::io::ErrorKind::PermissionDenied,
+                        format!("opening device {path_str} requires administrator privileges"),
+                    )))
+                }
+                Err(e) => Err(e.into()),
+            }
+        })
+        .await?;
+
+        let reactor_id = current_async_agent::with_io(|io| {
+            io.bind_io_primitive(&*handle, IoClass::Disk)
+                .map(|()| io.reactor_id())
+        })?;
+
+        // SAFETY: DISK_GEOMETRY is plain data, valid for any bit pattern.
+        let geometry: DISK_GEOMETRY =
+            unsafe { query_device(&handle, IOCTL_DISK_GET_DRIVE_GEOMETRY) }.await?;
+
+        Ok(Self {
+            handle: Arc::new(handle),
+            sector_size: Some(geometry.BytesPerSector as usize),
+            pending: PendingOperations::new(),
+            reactor_id,
+            position: 0,
+            append: false,
+        })
+    }
+
+    /// The sector size of the device, if the file was opened via `open_device()`. All reads from
+    /// a device must be aligned to its sector size.
+    pub fn sector_size(&self) -> Option<usize> {
+        self.sector_size
+    }
+
+    /// The ID of the reactor (async worker thread) whose I/O driver the file is bound to. All I/O
+    /// on the file must be issued from that reactor (see `rt::current_reactor_id()`), as the
+    /// completion notifications of the file only ever arrive there.
+    pub fn reactor_id(&self) -> usize {
+        self.reactor_id
+    }
+
+    /// Reads from the file at `offset` into the active region of the buffer.
+    ///
+    /// The buffer is returned with the active region set to the bytes read. The OS may read fewer
+    /// bytes than requested. An empty active region indicates end of file.
+    pub async fn read_at(&self, offset: usize, buffer: PinnedBuffer) -> io::Result<PinnedBuffer> {
+        self.read_at_with_priority(offset, buffer, IoPriority::default())
+            .await
+    }
+
+    /// Reads up to `len` bytes from the file at `offset` into a newly allocated buffer.
+    ///
+    /// Unlike `read_at()`, this keeps reading until `len` bytes have been read, so the returned
+    /// buffer is only shorter than `len` if the end of the file is reached first. An empty buffer
+    /// indicates that `offset` is at or past the end of the file.
+    ///
+    /// The buffer is not sector-aligned, so files opened via `open_device()` must use `read_at()`
+    /// with an aligned buffer instead.
+    pub async fn read_len_at(&self, offset: usize, len: usize) -> io::Result<PinnedBuffer> {
+        let mut buffers = [PinnedBuffer::from_boxed_slice(
+            vec![0; len].into_boxed_slice(),
+        )];
+        self.read_into_buffers(offset, &mut buffers).await?;
+
+        let [buffer] = buffers;
+        Ok(buffer)
+    }
+
+    /// Reads from the file at `offset` into the active region of the buffer, submitting the read
+    /// with the given priority if it has to wait for the in-flight operation limit configured via
+    /// `RuntimeBuilder::max_in_flight_io()`. The priority also applies to dispatching the completion
+    /// of the read if the runtime uses `IoPriorityPolicy::OperationPriority`.
+    ///
+    /// The buffer is returned with the active region set to the bytes read. The OS may read fewer
+    /// bytes than requested. An empty active region indicates end of file.
+    pub async fn read_at_with_priority(
+        &self,
+        offset: usize,
+        buffer: PinnedBuffer,
+        priority: IoPriority,
+    ) -> io::Result<PinnedBuffer> {
+        self.check_alignment(offset, &buffer)?;
+
+        read_buffer_from_file_with_priority(
+            &self.handle,
+            offset,
+            buffer,
+            priority,
+            Some(&self.pending),
+        )
+        .await
+        .map_err(io::OperationError::into_inner)
+    }
+
+    /// Writes the active region of the buffer to the file at `offset`. The file must have been
+    /// opened for writing via `create()` or `open_append()`. Files opened via `open_append()`
+    /// ignore the offset and append the data to the end of the file.
+    ///
+    /// The buffer is returned with the same active region, all of which has been written.
+    pub async fn write_at(&self, offset: usize, buffer: PinnedBuffer) -> io::Result<PinnedBuffer> {
+        let offset = if self.append { APPEND_OFFSET } else { offset };
+
+        write_buffer_to_file(&self.handle, offset, buffer)
+            .await
+            .map_err(io::OperationError::into_inner)
+    }
+
+    /// Reads a region of the file starting at `offset` and distributes it across the provided
+    /// buffers in order, filling the active region of each buffer before moving on to the next.
+    ///
+    /// When the call returns, the active region of each buffer is set to the bytes that were read
+    /// into it. If the end of the file is reached, the remaining buffers are left with an empty
+    /// active region. Returns the total number of bytes read.
+    ///
+    /// The buffers are filled by a sequence of reads. `ReadFileScatter()` is not used because it
+    /// requires unbuffered file handles and page-sized, page-aligned segments, which arbitrary
+    /// buffers do not satisfy. As a consequence, the reads are not atomic - a concurrent write to
+    /// the file may land between two of them.
+    ///
+    /// If a read fails, the caller still gets all the buffers back. The buffers filled before the
+    /// failure have their active regions set to the bytes read into them and the others keep the
+    /// active regions they had.
+    pub async fn read_into_buffers(
+        &self,
+        offset: usize,
+        buffers: &mut [PinnedBuffer],
+    ) -> io::Result<usize> {
+        // If the offset and every buffer are aligned, so is every read we make - the OS only ever
+        // reads whole sectors from a device.
+        for buffer in buffers.iter() {
+            self.check_alignment(offset, buffer)?;
+        }
+
+        let mut total_bytes_read = 0;
+        let mut end_of_file = false;
+
+        for slot in buffers.iter_mut() {
+            if end_of_file {
+                slot.set_len(0);
+                continue;
+            }
+
+            // We need to hand ownership of the buffer to the I/O driver, so we temporarily leave an
+            // empty buffer in its place. An empty boxed slice does not allocate.
+            let mut buffer = mem::replace(slot, PinnedBuffer::from_boxed_slice(Box::default()));
+
+            let start = buffer.start();
+            let wanted = buffer.len();
+            let mut filled = 0;
+
+            // The OS is within its rights to give us only a part of what we asked for, so we need
+            // to be prepared to loop until the buffer is full or we reach the end of the file.
+            while filled < wanted {
+                // The length is cleared first because the start and length are validated against
+                // the capacity of the buffer one by one.
+                buffer.set_len(0);
+                buffer.set_start(start + filled);
+                buffer.set_len(wanted - filled);
+
+                let result = read_buffer_from_file_with_priority(
+                    &self.handle,
+                    offset + total_bytes_read + filled,
+                    buffer,
+                    IoPriority::default(),
+                    Some(&self.pending),
+                )
+                .await;
+
+                buffer = match result {
+                    Ok(buffer) => buffer,
+                    Err(e) => {
+                        // The caller gets the buffer back with the active region it had.
+                        let (inner, mut buffer) = e.into_inner_and_buffer();
+
+                        buffer.set_len(0);
+                        buffer.set_start(start);
+                        buffer.set_len(wanted);
+                        *slot = buffer;
+
+                        return Err(inner);
+                    }
+                };
+
+                if buffer.is_empty() {
+                    end_of_file = true;
+                    break;
+                }
+
+                filled += buffer.len();
+            }
+
+            buffer.set_start(start);
+            buffer.set_len(filled);
+            *slot = buffer;
+
+            total_bytes_read += filled;
+        }
+
+        Ok(total_bytes_read)
+    }
+
+    /// Writes the active regions of the provided buffers to the file at `offset`, one after the
+    /// other. The file must have been opened for writing via `create()` or `open_append()`, in
+    /// which case each buffer is appended to the end of the file as a separate write.
+    ///
+    /// The buffers are returned with the same active regions, all of which have been written.
+    /// Returns the total number of bytes written.
+    ///
+    /// The buffers are written by a sequence of writes, for the same reason as in
+    /// `read_into_buffers()`, so the writes are not atomic. In particular, when other handles
+    /// append to a file opened via `open_append()` at the same time, their data may land between
+    /// the buffers.
+    ///
+    /// If a write fails, the caller still gets all the buffers back, with the same active regions.
+    /// Some of the data may have been written.
+    pub async fn write_from_buffers(
+        &self,
+        offset: usize,
+        buffers: &mut [PinnedBuffer],
+    ) -> io::Result<usize> {
+        let mut total_bytes_written = 0;
+
+        for slot in buffers.iter_mut() {
+            // As in `read_into_buffers()`, we temporarily leave an empty buffer in place.
+            let buffer = mem::replace(slot, PinnedBuffer::from_boxed_slice(Box::default()));
+
+            let offset = if self.append {
+                APPEND_OFFSET
+            } else {
+                offset + total_bytes_written
+            };
+
+            match write_buffer_to_file(&self.handle, offset, buffer).await {
+                Ok(buffer) => {
+                    total_bytes_written += buffer.len();
+                    *slot = buffer;
+                }
+                Err(e) => {
+                    let (inner, buffer) = e.into_inner_and_buffer();
+                    *slot = buffer;
+
+                    return Err(inner);
+                }
+            }
+        }
+
+        Ok(total_bytes_written)
+    }
+
+    /// Reads sequentially from the file into the provided buffers, continuing where the previous
+    /// sequential read ended (see `io::AsyncRead`). The buffers are filled as in
+    /// `read_into_buffers()`. Returns the total number of bytes read.
+    pub async fn read_vectored(&mut self, buffers: &mut [PinnedBuffer]) -> io::Result<usize> {
+        let bytes_read = self.read_into_buffers(self.position, buffers).await?;
+        self.position += bytes_read;
+
+        Ok(bytes_read)
+    }
+
+    /// Writes the active regions of the provided buffers sequentially to the file, continuing
+    /// where the previous sequential write ended (see `io::AsyncWrite`). The buffers are written
+    /// as in `write_from_buffers()`. Returns the total number of bytes written.
+    pub async fn write_vectored(&mut self, buffers: &mut [PinnedBuffer]) -> io::Result<usize> {
+        let bytes_written = self.write_from_buffers(self.position, buffers).await?;
+        self.position += bytes_written;
+
+        Ok(bytes_written)
+    }
+
+    /// Flushes the data and metadata of the file to storage, completing once the writes that
+    /// completed before the call have been durably stored. The file must have been opened for
+    /// writing via `create()` or `open_append()`.
+    pub async fn sync_all(&self) -> io::Result<()> {
+        let handle = Arc::clone(&self.handle);
+
+        // Flushing has no overlapped variant and may take a long time, so we kick it off to a
+        // synchronous worker thread to avoid blocking the async workers with this slow call.
+        spawn_sync(SynchronousTaskType::Syscall, move || -> io::Result<_> {
+            // SAFETY: The handle is valid because we are holding a reference to it.
+            unsafe {
+                FlushFileBuffers(**handle)?;
+            }
+
+            Ok(())
+        })
+        .await
+    }
+
+    /// Flushes the data of the file to storage, completing once the writes that completed before
+    /// the call have been durably stored.
+    ///
+    /// Windows offers no way to flush only the data, so this flushes the metadata as well and is
+    /// equivalent to `sync_all()`.
+    pub async fn sync_data(&self) -> io::Result<()> {
+        self.sync_all().await
+    }
+
+    /// Returns the size of the file (or device) in bytes.
+    pub async fn len(&self) -> io::Result<u64> {
+        if self.sector_size.is_some() {
+            // SAFETY: GET_LENGTH_INFORMATION is plain data, valid for any bit pattern.
+            let length: GET_LENGTH_INFORMATION =
+                unsafe { query_device(&self.handle, IOCTL_DISK_GET_LENGTH_INFO) }.await?;
+
+            return Ok(length.Length as u64);
+        }
+
+        let handle = Arc::clone(&self.handle);
+
+        // Probing the size may be a blocking operation, so we kick it off to a synchronous worker
+        // thread to avoid blocking the async workers with this slow call.
+        spawn_sync(SynchronousTaskType::Syscall, move || -> io::Result<_> {
+            let mut size: i64 = 0;
+
+            // SAFETY: The handle is valid because we are holding a reference to it and we pass a
+            // valid pointer to a local.
+            unsafe {
+                GetFileSizeEx(**handle, &mut size)?;
+            }
+
+            Ok(size as u64)
+        })
+        .await
+    }
+
+    /// Lists the regions of the file that have storage allocated for them, in ascending order.
+    ///
+    /// In a sparse file, the regions not covered by any allocated range are holes, which read as
+    /// zeros and can be skipped instead of read. For a file that is not sparse, the entire file is
+    /// one allocated range. Note that the ranges are aligned to the allocation units of the file
+    /// system, so the last range may extend past the end of the file.
+    ///
+    /// Returns an error if the file system does not support the query (e.g. FAT).
+    pub async fn read_allocated_ranges(&self) -> io::Result<Vec<AllocatedRange>> {
+        let handle = Arc::clone(&self.handle);
+
+        // Opening a handle is a blocking operation and the query may need to scan the allocation
+        // metadata of a large file, so we kick them off to a synchronous worker thread to avoid
+        // blocking the async workers with these slow calls.
+        spawn_sync(SynchronousTaskType::Syscall, move || {
+            // The OS may report that there are more ranges than fit into the output buffer via a
+            // warning status, which on a handle bound to a completion port still queues a
+            // completion notification even though the call reports failure. To stay clear of
+            // that, we perform the query synchronously, via a non-overlapped handle to the same
+            // file.
+            //
+            // SAFETY: The handle is valid because we are holding a reference to it and we take
+            // ownership of the returned handle, closing it when dropped.
+            let handle = unsafe {
+                OwnedHandle::new(ReOpenFile(
+                    **handle,
+                    FILE_GENERIC_READ.0,
+                    FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+                    FILE_FLAGS_AND_ATTRIBUTES(0),
+                )?)
+            };
+
+            query_allocated_ranges(&handle)
+        })
+        .await
+    }
+
+    /// Returns the metadata of the file, including its size and compression state.
+    pub async fn metadata(&self) -> io::Result<Metadata> {
+        let handle = Arc::clone(&self.handle);
+
+        // Probing the metadata may be a blocking operation, so we kick it off to a synchronous
+        // worker thread to avoid blocking the async workers with this slow call.
+        spawn_sync(SynchronousTaskType::Syscall, move || -> io::Result<_> {
+            // We borrow the handle as a std file to reuse its metadata query. The std file must
+            // never be dropped because the handle remains owned by us.
+            // SAFETY: The handle is valid because we are holding a reference to it.
+            let std_file = ManuallyDrop::new(unsafe { std::fs::File::from_raw_handle(handle.0) });
+            let metadata = std_file.metadata()?;
+
+            let mut compression = FILE_COMPRESSION_INFO::default();
+
+            // This is the handle-based equivalent of GetCompressedFileSize().
+            // SAFETY: The handle is valid because we are holding a reference to it and we pass
+            // a valid pointer to a local, with the correct size.
+            unsafe {
+                GetFileInformationByHandleEx(
+                    **handle,
+                    FileCompressionInfo,
+                    &mut compression as *mut _ as *mut _,
+                    mem::size_of_val(&compression) as u32,
+                )?;
+            }
+
+            Metadata::new(&metadata, compression.CompressedFileSize as u64)
+        })
+        .await
+    }
+
+    /// Enables or disables compression of the file by the file system. Existing data of the file
+    /// is compressed or decompressed before this returns.
+    ///
+    /// Returns an error if the file system does not support compression (e.g. FAT or ReFS).
+    pub async fn set_compression(&self, enabled: bool) -> io::Result<()> {
+        let handle = Arc::clone(&self.handle);
+
+        let format = if enabled {
+            COMPRESSION_FORMAT_DEFAULT
+        } else {
+            COMPRESSION_FORMAT_NONE
+        };
+
+        // Opening a handle and compressing existing data (which rewrites the whole file) are
+        // blocking operations, so we kick them off to a synchronous worker thread to avoid
+        // blocking the async workers with these slow calls.
+        spawn_sync(SynchronousTaskType::Syscall, move || -> io::Result<_> {
+            // The file is opened for reading only, so we need a handle with permission to change
+            // it. Changing attributes does not conflict with the sharing mode of the file, so we
+            // do not need to ask for write access to the data.
+            //
+            // SAFETY: The handle is valid because we are holding a reference to it and we take
+            // ownership of the returned handle, closing it when dropped.
+            let handle = unsafe {
+                OwnedHandle::new(ReOpenFile(
+                    **handle,
+                    (FILE_READ_ATTRIBUTES | FILE_WRITE_ATTRIBUTES).0,
+                    FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+                    FILE_FLAGS_AND_ATTRIBUTES(0),
+                )?)
+            };
+
+            let mut bytes_returned: u32 = 0;
+
+            // SAFETY: The handle is valid, the input buffer is valid for the size we specify and
+            // we pass a valid pointer to a local for the returned size.
+            unsafe {
+                DeviceIoControl(
+                    *handle,
+                    FSCTL_SET_COMPRESSION,
+                    Some(&format.0 as *const _ as *const _),
+                    mem::size_of_val(&format.0) as u32,
+                    None,
+                    0,
+                    Some(&mut bytes_returned),
+                    None,
+                )?;
+            }
+
+            Ok(())
+        })
+        .await
+    }
+
+    /// Returns a new handle to the file that is marked as inheritable, for passing to a child
+    /// process as its standard input via `process::Command::stdin()`. The file itself is not
+    /// inheritable - only the returned handle is.
+    ///
+    /// The returned handle is independent of this file: it has its own file position, starting at
+    /// the beginning of the file, and remains open after this file is dropped.
+    ///
+    /// Unlike most methods of `File`, this is not offloaded to a synchronous worker thread: opening
+    /// the new handle blocks the calling thread until the operating system completes the open.
+    pub fn inheritable_handle(&self) -> io::Result<InheritableHandle> {
+        // Our own handle is bound to the I/O driver, so any I/O the child process performs on it
+        // would notify our completion port. The child process gets a plain synchronous handle to
+        // the same file instead.
+        //
+        // SAFETY: The handle is valid and we take ownership of the returned handle, closing it
+        // when dropped.
+        let handle = unsafe {
+            OwnedHandle::new(ReOpenFile(
+                **self.handle,
+                FILE_GENERIC_READ.0,
+                FILE_SHARE_READ,
+                FILE_FLAGS_AND_ATTRIBUTES(0),
+            )?)
+        };
+
+        InheritableHandle::new(handle)
+    }
+
+    /// Reads the file from start to end in chunks of `chunk_size` bytes, with the last chunk being
+    /// shorter if the file size is not a multiple of the chunk size.
+    ///
+    /// Chunks that fall entirely into a hole of a sparse file (see `read_allocated_ranges()`) are
+    /// filled with zeros without reading them from storage, making this efficient for reading
+    /// large sparse files.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is zero.
+    pub fn chunks(&self, chunk_size: usize) -> impl Stream<Item = io::Result<PinnedBuffer>> + '_ {
+        assert!(chunk_size > 0, "chunk size must be greater than zero");
+
+        futures::stream::try_unfold(None, move |plan: Option<ChunkPlan>| async move {
+            let mut plan = match plan {
+                Some(plan) => plan,
+                None => self.plan_chunks().await?,
+            };
+
+            if plan.offset >= plan.len {
+                return Ok(None);
+            }
+
+            let chunk_len = (chunk_size as u64).min(plan.len - plan.offset) as usize;
+            let chunk = self.read_chunk(&plan, plan.offset, chunk_len).await?;
+
+            // The file got shorter while we were reading it. Nothing more to read.
+            if chunk.len() < chunk_len {
+                plan.len = plan.offset + chunk.len() as u64;
+            }
+
+            if chunk.is_empty() {
+                return Ok(None);
+            }
+
+            plan.offset += chunk.len() as u64;
+
+            Ok(Some((chunk, Some(plan))))
+        })
+    }
+
+    /// Same as `chunks()` but keeps reading up to `window` chunks ahead of the consumer, so the
+    /// reads of later chunks are in flight while the consumer is processing earlier ones. The
+    /// chunks are still yielded in order.
+    ///
+    /// This hides the latency of the storage behind the processing of the data, at the cost of
+    /// holding up to `window` chunks in memory at the same time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` or `window` is zero.
+    pub fn chunks_with_readahead(
+        &self,
+        chunk_size: usize,
+        window: usize,
+    ) -> impl Stream<Item = io::Result<PinnedBuffer>> + '_ {
+        assert!(chunk_size > 0, "chunk size must be greater than zero");
+        assert!(window > 0, "read-ahead window must be greater than zero");
+
+        stream::once(self.plan_chunks())
+            .map_ok(move |plan| {
+                let len = plan.len;
+                let plan = Rc::new(plan);
+
+                stream::iter((0..len).step_by(chunk_size))
+                    .map(move |offset| {
+                        let plan = Rc::clone(&plan);
+
+                        async move {
+                            let chunk_len = (chunk_size as u64).min(plan.len - offset) as usize;
+                            self.read_chunk(&plan, offset, chunk_len).await
+                        }
+                    })
+                    .buffered(window)
+            })
+            .try_flatten()
+            // The file got shorter while we were reading it. Nothing more to read.
+            .try_take_while(|chunk| future::ready(Ok(!chunk.is_empty())))
+    }
+
+    /// Reads one chunk for `chunks()`, skipping the read if the chunk is entirely in a hole. The
+    /// returned chunk is shorter than requested if the end of the file was reached.
+    async fn read_chunk(
+        &self,
+        plan: &ChunkPlan,
+        offset: u64,
+        len: usize,
+    ) -> io::Result<PinnedBuffer> {
+        let mut chunk = PinnedBuffer::from_boxed_slice(vec![0; len].into_boxed_slice());
+
+        if plan.is_allocated(offset, len as u64) {
+            self.read_into_buffers(offset as usize, slice::from_mut(&mut chunk))
+                .await?;
+        }
+
+        Ok(chunk)
+    }
+
+    async fn plan_chunks(&self) -> io::Result<ChunkPlan> {
+        let len = self.len().await?;
+
+        let allocated_ranges = match self.read_allocated_ranges().await {
+            Ok(ranges) => ranges,
+            // The file system does not support sparse files, so everything is allocated.
+            Err(io::Error::Windows(e)) if e.code() == ERROR_INVALID_FUNCTION.into() => {
+                vec![AllocatedRange { offset: 0, len }]
+            }
+            Err(e) => return Err(e),
+        };
+
+        Ok(ChunkPlan {
+            offset: 0,
+            len,
+            allocated_ranges,
+        })
+    }
+
+    /// Verifies that a read satisfies the alignment requirements of the device, if this is a
+    /// device. Files have no alignment requirements.
+    fn check_alignment(&self, offset: usize, buffer: &PinnedBuffer) -> io::Result<()> {
+        let Some(sector_size) = self.sector_size else {
+            return Ok(());
+        };
+
+        // Sector sizes are always powers of two.
+        let is_aligned = |value: usize| value & (sector_size - 1) == 0;
+
+        if !is_aligned(offset) {
+            return Err(io::Error::InvalidOptions(format!(
+                "offset {offset} is not a multiple of the device sector size {sector_size}"
+            )));
+        }
+
+        if !is_aligned(buffer.len()) {
+            return Err(io::Error::InvalidOptions(format!(
+                "buffer length {} is not a multiple of the device sector size {sector_size}",
+                buffer.len()
+            )));
+        }
+
+        if !is_aligned(buffer.as_slice().as_ptr() as usize) {
+            return Err(io::Error::InvalidOptions(format!(
+                "buffer address is not aligned to the device sector size {sector_size}"
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Cancels all reads and lock requests in flight on the file and waits for them to complete.
+    ///
+    /// The futures of the affected operations resolve with a cancellation error (see
+    /// `io::Error::is_cancelled()`), unless an operation managed to complete before the
+    /// cancellation took effect, in which case it resolves with its regular result. When this
+    /// returns, the operating system is done with the buffers of all the operations.
+    ///
+    /// This is cheaper than cancelling the operations one by one, as a single request to the
+    /// operating system cancels all of them.
+    pub async fn cancel_all(&self) -> io::Result<()> {
+        self.pending.cancel_all(**self.handle).await
+    }
+
+    /// Acquires an exclusive lock on the entire file, waiting until any conflicting lock held via
+    /// another handle is released. The task is parked while waiting - the lock is granted via an
+    /// I/O completion notification, so no polling is involved.
+    ///
+    /// The lock is released when the returned guard is dropped.
+    ///
+    /// Dropping the returned future cancels the pending lock request. If the lock had already been
+    /// granted by the time the future was dropped, there is no guard to release it, so it stays
+    /// held until the file is closed.
+    pub async fn lock_exclusive(&self) -> io::Result<FileLockGuard<'_>> {
+        self.lock(LOCKFILE_EXCLUSIVE_LOCK).await?;
+        Ok(FileLockGuard { file: self })
+    }
+
+    /// Attempts to acquire an exclusive lock on the entire file without waiting. Returns `None`
+    /// if a conflicting lock is held via another handle.
+    ///
+    /// The lock is released when the returned guard is dropped.
+    pub async fn try_lock_exclusive(&self) -> io::Result<Option<FileLockGuard<'_>>> {
+        match self
+            .lock(LOCKFILE_EXCLUSIVE_LOCK | LOCKFILE_FAIL_IMMEDIATELY)
+            .await
+        {
+            Ok(()) => Ok(Some(FileLockGuard { file: self })),
+            Err(io::Error::Windows(e)) if e.code() == ERROR_LOCK_VIOLATION.into() => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn lock(&self, flags: LOCK_FILE_FLAGS) -> io::Result<()> {
+        // Locking does not transfer any data but the I/O driver still requires a buffer.
+        let mut operation = current_async_agent::with_io(|io| {
+            io.new_operation(PinnedBuffer::from_boxed_slice(Box::default()))
+        });
+
+        // The lock range starts at the offset in the OVERLAPPED structure.
+        operation.set_offset(0);
+        operation.track(&self.pending);
+        operation.set_handle(&**self.handle);
+
+        // SAFETY: For safe usage of the I/O driver API, we are required to pass the `overlapped`
+        // argument to a native I/O call under all circumstances, to trigger an I/O completion. We
+        // do. We are also not allowed to use any of the callback arguments after the callback,
+        // even if the Rust compiler might allow us to.
+        unsafe {
+            operation
+                .begin(|_, overlapped, _| {
+                    Ok(LockFileEx(
+                        **self.handle,
+                        flags,
+                        0,
+                        u32::MAX,
+                        u32::MAX,
+                        overlapped,
+                    )?)
+                })
+                .await
+        }
+        .map(|_| ())
+        .map_err(io::OperationError::into_inner)
+    }
+
+    fn unlock(&self) -> io::Result<()> {
+        // Unlocking always completes synchronously, so the OVERLAPPED structure only serves to
+        // specify the offset of the range, which starts at zero.
+        let mut overlapped = OVERLAPPED::default();
+
+        // SAFETY: The handle is valid and the OVERLAPPED structure outlives the call.
+        unsafe {
+            UnlockFileEx(**self.handle, 0, u32::MAX, u32::MAX, &mut overlapped)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Issues a device control request that has no input and returns a fixed-size output structure.
+///
+/// # Safety
+///
+/// `T` must be a plain data structure that is valid for any bit pattern and must be the output
+/// structure of the request identified by `control_code`.
+async unsafe fn query_device<T>(handle: &HANDLE, control_code: u32) -> io::Result<T> {
+    let buffer = PinnedBuffer::from_boxed_slice(vec![0; mem::size_of::<T>()].into_boxed_slice());
+
+    let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+    operation.set_handle(handle);
+
+    // SAFETY: For safe usage of the I/O driver API, we are required to pass the `overlapped`
+    // argument to a native I/O call under all circumstances, to trigger an I/O completion. We do.
+    // We are also not allowed to use any of the callback arguments after the callback, even if
+    // the Rust compiler might allow us to.
+    let buffer = unsafe {
+        operation
+            .begin(|buffer, overlapped, bytes_transferred_immediately| {
+                Ok(DeviceIoControl(
+                    *handle,
+                    control_code,
+                    None,
+                    0,
+                    Some(buffer.as_mut_ptr() as *mut _),
+                    buffer.len() as u32,
+                    Some(bytes_transferred_immediately as *mut _),
+                    Some(overlapped),
+                )?)
+            })
+            .await
+            .map_err(io::OperationError::into_inner)?
+    };
+
+    if buffer.len() != mem::size_of::<T>() {
+        return Err(io::Error::Internal(format!(
+            "device control request {control_code} returned {} bytes, expected {}",
+            buffer.len(),
+            mem::size_of::<T>()
+        )));
+    }
+
+    // SAFETY: The buffer is filled and the caller guarantees that any bit pattern is valid for T.
+    Ok(unsafe { ptr::read_unaligned(buffer.as_slice().as_ptr() as *const T) })
+}
+
+fn query_allocated_ranges(handle: &HANDLE) -> io::Result<Vec<AllocatedRange>> {
+    const RANGES_PER_QUERY: usize = 512;
+
+    let mut ranges = Vec::new();
+    let mut batch = vec![FILE_ALLOCATED_RANGE_BUFFER::default(); RANGES_PER_QUERY];
+
+    let mut query = FILE_ALLOCATED_RANGE_BUFFER {
+        FileOffset: 0,
+        // We ask about everything until the end of the file.
+        Length: i64::MAX,
+    };
+
+    // The OS tells us when it runs out of room in the buffer, in which case we ask again for the
+    // ranges after the last one we received.
+    loop {
+        let mut bytes_returned: u32 = 0;
+
+        // SAFETY: The handle is valid, the buffers are valid for the sizes we specify and we pass
+        // a valid pointer to a local for the returned size.
+        let more = match unsafe {
+            DeviceIoControl(
+                *handle,
+                FSCTL_QUERY_ALLOCATED_RANGES,
+                Some(&query as *const _ as *const _),
+                mem::size_of_val(&query) as u32,
+                Some(batch.as_mut_ptr() as *mut _),
+                mem::size_of_val(batch.as_slice()) as u32,
+                Some(&mut bytes_returned),
+                None,
+            )
+        } {
+            Ok(()) => false,
+            Err(e) if e.code() == ERROR_MORE_DATA.into() => true,
+            Err(e) => return Err(e.into()),
+        };
+
+        let received =
+            &batch[..bytes_returned as usize / mem::size_of::<FILE_ALLOCATED_RANGE_BUFFER>()];
+
+        ranges.extend(received.iter().map(|range| AllocatedRange {
+            offset: range.FileOffset as u64,
+            len: range.Length as u64,
+        }));
+
+        match received.last() {
+            Some(last) if more => {
+                query.FileOffset = last.FileOffset + last.Length;
+                query.Length = i64::MAX - query.FileOffset;
+            }
+            _ => return Ok(ranges),
+        }
+    }
+}
+
+/// Tracks the progress of `File::chunks()` and `File::chunks_with_readahead()`.
+#[derive(Debug)]
+struct ChunkPlan {
+    offset: u64,
+    len: u64,
+    allocated_ranges: Vec<AllocatedRange>,
+}
+
+impl ChunkPlan {
+    /// Whether any part of the region has storage allocated for it.
+    fn is_allocated(&self, offset: u64, len: u64) -> bool {
+        // The ranges are sorted and do not overlap, so the first range that ends after the start
+        // of the region is the only candidate for overlapping with it.
+        let candidate = self
+            .allocated_ranges
+            .partition_point(|range| range.end() <= offset);
+
+        self.allocated_ranges
+            .get(candidate)
+            .is_some_and(|range| range.offset < offset + len)
+    }
+}
+
+/// Holds a lock on a `File`, releasing it when dropped.
+#[derive(Debug)]
+pub struct FileLockGuard<'a> {
+    file: &'a File,
+}
+
+impl Drop for FileLockGuard<'_> {
+    fn drop(&mut self) {
+        // The only way this can fail is if the lock is not held, which would be a bug in our code.
+        self.file
+            .unlock()
+            .expect("releasing a held file lock should never fail");
+    }
+}
+
+/// Reads the file sequentially, starting from the beginning of the file. Reads at explicit offsets
+/// via `read_at()` do not affect where the next sequential read starts.
+impl io::AsyncRead for File {
+    async fn read(&mut self, buffer: PinnedBuffer) -> io::Result<PinnedBuffer> {
+        let buffer = self.read_at(self.position, buffer).await?;
+        self.position += buffer.len();
+
+        Ok(buffer)
+    }
+}
+
+/// Writes the file sequentially, starting from the beginning of the file. Writes at explicit
+/// offsets via `write_at()` do not affect where the next sequential write starts.
+impl io::AsyncWrite for File {
+    async fn write(&mut self, buffer: PinnedBuffer) -> io::Result<PinnedBuffer> {
+        let buffer = self.write_at(self.position, buffer).await?;
+        self.position += buffer.len();
+
+        Ok(buffer)
+    }
+}
+
+#[negative_impl]
+impl !Send for File {}
+#[negative_impl]
+impl !Sync for File {}
@@ -1,26 +1,50 @@
 use crate::{
-    io::{self, PinnedBuffer},
+    io::{self, IoClass, IoOperationKind, IoPriority, PendingOperations, PinnedBuffer},
     rt::{current_async_agent, spawn_sync, SynchronousTaskType},
     windows::OwnedHandle,
 };
-use std::{ffi::CString, path::Path};
+use std::{
+    ffi::{CStr, CString},
+    mem::ManuallyDrop,
+    path::{Path, PathBuf},
+};
 use windows::{
     core::PCSTR,
     Win32::{
-        Foundation::{HANDLE, STATUS_END_OF_FILE},
+        Foundation::{ERROR_ALREADY_EXISTS, HANDLE, STATUS_END_OF_FILE},
         Storage::FileSystem::{
-            CreateFileA, GetFileSizeEx, ReadFile, FILE_FLAG_OVERLAPPED, FILE_FLAG_SEQUENTIAL_SCAN,
-            FILE_GENERIC_READ, FILE_SHARE_READ, OPEN_EXISTING,
+            CreateDirectoryA, CreateFileA, GetFileSizeEx, ReadDirectoryChangesW, ReadFile,
+            WriteFile, CREATE_ALWAYS, FILE_FLAG_BACKUP_SEMANTICS, FILE_FLAG_OVERLAPPED,
+            FILE_FLAG_SEQUENTIAL_SCAN, FILE_GENERIC_READ, FILE_GENERIC_WRITE, FILE_LIST_DIRECTORY,
+            FILE_NOTIFY_CHANGE_FILE_NAME, FILE_SHARE_DELETE, FILE_SHARE_READ, FILE_SHARE_WRITE,
+            OPEN_EXISTING,
         },
     },
 };
 
 // TODO: Review https://devblogs.microsoft.com/oldnewthing/20220425-00/?p=106526 for some good testing advice.
 
+// The size-probing read is the default because the probe happens in the same synchronous call as
+// opening the file, while `read_growable()` needs extra reads for anything but the smallest files.
+// The `read_many_tiny_files` benchmark compares the two.
 pub async fn read(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
     read_large_buffer(path).await
 }
 
+/// Writes the contents of a buffer to a file, replacing the contents of the file if it exists and
+/// creating it otherwise. The active region of the buffer is written, which for a buffer created
+/// from a vector or boxed slice is all of it.
+///
+/// Returns the number of bytes written.
+pub async fn write(path: impl AsRef<Path>, contents: impl Into<PinnedBuffer>) -> io::Result<usize> {
+    let file_handle = open_for_write(path).await?;
+
+    write_buffer_to_file(&file_handle, 0, contents.into())
+        .await
+        .map(|buffer| buffer.len())
+        .map_err(io::OperationError::into_inner)
+}
+
 // Maximum size of a single read submitted to the OS. We repeat reads of up to this size until we
 // have read the entire file. This is a complicated tradeoff between different factors but
 // approximately speaking, a larger buffer means more time spent in ReadFile() which is somewhat bad
@@ -28,77 +52,644 @@ pub async fn read(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
 const MAX_READ_SIZE_BYTES: usize = 10 * 1024 * 1024;
 
 /// Read the contents of a file to a vector of bytes using one giant buffer for the entire file.
+///
+/// The size of the file is probed when opening it, in the same synchronous call that opens the
+/// file, so it costs a syscall but no extra round trip. If the file changes size during the read,
+/// we return whatever we find in the file until the end of file is reached.
 pub async fn read_large_buffer(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+    let (file_handle, file_size) = open_for_sequential_read(path).await?;
+
+    current_async_agent::with_io(|io| io.bind_io_primitive(&*file_handle, IoClass::Disk))?;
+
+    // We allocate one byte more than we expect to read, so the read that reaches the end of the
+    // file tells us whether the file has grown since we probed its size.
+    read_to_end(&file_handle, file_size.saturating_add(1), usize::MAX).await
+}
+
+// Size of the buffer `read_growable()` starts with. Big enough to read typical small files (e.g.
+// source code or configuration files) with a single read.
+const INITIAL_GROWABLE_BUFFER_SIZE_BYTES: usize = 64 * 1024;
+
+/// Read the contents of a file to a vector of bytes without probing the size of the file first.
+///
+/// The file is read into a buffer that starts small and doubles in size whenever it fills up,
+/// until the end of the file is reached. This saves the size probe when opening the file but
+/// costs extra reads and copying for files larger than the initial buffer, so it is only
+/// worthwhile for reading many small files. Compare with `read()` using the
+/// `read_many_tiny_files` benchmark.
+pub async fn read_growable(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+    let path_cstr = CString::new(path.as_ref().to_str().unwrap()).unwrap();
+
+    // Opening the file is a blocking operation, so we kick it off to a synchronous worker thread
+    // to avoid blocking the async workers with this slow call.
+    let file_handle = spawn_sync(SynchronousTaskType::Syscall, move || {
+        open_for_sequential_read_blocking(&path_cstr)
+    })
+    .await?;
+
+    current_async_agent::with_io(|io| io.bind_io_primitive(&*file_handle, IoClass::Disk))?;
+
+    read_to_end(&file_handle, INITIAL_GROWABLE_BUFFER_SIZE_BYTES, usize::MAX).await
+}
+
+/// Reads the start of a file into the active region of a caller-provided buffer, reading as much
+/// as fits into the active region or until the end of the file is reached, whichever comes first.
+///
+/// Returns the buffer with the active region set to the bytes read, together with the number of
+/// bytes read. Unlike `read()`, this does not allocate, so the caller can reuse the same buffer
+/// for reading many files.
+pub async fn read_into(
+    path: impl AsRef<Path>,
+    mut buffer: PinnedBuffer,
+) -> io::Result<(PinnedBuffer, usize)> {
     let path_cstr = CString::new(path.as_ref().to_str().unwrap()).unwrap();
 
+    // Opening the file is a blocking operation, so we kick it off to a synchronous worker thread
+    // to avoid blocking the async workers with this slow call.
+    let file_handle = spawn_sync(SynchronousTaskType::Syscall, move || {
+        open_for_sequential_read_blocking(&path_cstr)
+    })
+    .await?;
+
+    current_async_agent::with_io(|io| io.bind_io_primitive(&*file_handle, IoClass::Disk))?;
+
+    let start = buffer.start();
+    let wanted = buffer.len();
+    let mut bytes_read = 0;
+
+    // The OS is within its rights to give us only a part of what we asked for, so we need to be
+    // prepared to loop until the active region is full or we reach the end of the file.
+    while bytes_read < wanted {
+        // The length is cleared first because the start and length are validated against the
+        // capacity of the buffer one by one.
+        buffer.set_len(0);
+        buffer.set_start(start + bytes_read);
+        buffer.set_len(wanted - bytes_read);
+
+        buffer = read_buffer_from_file(&file_handle, bytes_read, buffer).await?;
+
+        if buffer.is_empty() {
+            break;
+        }
+
+        bytes_read += buffer.len();
+    }
+
+    buffer.set_len(0);
+    buffer.set_start(start);
+    buffer.set_len(bytes_read);
+
+    Ok((buffer, bytes_read))
+}
+
+/// Options for `read_parallel()`.
+#[derive(Clone, Copy, Debug)]
+pub struct ParallelReadOptions {
+    /// Files smaller than this are read with a single sequence of reads, same as `read()` does.
+    pub threshold_bytes: usize,
+
+    /// How many range reads to keep in flight concurrently for files at or above the threshold.
+    pub concurrency: usize,
+}
+
+impl Default for ParallelReadOptions {
+    fn default() -> Self {
+        Self {
+            // Below a few times the maximum read size, there are too few reads to gain anything
+            // from overlapping them. Starting point - tune using the file_io benchmark.
+            threshold_bytes: 4 * MAX_READ_SIZE_BYTES,
+            concurrency: 4,
+        }
+    }
+}
+
+/// Read the contents of a file to a vector of bytes, splitting large files into multiple ranges
+/// that are read concurrently into the same destination buffer. Keeping multiple reads in flight
+/// keeps the disk queue deep, which can be faster for very large files.
+pub async fn read_parallel(
+    path: impl AsRef<Path>,
+    options: ParallelReadOptions,
+) -> io::Result<Vec<u8>> {
+    let (file_handle, file_size) = open_for_sequential_read(path).await?;
+
+    // An empty file has no ranges to split it into, nor anything to read.
+    if file_size == 0 {
+        return Ok(Vec::new());
+    }
+
+    current_async_agent::with_io(|io| io.bind_io_primitive(&*file_handle, IoClass::Disk))?;
+
+    if file_size < options.threshold_bytes || options.concurrency <= 1 {
+        let (buffer, bytes_read) = read_range(&file_handle, 0, new_read_buffer(file_size)).await?;
+
+        let mut as_vec = buffer.into_inner_boxed_slice().into_vec();
+        as_vec.truncate(bytes_read);
+        return Ok(as_vec);
+    }
+
+    // All the ranges are read directly into their final position in one buffer. If we get
+    // canceled while reads are in flight, the OS may still write into the buffer, so we must leak
+    // it rather than free it. Only once all reads have completed do we take ownership again.
+    let mut destination = ManuallyDrop::new(Vec::<u8>::with_capacity(file_size));
+
+    // SAFETY: They are just bytes destined for overwriting, meaningless.
+    #[allow(clippy::uninit_vec)]
     unsafe {
-        // Opening the file and probing its size are blocking operations, so we kick them off to
-        // a synchronous worker thread to avoid blocking the async workers with these slow calls.
-
-        let (file_handle, file_size) =
-            spawn_sync(SynchronousTaskType::Syscall, move || -> io::Result<_> {
-                let file_handle = OwnedHandle::new(CreateFileA(
-                    PCSTR::from_raw(path_cstr.as_ptr() as *const u8),
-                    FILE_GENERIC_READ.0,
-                    FILE_SHARE_READ,
-                    None,
-                    OPEN_EXISTING,
-                    FILE_FLAG_OVERLAPPED | FILE_FLAG_SEQUENTIAL_SCAN,
-                    None,
-                )?);
+        destination.set_len(file_size);
+    }
+
+    let destination_ptr = destination.as_mut_ptr();
+    let range_size = file_size.div_ceil(options.concurrency);
+
+    let ranges = (0..file_size).step_by(range_size).map(|offset| {
+        let len = range_size.min(file_size - offset);
 
-                // Get the size first to allocate the buffer with the correct size. If the size changes
-                // while we read it, that is fine - this is just the initial allocation and may change.
-                let mut file_size: i64 = 0;
+        // SAFETY: The ranges do not overlap and stay within the destination buffer, which is
+        // never moved and is leaked if we do not wait for all the reads to complete.
+        let buffer = unsafe { PinnedBuffer::from_ptr(destination_ptr.add(offset), len) };
 
-                GetFileSizeEx(*file_handle, &mut file_size as *mut _)?;
+        read_range(&file_handle, offset, buffer)
+    });
 
-                Ok((file_handle, file_size))
+    // We wait for all the ranges even if one fails, as we cannot release the destination buffer
+    // while any read into it is still in flight.
+    let results = futures::future::join_all(ranges).await;
+
+    let destination = ManuallyDrop::into_inner(destination);
+    let mut bytes_read = 0;
+
+    for result in results {
+        let (buffer, range_bytes_read) = result?;
+
+        if range_bytes_read != buffer.capacity() {
+            return Err(io::Error::LogicError(
+                "file size changed during read".to_string(),
+            ));
+        }
+
+        bytes_read += range_bytes_read;
+    }
+
+    debug_assert_eq!(bytes_read, file_size);
+
+    Ok(destination)
+}
+
+/// Reads from a file starting at `offset` until the buffer is full or the end of the file is
+/// reached. Returns the buffer and the number of bytes read into it.
+async fn read_range(
+    file: &HANDLE,
+    offset: usize,
+    mut buffer: PinnedBuffer,
+) -> io::Result<(PinnedBuffer, usize)> {
+    let capacity = buffer.capacity();
+    let mut bytes_read = 0;
+
+    loop {
+        // The OS is within its rights to give us only a part of what we asked for, so we need to
+        // be prepared to loop no matter what.
+        buffer = read_buffer_from_file(file, offset + bytes_read, buffer).await?;
+        bytes_read += buffer.len();
+
+        if buffer.is_empty() || bytes_read == capacity {
+            return Ok((buffer, bytes_read));
+        }
+
+        buffer = buffer.use_remainder();
+    }
+}
+
+/// Read the contents of a file to a vector of bytes, failing with `io::Error::FileTooLarge` if
+/// the file is larger than `max_bytes`.
+///
+/// The size of the file is checked before any buffer is allocated. If the file grows while we are
+/// reading it, we never read more than `max_bytes + 1` bytes - if the file turns out to exceed the
+/// limit, an error is returned instead of silently truncated contents.
+pub async fn read_limited(path: impl AsRef<Path>, max_bytes: usize) -> io::Result<Vec<u8>> {
+    let (file_handle, file_size) = open_for_sequential_read(path).await?;
+
+    if file_size > max_bytes {
+        return Err(io::Error::FileTooLarge { max_bytes });
+    }
+
+    current_async_agent::with_io(|io| io.bind_io_primitive(&*file_handle, IoClass::Disk))?;
+
+    // We allocate one byte more than we expect to read. If that byte gets filled, the file has
+    // grown since we probed its size and we need to either grow the buffer or give up.
+    read_to_end(&file_handle, file_size.saturating_add(1), max_bytes).await
+}
+
+/// Reads a file from the start until the end of the file is reached, starting with a buffer of
+/// `initial_size` and doubling the buffer whenever it fills up. Fails with
+/// `io::Error::FileTooLarge` if more than `max_bytes` bytes are found in the file, never reading
+/// more than `max_bytes + 1` bytes.
+async fn read_to_end(
+    file_handle: &HANDLE,
+    initial_size: usize,
+    max_bytes: usize,
+) -> io::Result<Vec<u8>> {
+    let max_buffer_size = max_bytes.saturating_add(1);
+    let mut buffer = new_read_buffer(initial_size.clamp(1, max_buffer_size));
+    let mut bytes_read = 0;
+
+    loop {
+        // The OS is within its rights to give us only a part of what we asked for, so we need to
+        // be prepared to loop no matter what.
+        buffer = read_buffer_from_file(file_handle, bytes_read, buffer).await?;
+        bytes_read += buffer.len();
+
+        if bytes_read > max_bytes {
+            return Err(io::Error::FileTooLarge { max_bytes });
+        }
+
+        if buffer.is_empty() {
+            return Ok(into_vec_of_bytes_read(buffer));
+        }
+
+        if bytes_read == buffer.capacity() {
+            // The file is bigger than the buffer. We grow the buffer, up to the point where we
+            // can tell whether the file exceeds the limit.
+            let new_size = buffer.capacity().saturating_mul(2).min(max_buffer_size);
+
+            let mut as_vec = buffer.into_inner_boxed_slice().into_vec();
+            as_vec.reserve_exact(new_size - as_vec.len());
+            // SAFETY: They are just bytes destined for overwriting, meaningless.
+            #[allow(clippy::uninit_vec)]
+            unsafe {
+                as_vec.set_len(new_size);
+            }
+
+            buffer = PinnedBuffer::from_boxed_slice(as_vec.into_boxed_slice());
+            buffer.set_len(new_size - bytes_read);
+            buffer.set_start(bytes_read);
+        } else {
+            buffer = buffer.use_remainder();
+        }
+    }
+}
+
+// Size of the buffer that receives directory change notifications. We do not inspect the
+// notifications (any change is just a reason to check again), so this does not need to be large.
+// If the buffer overflows, the OS simply tells us that something changed, which is all we need.
+const DIRECTORY_CHANGES_BUFFER_SIZE_BYTES: usize = 4096;
+
+/// Waits until a file exists at the given path.
+///
+/// Resolves immediately if the file already exists. Otherwise, the parent directory is watched
+/// via `ReadDirectoryChangesW()` and the function resolves when the file appears in it - there is
+/// no polling involved.
+///
+/// The parent directory must already exist. If it does not, an error is returned immediately
+/// instead of waiting for the directory to be created.
+pub async fn wait_for_file(path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref().to_path_buf();
+
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    let directory_cstr = CString::new(directory.to_str().unwrap()).unwrap();
+
+    let directory_handle = spawn_sync(SynchronousTaskType::Syscall, move || -> io::Result<_> {
+        // SAFETY: The path is a valid null-terminated string that outlives the call and we take
+        // ownership of the returned handle, closing it when dropped.
+        Ok(unsafe {
+            OwnedHandle::new(CreateFileA(
+                PCSTR::from_raw(directory_cstr.as_ptr() as *const u8),
+                FILE_LIST_DIRECTORY.0,
+                FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+                None,
+                OPEN_EXISTING,
+                // Backup semantics are required to open a handle to a directory.
+                FILE_FLAG_BACKUP_SEMANTICS | FILE_FLAG_OVERLAPPED,
+                None,
+            )?)
+        })
+    })
+    .await?;
+
+    current_async_agent::with_io(|io| io.bind_io_primitive(&*directory_handle, IoClass::Disk))?;
+
+    // The OS requires the buffer to be DWORD-aligned, which heap allocations of this size are.
+    let mut buffer = PinnedBuffer::from_boxed_slice(
+        vec![0; DIRECTORY_CHANGES_BUFFER_SIZE_BYTES].into_boxed_slice(),
+    );
+
+    loop {
+        let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+        operation.set_handle(&*directory_handle);
+
+        // We start watching before we check whether the file exists. The OS records all changes
+        // from the moment the watch is started, so a file created between the check and the start
+        // of the watch cannot slip through unnoticed.
+        //
+        // SAFETY: For safe usage of the I/O driver API, we are required to pass the `overlapped`
+        // argument to a native I/O call under all circumstances, to trigger an I/O completion. We
+        // do. We are also not allowed to use any of the callback arguments after the callback,
+        // even if the Rust compiler might allow us to.
+        let changes = unsafe {
+            operation.begin(|buffer, overlapped, bytes_transferred_immediately| {
+                Ok(ReadDirectoryChangesW(
+                    *directory_handle,
+                    buffer.as_mut_ptr() as *mut _,
+                    buffer.len() as u32,
+                    false,
+                    FILE_NOTIFY_CHANGE_FILE_NAME,
+                    Some(bytes_transferred_immediately as *mut _),
+                    Some(overlapped),
+                    None,
+                )?)
             })
-            .await?;
+        };
 
-        current_async_agent::with_io(|io| io.bind_io_primitive(&*file_handle))?;
+        if file_exists(path.clone()).await? {
+            // The pending watch is canceled when the directory handle is closed. Its completion
+            // notification still goes through the I/O driver, so nothing is leaked.
+            return Ok(());
+        }
 
-        // We create a boxed slice of the correct size to use as the target of the read operation.
-        // We must use a boxed slice because we need to pass ownership of the buffer to the I/O
-        // driver for the duration of the I/O operation, so it cannot be rooted in the stack, nor
-        // can we provide a reference while retaining ownership.
-        let mut buffer = Vec::<u8>::with_capacity(file_size as usize);
-        #[allow(clippy::uninit_vec)] // They are just bytes destined for overwriting, meaningless.
-        buffer.set_len(buffer.capacity());
-        let mut buffer = PinnedBuffer::from_boxed_slice(buffer.into_boxed_slice());
-
-        let mut bytes_read = 0;
-
-        // This does not account for the fact that the file size may theoretically change during
-        // the read operation. Not super interesting for our purposes - file is constant during
-        // benchmarking and this would just mean some reallocation/copying dynamically which we
-        // would in practice never run into.
-        loop {
-            // We ask the OS to read the entire file. It is within its rights to give us only a
-            // part of what we asked for, so we need to be prepared to loop no matter what.
-            buffer = read_buffer_from_file(&file_handle, bytes_read, buffer).await?;
-            bytes_read += buffer.len();
-
-            if buffer.is_empty() {
-                // We have read the entire file (we think). We are done.
-                assert_eq!(
-                    bytes_read, file_size as usize,
-                    "file size changed during read"
-                );
-
-                buffer = buffer.use_all_until_current();
-                let active_region = buffer.active_region();
-                let inner_slice = buffer.into_inner_boxed_slice();
-                let mut as_vec = inner_slice.into_vec();
-                as_vec.set_len(active_region.len());
-                return Ok(as_vec);
-            } else {
-                // More remains to be read.
-                buffer = buffer.use_remainder();
+        // We do not care what the changes were - any change to the names of the files in the
+        // directory is a reason to check again.
+        buffer = changes
+            .await
+            .map_err(io::OperationError::into_inner)?
+            .use_all();
+    }
+}
+
+async fn file_exists(path: PathBuf) -> io::Result<bool> {
+    // Probing the file system is a blocking operation, so we do it on a synchronous worker thread.
+    Ok(spawn_sync(SynchronousTaskType::Syscall, move || path.try_exists()).await?)
+}
+
+/// Creates a new, empty directory at the given path. The parent directory must already exist.
+///
+/// Returns an error if anything already exists at the path.
+pub async fn create_dir(path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref().to_path_buf();
+
+    // Creating a directory is a blocking operation, so we kick it off to a synchronous worker
+    // thread to avoid blocking the async workers with this slow call.
+    spawn_sync(SynchronousTaskType::Syscall, move || {
+        create_dir_blocking(&path)
+    })
+    .await
+}
+
+/// Creates a directory at the given path, including any missing parent directories.
+///
+/// Succeeds without doing anything if the directory already exists. Directories created
+/// concurrently by someone else (e.g. another task or process) are accepted as well.
+pub async fn create_dir_all(path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref().to_path_buf();
+
+    // Creating directories is a blocking operation, so we kick it off to a synchronous worker
+    // thread to avoid blocking the async workers with these slow calls.
+    spawn_sync(SynchronousTaskType::Syscall, move || {
+        create_dir_all_blocking(&path)
+    })
+    .await
+}
+
+/// Deletes a directory and everything in it.
+pub async fn remove_dir_all(path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref().to_path_buf();
+
+    // Deleting files and directories is a blocking operation, so we kick it off to a synchronous
+    // worker thread to avoid blocking the async workers with these slow calls.
+    Ok(spawn_sync(SynchronousTaskType::Syscall, move || {
+        std::fs::remove_dir_all(&path)
+    })
+    .await?)
+}
+
+fn create_dir_blocking(path: &Path) -> io::Result<()> {
+    let path_cstr = CString::new(path.to_str().unwrap()).unwrap();
+
+    // SAFETY: The path is a valid null-terminated string that outlives the call.
+    unsafe {
+        CreateDirectoryA(PCSTR::from_raw(path_cstr.as_ptr() as *const u8), None)?;
+    }
+
+    Ok(())
+}
+
+pub(super) fn create_dir_all_blocking(path: &Path) -> io::Result<()> {
+    if path.is_dir() {
+        return Ok(());
+    }
+
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            create_dir_all_blocking(parent)?;
+        }
+    }
+
+    match create_dir_blocking(path) {
+        Ok(()) => Ok(()),
+        // Someone else created it in the meantime. That is fine, as long as it is a directory.
+        Err(io::Error::Windows(e)) if e.code() == ERROR_ALREADY_EXISTS.into() && path.is_dir() => {
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Copies the contents of a file to another file, replacing the destination if it already exists.
+/// The attributes and last write time of the file are copied as well.
+///
+/// Returns the number of bytes copied.
+pub async fn copy(from: impl AsRef<Path>, to: impl AsRef<Path>) -> io::Result<u64> {
+    let from = from.as_ref().to_path_buf();
+    let to = to.as_ref().to_path_buf();
+
+    // Copying a file is a blocking operation, so we kick it off to a synchronous worker thread to
+    // avoid blocking the async workers with this slow call. The OS copies the data without it ever
+    // passing through our process.
+    Ok(spawn_sync(SynchronousTaskType::Syscall, move || {
+        std::fs::copy(&from, &to)
+    })
+    .await?)
+}
+
+// Maximum size of a single write submitted to the OS. The same tradeoff applies as for reads, see
+// `MAX_READ_SIZE_BYTES`.
+const MAX_WRITE_SIZE_BYTES: usize = 10 * 1024 * 1024;
+
+/// The special file offset that makes a write append to the end of the file, wherever it is at the
+/// time of the write. Each write is appended as a whole, even if other handles append concurrently.
+pub(super) const APPEND_OFFSET: usize = usize::MAX;
+
+/// Opens a file for overlapped writing, replacing the contents of the file if it exists and
+/// creating it otherwise. The file is bound to the I/O driver of the current thread.
+pub(super) async fn open_for_write(path: impl AsRef<Path>) -> io::Result<OwnedHandle<HANDLE>> {
+    let path_cstr = CString::new(path.as_ref().to_str().unwrap()).unwrap();
+
+    // Opening the file is a blocking operation, so we kick it off to a synchronous worker thread
+    // to avoid blocking the async workers with this slow call.
+    let file_handle = spawn_sync(SynchronousTaskType::Syscall, move || -> io::Result<_> {
+        // SAFETY: The path is a valid null-terminated string that outlives the call and we take
+        // ownership of the returned handle, closing it when dropped.
+        Ok(unsafe {
+            OwnedHandle::new(CreateFileA(
+                PCSTR::from_raw(path_cstr.as_ptr() as *const u8),
+                FILE_GENERIC_WRITE.0,
+                FILE_SHARE_READ,
+                None,
+                CREATE_ALWAYS,
+                FILE_FLAG_OVERLAPPED,
+                None,
+            )?)
+        })
+    })
+    .await?;
+
+    current_async_agent::with_io(|io| io.bind_io_primitive(&*file_handle, IoClass::Disk))?;
+
+    Ok(file_handle)
+}
+
+/// Writes the active region of the buffer to a file, starting at the given offset in the file or
+/// at the end of the file if the offset is `APPEND_OFFSET`. Returns the buffer with the same active
+/// region, all of which has been written.
+///
+/// If the write fails, the buffer is returned with the error, with the same active region. Some of
+/// the data may have been written.
+pub(super) async fn write_buffer_to_file(
+    file: &HANDLE,
+    offset: usize,
+    mut buffer: PinnedBuffer,
+) -> io::OperationResult {
+    let start = buffer.start();
+    let wanted = buffer.len();
+    let mut written = 0;
+
+    // The OS is within its rights to take only a part of what we give it, so we need to be
+    // prepared to loop until everything has been written.
+    while written < wanted {
+        // The length is cleared first because the start and length are validated against the
+        // capacity of the buffer one by one.
+        buffer.set_len(0);
+        buffer.set_start(start + written);
+        buffer.set_len((wanted - written).min(MAX_WRITE_SIZE_BYTES));
+
+        // We hold the permit until the operation has completed.
+        let _permit = current_async_agent::with_io(|io| io.admit(IoPriority::default())).await;
+
+        let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+        operation.set_handle(file);
+        operation.set_kind(IoOperationKind::Write);
+
+        if offset == APPEND_OFFSET {
+            operation.set_offset(APPEND_OFFSET);
+        } else {
+            operation.set_offset(offset + written);
+        }
+
+        // SAFETY: For safe usage of the I/O driver API, we are required to pass the `overlapped`
+        // argument to a native I/O call under all circumstances, to trigger an I/O completion. We
+        // do. We are also not allowed to use any of the callback arguments after the callback,
+        // even if the Rust compiler might allow us to.
+        let result = unsafe {
+            operation
+                .begin(|buffer, overlapped, bytes_transferred_immediately| {
+                    Ok(WriteFile(
+                        *file,
+                        Some(buffer),
+                        Some(bytes_transferred_immediately as *mut _),
+                        Some(overlapped),
+                    )?)
+                })
+                .await
+        };
+
+        buffer = match result {
+            Ok(buffer) => buffer,
+            Err(e) => {
+                let (inner, mut buffer) = e.into_inner_and_buffer();
+
+                buffer.set_len(0);
+                buffer.set_start(start);
+                buffer.set_len(wanted);
+                return Err(io::OperationError::new(inner, buffer));
             }
+        };
+
+        written += buffer.len();
+    }
+
+    buffer.set_len(0);
+    buffer.set_start(start);
+    buffer.set_len(wanted);
+    Ok(buffer)
+}
+
+/// Opens a file for overlapped sequential reading and probes its size.
+pub(super) async fn open_for_sequential_read(
+    path: impl AsRef<Path>,
+) -> io::Result<(OwnedHandle<HANDLE>, usize)> {
+    let path_cstr = CString::new(path.as_ref().to_str().unwrap()).unwrap();
+
+    // Opening the file and probing its size are blocking operations, so we kick them off to
+    // a synchronous worker thread to avoid blocking the async workers with these slow calls.
+    spawn_sync(SynchronousTaskType::Syscall, move || -> io::Result<_> {
+        let file_handle = open_for_sequential_read_blocking(&path_cstr)?;
+
+        // Get the size first to allocate the buffer with the correct size. If the size changes
+        // while we read it, that is fine - this is just the initial allocation and may change.
+        let mut file_size: i64 = 0;
+
+        // SAFETY: The handle is valid and we pass a valid pointer to a local.
+        unsafe {
+            GetFileSizeEx(*file_handle, &mut file_size as *mut _)?;
         }
+
+        Ok((file_handle, file_size as usize))
+    })
+    .await
+}
+
+fn open_for_sequential_read_blocking(path: &CStr) -> io::Result<OwnedHandle<HANDLE>> {
+    // SAFETY: The path is a valid null-terminated string that outlives the call and we take
+    // ownership of the returned handle, closing it when dropped.
+    Ok(unsafe {
+        OwnedHandle::new(CreateFileA(
+            PCSTR::from_raw(path.as_ptr() as *const u8),
+            FILE_GENERIC_READ.0,
+            FILE_SHARE_READ,
+            None,
+            OPEN_EXISTING,
+            FILE_FLAG_OVERLAPPED | FILE_FLAG_SEQUENTIAL_SCAN,
+            None,
+        )?)
+    })
+}
+
+/// Creates a buffer of the given size to use as the target of read operations.
+fn new_read_buffer(len: usize) -> PinnedBuffer {
+    // We create a boxed slice of the correct size to use as the target of the read operation.
+    // We must use a boxed slice because we need to pass ownership of the buffer to the I/O
+    // driver for the duration of the I/O operation, so it cannot be rooted in the stack, nor
+    // can we provide a reference while retaining ownership.
+    let mut buffer = Vec::<u8>::with_capacity(len);
+
+    // SAFETY: They are just bytes destined for overwriting, meaningless.
+    #[allow(clippy::uninit_vec)]
+    unsafe {
+        buffer.set_len(buffer.capacity());
     }
+
+    PinnedBuffer::from_boxed_slice(buffer.into_boxed_slice())
+}
+
+/// Converts a buffer that has reached end of file into a vector of all the bytes read into it.
+fn into_vec_of_bytes_read(buffer: PinnedBuffer) -> Vec<u8> {
+    let buffer = buffer.use_all_until_current();
+    let active_region = buffer.active_region();
+    let mut as_vec = buffer.into_inner_boxed_slice().into_vec();
+    as_vec.truncate(active_region.len());
+    as_vec
 }
 
 /// Reads a chunk of bytes from a file at a given offset and fills the provided buffer with them,
@@ -107,17 +698,51 @@ pub async fn read_large_buffer(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
 ///
 /// Returns the buffer in every case, with the action region of the buffer set to the data read.
 /// A zero-sized active region indicates end of file.
-async fn read_buffer_from_file(
+pub(super) async fn read_buffer_from_file(
     file: &HANDLE,
     offset: usize,
-    mut buffer: PinnedBuffer,
+    buffer: PinnedBuffer,
 ) -> io::Result<PinnedBuffer> {
+    read_buffer_from_file_with_priority(file, offset, buffer, IoPriority::default(), None)
+        .await
+        .map_err(io::OperationError::into_inner)
+}
+
+/// Same as `read_buffer_from_file()` but with an explicit priority that determines the order of
+/// submission if the read has to wait for the in-flight operation limit, as well as the order of
+/// dispatching its completion under `IoPriorityPolicy::OperationPriority`. If a tracker of pending
+/// operations is provided, the read is counted in it until the read has completed.
+///
+/// Unlike `read_buffer_from_file()`, the buffer is also returned if the read fails.
+pub(super) async fn read_buffer_from_file_with_priority(
+    file: &HANDLE,
+    offset: usize,
+    mut buffer: PinnedBuffer,
+    priority: IoPriority,
+    pending: Option<&PendingOperations>,
+) -> io::OperationResult {
     if buffer.len() > MAX_READ_SIZE_BYTES {
         buffer.set_len(MAX_READ_SIZE_BYTES);
     }
 
+    // We hold the permit until the operation has completed.
+    let _permit = match pending {
+        Some(pending) => match pending.admit(priority).await {
+            Ok(permit) => permit,
+            Err(e) => return Err(io::OperationError::new(e, buffer)),
+        },
+        None => current_async_agent::with_io(|io| io.admit(priority)).await,
+    };
+
     let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
     operation.set_offset(offset);
+    operation.set_priority(priority);
+    operation.set_handle(file);
+    operation.set_kind(IoOperationKind::Read);
+
+    if let Some(pending) = pending {
+        operation.track(pending);
+    }
 
     // SAFETY: For safe usage of the I/O driver API, we are required to pass the `overlapped`
     // argument to a native I/O call under all circumstances, to trigger an I/O completion. We do.
@@ -140,6 +765,49 @@ async fn read_buffer_from_file(
             inner: io::Error::Windows(external),
             buffer,
         }) if external.code() == STATUS_END_OF_FILE.into() => Ok(buffer),
-        Err(e) => Err(e.into_inner()),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rt::block_on;
+    use folo_testing::temp_path;
+
+    #[test]
+    fn read_limited_detects_growth_after_size_probe() {
+        let path = temp_path("read_limited_detects_growth_after_size_probe");
+        std::fs::write(&path, vec![0xAB; 2000]).unwrap();
+
+        let (over_limit, under_limit) = block_on({
+            let path = path.clone();
+
+            move || async move {
+                let (file_handle, _) = open_for_sequential_read(&path).await.unwrap();
+                current_async_agent::with_io(|io| {
+                    io.bind_io_primitive(&*file_handle, IoClass::Disk)
+                })
+                .unwrap();
+
+                // We pretend that the file was only 100 bytes when its size was probed and has
+                // grown to its real size by the time we read it.
+                let stale_size = 100;
+
+                (
+                    read_to_end(&file_handle, stale_size + 1, 1234).await,
+                    read_to_end(&file_handle, stale_size + 1, 5000).await,
+                )
+            }
+        });
+
+        // We must not return the contents truncated to the probed size or to the limit.
+        assert!(matches!(
+            over_limit,
+            Err(io::Error::FileTooLarge { max_bytes: 1234 })
+        ));
+        assert_eq!(under_limit.unwrap(), vec![0xAB; 2000]);
+
+        std::fs::remove_file(&path).unwrap();
     }
 }
@@ -0,0 +1,137 @@
+use crate::{
+    io,
+    rt::{spawn_sync, SynchronousTaskType},
+};
+use std::{ffi::CString, os::windows::fs::MetadataExt, path::Path, time::SystemTime};
+use windows::{
+    core::PCSTR,
+    Win32::{
+        Foundation::NO_ERROR,
+        Storage::FileSystem::{
+            GetCompressedFileSizeA, FILE_ATTRIBUTE_COMPRESSED, FILE_ATTRIBUTE_DIRECTORY,
+            INVALID_FILE_SIZE,
+        },
+    },
+};
+
+/// Information about a file or directory, obtained via `metadata()` or `File::metadata()`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Metadata {
+    len: u64,
+    compressed_len: u64,
+    attributes: u32,
+    created: SystemTime,
+    accessed: SystemTime,
+    modified: SystemTime,
+}
+
+impl Metadata {
+    pub(crate) fn new(metadata: &std::fs::Metadata, compressed_len: u64) -> io::Result<Self> {
+        Ok(Self {
+            len: metadata.file_size(),
+            compressed_len,
+            attributes: metadata.file_attributes(),
+            // These are always available on Windows.
+            created: metadata.created()?,
+            accessed: metadata.accessed()?,
+            modified: metadata.modified()?,
+        })
+    }
+
+    /// Logical size of the file in bytes, as seen when reading it. Zero for directories.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Size of the storage occupied by the data of the file in bytes, as reported by
+    /// `GetCompressedFileSize`. This is less than `len()` for compressed files and for sparse
+    /// files with holes in them. Zero for directories.
+    pub fn compressed_len(&self) -> u64 {
+        self.compressed_len
+    }
+
+    /// Whether the file or directory is compressed by the file system (see
+    /// `File::set_compression()`).
+    pub fn is_compressed(&self) -> bool {
+        self.attributes & FILE_ATTRIBUTE_COMPRESSED.0 != 0
+    }
+
+    /// Whether the size of the file is zero.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.attributes & FILE_ATTRIBUTE_DIRECTORY.0 != 0
+    }
+
+    pub fn is_file(&self) -> bool {
+        !self.is_dir()
+    }
+
+    /// The file attributes (`FILE_ATTRIBUTE_*` flags) of the file or directory.
+    pub fn attributes(&self) -> u32 {
+        self.attributes
+    }
+
+    pub fn created(&self) -> SystemTime {
+        self.created
+    }
+
+    /// When the file or directory was last accessed. The file system may update this lazily or
+    /// not at all, depending on its configuration.
+    pub fn accessed(&self) -> SystemTime {
+        self.accessed
+    }
+
+    pub fn modified(&self) -> SystemTime {
+        self.modified
+    }
+}
+
+/// Queries information about the file or directory at `path` without opening it for reading.
+/// Reparse points are followed, so for a link this describes the target of the link.
+pub async fn metadata(path: impl AsRef<Path>) -> io::Result<Metadata> {
+    let path = path.as_ref().to_path_buf();
+
+    // Querying the metadata is a blocking operation, so we kick it off to a synchronous worker
+    // thread to avoid blocking the async workers with this slow call.
+    spawn_sync(SynchronousTaskType::Syscall, move || -> io::Result<_> {
+        let metadata = std::fs::metadata(&path)?;
+
+        let compressed_len = if metadata.is_dir() {
+            0
+        } else {
+            compressed_len_blocking(&path)?
+        };
+
+        Metadata::new(&metadata, compressed_len)
+    })
+    .await
+}
+
+fn compressed_len_blocking(path: &Path) -> io::Result<u64> {
+    let path_cstr = CString::new(path.to_str().unwrap()).unwrap();
+    let mut high: u32 = 0;
+
+    // SAFETY: The path is a valid null-terminated string that outlives the call and we pass a
+    // valid pointer to a local for the high half of the size.
+    let low = unsafe {
+        GetCompressedFileSizeA(
+            PCSTR::from_raw(path_cstr.as_ptr() as *const u8),
+            Some(&mut high),
+        )
+    };
+
+    // The low half of a valid size may be equal to the error marker, so we also need to check
+    // whether an error was actually set.
+    if low == INVALID_FILE_SIZE {
+        let error = windows::core::Error::from_win32();
+
+        if error.code() != NO_ERROR.into() {
+            return Err(error.into());
+        }
+    }
+
+    Ok((u64::from(high) << 32) | u64::from(low))
+}
@@ -0,0 +1,234 @@
+use crate::{
+    io,
+    rt::{spawn_sync, SynchronousTaskType},
+    windows::OwnedHandle,
+};
+use negative_impl::negative_impl;
+use std::{
+    ffi::CString,
+    ops::{Deref, DerefMut, Range},
+    path::Path,
+    slice,
+    sync::Arc,
+};
+use windows::{
+    core::PCSTR,
+    Win32::{
+        Foundation::HANDLE,
+        Storage::FileSystem::{
+            CreateFileA, FlushFileBuffers, GetFileSizeEx, FILE_ATTRIBUTE_NORMAL, FILE_GENERIC_READ,
+            FILE_GENERIC_WRITE, FILE_SHARE_READ, OPEN_EXISTING,
+        },
+        System::Memory::{
+            CreateFileMappingA, FlushViewOfFile, MapViewOfFile, UnmapViewOfFile, FILE_MAP_READ,
+            FILE_MAP_WRITE, MEMORY_MAPPED_VIEW_ADDRESS, PAGE_READWRITE,
+        },
+    },
+};
+
+/// A writable memory-mapped view of an entire existing file. Writes into the view update the file
+/// in the OS cache, from which the OS writes them back to storage at a time of its choosing.
+///
+/// To control when the data reaches storage, use `flush()` to wait until a range of the view is
+/// durably stored or `flush_async()` to start writing back a range without waiting for it.
+///
+/// The size of the file is fixed for the lifetime of the view. Empty files cannot be mapped.
+#[derive(Debug)]
+pub struct MmapMut {
+    // Shared with the synchronous worker threads performing flushes, so the view remains mapped
+    // until any flush in progress has completed, even if the caller stopped waiting for it.
+    view: Arc<MappedView>,
+}
+
+impl MmapMut {
+    /// Opens an existing file for reading and writing and maps all of it into memory.
+    pub async fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path_cstr = CString::new(path.as_ref().to_str().unwrap()).unwrap();
+
+        // Opening and mapping the file are blocking operations, so we kick them off to a
+        // synchronous worker thread to avoid blocking the async workers with these slow calls.
+        let view = spawn_sync(SynchronousTaskType::Syscall, move || -> io::Result<_> {
+            // SAFETY: The path is a valid null-terminated string that outlives the call and we
+            // take ownership of the returned handle, closing it when dropped.
+            let file = unsafe {
+                OwnedHandle::new(CreateFileA(
+                    PCSTR::from_raw(path_cstr.as_ptr() as *const u8),
+                    (FILE_GENERIC_READ | FILE_GENERIC_WRITE).0,
+                    FILE_SHARE_READ,
+                    None,
+                    OPEN_EXISTING,
+                    FILE_ATTRIBUTE_NORMAL,
+                    None,
+                )?)
+            };
+
+            let mut size: i64 = 0;
+
+            // SAFETY: The handle is valid and we pass a valid pointer to a local.
+            unsafe {
+                GetFileSizeEx(*file, &mut size)?;
+            }
+
+            // SAFETY: The handle is valid and we take ownership of the returned handle, closing it
+            // when dropped. A size of zero maps the file at its current size.
+            let mapping = unsafe {
+                OwnedHandle::new(CreateFileMappingA(
+                    *file,
+                    None,
+                    PAGE_READWRITE,
+                    0,
+                    0,
+                    PCSTR::null(),
+                )?)
+            };
+
+            // SAFETY: The mapping is valid and the view is unmapped when the `MappedView` is
+            // dropped. A length of zero maps the entire file.
+            let address =
+                unsafe { MapViewOfFile(*mapping, FILE_MAP_READ | FILE_MAP_WRITE, 0, 0, 0) };
+
+            if address.Value.is_null() {
+                return Err(windows::core::Error::from_win32().into());
+            }
+
+            Ok(MappedView {
+                address,
+                len: size as usize,
+                _mapping: mapping,
+                file,
+            })
+        })
+        .await?;
+
+        Ok(Self {
+            view: Arc::new(view),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.view.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.view.len == 0
+    }
+
+    /// Writes the modified data in a range of the view back to the file and waits until both the
+    /// data and the file metadata have been durably stored. The blocking work happens on a
+    /// synchronous worker thread, so the async worker thread remains free for other tasks.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds of the view.
+    pub async fn flush(&self, range: Range<usize>) -> io::Result<()> {
+        self.view.check_range(&range);
+
+        if range.is_empty() {
+            return Ok(());
+        }
+
+        let view = Arc::clone(&self.view);
+
+        spawn_sync(SynchronousTaskType::Syscall, move || -> io::Result<_> {
+            // Flushing the view only starts writing the data to storage, so we also need to flush
+            // the file to wait for the data (and the metadata) to actually be stored.
+            view.flush_view(&range)?;
+
+            // SAFETY: The handle is valid because we are holding a reference to the view.
+            unsafe {
+                FlushFileBuffers(*view.file)?;
+            }
+
+            Ok(())
+        })
+        .await
+    }
+
+    /// Starts writing the modified data in a range of the view back to the file, without waiting
+    /// for the data to be durably stored. Use `flush()` if you need to know when the data has been
+    /// stored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds of the view.
+    pub fn flush_async(&self, range: Range<usize>) -> io::Result<()> {
+        self.view.check_range(&range);
+
+        if range.is_empty() {
+            return Ok(());
+        }
+
+        self.view.flush_view(&range)
+    }
+}
+
+impl Deref for MmapMut {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: The view remains mapped for as long as we hold a reference to it and nobody else
+        // accesses the memory - flushes only hand the address to the operating system.
+        unsafe { slice::from_raw_parts(self.view.address.Value as *const u8, self.view.len) }
+    }
+}
+
+impl DerefMut for MmapMut {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: As above. We have exclusive access to self, so nobody else can touch the data.
+        unsafe { slice::from_raw_parts_mut(self.view.address.Value as *mut u8, self.view.len) }
+    }
+}
+
+#[negative_impl]
+impl !Send for MmapMut {}
+#[negative_impl]
+impl !Sync for MmapMut {}
+
+#[derive(Debug)]
+struct MappedView {
+    address: MEMORY_MAPPED_VIEW_ADDRESS,
+    len: usize,
+
+    // The view keeps the mapping alive on its own but we do not want to rely on that.
+    _mapping: OwnedHandle<HANDLE>,
+    file: OwnedHandle<HANDLE>,
+}
+
+impl MappedView {
+    fn check_range(&self, range: &Range<usize>) {
+        assert!(
+            range.start <= range.end && range.end <= self.len,
+            "flush range {range:?} out of bounds of view with length {}",
+            self.len
+        );
+    }
+
+    fn flush_view(&self, range: &Range<usize>) -> io::Result<()> {
+        // SAFETY: The range is within the view, which is mapped for as long as we exist. The
+        // range must not be empty, as a length of zero means "flush the rest of the view".
+        unsafe {
+            FlushViewOfFile(
+                (self.address.Value as *const u8).add(range.start) as *const _,
+                range.len(),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+// SAFETY: The view is just a memory address that is valid on any thread and the handles are
+// thread-safe. Access to the memory itself is mediated by `MmapMut`.
+unsafe impl Send for MappedView {}
+// SAFETY: As above.
+unsafe impl Sync for MappedView {}
+
+impl Drop for MappedView {
+    fn drop(&mut self) {
+        // SAFETY: The address came from MapViewOfFile and nothing references the memory anymore.
+        // Unmapping only fails if the address is not a mapped view, which would be a bug of ours.
+        unsafe {
+            UnmapViewOfFile(self.address).expect("unmapping a mapped view must succeed");
+        }
+    }
+}
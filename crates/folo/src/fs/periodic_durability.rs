@@ -0,0 +1,149 @@
+use crate::{
+    collections::BuildPointerHasher,
+    fs::BufWriter,
+    io,
+    rt::{spawn, spawn_sync, SynchronousTaskType},
+    time::sleep,
+    windows::OwnedHandle,
+};
+use negative_impl::negative_impl;
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    mem,
+    rc::{Rc, Weak},
+    sync::Arc,
+    time::Duration,
+};
+use windows::Win32::{Foundation::HANDLE, Storage::FileSystem::FlushFileBuffers};
+
+/// Makes data written via `BufWriter` durable in periodic batches. All the files committed during
+/// one period are flushed to storage together at the end of the period, which is group commit on
+/// a timer instead of on demand.
+///
+/// This trades latency for throughput - a commit waits for up to one period before it completes
+/// but all the commits of a period share the cost of one batch of flushes, instead of each commit
+/// paying for a flush of its own.
+///
+/// The flushes are performed by a task on the current async worker thread, which stops once the
+/// `PeriodicDurability` is dropped. Commits still waiting for a flush at that point fail.
+#[derive(Debug)]
+pub struct PeriodicDurability {
+    inner: Rc<Inner>,
+}
+
+impl PeriodicDurability {
+    /// Starts flushing the committed files to storage every `interval`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread is not an async worker thread owned by the Folo runtime.
+    pub fn new(interval: Duration) -> Self {
+        let inner = Rc::new(Inner::default());
+
+        _ = spawn(run_flushes(Rc::downgrade(&inner), interval));
+
+        Self { inner }
+    }
+
+    /// Writes any data buffered by the writer to the file and waits for the next periodic flush
+    /// to make it durable. Completes only once the data has been flushed to storage.
+    pub async fn commit(&self, writer: &mut BufWriter) -> io::Result<()> {
+        writer.flush().await?;
+
+        let file = Arc::clone(writer.file());
+        let (tx, rx) = oneshot::channel();
+
+        // Commits of the same file during the same period share one flush.
+        self.inner
+            .pending
+            .borrow_mut()
+            .entry(Arc::as_ptr(&file))
+            .or_insert_with(|| PendingFile {
+                file,
+                waiters: Vec::new(),
+            })
+            .waiters
+            .push(tx);
+
+        rx.await.unwrap_or_else(|_| {
+            Err(io::Error::LogicError(
+                "periodic durability was dropped before the commit was flushed".to_string(),
+            ))
+        })
+    }
+
+    /// Number of periodic flushes that have completed. Periods without any commits do not flush.
+    pub fn flushes(&self) -> u64 {
+        self.inner.flushes.get()
+    }
+}
+
+#[negative_impl]
+impl !Send for PeriodicDurability {}
+#[negative_impl]
+impl !Sync for PeriodicDurability {}
+
+#[derive(Debug, Default)]
+struct Inner {
+    // Files committed since the last flush started, keyed by the address of their shared handle.
+    // Commits made while a flush is in progress wait for the next flush, as the flush may have
+    // already passed their file.
+    pending: RefCell<HashMap<*const OwnedHandle<HANDLE>, PendingFile, BuildPointerHasher>>,
+
+    flushes: Cell<u64>,
+}
+
+#[derive(Debug)]
+struct PendingFile {
+    // Keeps the file open until it has been flushed, even if the writer is dropped meanwhile.
+    file: Arc<OwnedHandle<HANDLE>>,
+
+    waiters: Vec<oneshot::Sender<io::Result<()>>>,
+}
+
+async fn run_flushes(inner: Weak<Inner>, interval: Duration) {
+    loop {
+        sleep(interval).await;
+
+        let Some(strong_inner) = inner.upgrade() else {
+            return;
+        };
+
+        let batch = mem::take(&mut *strong_inner.pending.borrow_mut());
+
+        // We do not keep the owner alive while flushing, so dropping it does not have to wait.
+        drop(strong_inner);
+
+        if batch.is_empty() {
+            continue;
+        }
+
+        let (files, waiters): (Vec<_>, Vec<_>) = batch
+            .into_values()
+            .map(|pending| (pending.file, pending.waiters))
+            .unzip();
+
+        // Flushing is a blocking operation, so we kick it off to a synchronous worker thread to
+        // avoid blocking the async workers with these slow calls. The handles stay open until then.
+        let results = spawn_sync(SynchronousTaskType::Syscall, move || {
+            files
+                .iter()
+                // SAFETY: The handle is valid because we hold a reference to it.
+                .map(|file| unsafe { FlushFileBuffers(***file) })
+                .collect::<Vec<_>>()
+        })
+        .await;
+
+        if let Some(strong_inner) = inner.upgrade() {
+            strong_inner.flushes.set(strong_inner.flushes.get() + 1);
+        }
+
+        for (result, waiters) in results.into_iter().zip(waiters) {
+            for waiter in waiters {
+                // The commit may have been abandoned by its caller, which is fine.
+                _ = waiter.send(result.clone().map_err(io::Error::from));
+            }
+        }
+    }
+}
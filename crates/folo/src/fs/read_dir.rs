@@ -0,0 +1,160 @@
+use crate::{
+    io,
+    rt::{spawn_sync, SynchronousTaskType},
+};
+use futures::{stream, Stream, StreamExt};
+use std::{
+    ffi::OsStr,
+    fs::ReadDir,
+    os::windows::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+use windows::Win32::Storage::FileSystem::{FILE_ATTRIBUTE_DIRECTORY, FILE_ATTRIBUTE_REPARSE_POINT};
+
+/// The type of an item found by `read_dir()`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FileType {
+    File,
+    Dir,
+
+    /// A reparse point (junction, symbolic link, ...), which may lead anywhere on the system.
+    /// `is_dir` tells whether it is a link to a directory.
+    ReparsePoint {
+        is_dir: bool,
+    },
+}
+
+impl FileType {
+    /// Whether the item is a directory or a reparse point that links to a directory.
+    pub fn is_dir(&self) -> bool {
+        matches!(self, Self::Dir | Self::ReparsePoint { is_dir: true })
+    }
+
+    /// Whether the item is a regular file or a reparse point that links to a file.
+    pub fn is_file(&self) -> bool {
+        !self.is_dir()
+    }
+
+    pub fn is_reparse_point(&self) -> bool {
+        matches!(self, Self::ReparsePoint { .. })
+    }
+}
+
+/// An item found by `read_dir()`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DirEntry {
+    pub path: PathBuf,
+
+    /// The type of the item itself - reparse points are not followed.
+    pub file_type: FileType,
+}
+
+impl DirEntry {
+    /// The name of the item, without the path of the directory it is in.
+    pub fn file_name(&self) -> &OsStr {
+        self.path
+            .file_name()
+            .expect("directory entries always have a file name")
+    }
+}
+
+/// Lists the contents of the directory at `path`, in no particular order. The entries for the
+/// directory itself (`.`) and its parent (`..`) are not included.
+///
+/// Reparse points among the contents are reported as such and not followed. If `path` itself is a
+/// reparse point, the contents of its target are listed. Paths longer than `MAX_PATH` are
+/// supported.
+pub async fn read_dir(path: impl AsRef<Path>) -> io::Result<Vec<DirEntry>> {
+    let path = path.as_ref().to_path_buf();
+
+    // Listing a directory is a blocking operation (the OS offers no overlapped variant), so we
+    // kick it off to a synchronous worker thread to avoid blocking the async workers.
+    spawn_sync(SynchronousTaskType::Syscall, move || {
+        read_dir_blocking(&path)
+    })
+    .await
+}
+
+/// Same as `read_dir()` but returns a stream of the entries, which are listed in batches as the
+/// stream is consumed instead of all at once. This keeps memory usage bounded for directories with
+/// a very large number of entries.
+///
+/// If listing the directory fails, the stream yields the error. Errors for individual entries are
+/// yielded in place of the entries and the listing continues after them.
+pub fn read_dir_stream(path: impl AsRef<Path>) -> impl Stream<Item = io::Result<DirEntry>> {
+    let state = ReadDirState::NotOpened(path.as_ref().to_path_buf());
+
+    stream::unfold(Some(state), |state| async move {
+        let state = state?;
+
+        // Listing a directory is a blocking operation (the OS offers no overlapped variant), so we
+        // kick it off to a synchronous worker thread, one batch of entries at a time.
+        let (entries, state) = spawn_sync(SynchronousTaskType::Syscall, move || {
+            read_dir_batch_blocking(state)
+        })
+        .await;
+
+        Some((stream::iter(entries), state))
+    })
+    .flatten()
+}
+
+// How many entries `read_dir_stream()` lists per trip to a synchronous worker thread.
+const READ_DIR_BATCH_SIZE: usize = 64;
+
+enum ReadDirState {
+    NotOpened(PathBuf),
+
+    // The iterator holds a find buffer of several hundred bytes, so we keep it on the heap.
+    Open(Box<ReadDir>),
+}
+
+/// Lists the next batch of entries, returning the state to continue from if there may be more.
+fn read_dir_batch_blocking(
+    state: ReadDirState,
+) -> (Vec<io::Result<DirEntry>>, Option<ReadDirState>) {
+    let mut read_dir = match state {
+        ReadDirState::NotOpened(path) => match std::fs::read_dir(path) {
+            Ok(read_dir) => Box::new(read_dir),
+            Err(e) => return (vec![Err(e.into())], None),
+        },
+        ReadDirState::Open(read_dir) => read_dir,
+    };
+
+    let entries = read_dir
+        .by_ref()
+        .take(READ_DIR_BATCH_SIZE)
+        .map(to_dir_entry)
+        .collect::<Vec<_>>();
+
+    if entries.len() < READ_DIR_BATCH_SIZE {
+        (entries, None)
+    } else {
+        (entries, Some(ReadDirState::Open(read_dir)))
+    }
+}
+
+fn read_dir_blocking(path: &Path) -> io::Result<Vec<DirEntry>> {
+    std::fs::read_dir(path)?.map(to_dir_entry).collect()
+}
+
+fn to_dir_entry(entry: std::io::Result<std::fs::DirEntry>) -> io::Result<DirEntry> {
+    let entry = entry?;
+
+    // This does not follow reparse points, so we see the attributes of the link itself.
+    let attributes = entry.metadata()?.file_attributes();
+    let is_dir = attributes & FILE_ATTRIBUTE_DIRECTORY.0 != 0;
+
+    let file_type = if attributes & FILE_ATTRIBUTE_REPARSE_POINT.0 != 0 {
+        FileType::ReparsePoint { is_dir }
+    } else if is_dir {
+        FileType::Dir
+    } else {
+        FileType::File
+    };
+
+    Ok(DirEntry {
+        path: entry.path(),
+        file_type,
+    })
+}
@@ -0,0 +1,95 @@
+use crate::{
+    fs::File,
+    io::{self, LineSplitter, PinnedBuffer},
+};
+use futures::Stream;
+use std::{error::Error, path::Path};
+
+/// Opens a file of line-delimited records (e.g. NDJSON) and returns a stream of the records,
+/// parsed from each line by the provided function. For example, to read NDJSON with `serde_json`:
+///
+/// ```ignore
+/// let records = folo::fs::records(path, |line| serde_json::from_str::<MyRecord>(line)).await?;
+/// ```
+///
+/// The file is read in buffers of limited size as the stream is consumed, so memory usage stays
+/// bounded regardless of the size of the file.
+///
+/// Lines are terminated by `\n` or `\r\n` and empty lines are skipped. Bytes that are not valid
+/// UTF-8 are replaced with `U+FFFD REPLACEMENT CHARACTER` before parsing.
+///
+/// If a line fails to parse, the stream yields an `io::Error::Other` for that record and continues
+/// with the next line. If reading the file fails, the stream yields the error and ends.
+pub async fn records<T, F, E>(
+    path: impl AsRef<Path>,
+    parse: F,
+) -> io::Result<impl Stream<Item = io::Result<T>>>
+where
+    F: FnMut(&str) -> Result<T, E>,
+    E: Into<Box<dyn Error + Send + Sync>>,
+{
+    let state = RecordsState {
+        file: File::open(path).await?,
+        offset: 0,
+        splitter: LineSplitter::new(),
+        parse,
+        finished: false,
+    };
+
+    Ok(futures::stream::unfold(state, |mut state| async move {
+        loop {
+            let line = match state.splitter.next_line() {
+                Some(line) => line,
+                None if state.finished => return None,
+                None => {
+                    match state
+                        .file
+                        .read_at(state.offset, PinnedBuffer::from_pool())
+                        .await
+                    {
+                        Ok(buffer) if buffer.is_empty() => {
+                            // Anything after the last line terminator is still a line, just an
+                            // unterminated one.
+                            state.finished = true;
+
+                            match state.splitter.finish() {
+                                Some(line) => line,
+                                None => return None,
+                            }
+                        }
+                        Ok(buffer) => {
+                            state.offset += buffer.len();
+                            state.splitter.push(buffer.as_slice());
+                            continue;
+                        }
+                        Err(e) => {
+                            state.finished = true;
+                            return Some((Err(e), state));
+                        }
+                    }
+                }
+            };
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let record = (state.parse)(&line).map_err(|e| io::Error::Other(e.into()));
+            return Some((record, state));
+        }
+    }))
+}
+
+struct RecordsState<F> {
+    file: File,
+
+    // Where in the file to continue reading from.
+    offset: usize,
+
+    splitter: LineSplitter,
+    parse: F,
+
+    // Set once the end of the file has been reached or reading has failed - there will be no more
+    // data.
+    finished: bool,
+}
@@ -0,0 +1,111 @@
+use crate::{fs::read, io};
+use std::{future::Future, path::Path};
+use tracing::{event, Level};
+
+/// Options for `read_resilient()`.
+#[derive(Clone, Copy, Debug)]
+pub struct ReopenPolicy {
+    /// How many times the file may be reopened after the read fails due to a stale handle, before
+    /// giving up and returning the error.
+    pub max_reopens: usize,
+}
+
+impl Default for ReopenPolicy {
+    fn default() -> Self {
+        Self { max_reopens: 3 }
+    }
+}
+
+/// Read the contents of a file to a vector of bytes, reopening the file and starting the read
+/// from scratch if the file handle becomes stale during the read (see
+/// `io::Error::is_stale_handle()`), e.g. because the volume the file is on was remounted.
+///
+/// Other errors are returned immediately, as reopening the file would not help with them.
+pub async fn read_resilient(path: impl AsRef<Path>, policy: ReopenPolicy) -> io::Result<Vec<u8>> {
+    let path = path.as_ref();
+
+    // Every read opens the file anew and closes it when done, so a retry always uses a new handle.
+    retry_with_reopen(policy, || read(path)).await
+}
+
+async fn retry_with_reopen<T, F, R>(policy: ReopenPolicy, mut attempt: F) -> io::Result<T>
+where
+    F: FnMut() -> R,
+    R: Future<Output = io::Result<T>>,
+{
+    let mut reopens = 0;
+
+    loop {
+        match attempt().await {
+            Err(e) if e.is_stale_handle() && reopens < policy.max_reopens => {
+                reopens += 1;
+
+                event!(
+                    Level::DEBUG,
+                    message = "reopening file after stale handle error",
+                    error = %e,
+                    reopens
+                );
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use std::cell::Cell;
+    use windows::Win32::Foundation::{ERROR_ACCESS_DENIED, STATUS_VOLUME_DISMOUNTED};
+
+    fn volume_dismounted() -> io::Error {
+        io::Error::Windows(STATUS_VOLUME_DISMOUNTED.into())
+    }
+
+    #[test]
+    fn reopens_after_stale_handle() {
+        let attempts = Cell::new(0);
+
+        // The handle goes stale during the first attempt, standing in for a remounted volume.
+        let result = block_on(retry_with_reopen(ReopenPolicy::default(), || {
+            attempts.set(attempts.get() + 1);
+
+            let result = match attempts.get() {
+                1 => Err(volume_dismounted()),
+                _ => Ok(b"contents".to_vec()),
+            };
+
+            async { result }
+        }));
+
+        assert_eq!(result.unwrap(), b"contents");
+        assert_eq!(attempts.get(), 2);
+    }
+
+    #[test]
+    fn gives_up_after_max_reopens() {
+        let attempts = Cell::new(0);
+
+        let result = block_on(retry_with_reopen(ReopenPolicy { max_reopens: 2 }, || {
+            attempts.set(attempts.get() + 1);
+            async { Err::<(), _>(volume_dismounted()) }
+        }));
+
+        assert!(result.unwrap_err().is_stale_handle());
+        assert_eq!(attempts.get(), 3);
+    }
+
+    #[test]
+    fn does_not_reopen_after_other_error() {
+        let attempts = Cell::new(0);
+
+        let result = block_on(retry_with_reopen(ReopenPolicy::default(), || {
+            attempts.set(attempts.get() + 1);
+            async { Err::<(), _>(io::Error::Windows(ERROR_ACCESS_DENIED.into())) }
+        }));
+
+        assert!(!result.unwrap_err().is_stale_handle());
+        assert_eq!(attempts.get(), 1);
+    }
+}
@@ -0,0 +1,251 @@
+use crate::{
+    fs::functions::APPEND_OFFSET,
+    io::{self, IoClass, IoOperationKind, PinnedBuffer},
+    rt::{current_async_agent, spawn_sync, SynchronousTaskType},
+    sync::LocalSemaphore,
+    windows::OwnedHandle,
+};
+use negative_impl::negative_impl;
+use std::{
+    cell::RefCell,
+    ffi::OsString,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use windows::{
+    core::HSTRING,
+    Win32::{
+        Foundation::{ERROR_ALREADY_EXISTS, ERROR_FILE_EXISTS, HANDLE},
+        Storage::FileSystem::{
+            CreateFileW, FlushFileBuffers, MoveFileExW, WriteFile, FILE_FLAG_OVERLAPPED,
+            FILE_GENERIC_WRITE, FILE_SHARE_DELETE, FILE_SHARE_READ, MOVE_FILE_FLAGS, OPEN_ALWAYS,
+        },
+    },
+};
+
+// Maximum size of a single write submitted to the OS. Longer writes are split into multiple
+// writes of up to this size.
+const MAX_WRITE_SIZE_BYTES: usize = 10 * 1024 * 1024;
+
+/// A log file that is appended to and can be rotated: the current file is moved aside under a
+/// timestamped name and writing continues in a new file at the original path.
+///
+/// Writes and rotations take turns - a write that starts during a rotation waits for the rotation
+/// to finish, so each write lands entirely in either the rotated file or the new file.
+///
+/// The log is bound to the I/O driver of the async worker thread that opened it, so all I/O on
+/// the log must happen on that same thread.
+#[derive(Debug)]
+pub struct RotatingLog {
+    path: PathBuf,
+
+    // Replaced with a handle to the new file when the log is rotated. Shared with the synchronous
+    // worker thread that flushes and renames the file during rotation, so the handle stays open
+    // until it is done, even if the rotation is abandoned in the meantime.
+    handle: RefCell<Arc<OwnedHandle<HANDLE>>>,
+
+    // Held by writes and rotations while they are in progress, so they never overlap.
+    turn: LocalSemaphore<1>,
+}
+
+impl RotatingLog {
+    /// Opens the log file at the given path for appending, creating it if it does not exist.
+    pub async fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let handle = open_for_append(path.clone()).await?;
+
+        Ok(Self {
+            path,
+            handle: RefCell::new(Arc::new(handle)),
+            turn: LocalSemaphore::new(),
+        })
+    }
+
+    /// The path of the current log file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends the data to the end of the current log file.
+    pub async fn write(&self, data: &[u8]) -> io::Result<()> {
+        let _turn = self.turn.acquire().await;
+
+        // The handle is not replaced while we hold the turn.
+        let handle = ***self.handle.borrow();
+
+        let mut buffer = PinnedBuffer::from_boxed_slice(data.into());
+        let mut written = 0;
+
+        // The OS is within its rights to write only a part of what we asked for, so we need to be
+        // prepared to loop until everything is written.
+        while written < data.len() {
+            buffer.set_len(0);
+            buffer.set_start(written);
+            buffer.set_len((data.len() - written).min(MAX_WRITE_SIZE_BYTES));
+
+            let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+            operation.set_offset(APPEND_OFFSET);
+            operation.set_handle(&handle);
+            operation.set_kind(IoOperationKind::Write);
+
+            // SAFETY: For safe usage of the I/O driver API, we are required to pass the
+            // `overlapped` argument to a native I/O call under all circumstances, to trigger an
+            // I/O completion. We do. We are also not allowed to use any of the callback arguments
+            // after the callback, even if the Rust compiler might allow us to.
+            buffer = unsafe {
+                operation
+                    .begin(|buffer, overlapped, bytes_transferred_immediately| {
+                        Ok(WriteFile(
+                            handle,
+                            Some(buffer),
+                            Some(bytes_transferred_immediately as *mut _),
+                            Some(overlapped),
+                        )?)
+                    })
+                    .await
+                    .map_err(io::OperationError::into_inner)?
+            };
+
+            written += buffer.len();
+        }
+
+        Ok(())
+    }
+
+    /// Rotates the log: flushes the current file to storage, renames it to a timestamped name in
+    /// the same directory (e.g. `app.log` becomes `app.1728990000123.log`) and continues writing
+    /// to a new file at the original path. Returns the path the current file was renamed to.
+    ///
+    /// If the rotation fails, writing continues to the file that was current before the rotation.
+    /// If the failure happens after the file was renamed, this is the renamed file.
+    pub async fn rotate(&self) -> io::Result<PathBuf> {
+        let _turn = self.turn.acquire().await;
+
+        let handle = Arc::clone(&self.handle.borrow());
+
+        let from = self.path.clone();
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+
+        // Flushing and renaming are blocking operations, so we kick them off to a synchronous
+        // worker thread to avoid blocking the async workers with these slow calls.
+        let to = spawn_sync(SynchronousTaskType::Syscall, move || {
+            flush_and_rename(&handle, &from, timestamp)
+        })
+        .await?;
+
+        let new_handle = open_for_append(self.path.clone()).await?;
+
+        // The old handle is closed here. Every write to it has completed because we hold the turn.
+        *self.handle.borrow_mut() = Arc::new(new_handle);
+
+        Ok(to)
+    }
+}
+
+#[negative_impl]
+impl !Send for RotatingLog {}
+#[negative_impl]
+impl !Sync for RotatingLog {}
+
+async fn open_for_append(path: PathBuf) -> io::Result<OwnedHandle<HANDLE>> {
+    // Opening the file is a blocking operation, so we kick it off to a synchronous worker thread
+    // to avoid blocking the async workers with this slow call.
+    let handle = spawn_sync(SynchronousTaskType::Syscall, move || -> io::Result<_> {
+        // SAFETY: The path is a valid null-terminated string that outlives the call and we take
+        // ownership of the returned handle, closing it when dropped.
+        Ok(unsafe {
+            OwnedHandle::new(CreateFileW(
+                &HSTRING::from(path.as_path()),
+                FILE_GENERIC_WRITE.0,
+                // Sharing delete access is what allows us to rename the file while it is open.
+                FILE_SHARE_READ | FILE_SHARE_DELETE,
+                None,
+                OPEN_ALWAYS,
+                FILE_FLAG_OVERLAPPED,
+                None,
+            )?)
+        })
+    })
+    .await?;
+
+    current_async_agent::with_io(|io| io.bind_io_primitive(&*handle, IoClass::Disk))?;
+
+    Ok(handle)
+}
+
+fn flush_and_rename(handle: &HANDLE, from: &Path, timestamp: u128) -> io::Result<PathBuf> {
+    // SAFETY: The caller guarantees that the handle remains valid for the duration of the call.
+    unsafe {
+        FlushFileBuffers(*handle)?;
+    }
+
+    // Our handle allows the file to be renamed while open. We never ask the OS to replace an
+    // existing file - if there already is a file with the same timestamp (e.g. from a rotation in
+    // the same millisecond), we add a sequence number to the name.
+    let mut sequence = 0;
+
+    loop {
+        let to = rotated_path(from, timestamp, sequence);
+
+        // SAFETY: The paths are valid null-terminated strings that outlive the call.
+        match unsafe {
+            MoveFileExW(
+                &HSTRING::from(from),
+                &HSTRING::from(to.as_path()),
+                MOVE_FILE_FLAGS(0),
+            )
+        } {
+            Ok(()) => return Ok(to),
+            Err(e)
+                if e.code() == ERROR_ALREADY_EXISTS.into()
+                    || e.code() == ERROR_FILE_EXISTS.into() =>
+            {
+                sequence += 1;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Inserts a timestamp between the file stem and the extension: `app.log` -> `app.<millis>.log`.
+/// A nonzero sequence number is appended to the timestamp: `app.<millis>-<sequence>.log`.
+fn rotated_path(path: &Path, timestamp: u128, sequence: u32) -> PathBuf {
+    let mut file_name = OsString::from(path.file_stem().unwrap_or_default());
+
+    match sequence {
+        0 => file_name.push(format!(".{timestamp}")),
+        _ => file_name.push(format!(".{timestamp}-{sequence}")),
+    }
+
+    if let Some(extension) = path.extension() {
+        file_name.push(".");
+        file_name.push(extension);
+    }
+
+    path.with_file_name(file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotated_path_keeps_extension() {
+        assert_eq!(
+            rotated_path(Path::new("logs/app.log"), 1234, 0),
+            Path::new("logs/app.1234.log")
+        );
+        assert_eq!(
+            rotated_path(Path::new("logs/app.log"), 1234, 2),
+            Path::new("logs/app.1234-2.log")
+        );
+        assert_eq!(
+            rotated_path(Path::new("app"), 1234, 0),
+            Path::new("app.1234")
+        );
+    }
+}
@@ -0,0 +1,126 @@
+use crate::{
+    io,
+    rt::{spawn_sync, SynchronousTaskType},
+};
+use std::path::{Path, PathBuf};
+use windows::{
+    core::HSTRING,
+    Win32::{
+        Foundation::ERROR_HANDLE_EOF,
+        Storage::FileSystem::{
+            FindClose, FindFirstStreamW, FindNextStreamW, FindStreamInfoStandard,
+            WIN32_FIND_STREAM_DATA,
+        },
+    },
+};
+
+/// Describes a named data stream of a file (an NTFS alternate data stream).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StreamInfo {
+    /// The name of the stream, without the leading colon and the `:$DATA` stream type suffix.
+    ///
+    /// To access the stream, append `:` and the name to the path of the file.
+    pub name: String,
+
+    /// Size of the stream in bytes.
+    pub size: u64,
+}
+
+/// Lists the named data streams (NTFS alternate data streams) of a file, together with their
+/// sizes. The default unnamed stream that holds the regular contents of the file is not included,
+/// so a file without any alternate data streams yields an empty list.
+///
+/// If the path already names a stream (`file.txt:name`), the streams of the file it belongs to are
+/// listed.
+///
+/// The individual streams can be opened by appending `:` and the stream name to the path of the
+/// file, e.g. via `File::open("file.txt:name")`.
+pub async fn streams(path: impl AsRef<Path>) -> io::Result<Vec<StreamInfo>> {
+    let path = without_stream_name(path.as_ref());
+
+    // Enumerating streams is a blocking operation, so we kick it off to a synchronous worker
+    // thread to avoid blocking the async workers with these slow calls.
+    spawn_sync(SynchronousTaskType::Syscall, move || find_streams(&path)).await
+}
+
+fn without_stream_name(path: &Path) -> PathBuf {
+    // The stream name, if any, is in the last component of the path, separated by a colon. A
+    // colon in any earlier component can only be part of a drive specifier.
+    match path.file_name().and_then(|name| name.to_str()) {
+        Some(name) if name.contains(':') => {
+            let file_name = name
+                .split(':')
+                .next()
+                .expect("split always yields one item");
+            path.with_file_name(file_name)
+        }
+        _ => path.to_path_buf(),
+    }
+}
+
+fn find_streams(path: &Path) -> io::Result<Vec<StreamInfo>> {
+    let mut data = WIN32_FIND_STREAM_DATA::default();
+    let mut streams = Vec::new();
+
+    // SAFETY: The path outlives the call and the data structure is of the type that matches
+    // the requested information level. We close the returned handle before returning.
+    let find_handle = match unsafe {
+        FindFirstStreamW(
+            &HSTRING::from(path),
+            FindStreamInfoStandard,
+            &mut data as *mut _ as *mut _,
+            0,
+        )
+    } {
+        Ok(handle) => handle,
+        // There are no streams at all (e.g. this is a directory).
+        Err(e) if e.code() == ERROR_HANDLE_EOF.into() => return Ok(streams),
+        Err(e) => return Err(e.into()),
+    };
+
+    let result = loop {
+        if let Some(stream) = stream_info(&data) {
+            streams.push(stream);
+        }
+
+        // SAFETY: The handle is valid until we close it below and the data structure is of the
+        // type that matches the information level the handle was opened with.
+        match unsafe { FindNextStreamW(find_handle, &mut data as *mut _ as *mut _) } {
+            Ok(()) => {}
+            Err(e) if e.code() == ERROR_HANDLE_EOF.into() => break Ok(streams),
+            Err(e) => break Err(e.into()),
+        }
+    };
+
+    // SAFETY: The handle came from FindFirstStreamW() and is not used after this.
+    unsafe {
+        FindClose(find_handle)?;
+    }
+
+    result
+}
+
+/// Converts the raw stream data into a `StreamInfo`, returning `None` for the default stream.
+fn stream_info(data: &WIN32_FIND_STREAM_DATA) -> Option<StreamInfo> {
+    let len = data
+        .cStreamName
+        .iter()
+        .position(|c| *c == 0)
+        .unwrap_or(data.cStreamName.len());
+
+    // The raw name is in the form ":name:$DATA", with an empty name for the default stream.
+    let raw_name = String::from_utf16_lossy(&data.cStreamName[..len]);
+    let name = raw_name
+        .strip_prefix(':')
+        .and_then(|name| name.strip_suffix(":$DATA"))
+        .unwrap_or(&raw_name);
+
+    if name.is_empty() {
+        return None;
+    }
+
+    Some(StreamInfo {
+        name: name.to_string(),
+        size: data.StreamSize as u64,
+    })
+}
@@ -0,0 +1,94 @@
+use crate::{
+    fs::{create_dir, remove_dir_all},
+    io,
+};
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::{event, Level};
+
+static NEXT_TEMP_DIR_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A uniquely named directory under the system temporary directory, for scratch files that are to
+/// be deleted once they are no longer needed.
+///
+/// Call `cleanup()` to delete the directory and everything in it. If the `TempDir` is dropped
+/// without being cleaned up, the directory is deleted synchronously on drop, blocking the current
+/// thread. This is a fallback - prefer calling `cleanup()`.
+#[derive(Debug)]
+pub struct TempDir {
+    // None once cleaned up.
+    path: Option<PathBuf>,
+}
+
+impl TempDir {
+    /// Creates a new, empty temporary directory.
+    pub async fn new() -> io::Result<Self> {
+        // The process ID and the counter make the name unique within this boot session and the
+        // timestamp takes care of directories leaked by an earlier process that had the same ID.
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+
+        let path = std::env::temp_dir().join(format!(
+            "folo-{}-{}-{}",
+            std::process::id(),
+            NEXT_TEMP_DIR_ID.fetch_add(1, Ordering::Relaxed),
+            nanos
+        ));
+
+        create_dir(&path).await?;
+
+        Ok(Self { path: Some(path) })
+    }
+
+    /// The path of the temporary directory.
+    pub fn path(&self) -> &Path {
+        self.path
+            .as_deref()
+            .expect("path is only removed when the TempDir is consumed")
+    }
+
+    /// Deletes the temporary directory and everything in it.
+    pub async fn cleanup(mut self) -> io::Result<()> {
+        let path = self
+            .path
+            .take()
+            .expect("path is only removed when the TempDir is consumed");
+
+        remove_dir_all(path).await
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        self.path()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let Some(path) = self.path.take() else {
+            return;
+        };
+
+        // We cannot await in drop, so the best we can do is to block the current thread.
+        event!(
+            Level::WARN,
+            message = "TempDir dropped without cleanup() - deleting synchronously",
+            path = %path.display()
+        );
+
+        if let Err(e) = std::fs::remove_dir_all(&path) {
+            event!(
+                Level::WARN,
+                message = "failed to delete TempDir",
+                path = %path.display(),
+                error = %e
+            );
+        }
+    }
+}
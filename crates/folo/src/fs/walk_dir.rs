@@ -0,0 +1,140 @@
+use crate::{
+    io,
+    rt::{spawn_sync, SynchronousTaskType},
+};
+use std::{
+    collections::HashSet,
+    fs::OpenOptions,
+    os::windows::{
+        fs::{MetadataExt, OpenOptionsExt},
+        io::AsRawHandle,
+    },
+    path::{Path, PathBuf},
+};
+use windows::Win32::{
+    Foundation::HANDLE,
+    Storage::FileSystem::{
+        GetFileInformationByHandle, BY_HANDLE_FILE_INFORMATION, FILE_ATTRIBUTE_DIRECTORY,
+        FILE_ATTRIBUTE_REPARSE_POINT, FILE_FLAG_BACKUP_SEMANTICS,
+    },
+};
+
+/// Options for `walk_dir()`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WalkDirOptions {
+    /// Whether to walk into directories that are reparse points (junctions, directory symbolic
+    /// links, ...). Each directory is walked at most once, even if it is reachable via multiple
+    /// reparse points, so links that form a cycle do not cause an infinite walk.
+    ///
+    /// Reparse points are reported as entries either way.
+    pub follow_reparse_points: bool,
+}
+
+/// An item found by `walk_dir()`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WalkDirEntry {
+    pub path: PathBuf,
+
+    /// Whether the item is a directory. For a reparse point, whether it is a link to a directory.
+    pub is_dir: bool,
+
+    /// Whether the item is a reparse point (junction, symbolic link, ...) that may lead outside
+    /// the tree being walked. Unless `WalkDirOptions::follow_reparse_points` is set, the walk does
+    /// not continue into reparse points, leaving it up to the caller to decide what to do.
+    pub is_reparse_point: bool,
+}
+
+/// Recursively lists the contents of the directory `root`, parents before their children. The
+/// root itself is not included in the returned entries.
+pub async fn walk_dir(
+    root: impl AsRef<Path>,
+    options: WalkDirOptions,
+) -> io::Result<Vec<WalkDirEntry>> {
+    let root = root.as_ref().to_path_buf();
+
+    // Listing directories is a blocking operation, so we kick it off to a synchronous worker
+    // thread to avoid blocking the async workers with this slow call.
+    spawn_sync(SynchronousTaskType::Syscall, move || {
+        walk_dir_blocking(&root, options)
+    })
+    .await
+}
+
+fn walk_dir_blocking(root: &Path, options: WalkDirOptions) -> io::Result<Vec<WalkDirEntry>> {
+    if !std::fs::metadata(root)?.is_dir() {
+        return Err(std::io::Error::from(std::io::ErrorKind::NotADirectory).into());
+    }
+
+    let mut entries = Vec::new();
+
+    // Identities of the directories we have walked, to avoid walking a directory reachable via
+    // reparse points more than once (or infinitely many times if they form a cycle). Paths are
+    // not good enough for this, as the same directory is reachable via many paths.
+    let mut visited = HashSet::new();
+
+    if options.follow_reparse_points {
+        visited.insert(file_id(root)?);
+    }
+
+    let mut pending = vec![root.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+
+            // This does not follow reparse points, so we see the attributes of the link itself.
+            let attributes = entry.metadata()?.file_attributes();
+
+            let entry = WalkDirEntry {
+                path: entry.path(),
+                is_dir: attributes & FILE_ATTRIBUTE_DIRECTORY.0 != 0,
+                is_reparse_point: attributes & FILE_ATTRIBUTE_REPARSE_POINT.0 != 0,
+            };
+
+            let walk_into = if !entry.is_dir {
+                false
+            } else if !options.follow_reparse_points {
+                !entry.is_reparse_point
+            } else {
+                // This follows reparse points, so we get the identity of the target directory.
+                visited.insert(file_id(&entry.path)?)
+            };
+
+            if walk_into {
+                pending.push(entry.path.clone());
+            }
+
+            entries.push(entry);
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Uniquely identifies a file or directory on the system.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+struct FileId {
+    volume_serial_number: u32,
+    file_index: u64,
+}
+
+fn file_id(path: &Path) -> io::Result<FileId> {
+    // Backup semantics are required to open directories.
+    let file = OpenOptions::new()
+        .access_mode(0)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS.0)
+        .open(path)?;
+
+    let mut info = BY_HANDLE_FILE_INFORMATION::default();
+
+    // SAFETY: The handle is valid because we own the file until the end of the function and we
+    // pass a valid pointer to a local.
+    unsafe {
+        GetFileInformationByHandle(HANDLE(file.as_raw_handle()), &mut info)?;
+    }
+
+    Ok(FileId {
+        volume_serial_number: info.dwVolumeSerialNumber,
+        file_index: (info.nFileIndexHigh as u64) << 32 | info.nFileIndexLow as u64,
+    })
+}
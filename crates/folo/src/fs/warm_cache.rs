@@ -0,0 +1,157 @@
+use crate::{
+    fs::functions::{open_for_sequential_read, read_buffer_from_file_with_priority},
+    io::{self, IoClass, IoPriority, PinnedBuffer},
+    rt::current_async_agent,
+};
+use futures::{stream, Future, StreamExt, TryStreamExt};
+use std::{mem, path::Path};
+use windows::Win32::Storage::FileSystem::{
+    FileIoPriorityHintInfo, IoPriorityHintLow, SetFileInformationByHandle,
+    FILE_IO_PRIORITY_HINT_INFO,
+};
+
+// Size of the buffer each file is read into. The data is discarded as soon as it has been read, so
+// one buffer per file is reused for all the reads of that file.
+const WARM_BUFFER_SIZE_BYTES: usize = 1024 * 1024;
+
+/// Options for `warm_cache()`.
+#[derive(Clone, Copy, Debug)]
+pub struct WarmCacheOptions {
+    /// How many files to read concurrently.
+    pub concurrency: usize,
+}
+
+impl Default for WarmCacheOptions {
+    fn default() -> Self {
+        Self { concurrency: 4 }
+    }
+}
+
+/// Reads the files at the given paths to pull their contents into the OS file cache, so that
+/// later reads of the files are served from memory. Useful before a latency-sensitive phase of
+/// work or to separate cold and warm measurements when benchmarking.
+///
+/// The data read is discarded immediately - only one buffer per file being read is held in memory.
+/// The reads are submitted with low priority, both to the OS (which services them after any
+/// normal priority I/O to the same device) and to the in-flight operation limit of the worker
+/// thread (see `RuntimeBuilder::max_in_flight_io()`), to stay out of the way of foreground I/O.
+///
+/// Returns the first error encountered, without waiting for the rest of the files to be read.
+///
+/// # Panics
+///
+/// Panics if `options.concurrency` is zero.
+pub async fn warm_cache(
+    paths: impl IntoIterator<Item = impl AsRef<Path>>,
+    options: WarmCacheOptions,
+) -> io::Result<()> {
+    for_each_concurrent(paths, options.concurrency, warm_file).await
+}
+
+async fn for_each_concurrent<I, F, R>(items: I, concurrency: usize, f: F) -> io::Result<()>
+where
+    I: IntoIterator,
+    F: Fn(I::Item) -> R,
+    R: Future<Output = io::Result<()>>,
+{
+    assert!(concurrency > 0, "concurrency must be greater than zero");
+
+    stream::iter(items)
+        .map(Ok)
+        .try_for_each_concurrent(concurrency, f)
+        .await
+}
+
+async fn warm_file(path: impl AsRef<Path>) -> io::Result<()> {
+    let (file_handle, file_size) = open_for_sequential_read(path).await?;
+
+    let hint = FILE_IO_PRIORITY_HINT_INFO {
+        PriorityHint: IoPriorityHintLow,
+    };
+
+    // SAFETY: The handle is valid and we pass a valid pointer to a local of the specified size.
+    unsafe {
+        SetFileInformationByHandle(
+            *file_handle,
+            FileIoPriorityHintInfo,
+            &hint as *const _ as *const _,
+            mem::size_of_val(&hint) as u32,
+        )?;
+    }
+
+    current_async_agent::with_io(|io| io.bind_io_primitive(&*file_handle, IoClass::Disk))?;
+
+    let mut buffer = PinnedBuffer::from_boxed_slice(vec![0; WARM_BUFFER_SIZE_BYTES].into());
+    let mut offset = 0;
+
+    while offset < file_size {
+        buffer = read_buffer_from_file_with_priority(
+            &file_handle,
+            offset,
+            buffer.use_all(),
+            IoPriority::Low,
+            None,
+        )
+        .await
+        .map_err(io::OperationError::into_inner)?;
+
+        // The file got shorter while we were reading it. Nothing more to read.
+        if buffer.is_empty() {
+            break;
+        }
+
+        offset += buffer.len();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use std::cell::Cell;
+
+    #[test]
+    fn concurrency_is_bounded() {
+        let active = Cell::new(0);
+        let max_active = Cell::new(0);
+
+        block_on(for_each_concurrent(0..10, 3, |_| {
+            let active = &active;
+            let max_active = &max_active;
+
+            async move {
+                active.set(active.get() + 1);
+                max_active.set(max_active.get().max(active.get()));
+
+                // Give the other items a chance to start while this one is active.
+                for _ in 0..3 {
+                    yield_once().await;
+                }
+
+                active.set(active.get() - 1);
+                Ok(())
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(max_active.get(), 3);
+        assert_eq!(active.get(), 0);
+    }
+
+    async fn yield_once() {
+        let mut yielded = false;
+
+        futures::future::poll_fn(|cx| {
+            if yielded {
+                return std::task::Poll::Ready(());
+            }
+
+            yielded = true;
+            cx.waker().wake_by_ref();
+            std::task::Poll::Pending
+        })
+        .await
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{
+    fs::functions::{open_for_write, write_buffer_to_file},
+    io::{self, PinnedBuffer},
+    rt::{spawn_sync, SynchronousTaskType},
+    windows::OwnedHandle,
+};
+use futures::StreamExt;
+use std::{
+    mem,
+    path::{Path, PathBuf},
+};
+use windows::Win32::{Foundation::HANDLE, Storage::FileSystem::FlushFileBuffers};
+
+// Number of written files whose flushes are combined into one task on a synchronous worker thread.
+// Flushing is a blocking operation that needs to happen on a synchronous worker thread, so doing
+// it per file would cost a round trip to a synchronous worker for every file.
+const FLUSH_BATCH_SIZE: usize = 64;
+
+/// Options for `write_many()`.
+#[derive(Clone, Copy, Debug)]
+pub struct WriteManyOptions {
+    /// The maximum number of files to write at the same time.
+    pub concurrency: usize,
+
+    /// Whether to flush the files to storage before returning, so that their contents survive a
+    /// power loss. The flushes are performed in batches, which is much cheaper than flushing each
+    /// file on its own.
+    pub durable: bool,
+}
+
+impl Default for WriteManyOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 32,
+            durable: true,
+        }
+    }
+}
+
+/// Writes many files, each given as a path and its contents. Existing files are replaced and
+/// missing files are created, with the parent directories required to exist.
+///
+/// Returns the number of bytes written to each file or the error that prevented the file from
+/// being written, in the same order as the items. A failure to write one file does not affect the
+/// others, although the file that failed may be left with partial contents.
+pub async fn write_many<I, P, B>(items: I, options: WriteManyOptions) -> Vec<io::Result<usize>>
+where
+    I: IntoIterator<Item = (P, B)>,
+    P: AsRef<Path>,
+    B: Into<Box<[u8]>>,
+{
+    let mut writes = futures::stream::iter(items.into_iter().enumerate())
+        .map(|(index, (path, contents))| {
+            let path = path.as_ref().to_path_buf();
+            let contents = contents.into();
+
+            async move { (index, write_one(path, contents).await) }
+        })
+        .buffer_unordered(options.concurrency.max(1));
+
+    let mut results = Vec::new();
+
+    // Files that have been written but not yet flushed. We keep them open until they are flushed.
+    let mut unflushed = Vec::new();
+
+    while let Some((index, result)) = writes.next().await {
+        if results.len() <= index {
+            results.resize_with(index + 1, || None);
+        }
+
+        match result {
+            Ok((file_handle, bytes_written)) => {
+                results[index] = Some(Ok(bytes_written));
+
+                if options.durable {
+                    unflushed.push((index, file_handle));
+
+                    if unflushed.len() >= FLUSH_BATCH_SIZE {
+                        flush_batch(mem::take(&mut unflushed), &mut results).await;
+                    }
+                }
+            }
+            Err(e) => results[index] = Some(Err(e)),
+        }
+    }
+
+    flush_batch(unflushed, &mut results).await;
+
+    results
+        .into_iter()
+        .map(|result| result.expect("every item produces a result"))
+        .collect()
+}
+
+async fn write_one(path: PathBuf, contents: Box<[u8]>) -> io::Result<(OwnedHandle<HANDLE>, usize)> {
+    let file_handle = open_for_write(path).await?;
+    let bytes_written =
+        write_buffer_to_file(&file_handle, 0, PinnedBuffer::from_boxed_slice(contents))
+            .await
+            .map_err(io::OperationError::into_inner)?
+            .len();
+
+    Ok((file_handle, bytes_written))
+}
+
+/// Flushes the written files to storage, replacing the result of any file that fails to flush with
+/// the error. The files are closed afterwards.
+async fn flush_batch(
+    files: Vec<(usize, OwnedHandle<HANDLE>)>,
+    results: &mut [Option<io::Result<usize>>],
+) {
+    if files.is_empty() {
+        return;
+    }
+
+    // Flushing is a blocking operation, so we kick it off to a synchronous worker thread to avoid
+    // blocking the async workers with these slow calls. The handles are closed there, as well.
+    let errors = spawn_sync(SynchronousTaskType::Syscall, move || {
+        files
+            .into_iter()
+            .filter_map(|(index, file_handle)| {
+                // SAFETY: The handle is valid because we own it.
+                unsafe { FlushFileBuffers(*file_handle) }
+                    .err()
+                    .map(|e| (index, io::Error::from(e)))
+            })
+            .collect::<Vec<_>>()
+    })
+    .await;
+
+    for (index, error) in errors {
+        results[index] = Some(Err(error));
+    }
+}
@@ -1,31 +1,61 @@
+mod async_read;
+mod async_write;
+mod buf_reader;
 mod completion_port;
 mod completion_port_shared;
 mod driver;
 mod driver_shared;
+mod driver_stats;
 mod error;
+mod in_flight_limiter;
+mod line_splitter;
 mod operation;
 mod operation_shared;
 mod operation_result;
 mod operation_result_shared;
+mod pending_operations;
 mod pinned_buffer;
+mod pinned_buffer_pool;
 mod pinned_buffer_shared;
+mod pipe;
 mod primitive;
+mod priority;
+mod registered_handle;
+pub(crate) mod rio;
+mod slow_io;
+mod wait;
 mod waker;
 
+pub use async_read::*;
+pub use async_write::*;
+pub use buf_reader::*;
 pub(crate) use completion_port::*;
 pub(crate) use completion_port_shared::*;
 pub(crate) use driver::*;
 pub(crate) use driver_shared::*;
+pub use driver_stats::*;
 pub use error::*;
+pub use in_flight_limiter::IoPriority;
+pub(crate) use in_flight_limiter::*;
+pub(crate) use line_splitter::*;
 pub(crate) use operation::*;
 pub use operation_result::*;
 pub use operation_result_shared::*;
+pub(crate) use pending_operations::*;
 pub use pinned_buffer::*;
+pub use pinned_buffer_pool::PinnedBufferPool;
 pub use pinned_buffer_shared::*;
+pub use pipe::*;
 pub(crate) use primitive::*;
+pub use priority::*;
+pub use registered_handle::*;
+pub use slow_io::{IoOperationKind, SlowIoOperation};
+pub(crate) use slow_io::*;
+pub use wait::*;
 pub(crate) use waker::*;
 
-/// Max number of I/O operations to dequeue in one go. Presumably getting more data from the OS with
+/// Default max number of I/O operations to dequeue in one go, which can be changed via
+/// `RuntimeBuilder::io_dequeue_batch_size()`. Presumably getting more data from the OS with
 /// a single call is desirable but the exact impact of different values on performance is not known.
 ///
 /// Known aspects of performance impact:
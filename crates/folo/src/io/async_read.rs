@@ -0,0 +1,99 @@
+use crate::io::{self, PinnedBuffer};
+use std::future::Future;
+
+// Size of the buffer for the first read of `read_to_end()`. Sources that turn out to contain more
+// data get a bigger buffer, so this only needs to be large enough for typical small payloads.
+const READ_TO_END_INITIAL_SIZE: usize = 8 * 1024;
+
+// The buffer of `read_to_end()` doubles in size whenever it fills up but never grows by more than
+// this at a time, so a large source does not make us reserve much more memory than it needs.
+const READ_TO_END_MAX_GROWTH: usize = 16 * 1024 * 1024;
+
+/// A source of data that is read sequentially, one buffer at a time, such as a pipe or a socket.
+pub trait AsyncRead {
+    /// Reads the next bytes from the source into the active region of the buffer.
+    ///
+    /// The buffer is returned with the active region set to the bytes read. An empty active region
+    /// indicates that the end of the data has been reached.
+    fn read(&mut self, buffer: PinnedBuffer) -> impl Future<Output = io::Result<PinnedBuffer>>;
+}
+
+/// Helpers available on every `AsyncRead`.
+pub trait AsyncReadExt: AsyncRead {
+    /// Reads from the source until the end of the data is reached and returns all the bytes read.
+    ///
+    /// This is meant for sources whose size is not known upfront. The bytes are read into a
+    /// buffer that starts small and doubles in size whenever it fills up. If the source may never
+    /// end, use `read_to_end_limited()` to bound the memory used.
+    fn read_to_end(&mut self) -> impl Future<Output = io::Result<Vec<u8>>> {
+        self.read_to_end_limited(usize::MAX)
+    }
+
+    /// Reads from the source until the end of the data is reached and returns all the bytes read,
+    /// failing with `io::Error::StreamTooLong` if the source contains more than `max_bytes` bytes.
+    ///
+    /// The buffer never grows larger than `max_bytes + 1`, which is just enough to tell whether
+    /// the limit is exceeded.
+    fn read_to_end_limited(
+        &mut self,
+        max_bytes: usize,
+    ) -> impl Future<Output = io::Result<Vec<u8>>> {
+        async move {
+            let max_buffer_size = max_bytes.saturating_add(1);
+            let mut buffer = new_read_buffer(READ_TO_END_INITIAL_SIZE.min(max_buffer_size));
+            let mut bytes_read = 0;
+
+            loop {
+                buffer = self.read(buffer).await?;
+
+                if buffer.is_empty() {
+                    let mut as_vec = buffer.into_inner_boxed_slice().into_vec();
+                    as_vec.truncate(bytes_read);
+                    return Ok(as_vec);
+                }
+
+                bytes_read += buffer.len();
+
+                if bytes_read > max_bytes {
+                    return Err(io::Error::StreamTooLong { max_bytes });
+                }
+
+                if bytes_read == buffer.capacity() {
+                    let growth = buffer.capacity().min(READ_TO_END_MAX_GROWTH);
+                    let new_size = buffer
+                        .capacity()
+                        .saturating_add(growth)
+                        .min(max_buffer_size);
+
+                    let mut as_vec = buffer.into_inner_boxed_slice().into_vec();
+                    as_vec.reserve_exact(new_size - as_vec.len());
+                    // SAFETY: They are just bytes destined for overwriting, meaningless.
+                    #[allow(clippy::uninit_vec)]
+                    unsafe {
+                        as_vec.set_len(new_size);
+                    }
+
+                    buffer = PinnedBuffer::from_boxed_slice(as_vec.into_boxed_slice());
+                    buffer.set_len(new_size - bytes_read);
+                    buffer.set_start(bytes_read);
+                } else {
+                    buffer = buffer.use_remainder();
+                }
+            }
+        }
+    }
+}
+
+impl<R> AsyncReadExt for R where R: AsyncRead + ?Sized {}
+
+fn new_read_buffer(len: usize) -> PinnedBuffer {
+    let mut buffer = Vec::<u8>::with_capacity(len);
+
+    // SAFETY: They are just bytes destined for overwriting, meaningless.
+    #[allow(clippy::uninit_vec)]
+    unsafe {
+        buffer.set_len(buffer.capacity());
+    }
+
+    PinnedBuffer::from_boxed_slice(buffer.into_boxed_slice())
+}
@@ -0,0 +1,79 @@
+use crate::io::{self, AsyncRead, PinnedBuffer};
+use std::future::Future;
+
+/// A destination of data that is written sequentially, one buffer at a time, such as a pipe or a
+/// socket.
+pub trait AsyncWrite {
+    /// Writes the active region of the buffer to the destination.
+    ///
+    /// The buffer is returned with the active region set to the bytes written. The destination may
+    /// accept fewer bytes than provided - use `write_all()` to write the entire active region.
+    fn write(&mut self, buffer: PinnedBuffer) -> impl Future<Output = io::Result<PinnedBuffer>>;
+}
+
+/// Helpers available on every `AsyncWrite`.
+pub trait AsyncWriteExt: AsyncWrite {
+    /// Writes the entire active region of the buffer to the destination, repeating the write for
+    /// the remaining bytes for as long as the destination accepts only a part of them.
+    ///
+    /// The buffer is returned with the same active region, so it can be reused.
+    fn write_all(
+        &mut self,
+        mut buffer: PinnedBuffer,
+    ) -> impl Future<Output = io::Result<PinnedBuffer>> {
+        async move {
+            let start = buffer.start();
+            let wanted = buffer.len();
+            let mut written = 0;
+
+            while written < wanted {
+                // The length is cleared first because the start and length are validated against
+                // the capacity of the buffer one by one.
+                buffer.set_len(0);
+                buffer.set_start(start + written);
+                buffer.set_len(wanted - written);
+
+                buffer = self.write(buffer).await?;
+
+                if buffer.is_empty() {
+                    return Err(io::Error::LogicError(
+                        "destination accepted no bytes from a non-empty write".to_string(),
+                    ));
+                }
+
+                written += buffer.len();
+            }
+
+            buffer.set_len(0);
+            buffer.set_start(start);
+            buffer.set_len(wanted);
+            Ok(buffer)
+        }
+    }
+}
+
+impl<W> AsyncWriteExt for W where W: AsyncWrite + ?Sized {}
+
+/// Reads from the source until the end of the data is reached and writes everything read to the
+/// destination, one buffer at a time. Returns the number of bytes copied.
+///
+/// The data is not flushed to storage - the destination is left to decide when that happens.
+pub async fn copy<R, W>(reader: &mut R, writer: &mut W) -> io::Result<u64>
+where
+    R: AsyncRead + ?Sized,
+    W: AsyncWrite + ?Sized,
+{
+    let mut buffer = PinnedBuffer::from_pool();
+    let mut copied = 0;
+
+    loop {
+        buffer = reader.read(buffer.use_all()).await?;
+
+        if buffer.is_empty() {
+            return Ok(copied);
+        }
+
+        buffer = writer.write_all(buffer).await?;
+        copied += buffer.len() as u64;
+    }
+}
@@ -0,0 +1,170 @@
+use crate::io::{self, AsyncRead, PinnedBuffer};
+
+// Capacity of the buffer of a reader created via `BufReader::new()`.
+const DEFAULT_CAPACITY: usize = 64 * 1024;
+
+/// Reads from a source through an in-memory buffer, filling the buffer with large reads and
+/// serving small reads from it. This makes it cheap to consume the data in small pieces, such as
+/// line by line via `read_line()`, which would otherwise require one I/O operation per piece.
+///
+/// Reading a file through this reads the file sequentially via its `io::AsyncRead`
+/// implementation, starting from the beginning of the file.
+#[derive(Debug)]
+pub struct BufReader<R> {
+    inner: R,
+    capacity: usize,
+
+    // The active region contains the data read from the source that has not yet been consumed
+    // (from `consumed` onward). This is `None` if a read from the source failed, as the failed
+    // operation keeps the buffer - we allocate a new one on the next read.
+    buffer: Option<PinnedBuffer>,
+    consumed: usize,
+}
+
+impl<R> BufReader<R>
+where
+    R: AsyncRead,
+{
+    /// Creates a reader with a buffer of the default capacity.
+    pub fn new(inner: R) -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY, inner)
+    }
+
+    /// Creates a reader with a buffer of `capacity` bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn with_capacity(capacity: usize, inner: R) -> Self {
+        assert!(capacity > 0, "buffer capacity must be at least 1 byte");
+
+        Self {
+            inner,
+            capacity,
+            buffer: None,
+            consumed: 0,
+        }
+    }
+
+    /// The source the reader reads from.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Unwraps the reader, returning the source. Any data still in the buffer is lost.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// The data that has been read from the source but not yet consumed.
+    pub fn buffered(&self) -> &[u8] {
+        match &self.buffer {
+            Some(buffer) => &buffer.as_slice()[self.consumed..],
+            None => &[],
+        }
+    }
+
+    /// Returns the buffered data, first refilling the buffer from the source if it is empty.
+    /// An empty result indicates that the end of the data has been reached.
+    ///
+    /// The data remains in the buffer until marked as consumed via `consume()`.
+    pub async fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.buffered().is_empty() {
+            let buffer = self
+                .buffer
+                .take()
+                .map(PinnedBuffer::use_all)
+                .unwrap_or_else(|| {
+                    PinnedBuffer::from_boxed_slice(vec![0; self.capacity].into_boxed_slice())
+                });
+
+            self.buffer = Some(self.inner.read(buffer).await?);
+            self.consumed = 0;
+        }
+
+        Ok(self.buffered())
+    }
+
+    /// Marks `amount` bytes of the buffered data as consumed, so they are not returned again.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `amount` is greater than the length of the buffered data.
+    pub fn consume(&mut self, amount: usize) {
+        assert!(
+            amount <= self.buffered().len(),
+            "cannot consume more than the buffered data"
+        );
+
+        self.consumed += amount;
+    }
+
+    /// Reads bytes into `output` until the delimiter or the end of the data is reached. The
+    /// delimiter is included in the output, if found.
+    ///
+    /// Returns the number of bytes read. Zero indicates that the end of the data has been reached.
+    pub async fn read_until(&mut self, delimiter: u8, output: &mut Vec<u8>) -> io::Result<usize> {
+        let mut read = 0;
+
+        loop {
+            let available = self.fill_buf().await?;
+
+            if available.is_empty() {
+                return Ok(read);
+            }
+
+            match available.iter().position(|b| *b == delimiter) {
+                Some(index) => {
+                    output.extend_from_slice(&available[..=index]);
+                    self.consume(index + 1);
+                    return Ok(read + index + 1);
+                }
+                None => {
+                    let len = available.len();
+                    output.extend_from_slice(available);
+                    self.consume(len);
+                    read += len;
+                }
+            }
+        }
+    }
+
+    /// Reads a line of UTF-8 text and appends it to `output`, including the terminating newline
+    /// (`\n`) if there is one. Line endings are not normalized, so a `\r\n` line ending is kept
+    /// as is.
+    ///
+    /// Returns the number of bytes read. Zero indicates that the end of the data has been reached.
+    /// If the line is not valid UTF-8, fails with an error of kind
+    /// `std::io::ErrorKind::InvalidData` and `output` is left unchanged.
+    pub async fn read_line(&mut self, output: &mut String) -> io::Result<usize> {
+        let mut line = Vec::new();
+        let read = self.read_until(b'\n', &mut line).await?;
+
+        let line = String::from_utf8(line)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        output.push_str(&line);
+
+        Ok(read)
+    }
+}
+
+impl<R> AsyncRead for BufReader<R>
+where
+    R: AsyncRead,
+{
+    async fn read(&mut self, mut buffer: PinnedBuffer) -> io::Result<PinnedBuffer> {
+        // Reads at least as large as our own buffer gain nothing from going through it.
+        if self.buffered().is_empty() && buffer.len() >= self.capacity {
+            return self.inner.read(buffer).await;
+        }
+
+        let available = self.fill_buf().await?;
+        let len = available.len().min(buffer.len());
+
+        buffer.as_mut_slice()[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+
+        buffer.set_len(len);
+        Ok(buffer)
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{
-    io::{self, IoPrimitive, IoWaker},
+    io::{self, IoClass, IoPrimitive, IoWaker, WakeQueue},
     metrics::{Event, EventBuilder},
     windows::OwnedHandle,
 };
@@ -38,6 +38,10 @@ pub(crate) struct CompletionPort {
     // are spawned for the purpose of remote I/O wakeup hold only weak references so they become
     // inert if the completion port is dropped before the wakers.
     handle: Arc<OwnedHandle<HANDLE>>,
+
+    // Task wakeups delivered via `IoWaker::wake_tasks()`. Same as with the handle, the wakers only
+    // hold weak references to this.
+    wake_queue: Arc<WakeQueue>,
 }
 
 impl CompletionPort {
@@ -53,19 +57,27 @@ impl CompletionPort {
             ).expect("creating an I/O completion port should never fail unless the OS is critically out of resources"))
         };
 
-        Self { handle: Arc::new(handle) }
+        Self {
+            handle: Arc::new(handle),
+            wake_queue: Arc::new(WakeQueue::default()),
+        }
     }
 
     /// Binds an I/O primitive to the completion port when provided a handle to the I/O primitive.
-    /// This causes notifications from that I/O primitive to arrive at the completion port.
-    pub(crate) fn bind(&self, handle: &(impl Into<IoPrimitive> + Copy)) -> io::Result<()> {
+    /// This causes notifications from that I/O primitive to arrive at the completion port, tagged
+    /// with the completion key of the specified class of I/O.
+    pub(crate) fn bind(
+        &self,
+        handle: &(impl Into<IoPrimitive> + Copy),
+        class: IoClass,
+    ) -> io::Result<()> {
         let handle = HANDLE::from((*handle).into());
 
         // SAFETY: Our own handle cannot be invalid because we are keeping it alive via Arc.
         // We have to assume the user provided a valid handle (but if not, it will just be an
         // error result). We ignore the return value because it is our own handle on success.
         unsafe {
-            CreateIoCompletionPort(handle, **self.handle, 0, 1)?;
+            CreateIoCompletionPort(handle, **self.handle, class.completion_key(), 1)?;
         }
 
         // Why FILE_SKIP_SET_EVENT_ON_HANDLE: https://devblogs.microsoft.com/oldnewthing/20200221-00/?p=103466/
@@ -93,7 +105,15 @@ impl CompletionPort {
     /// Obtains a waker that can be used to schedule a no-op completion on the I/O completion port,
     /// bringing any I/O driver out of a blocking wait state.
     pub(crate) fn waker(&self) -> IoWaker {
-        IoWaker::new(Arc::downgrade(&self.handle))
+        IoWaker::new(
+            Arc::downgrade(&self.handle),
+            Arc::downgrade(&self.wake_queue),
+        )
+    }
+
+    /// Wakes up the tasks whose wakeups have been delivered via `IoWaker::wake_tasks()`.
+    pub(crate) fn wake_queued_tasks(&self) {
+        self.wake_queue.wake_queued_tasks();
     }
 
     /// Returns the native handle that is necessary for invoking operating system I/O APIs.
@@ -1,5 +1,5 @@
 use crate::{
-    io::{self, IoPrimitive},
+    io::{self, IoClass, IoPrimitive},
     metrics::{Event, EventBuilder},
     windows::OwnedHandle,
 };
@@ -32,7 +32,9 @@ pub(crate) struct CompletionPortShared {
 }
 
 impl CompletionPortShared {
-    pub(crate) fn new() -> Self {
+    /// Creates a completion port that the operating system allows up to `concurrency` threads to
+    /// process completions from at the same time. Zero means one thread per processor.
+    pub(crate) fn new(concurrency: u32) -> Self {
         // SAFETY: We wrap it in OwnedHandle, ensuring it is released when dropped. I/O completion
         // ports are safe to close from any thread, as required by the OwnedHandle API contract.
         let handle = unsafe {
@@ -41,9 +43,7 @@ impl CompletionPortShared {
                 HANDLE::default(),
                 // Ignored as we are not binding a handle to the port.
                 0,
-                // The port may be read by one thread per processor (which also happens to be
-                // the max number of async worker threads Folo can create)
-                0,
+                concurrency,
             ).expect("creating an I/O completion port should never fail unless the OS is critically out of resources"))
         };
 
@@ -52,14 +52,18 @@ impl CompletionPortShared {
 
     /// Binds an I/O primitive to the completion port when provided a handle to the I/O primitive.
     /// This causes notifications from that I/O primitive to arrive at the completion port.
-    pub(crate) fn bind(&self, handle: &(impl Into<IoPrimitive> + Copy)) -> io::Result<()> {
+    pub(crate) fn bind(
+        &self,
+        handle: &(impl Into<IoPrimitive> + Copy),
+        class: IoClass,
+    ) -> io::Result<()> {
         let handle = HANDLE::from((*handle).into());
 
         // SAFETY: Our own handle cannot be invalid because we are keeping it alive via Arc.
         // We have to assume the user provided a valid handle (but if not, it will just be an
         // error result). We ignore the return value because it is our own handle on success.
         unsafe {
-            CreateIoCompletionPort(handle, *self.handle, 0, 1)?;
+            CreateIoCompletionPort(handle, *self.handle, class.completion_key(), 1)?;
         }
 
         // Why FILE_SKIP_SET_EVENT_ON_HANDLE: https://devblogs.microsoft.com/oldnewthing/20200221-00/?p=103466/
@@ -2,13 +2,18 @@ use crate::constants::GENERAL_MILLISECONDS_BUCKETS;
 use crate::io::{
     self,
     operation::{Operation, OperationStore},
-    CompletionPort, IoPrimitive, IoWaker, PinnedBuffer, IO_DEQUEUE_BATCH_SIZE,
-    WAKE_UP_COMPLETION_KEY,
+    rio::{self, RIO_NOTIFICATION_COMPLETION_KEY},
+    Admission, CompletionPort, DriverStats, InFlightLimiter, IoClass, IoPrimitive, IoPriority,
+    IoPriorityPolicy, IoWaker, PinnedBuffer, SlowIoHook, WAKE_UP_COMPLETION_KEY,
 };
 use crate::metrics::{Event, EventBuilder, Magnitude};
-use std::mem::{self, MaybeUninit};
+use std::{
+    cell::Cell,
+    mem::{self, MaybeUninit},
+    rc::Rc,
+};
 use windows::Win32::{
-    Foundation::WAIT_TIMEOUT,
+    Foundation::{HANDLE, WAIT_TIMEOUT},
     System::IO::{GetQueuedCompletionStatusEx, OVERLAPPED_ENTRY},
 };
 use windows_result::HRESULT;
@@ -31,33 +36,114 @@ pub(crate) struct Driver {
     //
     // This does not store the read/write buffers, only the operation metadata.
     operation_store: OperationStore,
+
+    // Determines the order in which we dispatch the completions received in one batch.
+    priority_policy: IoPriorityPolicy,
+
+    // Limits how many operations may be in flight at the same time, if configured.
+    in_flight_limiter: Rc<InFlightLimiter>,
+
+    // The number of Registered I/O completion queues that are armed to notify us of completions,
+    // each of which means that some Registered I/O requests are still in flight.
+    armed_rio_notifications: Rc<Cell<usize>>,
+
+    // Receives the completion notifications dequeued in one go. The length of this is the maximum
+    // number of completions we dequeue at a time.
+    completed: Box<[MaybeUninit<OVERLAPPED_ENTRY>]>,
+
+    // The dispatch rank of each entry in `completed`, determined before we dispatch any of them.
+    dispatch_ranks: Box<[u8]>,
+
+    // Identifies the async worker thread that owns the driver (see `rt::current_reactor_id()`).
+    reactor_id: usize,
+
+    // Activity counters, updated as we dequeue completions. The driver is single-threaded, so
+    // plain fields suffice.
+    stats: DriverStats,
 }
 
 impl Driver {
+    /// Each call to `process_completions()` dequeues up to `dequeue_batch_size` completion
+    /// notifications (see `IO_DEQUEUE_BATCH_SIZE`).
+    ///
     /// # Safety
     ///
     /// See safety requirements on the type.
-    pub(crate) unsafe fn new() -> Self {
+    pub(crate) unsafe fn new(
+        priority_policy: IoPriorityPolicy,
+        max_in_flight: Option<usize>,
+        slow_io: Option<SlowIoHook>,
+        verify_completions: bool,
+        dequeue_batch_size: usize,
+        reactor_id: usize,
+    ) -> Self {
+        assert!(
+            dequeue_batch_size > 0,
+            "I/O dequeue batch size must be at least 1"
+        );
+
+        let in_flight_limiter = Rc::new(InFlightLimiter::new(max_in_flight));
+
         Self {
             completion_port: CompletionPort::new(),
-            operation_store: OperationStore::new(),
+            operation_store: OperationStore::new(
+                slow_io,
+                verify_completions,
+                Rc::clone(&in_flight_limiter),
+            ),
+            priority_policy,
+            in_flight_limiter,
+            armed_rio_notifications: Rc::new(Cell::new(0)),
+            completed: vec![MaybeUninit::uninit(); dequeue_batch_size].into_boxed_slice(),
+            dispatch_ranks: vec![0; dequeue_batch_size].into_boxed_slice(),
+            reactor_id,
+            stats: DriverStats {
+                batch_size: dequeue_batch_size,
+                ..DriverStats::default()
+            },
         }
     }
 
+    /// The ID of the reactor (async worker thread) that owns the driver. I/O primitives bound to
+    /// the driver are bound to this reactor.
+    pub(crate) fn reactor_id(&self) -> usize {
+        self.reactor_id
+    }
+
+    /// A snapshot of the activity of the driver so far.
+    pub(crate) fn stats(&self) -> DriverStats {
+        self.stats
+    }
+
     /// Whether the driver has entered a state where it is safe to drop it. This requires that all
-    /// ongoing I/O operations be completed and the completion notification received.
+    /// ongoing I/O operations be completed and the completion notification received, including
+    /// the notifications of Registered I/O completion queues.
     pub fn is_inert(&self) -> bool {
-        self.operation_store.is_empty()
+        self.operation_store.is_empty() && self.armed_rio_notifications.get() == 0
+    }
+
+    /// Starts shutting down the driver by cancelling the operations in flight where possible. Keep
+    /// processing completions until the driver is inert to finish the shutdown.
+    ///
+    /// # Safety
+    ///
+    /// There must be no operations that have been created via `new_operation()` but not yet
+    /// started - on an async worker thread, this is the case once all its tasks have been dropped.
+    pub(crate) unsafe fn shut_down(&self) {
+        self.operation_store.shut_down();
     }
 
     /// Binds an I/O primitive to the completion port of this driver, provided a handle to the I/O
     /// primitive in question (file handle, socket, ...). This must be called once for every I/O
     /// primitive used with this I/O driver.
+    ///
+    /// The class of I/O determines how completions of the primitive are prioritized.
     pub(crate) fn bind_io_primitive(
         &self,
         handle: &(impl Into<IoPrimitive> + Copy),
+        class: IoClass,
     ) -> io::Result<()> {
-        self.completion_port.bind(handle)
+        self.completion_port.bind(handle, class)
     }
 
     /// Starts preparing for a new I/O operation on some primitive bound to this driver. The caller
@@ -79,6 +165,25 @@ impl Driver {
         self.operation_store.new_operation(buffer)
     }
 
+    /// Waits until the in-flight operation limit allows an operation with the given priority to be
+    /// started. Hold on to the returned permit until the operation has completed.
+    pub(crate) fn admit(&self, priority: IoPriority) -> Admission {
+        self.in_flight_limiter.admit(priority)
+    }
+
+    /// The completion port of the driver, for Registered I/O completion queues to deliver their
+    /// notifications to (see `rio::RequestQueue`).
+    pub(crate) fn completion_port_handle(&self) -> HANDLE {
+        *self.completion_port.as_native_handle()
+    }
+
+    /// The number of Registered I/O notifications armed to be delivered to this driver. Every
+    /// `rio::RequestQueue` keeps this up to date, so the driver does not become inert while any
+    /// Registered I/O requests are in flight.
+    pub(crate) fn armed_rio_notifications(&self) -> Rc<Cell<usize>> {
+        Rc::clone(&self.armed_rio_notifications)
+    }
+
     /// Obtains a waker that can be used to wake up the I/O driver from another thread when it
     /// is waiting for I/O.
     pub(crate) fn waker(&self) -> IoWaker {
@@ -88,15 +193,23 @@ impl Driver {
     /// Process any I/O completion notifications and return their results to the callers. If there
     /// is no queued I/O, we wait up to `max_wait_time_ms` milliseconds for new I/O activity, after
     /// which we simply return.
-    pub(crate) fn process_completions(&mut self, max_wait_time_ms: u32) {
-        let mut completed: [MaybeUninit<OVERLAPPED_ENTRY>; IO_DEQUEUE_BATCH_SIZE] =
-            [MaybeUninit::uninit(); IO_DEQUEUE_BATCH_SIZE];
+    ///
+    /// Returns the number of completion notifications dequeued, which is at most the dequeue
+    /// batch size of the driver.
+    pub(crate) fn process_completions(&mut self, max_wait_time_ms: u32) -> usize {
         let mut completed_items: u32 = 0;
 
+        // Operations whose completion notifications we set aside earlier may have completed since.
+        // SAFETY: We forget set-aside notifications whenever we complete their operations, so the
+        // remaining ones are all for operations that have not been completed yet.
+        unsafe {
+            self.operation_store.complete_set_aside_operations();
+        }
+
         // We intentionally do not loop here because we want to give the caller the opportunity to
         // process received I/O as soon as possible. Otherwise we might start taking too small
-        // chunks out of the I/O completion stream. Tuning the batch size above is valuable to make
-        // sure we make best use of each iteration and do not leave too much queued in the OS.
+        // chunks out of the I/O completion stream. Tuning the batch size is valuable to make sure
+        // we make best use of each iteration and do not leave too much queued in the OS.
 
         // SAFETY: TODO
         unsafe {
@@ -109,7 +222,7 @@ impl Driver {
                         mem::transmute::<
                             &mut [std::mem::MaybeUninit<OVERLAPPED_ENTRY>],
                             &mut [OVERLAPPED_ENTRY],
-                        >(completed.as_mut_slice()),
+                        >(&mut self.completed[..]),
                         &mut completed_items as *mut _,
                         max_wait_time_ms,
                         false,
@@ -117,6 +230,8 @@ impl Driver {
                 })
             });
 
+            self.stats.dequeue_calls += 1;
+
             match result {
                 Ok(()) => {}
                 // Timeout just means there was nothing to do - no I/O operations completed.
@@ -127,27 +242,45 @@ impl Driver {
                         WAIT_TIMEOUTS.with(Event::observe_unit);
                     }
 
-                    return;
+                    return 0;
                 }
                 Err(e) => panic!("unexpected error from GetQueuedCompletionStatusEx: {:?}", e),
             }
 
             ASYNC_COMPLETIONS_DEQUEUED.with(|x| x.observe(completed_items as Magnitude));
+            self.stats.completions_dequeued += completed_items as u64;
 
-            for index in 0..completed_items {
-                let overlapped_entry = completed[index as usize].assume_init();
+            // SAFETY: The OS has initialized the first `completed_items` entries.
+            let completed = mem::transmute::<&[MaybeUninit<OVERLAPPED_ENTRY>], &[OVERLAPPED_ENTRY]>(
+                &self.completed[..completed_items as usize],
+            );
 
+            for overlapped_entry in self
+                .priority_policy
+                .dispatch_order(completed, &mut self.dispatch_ranks)
+            {
                 // If the completion key matches our magic value, this is a wakeup packet and needs
                 // special processing.
                 if overlapped_entry.lpCompletionKey == WAKE_UP_COMPLETION_KEY {
-                    // This is not a normal I/O block. All it did was wake us up, we do no further
-                    // processing here. The OVERLAPPED pointer will be null here!
+                    // This is not a normal I/O block. It wakes us up and may have brought along
+                    // some task wakeups, which we deliver now. The OVERLAPPED pointer will be null!
+                    self.stats.wake_up_packets += 1;
+                    self.completion_port.wake_queued_tasks();
                     continue;
                 }
 
-                self.operation_store.complete_operation(overlapped_entry);
+                // Registered I/O completion queues notify us when they have completions ready to
+                // be dequeued. The OVERLAPPED pointer identifies the queue, not an operation.
+                if overlapped_entry.lpCompletionKey == RIO_NOTIFICATION_COMPLETION_KEY {
+                    rio::process_notification(overlapped_entry.lpOverlapped);
+                    continue;
+                }
+
+                self.operation_store.complete_operation(*overlapped_entry);
             }
         }
+
+        completed_items as usize
     }
 }
 
@@ -189,3 +322,140 @@ thread_local! {
         .build()
         .unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{executor::block_on, stream::FuturesUnordered, FutureExt, StreamExt};
+    use windows::Win32::{Foundation::ERROR_IO_PENDING, System::IO::PostQueuedCompletionStatus};
+
+    #[test]
+    fn dequeues_at_most_batch_size() {
+        // SAFETY: We do not start any I/O operations, so the driver is always inert.
+        let mut driver =
+            unsafe { Driver::new(IoPriorityPolicy::default(), None, None, false, 4, 0) };
+
+        for _ in 0..10 {
+            // SAFETY: The completion port is valid. Wakeup packets do not carry an OVERLAPPED.
+            unsafe {
+                PostQueuedCompletionStatus(
+                    *driver.completion_port.as_native_handle(),
+                    0,
+                    WAKE_UP_COMPLETION_KEY,
+                    None,
+                )
+                .unwrap();
+            }
+        }
+
+        assert_eq!(driver.process_completions(0), 4);
+        assert_eq!(driver.process_completions(0), 4);
+        assert_eq!(driver.process_completions(0), 2);
+        assert_eq!(driver.process_completions(0), 0);
+    }
+
+    #[test]
+    fn stats_count_dequeues() {
+        // SAFETY: We do not start any I/O operations, so the driver is always inert.
+        let mut driver =
+            unsafe { Driver::new(IoPriorityPolicy::default(), None, None, false, 4, 0) };
+
+        for _ in 0..6 {
+            // SAFETY: The completion port is valid. Wakeup packets do not carry an OVERLAPPED.
+            unsafe {
+                PostQueuedCompletionStatus(
+                    *driver.completion_port.as_native_handle(),
+                    0,
+                    WAKE_UP_COMPLETION_KEY,
+                    None,
+                )
+                .unwrap();
+            }
+        }
+
+        driver.process_completions(0);
+        driver.process_completions(0);
+        driver.process_completions(0);
+
+        let stats = driver.stats();
+        assert_eq!(stats.batch_size, 4);
+        assert_eq!(stats.dequeue_calls, 3);
+        assert_eq!(stats.completions_dequeued, 6);
+        assert_eq!(stats.wake_up_packets, 6);
+        assert_eq!(stats.average_batch_fill(), 0.5);
+    }
+
+    #[test]
+    fn operation_priority_dispatches_high_priority_first() {
+        // SAFETY: All the operations we start are completed below, before the driver is dropped.
+        let mut driver = unsafe {
+            Driver::new(
+                IoPriorityPolicy::OperationPriority,
+                None,
+                None,
+                false,
+                16,
+                0,
+            )
+        };
+
+        // The operations are identified by the capacity of their buffers.
+        let priorities = [
+            (1, IoPriority::Low),
+            (2, IoPriority::High),
+            (3, IoPriority::Normal),
+            (4, IoPriority::Low),
+            (5, IoPriority::High),
+        ];
+
+        let mut overlapped_ptrs = Vec::new();
+
+        let mut results = priorities
+            .into_iter()
+            .map(|(id, priority)| {
+                let mut operation =
+                    driver.new_operation(PinnedBuffer::from_boxed_slice(vec![0; id].into()));
+                operation.set_priority(priority);
+
+                // We pretend that the OS accepted the operation, so we can complete it ourselves.
+                // SAFETY: We post a completion notification for the OVERLAPPED pointer below.
+                unsafe {
+                    operation.begin(|_, overlapped, _| {
+                        overlapped_ptrs.push(overlapped);
+                        Err(io::Error::Windows(ERROR_IO_PENDING.into()))
+                    })
+                }
+            })
+            .collect::<FuturesUnordered<_>>();
+
+        // Nothing has completed yet. This registers the wakers, which are woken in the order in
+        // which the driver dispatches the completions.
+        assert!(results.next().now_or_never().is_none());
+
+        for overlapped in overlapped_ptrs {
+            // SAFETY: The completion port is valid and the OVERLAPPED pointer belongs to an
+            // operation that is waiting for its completion notification.
+            unsafe {
+                PostQueuedCompletionStatus(
+                    *driver.completion_port.as_native_handle(),
+                    0,
+                    IoClass::Other.completion_key(),
+                    Some(overlapped),
+                )
+                .unwrap();
+            }
+        }
+
+        // All the completions are dispatched as one batch.
+        assert_eq!(driver.process_completions(0), 5);
+        assert!(driver.is_inert());
+
+        let dispatched = block_on(
+            results
+                .map(|result| result.unwrap().capacity())
+                .collect::<Vec<_>>(),
+        );
+
+        assert_eq!(dispatched, [2, 5, 3, 1, 4]);
+    }
+}
@@ -2,7 +2,8 @@ use crate::constants::GENERAL_MILLISECONDS_BUCKETS;
 use crate::io::{
     self,
     operation_shared::{OperationShared, OperationStoreShared},
-    CompletionPortShared, IoPrimitive, PinnedBufferShared, IO_DEQUEUE_BATCH_SIZE,
+    CompletionPortShared, IoClass, IoPrimitive, PinnedBufferShared, SlowIoHook,
+    IO_DEQUEUE_BATCH_SIZE,
 };
 use crate::metrics::{Event, EventBuilder, Magnitude};
 use std::mem::{self, MaybeUninit};
@@ -37,13 +38,23 @@ pub(crate) struct DriverShared {
 }
 
 impl DriverShared {
+    /// The completion port allows up to `concurrency` threads to process completions at the same
+    /// time, with zero meaning one thread per processor.
+    ///
+    /// If `verify_completions` is set, completion notifications for operations that the OS still
+    /// considers to be in progress are ignored instead of delivering their results.
+    ///
     /// # Safety
     ///
     /// See safety requirements on the type.
-    pub(crate) unsafe fn new() -> Self {
+    pub(crate) unsafe fn new(
+        concurrency: u32,
+        verify_completions: bool,
+        slow_io: Option<SlowIoHook>,
+    ) -> Self {
         Self {
-            completion_port: CompletionPortShared::new(),
-            operation_store: OperationStoreShared::new(),
+            completion_port: CompletionPortShared::new(concurrency),
+            operation_store: OperationStoreShared::new(verify_completions, slow_io),
         }
     }
 
@@ -53,14 +64,24 @@ impl DriverShared {
         self.operation_store.is_empty()
     }
 
+    /// Starts shutting down the driver. New operations are rejected with `io::Error::ShuttingDown`
+    /// and the operations in flight are cancelled where possible. Keep processing completions
+    /// until the driver is inert to finish the shutdown.
+    ///
+    /// The driver is shared by all async worker threads, so this affects the tasks of all of them.
+    pub(crate) fn shut_down(&self) {
+        self.operation_store.shut_down();
+    }
+
     /// Binds an I/O primitive to the completion port of this driver, provided a handle to the I/O
     /// primitive in question (file handle, socket, ...). This must be called once for every I/O
     /// primitive used with this I/O driver.
     pub(crate) fn bind_io_primitive(
         &self,
         handle: &(impl Into<IoPrimitive> + Copy),
+        class: IoClass,
     ) -> io::Result<()> {
-        self.completion_port.bind(handle)
+        self.completion_port.bind(handle, class)
     }
 
     /// Starts preparing for a new I/O operation on some primitive bound to this driver. The caller
@@ -89,6 +110,13 @@ impl DriverShared {
             [MaybeUninit::uninit(); IO_DEQUEUE_BATCH_SIZE];
         let mut completed_items: u32 = 0;
 
+        // Operations whose completion notifications we set aside earlier may have completed since.
+        // SAFETY: We forget set-aside notifications whenever we complete their operations, so the
+        // remaining ones are all for operations that have not been completed yet.
+        unsafe {
+            self.operation_store.complete_set_aside_operations();
+        }
+
         // We intentionally do not loop here because we want to give the caller the opportunity to
         // process received I/O as soon as possible. Otherwise we might start taking too small
         // chunks out of the I/O completion stream. Tuning the batch size above is valuable to make
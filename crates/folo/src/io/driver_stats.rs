@@ -0,0 +1,63 @@
+use crate::rt::current_async_agent;
+
+/// A snapshot of the activity of the I/O driver of an async worker thread since the thread
+/// started, obtained via `io::driver_stats()`.
+///
+/// This is mainly useful for tuning the dequeue batch size (see
+/// `RuntimeBuilder::io_dequeue_batch_size()`) - if the batches are rarely filled, the driver is
+/// waking up for only a handful of completions at a time.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct DriverStats {
+    /// Maximum number of completion notifications the driver dequeues in one call.
+    pub batch_size: usize,
+
+    /// Number of calls made to dequeue completion notifications from the OS, including calls that
+    /// timed out without dequeuing anything.
+    pub dequeue_calls: u64,
+
+    /// Total number of completion notifications dequeued, including wake-up packets.
+    pub completions_dequeued: u64,
+
+    /// Number of wake-up packets dequeued. These are posted to wake up the driver from another
+    /// thread and do not represent any I/O activity.
+    pub wake_up_packets: u64,
+}
+
+impl DriverStats {
+    /// The average fraction of the batch filled by each dequeue call, between 0.0 and 1.0.
+    /// Returns 0.0 if nothing has been dequeued yet.
+    pub fn average_batch_fill(&self) -> f64 {
+        if self.dequeue_calls == 0 || self.batch_size == 0 {
+            return 0.0;
+        }
+
+        self.completions_dequeued as f64 / (self.dequeue_calls as f64 * self.batch_size as f64)
+    }
+}
+
+/// Returns a snapshot of the activity of the I/O driver of the current async worker thread.
+///
+/// # Panics
+///
+/// Panics if the current thread is not an async worker thread owned by the Folo runtime.
+pub fn driver_stats() -> DriverStats {
+    current_async_agent::with_io(|io| io.stats())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn average_batch_fill_is_fraction_of_batch() {
+        let stats = DriverStats {
+            batch_size: 4,
+            dequeue_calls: 2,
+            completions_dequeued: 6,
+            wake_up_packets: 0,
+        };
+
+        assert_eq!(stats.average_batch_fill(), 0.75);
+        assert_eq!(DriverStats::default().average_batch_fill(), 0.0);
+    }
+}
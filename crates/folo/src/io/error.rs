@@ -1,5 +1,15 @@
 use thiserror::Error;
-use windows::Win32::Networking::WinSock::WSA_ERROR;
+use windows::Win32::{
+    Foundation::{
+        RtlNtStatusToDosError, ERROR_DEVICE_REMOVED, ERROR_FILE_INVALID, ERROR_INVALID_HANDLE,
+        ERROR_INVALID_USER_BUFFER, ERROR_NETNAME_DELETED, ERROR_NOT_ENOUGH_MEMORY,
+        ERROR_NOT_ENOUGH_QUOTA, ERROR_NO_SYSTEM_RESOURCES, ERROR_OPERATION_ABORTED,
+        ERROR_WORKING_SET_QUOTA, NTSTATUS, STATUS_CANCELLED, STATUS_DEVICE_REMOVED,
+        STATUS_FILE_FORCED_CLOSED, STATUS_FILE_INVALID, STATUS_INVALID_HANDLE,
+        STATUS_NETWORK_NAME_DELETED, STATUS_VOLUME_DISMOUNTED, WIN32_ERROR,
+    },
+    Networking::WinSock::{WSAENOBUFS, WSA_ERROR, WSA_OPERATION_ABORTED},
+};
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -9,6 +19,18 @@ pub enum Error {
     #[error("logic error: {0}")]
     LogicError(String),
 
+    #[error("file is larger than the maximum allowed size of {max_bytes} bytes")]
+    FileTooLarge { max_bytes: usize },
+
+    #[error("stream is longer than the maximum allowed length of {max_bytes} bytes")]
+    StreamTooLong { max_bytes: usize },
+
+    #[error("datagram was larger than the buffer it was received into")]
+    DatagramTruncated,
+
+    #[error("the I/O driver is shutting down and does not accept new operations")]
+    ShuttingDown,
+
     #[error("Winsock error {} ({})", .code, .detail.0)]
     Winsock { code: i32, detail: WSA_ERROR },
 
@@ -27,13 +49,166 @@ pub enum Error {
     Other(#[from] Box<dyn std::error::Error + Send + Sync>), 
 }
 
+impl Error {
+    /// Whether the error indicates a temporary exhaustion of operating system resources (e.g. the
+    /// non-paged pool), as opposed to a problem with the operation itself.
+    ///
+    /// Under extreme I/O submission rates, the operating system may refuse to accept more
+    /// operations until some of the ongoing ones complete. Such errors are surfaced to the caller
+    /// like any other but it is reasonable to retry the operation after reducing concurrency or
+    /// waiting for other operations to complete. The async worker thread that submitted the
+    /// operation also admits fewer file I/O operations for a while (see
+    /// `RuntimeBuilder::max_in_flight_io()`).
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::Windows(e) => [
+                ERROR_INVALID_USER_BUFFER,
+                ERROR_NOT_ENOUGH_MEMORY,
+                ERROR_NOT_ENOUGH_QUOTA,
+                ERROR_NO_SYSTEM_RESOURCES,
+                ERROR_WORKING_SET_QUOTA,
+            ]
+            .into_iter()
+            .any(|code| e.code() == code.into()),
+            Error::Winsock { detail, .. } => *detail == WSAENOBUFS,
+            _ => false,
+        }
+    }
+
+    /// Whether the error indicates that the handle used for the operation is no longer usable
+    /// (e.g. because the volume it is on was dismounted and remounted, or the network share it is
+    /// on was disconnected). Retrying the operation on the same handle is futile but retrying
+    /// after reopening the file may succeed.
+    pub fn is_stale_handle(&self) -> bool {
+        match self {
+            // Errors from submitting the operation arrive as Win32 error codes, errors from
+            // completed operations arrive as NTSTATUS codes, so we need to check for both.
+            Error::Windows(e) => {
+                [
+                    ERROR_DEVICE_REMOVED,
+                    ERROR_FILE_INVALID,
+                    ERROR_INVALID_HANDLE,
+                    ERROR_NETNAME_DELETED,
+                ]
+                .into_iter()
+                .any(|code| e.code() == code.into())
+                    || [
+                        STATUS_DEVICE_REMOVED,
+                        STATUS_FILE_FORCED_CLOSED,
+                        STATUS_FILE_INVALID,
+                        STATUS_INVALID_HANDLE,
+                        STATUS_NETWORK_NAME_DELETED,
+                        STATUS_VOLUME_DISMOUNTED,
+                    ]
+                    .into_iter()
+                    .any(|code| e.code() == code.into())
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the error indicates that the operation was cancelled before it completed (e.g. via
+    /// `File::cancel_all()`).
+    pub fn is_cancelled(&self) -> bool {
+        match self {
+            // Errors from submitting the operation arrive as Win32 error codes, errors from
+            // completed operations arrive as NTSTATUS codes, so we need to check for both.
+            Error::Windows(e) => {
+                e.code() == ERROR_OPERATION_ABORTED.into() || e.code() == STATUS_CANCELLED.into()
+            }
+            Error::Winsock { detail, .. } => *detail == WSA_OPERATION_ABORTED,
+            _ => false,
+        }
+    }
+
+    /// The Win32 error code of the error, as returned by `GetLastError()`, if the error came from
+    /// the operating system. This is the same code that `std::io::Error::raw_os_error()` returns
+    /// and can be compared against constants like `ERROR_FILE_NOT_FOUND`.
+    ///
+    /// Errors from completed operations arrive as NTSTATUS codes, which are translated to the
+    /// equivalent Win32 error code. Use `nt_status()` to get the original NTSTATUS code.
+    pub fn raw_os_error(&self) -> Option<i32> {
+        match self {
+            Error::Windows(e) => {
+                if let Some(code) = WIN32_ERROR::from_error(e) {
+                    return Some(code.0 as i32);
+                }
+
+                // SAFETY: Nothing unsafe here, just an FFI call.
+                self.nt_status()
+                    .map(|status| unsafe { RtlNtStatusToDosError(status) } as i32)
+            }
+            Error::Winsock { detail, .. } => Some(detail.0),
+            Error::StdIo(e) => e.raw_os_error(),
+            _ => None,
+        }
+    }
+
+    /// The NTSTATUS code of the error, if the error came from the operating system as the status
+    /// of a completed operation.
+    pub fn nt_status(&self) -> Option<NTSTATUS> {
+        match self {
+            Error::Windows(e) => {
+                let hresult = e.code().0;
+
+                // NTSTATUS codes are stored in an HRESULT with the FACILITY_NT_BIT set.
+                (hresult & FACILITY_NT_BIT != 0).then_some(NTSTATUS(hresult & !FACILITY_NT_BIT))
+            }
+            _ => None,
+        }
+    }
+}
+
+const FACILITY_NT_BIT: i32 = 0x1000_0000;
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 impl From<Error> for std::io::Error {
     fn from(value: Error) -> Self {
         match value {
             Error::StdIo(error) => error,
-            _ => std::io::Error::other(value)
+            // This gives the error the matching `kind()`, e.g. `NotFound` for a missing file.
+            _ => match value.raw_os_error() {
+                Some(code) => std::io::Error::from_raw_os_error(code),
+                None => std::io::Error::other(value),
+            },
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use windows::Win32::Foundation::{ERROR_FILE_NOT_FOUND, STATUS_OBJECT_NAME_NOT_FOUND};
+
+    #[test]
+    fn raw_os_error_from_win32() {
+        let error = Error::Windows(ERROR_FILE_NOT_FOUND.into());
+
+        assert_eq!(error.raw_os_error(), Some(ERROR_FILE_NOT_FOUND.0 as i32));
+        assert_eq!(error.nt_status(), None);
+        assert_eq!(
+            std::io::Error::from(error).kind(),
+            std::io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn raw_os_error_from_ntstatus() {
+        let error = Error::Windows(STATUS_OBJECT_NAME_NOT_FOUND.into());
+
+        assert_eq!(error.raw_os_error(), Some(ERROR_FILE_NOT_FOUND.0 as i32));
+        assert_eq!(error.nt_status(), Some(STATUS_OBJECT_NAME_NOT_FOUND));
+    }
+
+    #[test]
+    fn raw_os_error_absent_for_logic_errors() {
+        let error = Error::LogicError("oops".to_string());
+
+        assert_eq!(error.raw_os_error(), None);
+        assert_eq!(
+            std::io::Error::from(error).kind(),
+            std::io::ErrorKind::Other
+        );
+    }
+}
@@ -0,0 +1,421 @@
+use std::{
+    cell::{Cell, RefCell},
+    cmp::Reverse,
+    collections::BTreeMap,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{self, Waker},
+};
+
+/// A hint for the order in which I/O operations are submitted to the operating system when the
+/// number of I/O operations in flight is limited via `RuntimeBuilder::max_in_flight_io()`.
+///
+/// Operations waiting for their turn are admitted in priority order - a high priority operation
+/// jumps ahead of all queued lower priority operations. Operations with the same priority are
+/// admitted in the order they started waiting. This does not affect how the operating system
+/// schedules operations that have already been submitted.
+///
+/// If the I/O driver uses `IoPriorityPolicy::OperationPriority`, the priority also determines the
+/// order in which the completions of operations received in one batch are dispatched.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum IoPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+// Waiters are ordered by descending priority, then by arrival order.
+type WaiterKey = (Reverse<IoPriority>, u64);
+
+#[derive(Debug)]
+enum Waiter {
+    Waiting(Waker),
+
+    // A slot has been handed over to the waiter but it has not yet picked it up.
+    Granted,
+}
+
+/// Limits the number of I/O operations in flight on one async worker thread, queueing operations
+/// that exceed the limit until earlier operations complete.
+///
+/// Callers obtain an `InFlightPermit` via `admit()` before starting an operation and hold it until
+/// the operation has completed.
+///
+/// If the operating system rejects an operation due to resource exhaustion, `throttle()` lowers
+/// the limit to half the number of operations in flight at that moment. The limit then grows back by
+/// one with each operation that completes, until it reaches the configured limit again or until no
+/// operations are left in flight.
+#[derive(Debug)]
+pub(crate) struct InFlightLimiter {
+    // If None, there is no limit and operations are admitted immediately.
+    limit: Option<usize>,
+
+    // If set, the OS has recently rejected operations due to resource exhaustion and this limit
+    // applies instead of the configured one until it grows back.
+    throttled_limit: Cell<Option<usize>>,
+
+    in_flight: Cell<usize>,
+
+    waiters: RefCell<BTreeMap<WaiterKey, Waiter>>,
+
+    next_sequence: Cell<u64>,
+}
+
+impl InFlightLimiter {
+    pub(crate) fn new(limit: Option<usize>) -> Self {
+        Self {
+            limit,
+            throttled_limit: Cell::new(None),
+            in_flight: Cell::new(0),
+            waiters: RefCell::new(BTreeMap::new()),
+            next_sequence: Cell::new(0),
+        }
+    }
+
+    /// Waits until an operation with the given priority may be started.
+    pub(crate) fn admit(self: &Rc<Self>, priority: IoPriority) -> Admission {
+        Admission {
+            limiter: Rc::clone(self),
+            priority,
+            key: None,
+        }
+    }
+
+    /// Temporarily reduces the number of operations that may be in flight, in response to the
+    /// operating system rejecting an operation due to resource exhaustion (see
+    /// `io::Error::is_retryable()`). Operations already admitted are not affected.
+    pub(crate) fn throttle(&self) {
+        // We back off sharply, as we do not know how far beyond its capacity the OS has been
+        // pushed. The limit grows back gradually as operations complete.
+        let throttled_limit = (self.in_flight.get() / 2).max(1);
+
+        let throttled_limit = match self.throttled_limit.get() {
+            Some(current) => current.min(throttled_limit),
+            None => throttled_limit,
+        };
+
+        self.throttled_limit.set(Some(throttled_limit));
+    }
+
+    fn effective_limit(&self) -> Option<usize> {
+        self.throttled_limit.get().or(self.limit)
+    }
+
+    fn try_acquire(&self) -> bool {
+        let has_waiting = self
+            .waiters
+            .borrow()
+            .values()
+            .any(|waiter| matches!(waiter, Waiter::Waiting(_)));
+
+        match self.effective_limit() {
+            Some(limit) if has_waiting || self.in_flight.get() >= limit => false,
+            _ => {
+                self.in_flight.set(self.in_flight.get() + 1);
+                true
+            }
+        }
+    }
+
+    fn release(&self) {
+        self.in_flight.set(self.in_flight.get() - 1);
+
+        let mut waiters = self.waiters.borrow_mut();
+
+        // Each completed operation lets the throttled limit grow back by one.
+        if let Some(throttled_limit) = self.throttled_limit.get() {
+            let throttled_limit = throttled_limit + 1;
+
+            let recovered = self.in_flight.get() == 0
+                || self.limit.is_some_and(|limit| throttled_limit >= limit);
+
+            self.throttled_limit
+                .set((!recovered).then_some(throttled_limit));
+        }
+
+        // We hand the freed slots over to the first waiters. Normally this is just our own slot
+        // but after recovering from throttling, there may be room for more.
+        for waiter in waiters
+            .values_mut()
+            .filter(|waiter| matches!(waiter, Waiter::Waiting(_)))
+        {
+            if self
+                .effective_limit()
+                .is_some_and(|limit| self.in_flight.get() >= limit)
+            {
+                break;
+            }
+
+            let Waiter::Waiting(waker) = std::mem::replace(waiter, Waiter::Granted) else {
+                unreachable!("we only selected waiting waiters");
+            };
+
+            self.in_flight.set(self.in_flight.get() + 1);
+            waker.wake();
+        }
+    }
+}
+
+/// Future returned by `InFlightLimiter::admit()`, resolving to a permit once the operation may
+/// be started.
+///
+/// An operation waiting here has not been submitted to the operating system yet, so dropping the
+/// future (e.g. because the task awaiting the operation was canceled) simply removes it from the
+/// queue without any OS call. If a slot had already been handed over to it, the slot is passed on
+/// to the next waiter.
+#[derive(Debug)]
+pub(crate) struct Admission {
+    limiter: Rc<InFlightLimiter>,
+    priority: IoPriority,
+
+    // Set once we have started waiting in the queue.
+    key: Option<WaiterKey>,
+}
+
+impl Future for Admission {
+    type Output = InFlightPermit;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let Some(key) = self.key else {
+            if self.limiter.try_acquire() {
+                return task::Poll::Ready(InFlightPermit {
+                    limiter: Rc::clone(&self.limiter),
+                });
+            }
+
+            let sequence = self.limiter.next_sequence.get();
+            self.limiter.next_sequence.set(sequence + 1);
+
+            let key = (Reverse(self.priority), sequence);
+            self.limiter
+                .waiters
+                .borrow_mut()
+                .insert(key, Waiter::Waiting(cx.waker().clone()));
+            self.key = Some(key);
+
+            return task::Poll::Pending;
+        };
+
+        let mut waiters = self.limiter.waiters.borrow_mut();
+        let waiter = waiters
+            .get_mut(&key)
+            .expect("waiter is only removed when admitted or dropped");
+
+        match waiter {
+            Waiter::Granted => {
+                waiters.remove(&key);
+                drop(waiters);
+
+                self.key = None;
+
+                task::Poll::Ready(InFlightPermit {
+                    limiter: Rc::clone(&self.limiter),
+                })
+            }
+            Waiter::Waiting(waker) => {
+                waker.clone_from(cx.waker());
+                task::Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for Admission {
+    fn drop(&mut self) {
+        let Some(key) = self.key else {
+            return;
+        };
+
+        let removed = self.limiter.waiters.borrow_mut().remove(&key);
+
+        // If we were handed a slot but never picked it up, we pass it on.
+        if let Some(Waiter::Granted) = removed {
+            self.limiter.release();
+        }
+    }
+}
+
+/// Permission to have one I/O operation in flight. Hold this until the operation has completed.
+#[derive(Debug)]
+pub(crate) struct InFlightPermit {
+    limiter: Rc<InFlightLimiter>,
+}
+
+impl Drop for InFlightPermit {
+    fn drop(&mut self) {
+        self.limiter.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{task::noop_waker_ref, FutureExt};
+
+    fn poll(admission: &mut Admission) -> Option<InFlightPermit> {
+        let mut cx = task::Context::from_waker(noop_waker_ref());
+
+        match admission.poll_unpin(&mut cx) {
+            task::Poll::Ready(permit) => Some(permit),
+            task::Poll::Pending => None,
+        }
+    }
+
+    #[test]
+    fn unlimited() {
+        let limiter = Rc::new(InFlightLimiter::new(None));
+
+        let permits = (0..100)
+            .map(|_| poll(&mut limiter.admit(IoPriority::Low)).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(permits.len(), 100);
+    }
+
+    #[test]
+    fn high_priority_jumps_queue() {
+        let limiter = Rc::new(InFlightLimiter::new(Some(1)));
+
+        let first = poll(&mut limiter.admit(IoPriority::Low)).unwrap();
+
+        let mut low1 = limiter.admit(IoPriority::Low);
+        let mut low2 = limiter.admit(IoPriority::Low);
+        let mut high = limiter.admit(IoPriority::High);
+
+        assert!(poll(&mut low1).is_none());
+        assert!(poll(&mut low2).is_none());
+        assert!(poll(&mut high).is_none());
+
+        drop(first);
+
+        // The high priority operation is admitted ahead of the queued low priority ones.
+        assert!(poll(&mut low1).is_none());
+        let high_permit = poll(&mut high).unwrap();
+        assert!(poll(&mut low2).is_none());
+
+        drop(high_permit);
+
+        // Then the low priority ones, in the order they were queued.
+        assert!(poll(&mut low2).is_none());
+        let low1_permit = poll(&mut low1).unwrap();
+
+        drop(low1_permit);
+
+        assert!(poll(&mut low2).is_some());
+    }
+
+    #[test]
+    fn dropped_queued_admission_is_removed() {
+        let limiter = Rc::new(InFlightLimiter::new(Some(1)));
+
+        let first = poll(&mut limiter.admit(IoPriority::Normal)).unwrap();
+
+        let mut second = limiter.admit(IoPriority::High);
+        let mut third = limiter.admit(IoPriority::Normal);
+
+        assert!(poll(&mut second).is_none());
+        assert!(poll(&mut third).is_none());
+
+        // The second gives up while still waiting for a slot, before any slot is released.
+        drop(second);
+        assert_eq!(limiter.waiters.borrow().len(), 1);
+
+        drop(first);
+
+        let third_permit = poll(&mut third).unwrap();
+        assert!(limiter.waiters.borrow().is_empty());
+
+        drop(third_permit);
+
+        // No slot was leaked by the queued admission that was dropped.
+        assert_eq!(limiter.in_flight.get(), 0);
+    }
+
+    #[test]
+    fn dropped_admission_passes_slot_on() {
+        let limiter = Rc::new(InFlightLimiter::new(Some(1)));
+
+        let first = poll(&mut limiter.admit(IoPriority::Normal)).unwrap();
+
+        let mut second = limiter.admit(IoPriority::Normal);
+        let mut third = limiter.admit(IoPriority::Normal);
+
+        assert!(poll(&mut second).is_none());
+        assert!(poll(&mut third).is_none());
+
+        // The slot is granted to the second but it gives up before picking it up.
+        drop(first);
+        drop(second);
+
+        assert!(poll(&mut third).is_some());
+    }
+
+    #[test]
+    fn throttled_limit_grows_back_as_operations_complete() {
+        let limiter = Rc::new(InFlightLimiter::new(Some(8)));
+
+        let mut permits = (0..8)
+            .map(|_| poll(&mut limiter.admit(IoPriority::Normal)).unwrap())
+            .collect::<Vec<_>>();
+
+        // The last operation was rejected by the OS, which halves the limit. The rejected
+        // operation then gives up its permit, which counts as a completion.
+        limiter.throttle();
+        drop(permits.pop());
+        assert_eq!(limiter.throttled_limit.get(), Some(5));
+
+        let mut waiting = (0..3)
+            .map(|_| limiter.admit(IoPriority::Normal))
+            .collect::<Vec<_>>();
+
+        assert!(waiting
+            .iter_mut()
+            .all(|admission| poll(admission).is_none()));
+
+        // With 5 in flight and the limit grown to 7, two of the waiting operations get in.
+        permits.truncate(5);
+
+        assert!(poll(&mut waiting[0]).is_some());
+        assert!(poll(&mut waiting[1]).is_some());
+        assert!(poll(&mut waiting[2]).is_none());
+
+        // Back at the configured limit, the throttling is lifted.
+        drop(permits.pop());
+
+        assert!(poll(&mut waiting[2]).is_some());
+        assert_eq!(limiter.throttled_limit.get(), None);
+    }
+
+    #[test]
+    fn unlimited_throttled_until_nothing_in_flight() {
+        let limiter = Rc::new(InFlightLimiter::new(None));
+
+        let mut permits = (0..4)
+            .map(|_| poll(&mut limiter.admit(IoPriority::Normal)).unwrap())
+            .collect::<Vec<_>>();
+
+        limiter.throttle();
+        drop(permits.pop());
+
+        let mut waiting = limiter.admit(IoPriority::Normal);
+        assert!(poll(&mut waiting).is_none());
+
+        drop(permits.pop());
+
+        let waiting_permit = poll(&mut waiting).unwrap();
+        assert!(limiter.throttled_limit.get().is_some());
+
+        // Once nothing is in flight anymore, the throttling is lifted.
+        drop(waiting_permit);
+        drop(permits);
+        assert_eq!(limiter.throttled_limit.get(), None);
+
+        let more = (0..10)
+            .map(|_| poll(&mut limiter.admit(IoPriority::Normal)).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(more.len(), 10);
+    }
+}
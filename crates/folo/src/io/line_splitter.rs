@@ -0,0 +1,96 @@
+/// Splits a stream of bytes into lines, buffering any incomplete line until the rest of it arrives.
+///
+/// Lines are terminated by `\n` or `\r\n`, with the terminator not included in the returned lines.
+/// Bytes that are not valid UTF-8 are replaced with `U+FFFD REPLACEMENT CHARACTER`.
+///
+/// Unlike `BufReader::read_until()`, which pulls data from a source on demand, this is fed the
+/// bytes by the caller, so it fits streams that read the data themselves (e.g. at file offsets or
+/// from a pipe) and want lossy text lines instead of raw bytes.
+#[derive(Debug, Default)]
+pub(crate) struct LineSplitter {
+    pending: Vec<u8>,
+}
+
+impl LineSplitter {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends the next chunk of the byte stream.
+    pub(crate) fn push(&mut self, bytes: &[u8]) {
+        self.pending.extend_from_slice(bytes);
+    }
+
+    /// Takes the next complete line, if one has been received.
+    pub(crate) fn next_line(&mut self) -> Option<String> {
+        let end = self.pending.iter().position(|b| *b == b'\n')?;
+
+        let mut line = self.pending.drain(..=end).collect::<Vec<_>>();
+        line.pop();
+
+        Some(into_string(line))
+    }
+
+    /// Takes whatever remains after the last line terminator, to be called once the byte stream
+    /// has ended. The last line of a stream is not required to be terminated.
+    pub(crate) fn finish(&mut self) -> Option<String> {
+        if self.pending.is_empty() {
+            return None;
+        }
+
+        Some(into_string(std::mem::take(&mut self.pending)))
+    }
+}
+
+fn into_string(mut line: Vec<u8>) -> String {
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+
+    match String::from_utf8(line) {
+        Ok(line) => line,
+        Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_across_chunks() {
+        let mut splitter = LineSplitter::new();
+
+        splitter.push(b"first\r\nsec");
+        assert_eq!(splitter.next_line().as_deref(), Some("first"));
+        assert_eq!(splitter.next_line(), None);
+
+        splitter.push(b"ond\n\nthird\n");
+        assert_eq!(splitter.next_line().as_deref(), Some("second"));
+        assert_eq!(splitter.next_line().as_deref(), Some(""));
+        assert_eq!(splitter.next_line().as_deref(), Some("third"));
+        assert_eq!(splitter.next_line(), None);
+
+        assert_eq!(splitter.finish(), None);
+    }
+
+    #[test]
+    fn unterminated_last_line() {
+        let mut splitter = LineSplitter::new();
+
+        splitter.push(b"complete\npartial");
+        assert_eq!(splitter.next_line().as_deref(), Some("complete"));
+        assert_eq!(splitter.next_line(), None);
+
+        assert_eq!(splitter.finish().as_deref(), Some("partial"));
+        assert_eq!(splitter.finish(), None);
+    }
+
+    #[test]
+    fn invalid_utf8_is_replaced() {
+        let mut splitter = LineSplitter::new();
+
+        splitter.push(b"a\xFFb\n");
+        assert_eq!(splitter.next_line().as_deref(), Some("a\u{FFFD}b"));
+    }
+}
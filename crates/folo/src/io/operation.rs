@@ -1,25 +1,31 @@
 use crate::{
     constants::{GENERAL_BYTES_BUCKETS, GENERAL_MILLISECONDS_BUCKETS},
-    io::{self, OperationResult, PinnedBuffer},
+    io::{
+        self, InFlightLimiter, IoClass, IoOperationKind, IoPrimitive, IoPriority, OperationResult,
+        PendingOperationToken, PendingOperations, PinnedBuffer, SlowIoHook, SlowIoOperation,
+    },
     mem::{DropPolicy, PinnedSlabChain},
     metrics::{Event, EventBuilder, Magnitude},
     time::UltraLowPrecisionInstant,
 };
 use negative_impl::negative_impl;
-use pin_project::pin_project;
+use pin_project::{pin_project, pinned_drop};
 use std::{
+    any::Any,
     cell::{RefCell, UnsafeCell},
     fmt,
     future::Future,
+    iter,
     mem::{self, ManuallyDrop},
     ptr,
+    rc::Rc,
     task::Poll,
 };
 use tracing::{event, Level};
 use windows::Win32::{
-    Foundation::{ERROR_IO_PENDING, NTSTATUS, STATUS_SUCCESS},
+    Foundation::{ERROR_IO_PENDING, HANDLE, NTSTATUS, STATUS_PENDING, STATUS_SUCCESS},
     Networking::WinSock::{SOCKET_ERROR, WSA_IO_PENDING},
-    System::IO::{OVERLAPPED, OVERLAPPED_ENTRY},
+    System::IO::{CancelIoEx, OVERLAPPED, OVERLAPPED_ENTRY},
 };
 
 /// Maintains the backing storage for the metadata structures of I/O operations submitted to the
@@ -45,16 +51,38 @@ pub(super) struct OperationStore {
     // reference from the slab chain and giving it to the operating system to mutate, which would
     // be invalid Rust without Unsafecell.
     items: RefCell<PinnedSlabChain<UnsafeCell<OperationCore>>>,
+
+    // If set, we report operations that take longer than a threshold to complete.
+    slow_io: Option<SlowIoHook>,
+
+    // If set, we verify that each completion notification is for an operation that has actually
+    // completed before delivering its result. See `RuntimeBuilder::verify_io_completions()`.
+    verify_completions: bool,
+
+    // Completion notifications received for operations that were still pending at the time, to be
+    // checked again on the next `complete_set_aside_operations()`.
+    set_aside: RefCell<SetAsideCompletions>,
+
+    // Throttled when the OS rejects operations due to resource exhaustion.
+    in_flight_limiter: Rc<InFlightLimiter>,
 }
 
 impl OperationStore {
-    pub fn new() -> Self {
+    pub fn new(
+        slow_io: Option<SlowIoHook>,
+        verify_completions: bool,
+        in_flight_limiter: Rc<InFlightLimiter>,
+    ) -> Self {
         Self {
             // We use a MustNotDropItems policy because the operations are shared with the operating
             // system so it is in general not safe to drop the memory unless the OS is done with it,
             // in which case our completion methods below will remove the operation from the items
             // collection.
             items: RefCell::new(PinnedSlabChain::new(DropPolicy::MustNotDropItems)),
+            slow_io,
+            verify_completions,
+            set_aside: RefCell::new(SetAsideCompletions::default()),
+            in_flight_limiter,
         }
     }
 
@@ -63,6 +91,36 @@ impl OperationStore {
         self.items.borrow().is_empty()
     }
 
+    /// Requests cancellation of the operations in flight whose I/O primitive is known (see
+    /// `Operation::set_handle()`). Other operations in flight are left to complete on their own.
+    ///
+    /// The cancelled operations complete via the usual completion notifications, which must still
+    /// be processed before the store is empty.
+    ///
+    /// # Safety
+    ///
+    /// There must be no operations that have been created but not yet started, as we inspect the
+    /// operation cores, to which such operations hold exclusive references.
+    pub unsafe fn shut_down(&self) {
+        for core in self.items.borrow().iter() {
+            let core = core.get();
+
+            // SAFETY: All the operations have been started, so only the OS references the cores
+            // and the OS does not touch the fields we read here.
+            let (handle, started) = unsafe { ((*core).handle, (*core).started.is_some()) };
+
+            let Some(handle) = handle.filter(|_| started) else {
+                continue;
+            };
+
+            // The operation may have already completed, with the notification still waiting to be
+            // processed, in which case there is nothing to cancel. That is fine.
+            // SAFETY: Cancellation does not touch any memory of ours - the operation is completed
+            // via the usual completion notification, which takes care of releasing its resources.
+            _ = unsafe { CancelIoEx(handle, Some(ptr::addr_of!((*core).overlapped))) };
+        }
+    }
+
     /// Creates a new operation for performing I/O. You need to wrap each native I/O API call you
     /// make into a new one of these operations. The caller provides a buffer for any input/output
     /// data, which the operation takes ownership of. Once the operation has completed, the buffer
@@ -102,6 +160,29 @@ impl OperationStore {
     pub unsafe fn complete_operation(&self, overlapped_entry: OVERLAPPED_ENTRY) {
         event!(Level::TRACE, message = "I/O operation completed asynchronously", overlapped_ptr = overlapped_entry.lpOverlapped as usize);
 
+        if self.verify_completions && is_still_pending(overlapped_entry.lpOverlapped) {
+            // The notification cannot be trusted yet, so we set it aside. The operation stays
+            // as-is until either another notification arrives for it or we see it completed when
+            // checking the set-aside notifications again.
+            event!(
+                Level::WARN,
+                message =
+                    "setting aside completion notification for I/O operation that is still pending",
+                overlapped_ptr = overlapped_entry.lpOverlapped as usize
+            );
+            SPURIOUS_COMPLETIONS_IGNORED.with(Event::observe_unit);
+            self.set_aside.borrow_mut().set_aside(overlapped_entry);
+            return;
+        }
+
+        if self.verify_completions {
+            // The operation is being completed now, so any notification for it that we set aside
+            // earlier must not complete it a second time.
+            self.set_aside
+                .borrow_mut()
+                .forget(overlapped_entry.lpOverlapped);
+        }
+
         let bytes_transferred = overlapped_entry.dwNumberOfBytesTransferred as usize;
         let status = NTSTATUS(overlapped_entry.Internal as i32);
 
@@ -119,7 +200,7 @@ impl OperationStore {
             .take()
             .expect("buffer must exist because we only remove it after completion");
 
-        buffer.set_len(bytes_transferred);
+        set_bytes_transferred(&mut buffer, core.extra_buffers.take(), bytes_transferred);
 
         let duration = UltraLowPrecisionInstant::now().duration_since(
             core.started
@@ -129,6 +210,17 @@ impl OperationStore {
 
         OPERATION_COMPLETED_ASYNC_OK_DURATION.with(|x| x.observe_millis(duration));
 
+        if let Some(slow_io) = &self.slow_io {
+            slow_io.observe(&SlowIoOperation {
+                kind: core.kind,
+                handle: core.handle,
+                class: IoClass::from_completion_key(overlapped_entry.lpCompletionKey),
+                duration,
+                bytes_transferred,
+                succeeded: status == STATUS_SUCCESS,
+            });
+        }
+
         let result_tx = core
             .result_tx
             .take()
@@ -149,6 +241,27 @@ impl OperationStore {
         self.release(core.key);
     }
 
+    /// Completes the operations whose completion notifications were set aside because the operating
+    /// system still considered them to be in progress, if they have since completed. This is a
+    /// no-op unless completion verification is enabled (see
+    /// `RuntimeBuilder::verify_io_completions()`).
+    ///
+    /// # Safety
+    ///
+    /// Same requirements as `complete_operation()` apply to the notifications that were set aside.
+    pub unsafe fn complete_set_aside_operations(&self) {
+        if !self.verify_completions {
+            return;
+        }
+
+        // We must not hold the borrow while completing, as completion may set the entry aside again.
+        let completed = self.set_aside.borrow_mut().take_completed();
+
+        for overlapped_entry in completed {
+            self.complete_operation(overlapped_entry);
+        }
+    }
+
     /// Delivers the result of an operation that has completed synchronously to its originator and
     /// releases any resources held by the operation store. We consume here the OVERLAPPED
     /// structure that represents the operation core.
@@ -178,13 +291,15 @@ impl OperationStore {
             .take()
             .expect("buffer must exist because we only remove it after completion");
 
+        let extra_buffers = core.extra_buffers.take();
+
         let bytes_transferred = core.immediate_bytes_transferred as usize;
-        assert!(bytes_transferred <= buffer.len());
+        assert!(extra_buffers.is_some() || bytes_transferred <= buffer.len());
 
         OPERATIONS_COMPLETED_SYNC.with(Event::observe_unit);
         OPERATION_COMPLETED_BYTES.with(|x| x.observe(bytes_transferred as Magnitude));
 
-        buffer.set_len(bytes_transferred);
+        set_bytes_transferred(&mut buffer, extra_buffers, bytes_transferred);
 
         _ = core
             .result_tx
@@ -228,6 +343,10 @@ impl ControlNode {
         self.store.release(key);
     }
 
+    fn throttle(&self) {
+        self.store.in_flight_limiter.throttle();
+    }
+
     unsafe fn complete_immediately(&mut self, overlapped: *mut OVERLAPPED) {
         self.store.complete_immediately(overlapped)
     }
@@ -280,6 +399,32 @@ struct OperationCore {
     /// Timestamp of when the operation is started. Used to report I/O operation durations.
     started: Option<UltraLowPrecisionInstant>,
 
+    /// If the operation is tracked via `Operation::track()`, this keeps it counted as pending
+    /// until the operation core is released.
+    pending_token: Option<PendingOperationToken>,
+
+    /// The priority the operation was submitted with, which determines the order in which its
+    /// completion is dispatched under `IoPriorityPolicy::OperationPriority`.
+    priority: IoPriority,
+
+    /// The I/O primitive the operation is performed on, if recorded via `Operation::set_handle()`.
+    /// This allows the operation to be cancelled if its result future is dropped or the I/O driver
+    /// shuts down before it completes.
+    handle: Option<HANDLE>,
+
+    /// What the operation is doing, if recorded via `Operation::set_kind()`. Reported together
+    /// with slow operations.
+    kind: IoOperationKind,
+
+    /// The buffers of a vectored operation that follow the primary buffer, if set via
+    /// `Operation::set_extra_buffers()`. Shared with the originator, who gets the buffers back
+    /// from here once the operation has completed.
+    extra_buffers: Option<Rc<RefCell<Vec<PinnedBuffer>>>>,
+
+    /// Any other memory the OS may access until the operation completes, if set via
+    /// `Operation::keep_alive()`. Released together with the operation core.
+    keep_alive: Option<Rc<dyn Any>>,
+
     // Once pinned, this type cannot be unpinned.
     _phantom_pin: std::marker::PhantomPinned,
 }
@@ -301,6 +446,12 @@ impl OperationCore {
             result_tx: Some(result_tx),
             result_rx: Some(result_rx),
             started: None,
+            pending_token: None,
+            priority: IoPriority::default(),
+            handle: None,
+            kind: IoOperationKind::default(),
+            extra_buffers: None,
+            keep_alive: None,
             _phantom_pin: std::marker::PhantomPinned,
         }
     }
@@ -318,6 +469,12 @@ impl fmt::Debug for OperationCore {
             .field("result_tx", &self.result_tx)
             .field("result_rx", &self.result_rx)
             .field("started", &self.started)
+            .field("pending_token", &self.pending_token)
+            .field("priority", &self.priority)
+            .field("handle", &self.handle)
+            .field("kind", &self.kind)
+            .field("extra_buffers", &self.extra_buffers)
+            .field("keep_alive", &self.keep_alive)
             .finish()
     }
 }
@@ -350,6 +507,52 @@ impl Operation {
         self.core.overlapped.Anonymous.Anonymous.OffsetHigh = (offset >> 32) as u32;
     }
 
+    /// Counts the operation as pending in the given tracker until the operation has completed and
+    /// its buffer has been released by the operating system.
+    pub fn track(&mut self, pending: &PendingOperations) {
+        self.core.pending_token = Some(pending.track());
+    }
+
+    /// Records the priority of the operation, which determines the order in which its completion
+    /// is dispatched if the I/O driver uses `IoPriorityPolicy::OperationPriority`.
+    pub fn set_priority(&mut self, priority: IoPriority) {
+        self.core.priority = priority;
+    }
+
+    /// Records what the operation is doing, for reporting slow operations.
+    pub fn set_kind(&mut self, kind: IoOperationKind) {
+        self.core.kind = kind;
+    }
+
+    /// Records the I/O primitive the operation is performed on, which allows the operation to be
+    /// cancelled if its result future is dropped or the runtime shuts down while the operation is
+    /// in flight. Operations without a recorded I/O primitive run until they complete on their own,
+    /// delaying the shutdown until then.
+    pub fn set_handle(&mut self, handle: &(impl Into<IoPrimitive> + Copy)) {
+        let primitive: IoPrimitive = (*handle).into();
+        self.core.handle = Some(primitive.into());
+    }
+
+    /// Makes this a vectored (scatter/gather) operation, which transfers data to or from the primary
+    /// buffer first and then the extra buffers in order. On completion, the active region of each
+    /// buffer is set to the bytes transferred to or from it - buffers that the operation did not
+    /// reach are left with an empty active region.
+    ///
+    /// The callback of `begin()` only receives the primary buffer. The caller is responsible for
+    /// passing the active regions of the extra buffers to the native API after it, which is valid
+    /// because the storage of a buffer does not move. The operation keeps the extra buffers alive
+    /// until it completes, after which the caller can take them back from the shared list.
+    pub fn set_extra_buffers(&mut self, buffers: Rc<RefCell<Vec<PinnedBuffer>>>) {
+        self.core.extra_buffers = Some(buffers);
+    }
+
+    /// Keeps a value alive until the operation completes, even if the originator stops waiting for
+    /// the result. Use this for memory other than the buffers that the native API may access until
+    /// completion, such as a structure that receives the address of a remote endpoint.
+    pub fn keep_alive(&mut self, value: Rc<dyn Any>) {
+        self.core.keep_alive = Some(value);
+    }
+
     /// Executes an I/O operation, using the specified callback to pass the operation buffer and
     /// OVERLAPPED metadata structure to native OS functions.
     ///
@@ -385,6 +588,8 @@ impl Operation {
             .take()
             .expect("operation is always expected to have result rx when beginning I/O");
 
+        let handle = self.core.handle;
+
         // We clone the control node because we may need to release the operation core if the
         // callback fails or even resurrect it immediately if the callback completes synchronously.
         let mut control_node = self.control.clone();
@@ -392,9 +597,14 @@ impl Operation {
 
         match f(buffer, overlapped, immediate_bytes_transferred) {
             // The operation was started asynchronously. This is what we want to see.
-            Err(io::Error::Windows(e)) if e.code() == ERROR_IO_PENDING.into() => {}
+            Err(io::Error::Windows(e)) if e.code() == ERROR_IO_PENDING.into() => {
+                return OperationResultFuture::pending(result_rx, handle, overlapped);
+            }
             Err(io::Error::Winsock { code, detail })
-                if code == SOCKET_ERROR && detail == WSA_IO_PENDING => {}
+                if code == SOCKET_ERROR && detail == WSA_IO_PENDING =>
+            {
+                return OperationResultFuture::pending(result_rx, handle, overlapped);
+            }
 
             // The operation completed synchronously. This means we will not get a completion
             // notification and must handle the result inline (because we set a flag saying this
@@ -413,6 +623,21 @@ impl Operation {
             // We need to free the operation core ourselves to avoid leaking it forever, as well
             // as resurrect the core so we can get the buffer out of it and back to the originator.
             Err(e) => {
+                // The OS may refuse new operations when it is running low on resources. This is
+                // not fatal - the caller gets a retryable error and may try again later, while we
+                // admit fewer operations until the ones in flight start completing.
+                if e.is_retryable() {
+                    control_node.throttle();
+
+                    event!(
+                        Level::WARN,
+                        message = "I/O operation rejected due to resource exhaustion",
+                        error = %e
+                    );
+
+                    OPERATIONS_REJECTED_RETRYABLE.with(Event::observe_unit);
+                }
+
                 // SAFETY: The core is only referenced by either Operation or the operating system at any
                 // given time, so there is no possibility of multiple exclusive references being created.
                 let core = overlapped as *mut OperationCore;
@@ -426,6 +651,7 @@ impl Operation {
                 return OperationResultFuture {
                     receiver: result_rx,
                     error: Some(io::OperationError::new(e, buffer)),
+                    cancel_target: None,
                 };
             }
         }
@@ -433,6 +659,7 @@ impl Operation {
         OperationResultFuture {
             receiver: result_rx,
             error: None,
+            cancel_target: None,
         }
     }
 
@@ -469,12 +696,64 @@ impl Operation {
     }
 }
 
-#[pin_project]
+/// Sets the active region of the buffer of a completed operation to the bytes transferred. For
+/// vectored operations, the bytes are distributed over the primary buffer and the extra buffers in
+/// order, as the OS fills (or drains) each buffer before moving on to the next one.
+fn set_bytes_transferred(
+    buffer: &mut PinnedBuffer,
+    extra_buffers: Option<Rc<RefCell<Vec<PinnedBuffer>>>>,
+    bytes_transferred: usize,
+) {
+    let Some(extra_buffers) = extra_buffers else {
+        buffer.set_len(bytes_transferred);
+        return;
+    };
+
+    let mut extra_buffers = extra_buffers.borrow_mut();
+    let mut remaining = bytes_transferred;
+
+    for buffer in iter::once(buffer).chain(extra_buffers.iter_mut()) {
+        let len = buffer.len().min(remaining);
+        buffer.set_len(len);
+        remaining -= len;
+    }
+
+    assert_eq!(
+        remaining, 0,
+        "more bytes transferred than fit into the buffers of the operation"
+    );
+}
+
+/// Resolves to the result of an operation once it has completed.
+///
+/// If the future is dropped while the operation is still in flight, the operation is cancelled,
+/// provided that its I/O primitive is known (see `Operation::set_handle()`). Either way, the buffer
+/// of the operation stays alive in the operation store until the completion notification arrives,
+/// as the OS may write into it until then.
+#[pin_project(PinnedDrop)]
 #[derive(Debug)]
 pub struct OperationResultFuture {
     #[pin]
     receiver: oneshot::Receiver<io::OperationResult>,
     error: Option<io::OperationError>,
+
+    // The I/O primitive and OVERLAPPED of an operation started asynchronously on a known I/O
+    // primitive, to cancel the operation with if the future is dropped before it completes.
+    cancel_target: Option<(HANDLE, *const OVERLAPPED)>,
+}
+
+impl OperationResultFuture {
+    fn pending(
+        receiver: oneshot::Receiver<io::OperationResult>,
+        handle: Option<HANDLE>,
+        overlapped: *const OVERLAPPED,
+    ) -> Self {
+        Self {
+            receiver,
+            error: None,
+            cancel_target: handle.map(|handle| (handle, overlapped)),
+        }
+    }
 }
 
 impl Future for OperationResultFuture {
@@ -488,19 +767,148 @@ impl Future for OperationResultFuture {
         }
 
         match this.receiver.poll(cx) {
-            Poll::Ready(v) => Poll::Ready(v.expect("")),
+            Poll::Ready(v) => {
+                *this.cancel_target = None;
+                Poll::Ready(v.expect(""))
+            }
             Poll::Pending => Poll::Pending,
         }
     }
 }
 
+#[pinned_drop]
+impl PinnedDrop for OperationResultFuture {
+    fn drop(self: std::pin::Pin<&mut Self>) {
+        let this = self.project();
+
+        let Some((handle, overlapped)) = this.cancel_target.take() else {
+            return;
+        };
+
+        // The operation core holds the sender until the result has been delivered, after which
+        // the core is released and may be reused by another operation. As long as the channel is
+        // empty, the OVERLAPPED still belongs to our operation, which is still in flight.
+        if !matches!(this.receiver.try_recv(), Err(oneshot::TryRecvError::Empty)) {
+            return;
+        }
+
+        // The operation may have completed already, with the notification still waiting to be
+        // processed, in which case there is nothing to cancel. That is fine. The cancelled
+        // operation completes via the usual completion notification, which releases the buffer.
+        // SAFETY: Cancellation does not touch any memory of ours and the OVERLAPPED is valid, as
+        // established above.
+        _ = unsafe { CancelIoEx(handle, Some(overlapped)) };
+    }
+}
+
 impl Drop for Operation {
     fn drop(&mut self) {
         self.control.release(self.core.key);
     }
 }
 
+/// Whether the OS still considers the operation to be in progress, equivalent to the negation of
+/// `HasOverlappedIoCompleted()`.
+///
+/// # Safety
+///
+/// The pointer must be a valid OVERLAPPED pointer that was handed to the OS.
+pub(super) unsafe fn is_still_pending(overlapped: *const OVERLAPPED) -> bool {
+    // The OS may update the status at any time until the operation completes, so we must not let
+    // the compiler assume anything about its value.
+    let status = ptr::read_volatile(ptr::addr_of!((*overlapped).Internal));
+
+    status == STATUS_PENDING.0 as usize
+}
+
+/// Completion notifications received for operations that the OS still considered to be in progress
+/// at the time (see `RuntimeBuilder::verify_io_completions()`). These are checked again each time
+/// completions are processed, so the result is delivered even if no further notification arrives.
+#[derive(Debug, Default)]
+pub(super) struct SetAsideCompletions {
+    entries: Vec<OVERLAPPED_ENTRY>,
+}
+
+impl SetAsideCompletions {
+    pub fn set_aside(&mut self, overlapped_entry: OVERLAPPED_ENTRY) {
+        // Repeated notifications for the same operation are all equally untrustworthy.
+        if !self.contains(overlapped_entry.lpOverlapped) {
+            self.entries.push(overlapped_entry);
+        }
+    }
+
+    /// Discards any set-aside notification for the operation, which is being completed via another
+    /// notification.
+    pub fn forget(&mut self, overlapped: *mut OVERLAPPED) {
+        self.entries
+            .retain(|entry| entry.lpOverlapped != overlapped);
+    }
+
+    /// Removes and returns the notifications for the operations that are no longer pending.
+    ///
+    /// The set-aside notifications cannot be trusted, so the status and the number of bytes
+    /// transferred in the returned notifications are taken from the OVERLAPPED structure, where the
+    /// OS stored the actual result of the operation.
+    ///
+    /// # Safety
+    ///
+    /// The set-aside notifications must be for operations that have not been completed yet.
+    pub unsafe fn take_completed(&mut self) -> Vec<OVERLAPPED_ENTRY> {
+        let mut completed = Vec::new();
+
+        self.entries.retain(|entry| {
+            if is_still_pending(entry.lpOverlapped) {
+                return true;
+            }
+
+            let overlapped = entry.lpOverlapped;
+
+            completed.push(OVERLAPPED_ENTRY {
+                Internal: ptr::read_volatile(ptr::addr_of!((*overlapped).Internal)),
+                dwNumberOfBytesTransferred: ptr::read_volatile(ptr::addr_of!(
+                    (*overlapped).InternalHigh
+                )) as u32,
+                ..*entry
+            });
+
+            false
+        });
+
+        completed
+    }
+
+    fn contains(&self, overlapped: *mut OVERLAPPED) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| entry.lpOverlapped == overlapped)
+    }
+}
+
+// SAFETY: The OVERLAPPED pointers are only dereferenced by the owner of the operation store, which
+// synchronizes access to the set-aside notifications together with the operations themselves.
+unsafe impl Send for SetAsideCompletions {}
+
+/// The priority that the operation was submitted with (see `Operation::set_priority()`).
+///
+/// # Safety
+///
+/// The pointer must be the OVERLAPPED pointer of an operation whose completion notification has
+/// been received but not yet processed.
+pub(super) unsafe fn operation_priority(overlapped: *const OVERLAPPED) -> IoPriority {
+    (*(overlapped as *const OperationCore)).priority
+}
+
 thread_local! {
+    static SPURIOUS_COMPLETIONS_IGNORED: Event = EventBuilder::new()
+        .name("io_spurious_completions_ignored")
+        .build()
+        .unwrap();
+
+    static OPERATIONS_REJECTED_RETRYABLE: Event = EventBuilder::new()
+        .name("io_ops_rejected_retryable")
+        .build()
+        .unwrap();
+
     static OPERATIONS_ALLOCATED: Event = EventBuilder::new()
         .name("io_ops_allocated")
         .build()
@@ -528,3 +936,268 @@ thread_local! {
         .build()
         .unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{executor::block_on, task::noop_waker_ref, FutureExt};
+    use std::{
+        sync::{Arc, Mutex},
+        task, thread,
+        time::Duration,
+    };
+    use windows::Win32::Foundation::{ERROR_INVALID_USER_BUFFER, STATUS_CANCELLED};
+
+    #[test]
+    fn submission_rejected_due_to_resource_exhaustion() {
+        let limiter = Rc::new(InFlightLimiter::new(None));
+        let store = OperationStore::new(None, false, Rc::clone(&limiter));
+
+        let mut permits = (0..4)
+            .map(|_| limiter.admit(IoPriority::Normal).now_or_never().unwrap())
+            .collect::<Vec<_>>();
+
+        let buffer = PinnedBuffer::from_boxed_slice(vec![0; 16].into_boxed_slice());
+        let operation = store.new_operation(buffer);
+
+        // We simulate the native I/O call refusing the operation, which is what the OS does when
+        // the non-paged pool is exhausted.
+        // SAFETY: A failed native call is a valid use of the callback arguments.
+        let result = block_on(unsafe {
+            operation.begin(|_, _, _| {
+                Err(io::Error::Windows(windows_result::Error::from(
+                    ERROR_INVALID_USER_BUFFER.to_hresult(),
+                )))
+            })
+        });
+
+        let error = result.unwrap_err();
+        assert!(error.inner.is_retryable());
+        assert_eq!(error.buffer.capacity(), 16);
+
+        // The operation has been released, so the driver is free to continue (or shut down).
+        assert!(store.is_empty());
+
+        // Fewer operations are admitted until the ones in flight start completing.
+        drop(permits.pop());
+        assert!(limiter.admit(IoPriority::Normal).now_or_never().is_none());
+
+        drop(permits);
+        assert!(limiter.admit(IoPriority::Normal).now_or_never().is_some());
+    }
+
+    #[test]
+    fn slow_operation_reported() {
+        let reported = Arc::new(Mutex::new(Vec::new()));
+
+        let hook = SlowIoHook::new(Duration::from_millis(50), {
+            let reported = Arc::clone(&reported);
+            move |operation: &SlowIoOperation| reported.lock().unwrap().push(*operation)
+        });
+
+        let store = OperationStore::new(Some(hook), false, Rc::new(InFlightLimiter::new(None)));
+
+        let started = |store: &OperationStore| {
+            let buffer = PinnedBuffer::from_boxed_slice(vec![0; 16].into_boxed_slice());
+            let mut overlapped_ptr = ptr::null_mut();
+
+            let mut operation = store.new_operation(buffer);
+            operation.set_kind(IoOperationKind::Read);
+
+            // This is never used to cancel the operation, as we always wait for it to complete.
+            operation.set_handle(&HANDLE(42 as *mut _));
+
+            // We pretend that the operation was started asynchronously, standing in for the OS.
+            // SAFETY: We complete the operation ourselves below, as the OS would.
+            let future = unsafe {
+                operation.begin(|_, overlapped, _| {
+                    overlapped_ptr = overlapped;
+                    Err(io::Error::Windows(ERROR_IO_PENDING.into()))
+                })
+            };
+
+            (future, overlapped_ptr)
+        };
+
+        let complete = |overlapped: *mut OVERLAPPED| {
+            // SAFETY: The operation was started above and has not been completed yet.
+            unsafe {
+                store.complete_operation(OVERLAPPED_ENTRY {
+                    lpCompletionKey: IoClass::Disk.completion_key(),
+                    lpOverlapped: overlapped,
+                    dwNumberOfBytesTransferred: 10,
+                    ..Default::default()
+                });
+            }
+        };
+
+        // A fast operation is not reported.
+        let (fast, overlapped) = started(&store);
+        complete(overlapped);
+        block_on(fast).unwrap();
+
+        assert!(reported.lock().unwrap().is_empty());
+
+        // We inject latency that exceeds the threshold.
+        let (slow, overlapped) = started(&store);
+        thread::sleep(Duration::from_millis(200));
+        complete(overlapped);
+        block_on(slow).unwrap();
+
+        let reported = reported.lock().unwrap();
+        assert_eq!(reported.len(), 1);
+        assert_eq!(reported[0].kind, IoOperationKind::Read);
+        assert_eq!(reported[0].handle, Some(HANDLE(42 as *mut _)));
+        assert_eq!(reported[0].class, IoClass::Disk);
+        assert_eq!(reported[0].bytes_transferred, 10);
+        assert!(reported[0].succeeded);
+        assert!(reported[0].duration > Duration::from_millis(50));
+
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn dropped_future_keeps_buffer_until_completion() {
+        let store = OperationStore::new(None, false, Rc::new(InFlightLimiter::new(None)));
+        let buffer = PinnedBuffer::from_boxed_slice(vec![0; 16].into_boxed_slice());
+        let mut overlapped_ptr = ptr::null_mut();
+
+        let mut operation = store.new_operation(buffer);
+
+        // There is no real I/O primitive behind this, so the cancellation fails, which is fine -
+        // the completion notification below stands in for the result of the cancellation.
+        operation.set_handle(&HANDLE(ptr::null_mut()));
+
+        // We pretend that the operation was started asynchronously, standing in for the OS.
+        // SAFETY: We complete the operation ourselves below, as the OS would.
+        let future = unsafe {
+            operation.begin(|_, overlapped, _| {
+                overlapped_ptr = overlapped;
+                Err(io::Error::Windows(ERROR_IO_PENDING.into()))
+            })
+        };
+
+        drop(future);
+
+        // The OS may still write into the buffer, so the operation must not be released yet.
+        assert!(!store.is_empty());
+
+        // SAFETY: The operation was started above and has not been completed yet.
+        unsafe {
+            store.complete_operation(OVERLAPPED_ENTRY {
+                lpCompletionKey: IoClass::Disk.completion_key(),
+                lpOverlapped: overlapped_ptr,
+                Internal: STATUS_CANCELLED.0 as usize,
+                ..Default::default()
+            });
+        }
+
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn spurious_completion_ignored() {
+        let store = OperationStore::new(None, true, Rc::new(InFlightLimiter::new(None)));
+        let buffer = PinnedBuffer::from_boxed_slice(vec![0; 16].into_boxed_slice());
+        let mut overlapped_ptr = ptr::null_mut();
+
+        // We pretend that the operation was started asynchronously, standing in for the OS.
+        // SAFETY: We complete the operation ourselves below, as the OS would.
+        let mut future = Box::pin(unsafe {
+            store.new_operation(buffer).begin(|_, overlapped, _| {
+                overlapped_ptr = overlapped;
+                Err(io::Error::Windows(ERROR_IO_PENDING.into()))
+            })
+        });
+
+        let entry = OVERLAPPED_ENTRY {
+            lpCompletionKey: IoClass::Disk.completion_key(),
+            lpOverlapped: overlapped_ptr,
+            dwNumberOfBytesTransferred: 10,
+            ..Default::default()
+        };
+
+        // The OS marks the operation as pending while it is in progress. We inject a completion
+        // notification while the operation is still in that state.
+        // SAFETY: The operation was started above and has not been completed yet.
+        unsafe {
+            (*overlapped_ptr).Internal = STATUS_PENDING.0 as usize;
+            store.complete_operation(entry);
+        }
+
+        let mut cx = task::Context::from_waker(noop_waker_ref());
+        assert!(future.as_mut().poll(&mut cx).is_pending());
+        assert!(!store.is_empty());
+
+        // Now the operation really completes.
+        // SAFETY: The operation was started above and has not been completed yet.
+        unsafe {
+            (*overlapped_ptr).Internal = STATUS_SUCCESS.0 as usize;
+            store.complete_operation(entry);
+        }
+
+        let buffer = block_on(future).unwrap();
+        assert_eq!(buffer.len(), 10);
+
+        assert!(store.is_empty());
+
+        // The set-aside notification was discarded when the operation completed, so there is
+        // nothing left to complete a second time.
+        // SAFETY: There are no set-aside notifications left.
+        unsafe {
+            store.complete_set_aside_operations();
+        }
+    }
+
+    #[test]
+    fn set_aside_completion_delivered_once_no_longer_pending() {
+        let store = OperationStore::new(None, true, Rc::new(InFlightLimiter::new(None)));
+        let buffer = PinnedBuffer::from_boxed_slice(vec![0; 16].into_boxed_slice());
+        let mut overlapped_ptr = ptr::null_mut();
+
+        // We pretend that the operation was started asynchronously, standing in for the OS.
+        // SAFETY: We complete the operation ourselves below, as the OS would.
+        let mut future = Box::pin(unsafe {
+            store.new_operation(buffer).begin(|_, overlapped, _| {
+                overlapped_ptr = overlapped;
+                Err(io::Error::Windows(ERROR_IO_PENDING.into()))
+            })
+        });
+
+        // We inject a completion notification while the operation is still pending.
+        // SAFETY: The operation was started above and has not been completed yet.
+        unsafe {
+            (*overlapped_ptr).Internal = STATUS_PENDING.0 as usize;
+            store.complete_operation(OVERLAPPED_ENTRY {
+                lpCompletionKey: IoClass::Disk.completion_key(),
+                lpOverlapped: overlapped_ptr,
+                ..Default::default()
+            });
+        }
+
+        let mut cx = task::Context::from_waker(noop_waker_ref());
+        assert!(future.as_mut().poll(&mut cx).is_pending());
+
+        // Checking again while the operation is still pending changes nothing.
+        // SAFETY: The operation has not been completed yet.
+        unsafe {
+            store.complete_set_aside_operations();
+        }
+
+        assert!(future.as_mut().poll(&mut cx).is_pending());
+        assert!(!store.is_empty());
+
+        // The operation completes but no further notification arrives for it.
+        // SAFETY: The operation was started above and has not been completed yet.
+        unsafe {
+            (*overlapped_ptr).InternalHigh = 10;
+            (*overlapped_ptr).Internal = STATUS_SUCCESS.0 as usize;
+            store.complete_set_aside_operations();
+        }
+
+        let buffer = block_on(future).unwrap();
+        assert_eq!(buffer.len(), 10);
+
+        assert!(store.is_empty());
+    }
+}
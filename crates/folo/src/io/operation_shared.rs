@@ -1,6 +1,11 @@
 use crate::{
     constants::{self, GENERAL_BYTES_BUCKETS, GENERAL_MILLISECONDS_BUCKETS},
-    io::{self, OperationResultShared, PinnedBufferShared},
+    io::{
+        self,
+        operation::{is_still_pending, SetAsideCompletions},
+        IoClass, IoOperationKind, IoPrimitive, OperationResultShared, PinnedBufferShared,
+        SlowIoHook, SlowIoOperation,
+    },
     mem::{DropPolicy, PinnedSlabChain},
     metrics::{Event, EventBuilder, Magnitude},
     time::{UltraLowPrecisionInstant},
@@ -8,18 +13,22 @@ use crate::{
 use pin_project::pin_project;
 use std::{
     cell::{RefCell, UnsafeCell},
+    collections::HashMap,
     fmt,
     future::Future,
     mem::{self, ManuallyDrop},
     ptr,
-    sync::Mutex,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
     task::Poll,
 };
 use tracing::{event, Level};
 use windows::Win32::{
-    Foundation::{ERROR_IO_PENDING, NTSTATUS, STATUS_SUCCESS},
+    Foundation::{ERROR_IO_PENDING, HANDLE, NTSTATUS, STATUS_SUCCESS},
     Networking::WinSock::{SOCKET_ERROR, WSA_IO_PENDING},
-    System::IO::{OVERLAPPED, OVERLAPPED_ENTRY},
+    System::IO::{CancelIoEx, OVERLAPPED, OVERLAPPED_ENTRY},
 };
 
 /// Maintains the backing storage for the metadata structures of I/O operations submitted to the
@@ -45,10 +54,31 @@ pub(super) struct OperationStoreShared {
     // reference from the slab chain and giving it to the operating system to mutate, which would
     // be invalid Rust without Unsafecell.
     items: Mutex<RefCell<PinnedSlabChain<UnsafeCell<OperationCore>>>>,
+
+    // If set, we verify that each completion notification is for an operation that has actually
+    // completed before delivering its result. See `RuntimeBuilder::verify_io_completions()`.
+    verify_completions: bool,
+
+    // Completion notifications received for operations that were still pending at the time, to be
+    // checked again on the next `complete_set_aside_operations()`.
+    set_aside: Mutex<SetAsideCompletions>,
+
+    // If set, we report operations that take longer than a threshold to complete.
+    // See `RuntimeBuilder::on_slow_io()`.
+    slow_io: Option<SlowIoHook>,
+
+    // Set once the store is shutting down, after which no new operations may begin. The store is
+    // shared by all async worker threads, so other threads may still be running tasks that try.
+    shutting_down: AtomicBool,
+
+    // The operations that can be cancelled on shutdown because their I/O primitive is known (see
+    // `OperationShared::set_handle()`). Other threads may be preparing operations at any time, so
+    // we cannot inspect the operation cores themselves like the single-threaded store does.
+    cancel_targets: Mutex<HashMap<OperationKey, CancelTarget>>,
 }
 
 impl OperationStoreShared {
-    pub fn new() -> Self {
+    pub fn new(verify_completions: bool, slow_io: Option<SlowIoHook>) -> Self {
         Self {
             // We use a MustNotDropItems policy because the operations are shared with the operating
             // system so it is in general not safe to drop the memory unless the OS is done with it,
@@ -57,6 +87,31 @@ impl OperationStoreShared {
             items: Mutex::new(RefCell::new(PinnedSlabChain::new(
                 DropPolicy::MustNotDropItems,
             ))),
+            verify_completions,
+            set_aside: Mutex::new(SetAsideCompletions::default()),
+            slow_io,
+            shutting_down: AtomicBool::new(false),
+            cancel_targets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Stops accepting new operations and requests cancellation of the operations in flight whose
+    /// I/O primitive is known (see `OperationShared::set_handle()`). New operations are rejected
+    /// with `io::Error::ShuttingDown` and other operations in flight are left to complete on their
+    /// own.
+    ///
+    /// The cancelled operations complete via the usual completion notifications, which must still
+    /// be processed before the store is empty.
+    pub fn shut_down(&self) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+
+        for target in self
+            .cancel_targets
+            .lock()
+            .expect(constants::POISONED_LOCK)
+            .values()
+        {
+            target.cancel();
         }
     }
 
@@ -92,6 +147,31 @@ impl OperationStoreShared {
         }
     }
 
+    /// Completes the operations whose completion notifications were set aside because the operating
+    /// system still considered them to be in progress, if they have since completed. This is a
+    /// no-op unless completion verification is enabled (see
+    /// `RuntimeBuilder::verify_io_completions()`).
+    ///
+    /// # Safety
+    ///
+    /// Same requirements as `complete_operation()` apply to the notifications that were set aside.
+    pub unsafe fn complete_set_aside_operations(&self) {
+        if !self.verify_completions {
+            return;
+        }
+
+        // We must not hold the lock while completing, as completion may set the entry aside again.
+        let completed = self
+            .set_aside
+            .lock()
+            .expect(constants::POISONED_LOCK)
+            .take_completed();
+
+        for overlapped_entry in completed {
+            self.complete_operation(overlapped_entry);
+        }
+    }
+
     /// Delivers the result of an operation that has completed asynchronously to its originator and
     /// releases any resources held by the operation store. We consume here the OVERLAPPED_ENTRY
     /// structure that represents not only the operation core but also the status and the number of
@@ -107,6 +187,33 @@ impl OperationStoreShared {
     /// You must also have received a completion notification from the OS, saying that the operation
     /// has completed.
     pub unsafe fn complete_operation(&self, overlapped_entry: OVERLAPPED_ENTRY) {
+        if self.verify_completions && is_still_pending(overlapped_entry.lpOverlapped) {
+            // The notification cannot be trusted yet, so we set it aside. The operation stays
+            // as-is until either another notification arrives for it or we see it completed when
+            // checking the set-aside notifications again.
+            event!(
+                Level::WARN,
+                message =
+                    "setting aside completion notification for I/O operation that is still pending",
+                overlapped_ptr = overlapped_entry.lpOverlapped as usize
+            );
+            SPURIOUS_COMPLETIONS_IGNORED.with(Event::observe_unit);
+            self.set_aside
+                .lock()
+                .expect(constants::POISONED_LOCK)
+                .set_aside(overlapped_entry);
+            return;
+        }
+
+        if self.verify_completions {
+            // The operation is being completed now, so any notification for it that we set aside
+            // earlier must not complete it a second time.
+            self.set_aside
+                .lock()
+                .expect(constants::POISONED_LOCK)
+                .forget(overlapped_entry.lpOverlapped);
+        }
+
         let bytes_transferred = overlapped_entry.dwNumberOfBytesTransferred as usize;
         let status = NTSTATUS(overlapped_entry.Internal as i32);
 
@@ -134,6 +241,17 @@ impl OperationStoreShared {
 
         OPERATION_COMPLETED_ASYNC_OK_DURATION.with(|x| x.observe_millis(duration));
 
+        if let Some(slow_io) = &self.slow_io {
+            slow_io.observe(&SlowIoOperation {
+                kind: core.kind,
+                handle: core.handle,
+                class: IoClass::from_completion_key(overlapped_entry.lpCompletionKey),
+                duration,
+                bytes_transferred,
+                succeeded: status == STATUS_SUCCESS,
+            });
+        }
+
         let result_tx = core
             .result_tx
             .take()
@@ -202,10 +320,22 @@ impl OperationStoreShared {
     fn release(&self, key: OperationKey) {
         assert!(key != OperationKey::MAX);
 
+        self.cancel_targets
+            .lock()
+            .expect(constants::POISONED_LOCK)
+            .remove(&key);
+
         let items_guard = self.items.lock().expect(constants::POISONED_LOCK);
         (*items_guard).borrow_mut().remove(key);
     }
 
+    fn add_cancel_target(&self, key: OperationKey, target: CancelTarget) {
+        self.cancel_targets
+            .lock()
+            .expect(constants::POISONED_LOCK)
+            .insert(key, target);
+    }
+
     fn control_node(&self) -> ControlNode {
         ControlNode {
             // SAFETY: We pretend that the store is 'static to avoid overcomplex lifetime
@@ -218,6 +348,27 @@ impl OperationStoreShared {
 
 type OperationKey = usize;
 
+/// Identifies an operation to `CancelIoEx()`.
+#[derive(Clone, Copy, Debug)]
+struct CancelTarget {
+    handle: HANDLE,
+    overlapped: *const OVERLAPPED,
+}
+
+impl CancelTarget {
+    fn cancel(&self) {
+        // The operation may have already completed or not yet been submitted, in which case there
+        // is nothing to cancel. That is fine.
+        // SAFETY: Cancellation does not touch any memory of ours - the operation is completed via
+        // the usual completion notification, which takes care of releasing its resources.
+        _ = unsafe { CancelIoEx(self.handle, Some(self.overlapped)) };
+    }
+}
+
+// SAFETY: The handle and the OVERLAPPED pointer are only used to identify the operation to the
+// operating system, which does not care which thread asks.
+unsafe impl Send for CancelTarget {}
+
 /// Constrained API surface that allows an operation to command the store that owns it. This creates
 /// a circular reference between an operation and the OperationStore, so we always use
 /// OperationStore via interior mutability to prevent accidents here.
@@ -235,6 +386,14 @@ impl ControlNode {
     unsafe fn complete_immediately(&mut self, overlapped: *mut OVERLAPPED) {
         self.store.complete_immediately(overlapped)
     }
+
+    fn is_shutting_down(&self) -> bool {
+        self.store.shutting_down.load(Ordering::SeqCst)
+    }
+
+    fn add_cancel_target(&mut self, key: OperationKey, target: CancelTarget) {
+        self.store.add_cancel_target(key, target);
+    }
 }
 
 /// The operation core contains the data structures required to communicate with the operating
@@ -278,6 +437,14 @@ struct OperationCore {
     /// Timestamp of when the operation is started. Used to report I/O operation durations.
     started: Option<UltraLowPrecisionInstant>,
 
+    /// The I/O primitive the operation is performed on, if recorded via
+    /// `OperationShared::set_handle()`. Used to cancel the operation on shutdown.
+    handle: Option<HANDLE>,
+
+    /// What the operation is doing, if recorded via `OperationShared::set_kind()`. Reported
+    /// together with slow operations.
+    kind: IoOperationKind,
+
     // Once pinned, this type cannot be unpinned.
     _phantom_pin: std::marker::PhantomPinned,
 }
@@ -299,6 +466,8 @@ impl OperationCore {
             result_tx: Some(result_tx),
             result_rx: Some(result_rx),
             started: None,
+            handle: None,
+            kind: IoOperationKind::default(),
             _phantom_pin: std::marker::PhantomPinned,
         }
     }
@@ -320,6 +489,8 @@ impl fmt::Debug for OperationCore {
             .field("result_tx", &self.result_tx)
             .field("result_rx", &self.result_rx)
             .field("started", &self.started)
+            .field("handle", &self.handle)
+            .field("kind", &self.kind)
             .finish()
     }
 }
@@ -341,6 +512,18 @@ impl OperationShared {
         self.core.overlapped.Anonymous.Anonymous.OffsetHigh = (offset >> 32) as u32;
     }
 
+    /// Records the I/O primitive the operation is performed on, which allows the operation to be
+    /// cancelled when the runtime shuts down.
+    pub fn set_handle(&mut self, handle: &(impl Into<IoPrimitive> + Copy)) {
+        let primitive: IoPrimitive = (*handle).into();
+        self.core.handle = Some(primitive.into());
+    }
+
+    /// Records what the operation is doing, for reporting slow operations.
+    pub fn set_kind(&mut self, kind: IoOperationKind) {
+        self.core.kind = kind;
+    }
+
     /// Executes an I/O operation, using the specified callback to pass the operation buffer and
     /// OVERLAPPED metadata structure to native OS functions.
     ///
@@ -376,16 +559,60 @@ impl OperationShared {
             .take()
             .expect("operation is always expected to have result rx when beginning I/O");
 
+        if self.control.is_shutting_down() {
+            let buffer = self
+                .core
+                .buffer
+                .take()
+                .expect("buffer must exist because the operation has not started");
+
+            // Dropping the operation releases the operation core.
+            drop(self);
+
+            return OperationResultSharedFuture {
+                receiver: result_rx,
+                error: Some(io::OperationErrorShared::new(
+                    io::Error::ShuttingDown,
+                    buffer,
+                )),
+            };
+        }
+
+        let key = self.core.key;
+        let handle = self.core.handle;
+
         // We clone the control node because we may need to release the operation core if the
         // callback fails or even resurrect it immediately if the callback completes synchronously.
         let mut control_node = self.control.clone();
         let (buffer, overlapped, immediate_bytes_transferred) = self.into_callback_arguments();
 
+        // The target is registered before the operation is submitted, so it is already there if
+        // the operation completes on another thread right away - the target is removed when the
+        // operation core is released.
+        let cancel_target = handle.map(|handle| CancelTarget { handle, overlapped });
+
+        if let Some(target) = cancel_target {
+            control_node.add_cancel_target(key, target);
+        }
+
+        // If shutdown started while we were submitting the operation, it may have tried to cancel
+        // the operation before the OS knew about it, so we cancel it ourselves.
+        let cancel_if_shutting_down = |control_node: &ControlNode| {
+            if let Some(target) = cancel_target.filter(|_| control_node.is_shutting_down()) {
+                target.cancel();
+            }
+        };
+
         match f(buffer, overlapped, immediate_bytes_transferred) {
             // The operation was started asynchronously. This is what we want to see.
-            Err(io::Error::Windows(e)) if e.code() == ERROR_IO_PENDING.into() => {}
+            Err(io::Error::Windows(e)) if e.code() == ERROR_IO_PENDING.into() => {
+                cancel_if_shutting_down(&control_node);
+            }
             Err(io::Error::Winsock { code, detail })
-                if code == SOCKET_ERROR && detail == WSA_IO_PENDING => {}
+                if code == SOCKET_ERROR && detail == WSA_IO_PENDING =>
+            {
+                cancel_if_shutting_down(&control_node);
+            }
 
             // The operation completed synchronously. This means we will not get a completion
             // notification and must handle the result inline (because we set a flag saying this
@@ -404,6 +631,18 @@ impl OperationShared {
             // We need to free the operation core ourselves to avoid leaking it forever, as well
             // as resurrect the core so we can get the buffer out of it and back to the originator.
             Err(e) => {
+                // The OS may refuse new operations when it is running low on resources. This is
+                // not fatal - the caller gets a retryable error and may try again later.
+                if e.is_retryable() {
+                    event!(
+                        Level::WARN,
+                        message = "I/O operation rejected due to resource exhaustion",
+                        error = %e
+                    );
+
+                    OPERATIONS_REJECTED_RETRYABLE.with(Event::observe_unit);
+                }
+
                 // SAFETY: The core is only referenced by either Operation or the operating system at any
                 // given time, so there is no possibility of multiple exclusive references being created.
                 let core = overlapped as *mut OperationCore;
@@ -492,11 +731,21 @@ impl Drop for OperationShared {
 }
 
 thread_local! {
+    static OPERATIONS_REJECTED_RETRYABLE: Event = EventBuilder::new()
+        .name("io_shared_ops_rejected_retryable")
+        .build()
+        .unwrap();
+
     static OPERATIONS_ALLOCATED: Event = EventBuilder::new()
         .name("io_shared_ops_allocated")
         .build()
         .unwrap();
 
+    static SPURIOUS_COMPLETIONS_IGNORED: Event = EventBuilder::new()
+        .name("io_shared_spurious_completions_ignored")
+        .build()
+        .unwrap();
+
     static OPERATIONS_COMPLETED_ASYNC: Event = EventBuilder::new()
         .name("io_shared_ops_completed_async")
         .build()
@@ -519,3 +768,85 @@ thread_local! {
         .build()
         .unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use std::{sync::Arc, thread, time::Duration};
+
+    #[test]
+    fn shut_down_rejects_new_operations() {
+        let store = OperationStoreShared::new(false, None);
+        store.shut_down();
+
+        let buffer = PinnedBufferShared::from_boxed_slice(vec![0; 16].into_boxed_slice());
+        let operation = store.new_operation(buffer);
+
+        // SAFETY: The callback is never called because the store is shutting down.
+        let result = block_on(unsafe {
+            operation.begin(|_, _, _| panic!("operation must not start during shutdown"))
+        });
+
+        let (error, buffer) = result.unwrap_err().into_inner_and_buffer();
+        assert!(matches!(error, io::Error::ShuttingDown));
+        assert_eq!(buffer.capacity(), 16);
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn slow_operation_reported() {
+        let reported = Arc::new(Mutex::new(Vec::new()));
+
+        let hook = SlowIoHook::new(Duration::from_millis(50), {
+            let reported = Arc::clone(&reported);
+            move |operation: &SlowIoOperation| reported.lock().unwrap().push(*operation)
+        });
+
+        let store = OperationStoreShared::new(false, Some(hook));
+
+        let buffer = PinnedBufferShared::from_boxed_slice(vec![0; 16].into_boxed_slice());
+        let mut overlapped_ptr = ptr::null_mut();
+
+        let mut operation = store.new_operation(buffer);
+        operation.set_kind(IoOperationKind::Accept);
+
+        // This is never used to cancel the operation, as we always wait for it to complete.
+        operation.set_handle(&HANDLE(42 as *mut _));
+
+        // We pretend that the operation was started asynchronously, standing in for the OS.
+        // SAFETY: We complete the operation ourselves below, as the OS would.
+        let future = unsafe {
+            operation.begin(|_, overlapped, _| {
+                overlapped_ptr = overlapped;
+                Err(io::Error::Windows(ERROR_IO_PENDING.into()))
+            })
+        };
+
+        // We inject latency that exceeds the threshold.
+        thread::sleep(Duration::from_millis(200));
+
+        // SAFETY: The operation was started above and has not been completed yet.
+        unsafe {
+            store.complete_operation(OVERLAPPED_ENTRY {
+                lpCompletionKey: IoClass::Network.completion_key(),
+                lpOverlapped: overlapped_ptr,
+                dwNumberOfBytesTransferred: 10,
+                ..Default::default()
+            });
+        }
+
+        block_on(future).unwrap();
+
+        let reported = reported.lock().unwrap();
+        assert_eq!(reported.len(), 1);
+        assert_eq!(reported[0].kind, IoOperationKind::Accept);
+        assert_eq!(reported[0].handle, Some(HANDLE(42 as *mut _)));
+        assert_eq!(reported[0].class, IoClass::Network);
+        assert_eq!(reported[0].bytes_transferred, 10);
+        assert!(reported[0].succeeded);
+        assert!(reported[0].duration > Duration::from_millis(50));
+
+        assert!(store.is_empty());
+    }
+}
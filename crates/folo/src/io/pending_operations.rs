@@ -0,0 +1,170 @@
+use crate::{
+    io::{self, InFlightPermit, IoPriority},
+    rt::current_async_agent,
+};
+use std::{
+    cell::{Cell, RefCell},
+    future, mem,
+    rc::Rc,
+    task::{Poll, Waker},
+};
+use windows::Win32::{
+    Foundation::{ERROR_NOT_FOUND, ERROR_OPERATION_ABORTED, HANDLE},
+    System::IO::CancelIoEx,
+};
+
+/// Keeps count of the I/O operations in flight on one I/O primitive (file, socket, ...), so that
+/// the owner of the I/O primitive can wait for all of them to complete, e.g. after cancelling them
+/// via `cancel_all()`.
+///
+/// An operation is counted from the moment it is associated with this via `Operation::track()`
+/// until its operation core is released, which only happens after the operating system is done
+/// with the buffer of the operation. Operations are only associated once they have been admitted
+/// by the in-flight operation limit (see `admit()`), right before they are submitted.
+#[derive(Debug, Default)]
+pub(crate) struct PendingOperations {
+    inner: Rc<PendingOperationsInner>,
+}
+
+#[derive(Debug, Default)]
+struct PendingOperationsInner {
+    count: Cell<usize>,
+
+    // Incremented by every call to `cancel_all()`, so operations waiting for admission can tell
+    // that they have been cancelled.
+    cancellations: Cell<u64>,
+
+    // Tasks waiting for the count to reach zero.
+    idle_waiters: RefCell<Vec<Waker>>,
+}
+
+impl PendingOperations {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts a new operation as pending until the returned token is dropped.
+    pub(crate) fn track(&self) -> PendingOperationToken {
+        self.inner.count.set(self.inner.count.get() + 1);
+
+        PendingOperationToken {
+            inner: Rc::clone(&self.inner),
+        }
+    }
+
+    /// Waits until the in-flight operation limit of the current thread admits an operation with
+    /// the given priority (see `RuntimeBuilder::max_in_flight_io()`). Hold on to the returned
+    /// permit until the operation has completed.
+    ///
+    /// An operation waiting for admission is not yet known to the operating system, so
+    /// `cancel_all()` cannot cancel it via `CancelIoEx()`. Instead, if `cancel_all()` is called
+    /// while we wait, the admission fails with the same error as a cancelled operation.
+    pub(crate) async fn admit(&self, priority: IoPriority) -> io::Result<InFlightPermit> {
+        let cancellations = self.inner.cancellations.get();
+
+        let permit = current_async_agent::with_io(|io| io.admit(priority)).await;
+
+        if self.inner.cancellations.get() != cancellations {
+            return Err(io::Error::Windows(ERROR_OPERATION_ABORTED.into()));
+        }
+
+        Ok(permit)
+    }
+
+    /// Requests cancellation of all operations in flight on the handle and waits for all the
+    /// tracked operations to complete, whether they were cancelled or managed to complete
+    /// normally before the cancellation took effect. Operations still waiting for admission fail
+    /// once admitted, without being submitted (see `admit()`).
+    ///
+    /// The handle must be the I/O primitive whose operations are tracked by this instance.
+    pub(crate) async fn cancel_all(&self, handle: HANDLE) -> io::Result<()> {
+        self.inner
+            .cancellations
+            .set(self.inner.cancellations.get() + 1);
+
+        // SAFETY: Cancellation does not touch any memory of ours - the operations are completed
+        // via the usual completion notifications, which take care of releasing their resources.
+        match unsafe { CancelIoEx(handle, None) } {
+            Ok(()) => {}
+            // There was nothing in flight to cancel. Not an error for us.
+            Err(e) if e.code() == ERROR_NOT_FOUND.into() => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        self.wait_until_idle().await;
+        Ok(())
+    }
+
+    /// Waits until there are no pending operations.
+    async fn wait_until_idle(&self) {
+        future::poll_fn(|cx| {
+            if self.inner.count.get() == 0 {
+                return Poll::Ready(());
+            }
+
+            self.inner
+                .idle_waiters
+                .borrow_mut()
+                .push(cx.waker().clone());
+            Poll::Pending
+        })
+        .await
+    }
+}
+
+/// Marks an operation as pending for as long as it is alive. See `PendingOperations::track()`.
+#[derive(Debug)]
+pub(crate) struct PendingOperationToken {
+    inner: Rc<PendingOperationsInner>,
+}
+
+impl Drop for PendingOperationToken {
+    fn drop(&mut self) {
+        let count = self.inner.count.get() - 1;
+        self.inner.count.set(count);
+
+        if count == 0 {
+            let waiters = mem::take(&mut *self.inner.idle_waiters.borrow_mut());
+
+            for waker in waiters {
+                waker.wake();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{task::noop_waker_ref, FutureExt};
+    use std::{pin::pin, task};
+
+    #[test]
+    fn idle_once_all_tokens_dropped() {
+        let pending = PendingOperations::new();
+
+        let first = pending.track();
+        let second = pending.track();
+
+        let mut cx = task::Context::from_waker(noop_waker_ref());
+        let mut idle = pin!(pending.wait_until_idle());
+
+        assert!(idle.poll_unpin(&mut cx).is_pending());
+
+        drop(first);
+        assert!(idle.poll_unpin(&mut cx).is_pending());
+
+        drop(second);
+        assert!(idle.poll_unpin(&mut cx).is_ready());
+    }
+
+    #[test]
+    fn idle_immediately_without_operations() {
+        let pending = PendingOperations::new();
+
+        let mut cx = task::Context::from_waker(noop_waker_ref());
+        assert!(pin!(pending.wait_until_idle())
+            .poll_unpin(&mut cx)
+            .is_ready());
+    }
+}
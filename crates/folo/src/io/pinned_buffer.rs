@@ -1,17 +1,20 @@
 use crate::{
-    mem::{DropPolicy, PinnedSlabChain},
+    io::{self, pinned_buffer_pool::PoolInner, rio::BufferRegistration},
+    mem::{DropPolicy, NumaPlacement, PinnedSlabChain},
     metrics::{Event, EventBuilder},
 };
 use core::slice;
 use negative_impl::negative_impl;
 use std::{
-    cell::{RefCell, UnsafeCell},
+    cell::{Cell, RefCell, UnsafeCell},
     fmt,
     mem::{self},
     ops::Range,
     pin::Pin,
     ptr,
+    rc::Weak,
 };
+use windows::Win32::Networking::WinSock::{RIO_BUF, RIO_BUFFERID};
 
 /// A buffer of bytes for reading from or writing to as part of low level I/O operations. This is
 /// typically not visible to user code, rather it is used as the primitive inside the Folo I/O API.
@@ -61,6 +64,12 @@ enum Mode {
         inner: *mut u8,
         capacity: usize,
     },
+    Recycled {
+        // The storage is returned to the `PinnedBufferPool` it came from when the buffer is
+        // dropped, unless the pool no longer exists by then.
+        inner: Pin<Box<[u8]>>,
+        pool: Weak<PoolInner>,
+    },
 }
 
 impl fmt::Debug for Mode {
@@ -76,16 +85,23 @@ impl fmt::Debug for Mode {
                 .field("inner", &format_args!("{:p}", inner))
                 .field("capacity", capacity)
                 .finish(),
+            Self::Recycled { .. } => f.debug_struct("Recycled").finish(),
         }
     }
 }
 
 impl PinnedBuffer {
     /// Obtains a new buffer from the current thread's buffer pool.
+    ///
+    /// On Folo worker threads, the pool is created after the thread has been pinned to its
+    /// processor, with the memory placed on NUMA nodes according to
+    /// `RuntimeBuilder::buffer_numa_placement()`. By default, the buffers come from memory on the
+    /// NUMA node of that processor. On other threads, the buffers come from the NUMA node of
+    /// whatever processor the thread happens to run on when it first uses the pool.
     pub fn from_pool() -> Self {
         POOL.with(|pool| {
             let mut pool = pool.borrow_mut();
-            let inserter = pool.begin_insert();
+            let inserter = pool.slabs.begin_insert();
             let index = inserter.index();
 
             // We do not initialize the buffer when we take it from the pool. It has whatever data
@@ -127,8 +143,61 @@ impl PinnedBuffer {
         })
     }
 
+    /// Resets the buffer pool of the current thread to a known state, so that taking buffers from
+    /// the pool behaves the same way in every benchmark iteration instead of only the first
+    /// iteration paying the cost of allocating the pool.
+    ///
+    /// After this, the pool has room for at least `vacant_buffers` more buffers than are currently
+    /// taken from it, with the memory for them already allocated and committed. Any memory of the
+    /// pool beyond that is released where possible.
+    ///
+    /// This is only intended for benchmarks and tests - there is no reason to micromanage the pool
+    /// in real workloads.
+    #[cfg(any(test, feature = "criterion"))]
+    pub fn reset_pool(vacant_buffers: usize) {
+        POOL.with(|pool| {
+            let mut pool = pool.borrow_mut();
+
+            // Writing to every buffer ensures that the memory is committed, so the first I/O into
+            // the buffer does not pay for page faults.
+            let indexes = (0..vacant_buffers)
+                .map(|_| {
+                    let inserter = pool.slabs.begin_insert();
+                    let index = inserter.index();
+
+                    // SAFETY: We write the full size of the item, so it is fully initialized.
+                    unsafe {
+                        ptr::write_bytes(inserter.insert_uninit(), 0, 1);
+                    }
+
+                    index
+                })
+                .collect::<Vec<_>>();
+
+            let BufferPool {
+                rio_registrations,
+                slabs,
+            } = &mut *pool;
+
+            for index in indexes {
+                slabs.remove(index);
+            }
+
+            let min_capacity = slabs.len() + vacant_buffers;
+            slabs.shrink_to_with(min_capacity, |slab_index| {
+                // The memory of the slab is about to be released, so it must not stay registered.
+                if let Some(registration) = rio_registrations.get_mut(slab_index) {
+                    *registration = None;
+                }
+            });
+        });
+    }
+
     /// Creates a new buffer from a slice of bytes provided by the caller. Once the buffer has been
     /// used up, the caller may get the inner slice back via `.into_inner_boxed_slice()`.
+    ///
+    /// The slice is adopted as-is, without copying or reallocating. The heap allocation behind a
+    /// boxed slice never moves, so it is pinned for as long as the buffer owns it.
     pub fn from_boxed_slice(slice: Box<[u8]>) -> Self {
         CALLER_BUFFERS_REFERENCED.with(Event::observe_unit);
 
@@ -166,10 +235,25 @@ impl PinnedBuffer {
         }
     }
 
+    /// Creates a new buffer from storage owned by a `PinnedBufferPool`, returning the storage to
+    /// the pool when the buffer is dropped.
+    pub(super) fn from_recycled(storage: Box<[u8]>, pool: Weak<PoolInner>) -> Self {
+        let len = storage.len();
+
+        PinnedBuffer {
+            mode: Mode::Recycled {
+                inner: Pin::new(storage),
+                pool,
+            },
+            len,
+            start: 0,
+        }
+    }
+
     pub fn capacity(&self) -> usize {
         match &self.mode {
             Mode::Pooled { inner, .. } => inner.len(),
-            Mode::BoxedSlice { inner } => inner.len(),
+            Mode::BoxedSlice { inner } | Mode::Recycled { inner, .. } => inner.len(),
             Mode::Ptr { capacity, .. } => *capacity,
         }
     }
@@ -227,7 +311,9 @@ impl PinnedBuffer {
     pub fn as_mut_slice(&mut self) -> &mut [u8] {
         match &mut self.mode {
             Mode::Pooled { inner, .. } => &mut inner[self.start..(self.start + self.len)],
-            Mode::BoxedSlice { inner } => &mut inner[self.start..(self.start + self.len)],
+            Mode::BoxedSlice { inner } | Mode::Recycled { inner, .. } => {
+                &mut inner[self.start..(self.start + self.len)]
+            }
             Mode::Ptr { inner, .. } => unsafe {
                 slice::from_raw_parts_mut(inner.add(self.start), self.len)
             },
@@ -246,7 +332,9 @@ impl PinnedBuffer {
     pub fn as_slice(&self) -> &[u8] {
         match &self.mode {
             Mode::Pooled { inner, .. } => &inner[self.start..(self.start + self.len)],
-            Mode::BoxedSlice { inner } => &inner[self.start..(self.start + self.len)],
+            Mode::BoxedSlice { inner } | Mode::Recycled { inner, .. } => {
+                &inner[self.start..(self.start + self.len)]
+            }
             Mode::Ptr { inner, .. } => unsafe {
                 slice::from_raw_parts(inner.add(self.start), self.len)
             },
@@ -260,6 +348,36 @@ impl PinnedBuffer {
         }
     }
 
+    /// Describes the active region of the buffer for Registered I/O, if the buffer comes from the
+    /// buffer pool of the current thread. The pool registers the memory of each of its slabs the
+    /// first time a buffer from the slab is used for Registered I/O and keeps the registration
+    /// until the slab is released, so later operations on buffers from the slab skip the
+    /// registration.
+    ///
+    /// Returns `None` for buffers that do not come from the pool, which have to be registered for
+    /// the duration of each operation.
+    pub(crate) fn rio_buf(&mut self) -> io::Result<Option<RIO_BUF>> {
+        let Mode::Pooled {
+            inner,
+            index_in_pool,
+        } = &mut self.mode
+        else {
+            return Ok(None);
+        };
+
+        let active_region = inner.as_mut_ptr().wrapping_add(self.start);
+
+        let (buffer_id, slab_start) =
+            POOL.with(|pool| pool.borrow_mut().rio_registration(*index_in_pool))?;
+
+        Ok(Some(RIO_BUF {
+            BufferId: buffer_id,
+            // The registration only succeeds for slabs smaller than 4 GB, so this fits.
+            Offset: (active_region as usize - slab_start as usize) as u32,
+            Length: self.len as u32,
+        }))
+    }
+
     /// Consumes the buffer and returns the inner boxed slice that was used to create the object.
     /// Note that the inner boxed slice will be returned in its full extent, ignoring active region.
     ///
@@ -275,7 +393,7 @@ impl PinnedBuffer {
         mem::forget(self);
 
         match mode {
-            Mode::Pooled { .. } | Mode::Ptr { .. } => {
+            Mode::Pooled { .. } | Mode::Ptr { .. } | Mode::Recycled { .. } => {
                 unreachable!("we already asserted that this is a boxed slice")
             }
             Mode::BoxedSlice { inner } => Pin::into_inner(inner),
@@ -285,21 +403,90 @@ impl PinnedBuffer {
 
 impl Drop for PinnedBuffer {
     fn drop(&mut self) {
-        if let Mode::Pooled { index_in_pool, .. } = self.mode {
-            POOL.with(|pool| {
-                let mut pool = pool.borrow_mut();
-                pool.remove(index_in_pool);
-                POOL_DROPPED.with(Event::observe_unit);
-            });
+        match &mut self.mode {
+            Mode::Pooled { index_in_pool, .. } => {
+                POOL.with(|pool| {
+                    let mut pool = pool.borrow_mut();
+                    pool.slabs.remove(*index_in_pool);
+                    POOL_DROPPED.with(Event::observe_unit);
+                });
+            }
+            Mode::Recycled { inner, pool } => {
+                if let Some(pool) = pool.upgrade() {
+                    // An empty boxed slice does not allocate, so this is just moving the storage.
+                    let storage = mem::replace(inner, Pin::new(Box::default()));
+                    pool.release(Pin::into_inner(storage));
+                }
+            }
+            Mode::BoxedSlice { .. } | Mode::Ptr { .. } => {}
         }
     }
 }
 
+impl From<Box<[u8]>> for PinnedBuffer {
+    fn from(slice: Box<[u8]>) -> Self {
+        Self::from_boxed_slice(slice)
+    }
+}
+
+impl From<Vec<u8>> for PinnedBuffer {
+    fn from(vec: Vec<u8>) -> Self {
+        Self::from_boxed_slice(vec.into_boxed_slice())
+    }
+}
+
 #[negative_impl]
 impl !Send for PinnedBuffer {}
 #[negative_impl]
 impl !Sync for PinnedBuffer {}
 
+/// Sets the NUMA placement of the buffer pool of the current thread. This must be called before the
+/// thread first uses the pool - afterwards, it has no effect.
+pub(crate) fn set_pool_numa_placement(placement: NumaPlacement) {
+    POOL_NUMA_PLACEMENT.set(placement);
+}
+
+/// The buffer pool of a thread, together with the Registered I/O registrations of its slabs.
+struct BufferPool {
+    // The registrations cover the memory of the slabs, so they are declared first to be released
+    // before the slabs when the pool is dropped. Indexed by slab, with `None` for slabs that have
+    // not been used for Registered I/O.
+    rio_registrations: Vec<Option<BufferRegistration>>,
+
+    slabs: PinnedSlabChain<UnsafeCell<[u8; POOL_BUFFER_CAPACITY_BYTES]>>,
+}
+
+impl BufferPool {
+    /// The ID of the Registered I/O registration that covers the slab holding the buffer with the
+    /// given index, together with the start of the memory of the slab. The slab is registered the
+    /// first time this is called for one of its buffers.
+    fn rio_registration(&mut self, index: usize) -> io::Result<(RIO_BUFFERID, *const u8)> {
+        let slab_index = self.slabs.slab_index_of(index);
+        let (slab_start, slab_len) = self.slabs.slab_memory_region(slab_index);
+
+        if self.rio_registrations.len() <= slab_index {
+            self.rio_registrations.resize_with(slab_index + 1, || None);
+        }
+
+        let registration = &mut self.rio_registrations[slab_index];
+
+        if registration.is_none() {
+            // SAFETY: The memory of the slab stays in place until the slab is released, before
+            // which we release the registration.
+            *registration = Some(unsafe { BufferRegistration::new(slab_start, slab_len)? });
+
+            POOL_SLABS_REGISTERED.with(Event::observe_unit);
+        }
+
+        let buffer_id = registration
+            .as_ref()
+            .expect("we just registered the slab if it was not registered yet")
+            .id();
+
+        Ok((buffer_id, slab_start))
+    }
+}
+
 // 64 KB is the default "stream to stream" copy size in .NET, so we use that as a default buffer
 // size, as well. Note that this is not necessarily the best for high throughput single-stream I/O
 // and larger buffers will often provide better throughput for a single high throughput stream.
@@ -312,8 +499,16 @@ thread_local! {
     // We use MustNotDropItems policy because buffers are often referenced via raw pointers, so if
     // some items still exist in the collection, we have a high probability of dangling pointers,
     // which can be a big safety problem.
-    static POOL: RefCell<PinnedSlabChain<UnsafeCell<[u8; POOL_BUFFER_CAPACITY_BYTES]>>> =
-        RefCell::new(PinnedSlabChain::new(DropPolicy::MustNotDropItems));
+    static POOL: RefCell<BufferPool> = RefCell::new(BufferPool {
+        rio_registrations: Vec::new(),
+        slabs: PinnedSlabChain::with_numa_placement(
+            DropPolicy::MustNotDropItems,
+            POOL_NUMA_PLACEMENT.get(),
+        ),
+    });
+
+    // Read once, when the pool of the thread is created on first use.
+    static POOL_NUMA_PLACEMENT: Cell<NumaPlacement> = const { Cell::new(NumaPlacement::Local) };
 
     static CALLER_BUFFERS_REFERENCED: Event = EventBuilder::new()
         .name("isolated_caller_buffers_referenced")
@@ -334,4 +529,66 @@ thread_local! {
         .name("isolated_pool_buffers_dropped")
         .build()
         .unwrap();
+
+    static POOL_SLABS_REGISTERED: Event = EventBuilder::new()
+        .name("io_rio_pool_slabs_registered")
+        .build()
+        .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boxed_slice_round_trip() {
+        let slice = vec![0u8; 100].into_boxed_slice();
+        let original_ptr = slice.as_ptr();
+
+        let mut buffer = PinnedBuffer::from_boxed_slice(slice);
+        assert_eq!(buffer.capacity(), 100);
+        assert_eq!(buffer.len(), 100);
+
+        // Simulate an I/O operation filling part of the buffer.
+        buffer.set_len(10);
+        buffer.as_mut_slice().copy_from_slice(&[42; 10]);
+
+        let slice = buffer.into_inner_boxed_slice();
+
+        // We get back the same allocation, in its full extent.
+        assert_eq!(slice.as_ptr(), original_ptr);
+        assert_eq!(slice.len(), 100);
+        assert_eq!(&slice[..10], &[42; 10]);
+        assert_eq!(&slice[10..], &[0; 90]);
+    }
+
+    #[test]
+    fn reset_pool_sets_capacity() {
+        let taken = PinnedBuffer::from_pool();
+
+        PinnedBuffer::reset_pool(200);
+        let capacity = POOL.with(|pool| pool.borrow().slabs.capacity());
+        assert!(capacity >= 201);
+
+        // Taking the prewarmed buffers does not grow the pool.
+        let buffers = (0..200)
+            .map(|_| PinnedBuffer::from_pool())
+            .collect::<Vec<_>>();
+        assert_eq!(POOL.with(|pool| pool.borrow().slabs.capacity()), capacity);
+
+        drop(buffers);
+
+        // Only the buffer still taken needs to fit now.
+        PinnedBuffer::reset_pool(0);
+        assert!(POOL.with(|pool| pool.borrow().slabs.capacity()) < capacity);
+
+        drop(taken);
+    }
+
+    #[test]
+    #[should_panic]
+    fn into_inner_boxed_slice_panics_for_pooled() {
+        let buffer = PinnedBuffer::from_pool();
+        _ = buffer.into_inner_boxed_slice();
+    }
 }
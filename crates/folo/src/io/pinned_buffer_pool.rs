@@ -0,0 +1,124 @@
+use crate::io::PinnedBuffer;
+use negative_impl::negative_impl;
+use std::{cell::RefCell, rc::Rc};
+
+/// A pool of buffers of one size class. Buffers handed out by `acquire()` return their storage to
+/// the pool when dropped and the next `acquire()` reuses it, so performing many I/O operations in
+/// a row does not need a fresh allocation for every operation.
+///
+/// The pool is single threaded, like the buffers it hands out - create one pool per thread. It
+/// caches up to `max_cached` buffers, any buffers returned beyond that are freed. Buffers that
+/// outlive the pool are likewise freed when dropped.
+#[derive(Debug)]
+pub struct PinnedBufferPool {
+    inner: Rc<PoolInner>,
+}
+
+impl PinnedBufferPool {
+    /// Creates an empty pool of buffers of `buffer_size` bytes, caching up to `max_cached` of them
+    /// for reuse.
+    pub fn new(buffer_size: usize, max_cached: usize) -> Self {
+        Self {
+            inner: Rc::new(PoolInner {
+                buffer_size,
+                max_cached,
+                cached: RefCell::new(Vec::with_capacity(max_cached)),
+            }),
+        }
+    }
+
+    /// Takes a buffer from the pool or allocates a new one if there are none cached. The buffer
+    /// returns to the pool when dropped.
+    ///
+    /// The buffer is not cleared when taken from the pool - it has whatever data it had when it was
+    /// last dropped.
+    pub fn acquire(&self) -> PinnedBuffer {
+        let storage = self
+            .inner
+            .cached
+            .borrow_mut()
+            .pop()
+            .unwrap_or_else(|| vec![0; self.inner.buffer_size].into_boxed_slice());
+
+        PinnedBuffer::from_recycled(storage, Rc::downgrade(&self.inner))
+    }
+
+    /// Size of the buffers handed out by the pool, in bytes.
+    pub fn buffer_size(&self) -> usize {
+        self.inner.buffer_size
+    }
+
+    /// Number of buffers currently cached in the pool, ready to be reused.
+    pub fn cached(&self) -> usize {
+        self.inner.cached.borrow().len()
+    }
+}
+
+#[negative_impl]
+impl !Send for PinnedBufferPool {}
+#[negative_impl]
+impl !Sync for PinnedBufferPool {}
+
+#[derive(Debug)]
+pub(super) struct PoolInner {
+    buffer_size: usize,
+    max_cached: usize,
+
+    cached: RefCell<Vec<Box<[u8]>>>,
+}
+
+impl PoolInner {
+    /// Takes back the storage of a dropped buffer, unless the pool is already full.
+    pub(super) fn release(&self, storage: Box<[u8]>) {
+        let mut cached = self.cached.borrow_mut();
+
+        if cached.len() < self.max_cached {
+            cached.push(storage);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn acquire_reuses_dropped_buffer() {
+        let pool = PinnedBufferPool::new(100, 4);
+
+        let mut buffer = pool.acquire();
+        assert_eq!(buffer.capacity(), 100);
+
+        // Simulate an I/O operation using part of the buffer.
+        buffer.set_len(10);
+        let original_ptr = buffer.as_slice().as_ptr();
+
+        drop(buffer);
+        assert_eq!(pool.cached(), 1);
+
+        // We get back the same allocation, in its full extent.
+        let buffer = pool.acquire();
+        assert_eq!(buffer.as_slice().as_ptr(), original_ptr);
+        assert_eq!(buffer.len(), 100);
+        assert_eq!(pool.cached(), 0);
+    }
+
+    #[test]
+    fn release_beyond_limit_frees_buffer() {
+        let pool = PinnedBufferPool::new(100, 2);
+
+        let buffers = (0..5).map(|_| pool.acquire()).collect::<Vec<_>>();
+        drop(buffers);
+
+        assert_eq!(pool.cached(), 2);
+    }
+
+    #[test]
+    fn buffer_outlives_pool() {
+        let pool = PinnedBufferPool::new(100, 2);
+        let buffer = pool.acquire();
+
+        drop(pool);
+        drop(buffer);
+    }
+}
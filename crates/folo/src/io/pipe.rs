@@ -0,0 +1,237 @@
+use crate::{
+    io::{self, IoClass, IoOperationKind, PinnedBuffer},
+    rt::current_async_agent,
+    windows::OwnedHandle,
+};
+use negative_impl::negative_impl;
+use std::{
+    ffi::CString,
+    mem,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use windows::{
+    core::PCSTR,
+    Win32::{
+        Foundation::{ERROR_BROKEN_PIPE, HANDLE, STATUS_PIPE_BROKEN, TRUE},
+        Security::SECURITY_ATTRIBUTES,
+        Storage::FileSystem::{
+            CreateFileA, ReadFile, WriteFile, FILE_ATTRIBUTE_NORMAL, FILE_FLAGS_AND_ATTRIBUTES,
+            FILE_FLAG_FIRST_PIPE_INSTANCE, FILE_FLAG_OVERLAPPED, FILE_GENERIC_WRITE,
+            FILE_SHARE_NONE, OPEN_EXISTING, PIPE_ACCESS_INBOUND,
+        },
+        System::Pipes::{
+            CreateNamedPipeA, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE,
+            PIPE_WAIT,
+        },
+    },
+};
+
+/// Size of the buffer the operating system allocates for data in transit through the pipe.
+/// Writes that do not fit into the free space of the buffer wait until the reader makes room.
+pub const PIPE_BUFFER_SIZE_BYTES: u32 = 64 * 1024;
+
+// Anonymous pipes do not support overlapped I/O, so we use named pipes with unique names instead.
+static NEXT_PIPE_ID: AtomicU64 = AtomicU64::new(0);
+
+/// The reading end of a pipe, bound to the I/O driver of the async worker thread that created it.
+#[derive(Debug)]
+pub struct PipeReader {
+    handle: OwnedHandle<HANDLE>,
+}
+
+impl PipeReader {
+    /// Reads from the pipe into the active region of the buffer.
+    ///
+    /// The buffer is returned with the active region set to the bytes read. An empty active region
+    /// indicates that the writing end of the pipe has been closed.
+    pub async fn read(&self, buffer: PinnedBuffer) -> io::Result<PinnedBuffer> {
+        let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+        operation.set_handle(&*self.handle);
+        operation.set_kind(IoOperationKind::Read);
+
+        // SAFETY: For safe usage of the I/O driver API, we are required to pass the `overlapped`
+        // argument to a native I/O call under all circumstances, to trigger an I/O completion. We do.
+        // We are also not allowed to use any of the callback arguments after the callback, even if
+        // the Rust compiler might allow us to.
+        match unsafe {
+            operation
+                .begin(|buffer, overlapped, bytes_transferred_immediately| {
+                    Ok(ReadFile(
+                        *self.handle,
+                        Some(buffer),
+                        Some(bytes_transferred_immediately as *mut _),
+                        Some(overlapped),
+                    )?)
+                })
+                .await
+        } {
+            Ok(buffer) => Ok(buffer),
+            // Depending on whether the writer goes away before or during the read, we get the
+            // closing of the pipe reported either as a Win32 error or as an NTSTATUS.
+            Err(io::OperationError {
+                inner: io::Error::Windows(external),
+                mut buffer,
+            }) if external.code() == ERROR_BROKEN_PIPE.into()
+                || external.code() == STATUS_PIPE_BROKEN.into() =>
+            {
+                buffer.set_len(0);
+                Ok(buffer)
+            }
+            Err(e) => Err(e.into_inner()),
+        }
+    }
+}
+
+impl io::AsyncRead for PipeReader {
+    async fn read(&mut self, buffer: PinnedBuffer) -> io::Result<PinnedBuffer> {
+        PipeReader::read(self, buffer).await
+    }
+}
+
+#[negative_impl]
+impl !Send for PipeReader {}
+#[negative_impl]
+impl !Sync for PipeReader {}
+
+/// The writing end of a pipe created by `anon_pipe()`, bound to the I/O driver of the async worker
+/// thread that created it. The pipe is closed when this is dropped.
+#[derive(Debug)]
+pub struct PipeWriter {
+    handle: OwnedHandle<HANDLE>,
+}
+
+impl PipeWriter {
+    /// Writes the active region of the buffer into the pipe.
+    ///
+    /// If the pipe buffer (see `PIPE_BUFFER_SIZE_BYTES`) does not have room for all the data, the
+    /// task waits until the reader has made room, without blocking the async worker thread.
+    ///
+    /// The buffer is returned with the same active region, so it can be reused.
+    pub async fn write(&self, mut buffer: PinnedBuffer) -> io::Result<PinnedBuffer> {
+        let start = buffer.start();
+        let wanted = buffer.len();
+        let mut written = 0;
+
+        // The OS is within its rights to take only a part of what we give it, so we need to be
+        // prepared to loop until everything has been written.
+        while written < wanted {
+            // The length is cleared first because the start and length are validated against the
+            // capacity of the buffer one by one.
+            buffer.set_len(0);
+            buffer.set_start(start + written);
+            buffer.set_len(wanted - written);
+
+            let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+            operation.set_handle(&*self.handle);
+            operation.set_kind(IoOperationKind::Write);
+
+            // SAFETY: For safe usage of the I/O driver API, we are required to pass the
+            // `overlapped` argument to a native I/O call under all circumstances, to trigger an
+            // I/O completion. We do. We are also not allowed to use any of the callback arguments
+            // after the callback, even if the Rust compiler might allow us to.
+            buffer = unsafe {
+                operation
+                    .begin(|buffer, overlapped, bytes_transferred_immediately| {
+                        Ok(WriteFile(
+                            *self.handle,
+                            Some(buffer),
+                            Some(bytes_transferred_immediately as *mut _),
+                            Some(overlapped),
+                        )?)
+                    })
+                    .await
+                    .map_err(io::OperationError::into_inner)?
+            };
+
+            written += buffer.len();
+        }
+
+        buffer.set_start(start);
+        buffer.set_len(wanted);
+        Ok(buffer)
+    }
+}
+
+impl io::AsyncWrite for PipeWriter {
+    async fn write(&mut self, buffer: PinnedBuffer) -> io::Result<PinnedBuffer> {
+        PipeWriter::write(self, buffer).await
+    }
+}
+
+#[negative_impl]
+impl !Send for PipeWriter {}
+#[negative_impl]
+impl !Sync for PipeWriter {}
+
+/// Creates a one-way pipe for transferring data between tasks on the current async worker thread.
+/// Both ends are bound to the I/O driver of the current async worker thread.
+///
+/// The reader receives an empty buffer once the writer has been dropped and all data written
+/// before that has been read.
+pub fn anon_pipe() -> io::Result<(PipeReader, PipeWriter)> {
+    let (reader, writer) = create_pipe(FILE_FLAG_OVERLAPPED, false)?;
+
+    current_async_agent::with_io(|io| io.bind_io_primitive(&*writer, IoClass::Other))?;
+
+    Ok((reader, PipeWriter { handle: writer }))
+}
+
+/// Creates a one-way pipe. The reading end is bound to the I/O driver of the current async worker
+/// thread, while the writing end is a plain synchronous handle that can be inherited by a child
+/// process.
+pub(crate) fn inheritable_pipe() -> io::Result<(PipeReader, OwnedHandle<HANDLE>)> {
+    create_pipe(FILE_ATTRIBUTE_NORMAL, true)
+}
+
+fn create_pipe(
+    writer_flags: FILE_FLAGS_AND_ATTRIBUTES,
+    writer_inheritable: bool,
+) -> io::Result<(PipeReader, OwnedHandle<HANDLE>)> {
+    let name = CString::new(format!(
+        r"\\.\pipe\folo-{}-{}",
+        std::process::id(),
+        NEXT_PIPE_ID.fetch_add(1, Ordering::Relaxed)
+    ))
+    .expect("pipe name cannot contain null bytes");
+
+    // Creating a pipe does not touch any storage, so unlike opening files we do not bother
+    // offloading this to a synchronous worker thread.
+    //
+    // SAFETY: The name is a valid null-terminated string that outlives the call and we take
+    // ownership of the returned handle, closing it when dropped.
+    let reader = unsafe {
+        OwnedHandle::new(CreateNamedPipeA(
+            PCSTR::from_raw(name.as_ptr() as *const u8),
+            PIPE_ACCESS_INBOUND | FILE_FLAG_OVERLAPPED | FILE_FLAG_FIRST_PIPE_INSTANCE,
+            PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+            1,
+            PIPE_BUFFER_SIZE_BYTES,
+            PIPE_BUFFER_SIZE_BYTES,
+            0,
+            None,
+        )?)
+    };
+
+    let security_attributes = SECURITY_ATTRIBUTES {
+        nLength: mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+        lpSecurityDescriptor: std::ptr::null_mut(),
+        bInheritHandle: TRUE,
+    };
+
+    // SAFETY: As above. The security attributes outlive the call.
+    let writer = unsafe {
+        OwnedHandle::new(CreateFileA(
+            PCSTR::from_raw(name.as_ptr() as *const u8),
+            FILE_GENERIC_WRITE.0,
+            FILE_SHARE_NONE,
+            writer_inheritable.then_some(&security_attributes as *const _),
+            OPEN_EXISTING,
+            writer_flags,
+            None,
+        )?)
+    };
+
+    current_async_agent::with_io(|io| io.bind_io_primitive(&*reader, IoClass::Other))?;
+
+    Ok((PipeReader { handle: reader }, writer))
+}
@@ -0,0 +1,212 @@
+use crate::io::{operation::operation_priority, rio::RIO_NOTIFICATION_COMPLETION_KEY, IoPriority};
+use windows::Win32::System::IO::OVERLAPPED_ENTRY;
+
+/// The class of I/O that an I/O primitive performs. Every I/O primitive is tagged with its class
+/// when it is bound to a completion port, which allows the I/O driver to tell completions of
+/// different classes apart and dispatch them according to an `IoPriorityPolicy`.
+///
+/// We do not use separate completion ports for different classes of I/O. A worker thread can only
+/// wait on one port at a time via `GetQueuedCompletionStatusEx()`, so splitting I/O across ports
+/// would force us to either poll multiple ports (wasting time in syscalls and adding latency) or
+/// to starve one port while blocked on another. Instead, all primitives of a thread share a single
+/// port and the class is carried in the completion key of each completion notification.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum IoClass {
+    /// I/O that does not fit any of the other classes.
+    #[default]
+    Other,
+
+    /// File system I/O.
+    Disk,
+
+    /// Network I/O (sockets).
+    Network,
+}
+
+impl IoClass {
+    /// The completion key used for completion notifications of this class of I/O.
+    pub(crate) const fn completion_key(self) -> usize {
+        match self {
+            IoClass::Other => 0,
+            IoClass::Disk => 1,
+            IoClass::Network => 2,
+        }
+    }
+
+    /// The class of I/O that a completion notification with the given completion key belongs to.
+    pub(crate) const fn from_completion_key(key: usize) -> Self {
+        match key {
+            1 => IoClass::Disk,
+            2 => IoClass::Network,
+            _ => IoClass::Other,
+        }
+    }
+}
+
+/// Determines the order in which the I/O driver dispatches the I/O completions it has received
+/// in one batch from the operating system.
+///
+/// This only affects the ordering within a batch of completions dequeued together - a prioritized
+/// class of I/O cannot overtake completions that have already been dispatched, nor can it prevent
+/// other classes of I/O from being processed.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum IoPriorityPolicy {
+    /// Completions are dispatched in the order they were received from the operating system.
+    #[default]
+    Fifo,
+
+    /// Completions of the given class are dispatched before completions of any other class.
+    /// Within each group, the order in which they were received is preserved.
+    Prioritize(IoClass),
+
+    /// Completions are dispatched in order of the `IoPriority` their operations were submitted
+    /// with, high priority first. Within each priority, the order in which they were received is
+    /// preserved.
+    OperationPriority,
+}
+
+impl IoPriorityPolicy {
+    /// Returns the completions in the order in which they are to be dispatched.
+    ///
+    /// The rank of every completion is determined upfront and stored in `ranks`, which must be at
+    /// least as long as `entries`. Under `OperationPriority`, the rank comes from the operation of
+    /// the completion, which is released as soon as the completion is dispatched, so we must not
+    /// look at any of the operations once dispatching has started.
+    ///
+    /// # Safety
+    ///
+    /// The entries must be completion notifications received from the completion port of the I/O
+    /// driver, not yet processed. Under `OperationPriority`, we inspect the operations they carry.
+    pub(crate) unsafe fn dispatch_order<'a>(
+        self,
+        entries: &'a [OVERLAPPED_ENTRY],
+        ranks: &'a mut [u8],
+    ) -> impl Iterator<Item = &'a OVERLAPPED_ENTRY> {
+        // Every completion is assigned a rank and we dispatch them rank by rank. There are only a
+        // few ranks, so a pass over the batch per rank is cheaper than sorting the batch and it
+        // naturally preserves the order of the completions within each rank.
+        let rank_count = match self {
+            IoPriorityPolicy::Fifo => 1,
+            IoPriorityPolicy::Prioritize(_) => 2,
+            IoPriorityPolicy::OperationPriority => 3,
+        };
+
+        let ranks = &mut ranks[..entries.len()];
+
+        for (entry, rank) in entries.iter().zip(ranks.iter_mut()) {
+            *rank = match self {
+                IoPriorityPolicy::Fifo => 0,
+                // Registered I/O notifications carry their own completion key but are network I/O.
+                IoPriorityPolicy::Prioritize(IoClass::Network)
+                    if entry.lpCompletionKey == RIO_NOTIFICATION_COMPLETION_KEY =>
+                {
+                    0
+                }
+                IoPriorityPolicy::Prioritize(class) => {
+                    u8::from(entry.lpCompletionKey != class.completion_key())
+                }
+                // Wakeup packets and Registered I/O notifications do not carry an operation. They
+                // go first, as they are cheap or bring along the completions of many requests.
+                IoPriorityPolicy::OperationPriority
+                    if entry.lpOverlapped.is_null()
+                        || entry.lpCompletionKey == RIO_NOTIFICATION_COMPLETION_KEY =>
+                {
+                    0
+                }
+                IoPriorityPolicy::OperationPriority => {
+                    // SAFETY: The caller guarantees that the entry carries an unprocessed
+                    // operation.
+                    match unsafe { operation_priority(entry.lpOverlapped) } {
+                        IoPriority::High => 0,
+                        IoPriority::Normal => 1,
+                        IoPriority::Low => 2,
+                    }
+                }
+            };
+        }
+
+        let ranks = &*ranks;
+
+        (0..rank_count).flat_map(move |current_rank| {
+            entries
+                .iter()
+                .zip(ranks)
+                .filter(move |(_, rank)| **rank == current_rank)
+                .map(|(entry, _)| entry)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(class: IoClass, bytes: u32) -> OVERLAPPED_ENTRY {
+        OVERLAPPED_ENTRY {
+            lpCompletionKey: class.completion_key(),
+            dwNumberOfBytesTransferred: bytes,
+            ..Default::default()
+        }
+    }
+
+    fn dispatched(policy: IoPriorityPolicy, entries: &[OVERLAPPED_ENTRY]) -> Vec<u32> {
+        let mut ranks = vec![0; entries.len()];
+
+        // SAFETY: These policies do not inspect the operations of the entries.
+        unsafe { policy.dispatch_order(entries, &mut ranks) }
+            .map(|entry| entry.dwNumberOfBytesTransferred)
+            .collect()
+    }
+
+    #[test]
+    fn fifo_preserves_order() {
+        let entries = [
+            entry(IoClass::Disk, 1),
+            entry(IoClass::Network, 2),
+            entry(IoClass::Disk, 3),
+            entry(IoClass::Other, 4),
+        ];
+
+        assert_eq!(dispatched(IoPriorityPolicy::Fifo, &entries), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn network_dispatched_before_disk() {
+        let entries = [
+            entry(IoClass::Disk, 1),
+            entry(IoClass::Network, 2),
+            entry(IoClass::Disk, 3),
+            entry(IoClass::Other, 4),
+            entry(IoClass::Network, 5),
+        ];
+
+        assert_eq!(
+            dispatched(IoPriorityPolicy::Prioritize(IoClass::Network), &entries),
+            [2, 5, 1, 3, 4]
+        );
+    }
+
+    #[test]
+    fn rio_notifications_dispatched_as_network() {
+        let rio_notification = OVERLAPPED_ENTRY {
+            lpCompletionKey: RIO_NOTIFICATION_COMPLETION_KEY,
+            dwNumberOfBytesTransferred: 2,
+            ..Default::default()
+        };
+
+        let entries = [
+            entry(IoClass::Disk, 1),
+            rio_notification,
+            entry(IoClass::Network, 3),
+        ];
+
+        assert_eq!(
+            dispatched(IoPriorityPolicy::Prioritize(IoClass::Network), &entries),
+            [2, 3, 1]
+        );
+        assert_eq!(
+            dispatched(IoPriorityPolicy::Prioritize(IoClass::Disk), &entries),
+            [1, 2, 3]
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use crate::{
+    io::{self, IoClass, IoOperationKind, PendingOperations, PinnedBuffer},
+    rt::current_async_agent,
+    windows::OwnedHandle,
+};
+use negative_impl::negative_impl;
+use windows::Win32::{
+    Foundation::HANDLE,
+    Storage::FileSystem::{ReadFile, WriteFile},
+    System::IO::OVERLAPPED,
+};
+
+/// A handle opened by other code (e.g. a device or a third-party library) that is bound to the I/O
+/// driver of the current async worker thread, so overlapped I/O on it completes through the
+/// runtime like the I/O of files and sockets.
+///
+/// The handle must have been opened for overlapped I/O (e.g. with `FILE_FLAG_OVERLAPPED`) and must
+/// not already be bound to another completion port. All I/O on the handle must happen on the async
+/// worker thread that registered it.
+#[derive(Debug)]
+pub struct RegisteredHandle {
+    handle: OwnedHandle<HANDLE>,
+
+    // Operations in flight on the handle, which `cancel_all()` waits for.
+    pending: PendingOperations,
+}
+
+impl RegisteredHandle {
+    /// Binds the handle to the I/O driver of the current async worker thread, taking ownership of
+    /// it. The handle is closed when this is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread is not an async worker thread owned by the Folo runtime.
+    pub fn new(handle: OwnedHandle<HANDLE>) -> io::Result<Self> {
+        current_async_agent::with_io(|io| io.bind_io_primitive(&*handle, IoClass::Other))?;
+
+        Ok(Self {
+            handle,
+            pending: PendingOperations::new(),
+        })
+    }
+
+    /// The raw handle, for passing to native APIs.
+    pub fn as_raw(&self) -> HANDLE {
+        *self.handle
+    }
+
+    /// Reads from the handle into the active region of the buffer via `ReadFile`, at `offset` for
+    /// seekable handles. Other handles ignore the offset.
+    ///
+    /// The buffer is returned with the active region set to the bytes read.
+    pub async fn read(&self, offset: usize, buffer: PinnedBuffer) -> io::Result<PinnedBuffer> {
+        // SAFETY: We pass the OVERLAPPED pointer to the native API and report its result as is.
+        unsafe {
+            self.submit_as(
+                IoOperationKind::Read,
+                offset,
+                buffer,
+                |handle, buffer, overlapped, bytes_transferred| {
+                    Ok(ReadFile(
+                        handle,
+                        Some(buffer),
+                        Some(bytes_transferred as *mut _),
+                        Some(overlapped),
+                    )?)
+                },
+            )
+        }
+        .await
+        .map_err(io::OperationError::into_inner)
+    }
+
+    /// Writes the active region of the buffer to the handle via `WriteFile`, at `offset` for
+    /// seekable handles. Other handles ignore the offset.
+    ///
+    /// The buffer is returned with the active region set to the bytes written, which may be fewer
+    /// than requested.
+    pub async fn write(&self, offset: usize, buffer: PinnedBuffer) -> io::Result<PinnedBuffer> {
+        // SAFETY: We pass the OVERLAPPED pointer to the native API and report its result as is.
+        unsafe {
+            self.submit_as(
+                IoOperationKind::Write,
+                offset,
+                buffer,
+                |handle, buffer, overlapped, bytes_transferred| {
+                    Ok(WriteFile(
+                        handle,
+                        Some(buffer),
+                        Some(bytes_transferred as *mut _),
+                        Some(overlapped),
+                    )?)
+                },
+            )
+        }
+        .await
+        .map_err(io::OperationError::into_inner)
+    }
+
+    /// Submits a custom overlapped operation on the handle (e.g. `DeviceIoControl`), which the
+    /// callback starts by calling the native API with the provided arguments:
+    ///
+    /// 1. The handle.
+    /// 2. The active region of the buffer, for the data of the operation.
+    /// 3. The OVERLAPPED structure, with the offset already set. Pass it along without modification.
+    /// 4. A variable that receives the number of bytes transferred if the native API completes
+    ///    the operation immediately.
+    ///
+    /// The callback must return the result of the native API as is - `ERROR_IO_PENDING` (or
+    /// `WSA_IO_PENDING`) if the operation was started asynchronously. The operation completes via
+    /// the I/O driver like any other, with the buffer returned with the active region set to the
+    /// bytes transferred, also when the operation fails.
+    ///
+    /// If the returned future is dropped before the operation completes, the operation is
+    /// cancelled. The buffer remains owned by the I/O driver until the OS is done with it.
+    ///
+    /// # Safety
+    ///
+    /// The callback must call a native API that performs overlapped I/O on the handle with the
+    /// OVERLAPPED pointer, even if the call fails, and must not use any of the arguments after it
+    /// returns. The native API must not access any memory other than the buffer after the call
+    /// returns.
+    pub async unsafe fn submit<F>(
+        &self,
+        offset: usize,
+        buffer: PinnedBuffer,
+        f: F,
+    ) -> io::OperationResult
+    where
+        F: FnOnce(HANDLE, &mut [u8], *mut OVERLAPPED, &mut u32) -> io::Result<()>,
+    {
+        // SAFETY: Forwarded to the caller.
+        self.submit_as(IoOperationKind::Other, offset, buffer, f)
+            .await
+    }
+
+    /// Submits an overlapped operation of a known kind on the handle. See `submit()`.
+    ///
+    /// # Safety
+    ///
+    /// See `submit()`.
+    async unsafe fn submit_as<F>(
+        &self,
+        kind: IoOperationKind,
+        offset: usize,
+        buffer: PinnedBuffer,
+        f: F,
+    ) -> io::OperationResult
+    where
+        F: FnOnce(HANDLE, &mut [u8], *mut OVERLAPPED, &mut u32) -> io::Result<()>,
+    {
+        let handle = *self.handle;
+
+        let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+        operation.track(&self.pending);
+        operation.set_handle(&handle);
+        operation.set_kind(kind);
+        operation.set_offset(offset);
+
+        // SAFETY: Forwarded to the caller.
+        operation
+            .begin(|buffer, overlapped, immediate_bytes_transferred| {
+                f(handle, buffer, overlapped, immediate_bytes_transferred)
+            })
+            .await
+    }
+
+    /// Cancels all operations in flight on the handle and waits for them to complete.
+    ///
+    /// The futures of the affected operations resolve with a cancellation error (see
+    /// `io::Error::is_cancelled()`), unless an operation managed to complete before the
+    /// cancellation took effect.
+    pub async fn cancel_all(&self) -> io::Result<()> {
+        self.pending.cancel_all(*self.handle).await
+    }
+}
+
+#[negative_impl]
+impl !Send for RegisteredHandle {}
+#[negative_impl]
+impl !Sync for RegisteredHandle {}
@@ -0,0 +1,485 @@
+use crate::{
+    io::{self, Driver, PinnedBuffer},
+    metrics::{Event, EventBuilder},
+    net::winsock,
+};
+use std::{
+    cell::{Cell, RefCell, UnsafeCell},
+    collections::HashMap,
+    ffi::c_void,
+    fmt, mem, ptr,
+    rc::Rc,
+    sync::OnceLock,
+};
+use windows::{
+    core::PCSTR,
+    Win32::{
+        Networking::WinSock::{
+            WSAGetLastError, WSAIoctl, RIORESULT, RIO_BUF, RIO_BUFFERID, RIO_CORRUPT_CQ, RIO_CQ,
+            RIO_EXTENSION_FUNCTION_TABLE, RIO_IOCP_COMPLETION, RIO_NOTIFICATION_COMPLETION,
+            RIO_NOTIFICATION_COMPLETION_0, RIO_NOTIFICATION_COMPLETION_0_1, RIO_RQ,
+            SIO_GET_MULTIPLE_EXTENSION_FUNCTION_POINTER, SOCKET, SOCKET_ERROR, WSAID_MULTIPLE_RIO,
+            WSA_ERROR,
+        },
+        System::IO::OVERLAPPED,
+    },
+};
+
+/// Notifications of Registered I/O completion queues arrive at the completion port of the I/O
+/// driver with this completion key. Instead of an operation, their OVERLAPPED pointer identifies
+/// the `RequestQueue` that has completions ready to be dequeued.
+pub(crate) const RIO_NOTIFICATION_COMPLETION_KEY: usize = 0x52494f4e4f54;
+
+/// The maximum number of sends and the maximum number of receives that may be in flight on one
+/// `RequestQueue` at the same time. Requests beyond that fail with `WSAENOBUFS`.
+const MAX_REQUESTS_IN_FLIGHT: u32 = 512;
+
+// The completion queue of a request queue has room for the completions of all its requests.
+const COMPLETION_QUEUE_SIZE: u32 = 2 * MAX_REQUESTS_IN_FLIGHT;
+
+// The number of completions we dequeue in one go when the completion queue notifies us.
+const DEQUEUE_BATCH_SIZE: usize = 64;
+
+// These are macros in the Windows headers, so they are missing from the bindings.
+const RIO_INVALID_BUFFERID: RIO_BUFFERID = RIO_BUFFERID(0xFFFF_FFFF);
+const RIO_INVALID_CQ: RIO_CQ = RIO_CQ(0);
+const RIO_INVALID_RQ: RIO_RQ = RIO_RQ(0);
+
+const FUNCTION_PRESENT: &str =
+    "Registered I/O functions are always present if the lookup succeeded";
+
+/// Looks up the Registered I/O functions via a socket created with `WSA_FLAG_REGISTERED_IO`,
+/// unless already done. The functions are the same for every socket, so the lookup only needs to
+/// succeed once per process.
+pub(crate) fn load_functions(socket: SOCKET) -> io::Result<()> {
+    if FUNCTIONS.get().is_some() {
+        return Ok(());
+    }
+
+    let guid = WSAID_MULTIPLE_RIO;
+    let mut functions = RIO_EXTENSION_FUNCTION_TABLE {
+        cbSize: mem::size_of::<RIO_EXTENSION_FUNCTION_TABLE>() as u32,
+        ..Default::default()
+    };
+    let mut bytes_returned: u32 = 0;
+
+    // SAFETY: All we need to be concerned about is passing in valid arguments, which we do.
+    winsock::to_io_result(unsafe {
+        WSAIoctl(
+            socket,
+            SIO_GET_MULTIPLE_EXTENSION_FUNCTION_POINTER,
+            Some(&guid as *const _ as *const c_void),
+            mem::size_of_val(&guid) as u32,
+            Some(&mut functions as *mut _ as *mut c_void),
+            mem::size_of_val(&functions) as u32,
+            &mut bytes_returned as *mut _,
+            None,
+            None,
+        )
+    })?;
+
+    // Another thread may have beaten us to it, in which case it got the same functions.
+    _ = FUNCTIONS.set(functions);
+
+    Ok(())
+}
+
+fn functions() -> &'static RIO_EXTENSION_FUNCTION_TABLE {
+    FUNCTIONS.get().expect(
+        "Registered I/O functions are loaded when the first Registered I/O socket is created",
+    )
+}
+
+static FUNCTIONS: OnceLock<RIO_EXTENSION_FUNCTION_TABLE> = OnceLock::new();
+
+/// Registered I/O functions report errors via `WSAGetLastError()`.
+fn last_error() -> io::Error {
+    io::Error::Winsock {
+        code: SOCKET_ERROR,
+        // SAFETY: Nothing unsafe here, just an FFI call.
+        detail: unsafe { WSAGetLastError() },
+    }
+}
+
+/// A region of memory registered for use with Registered I/O. Requests refer to the memory via
+/// the buffer ID of the registration. The registration is released when dropped.
+///
+/// Registering memory probes and locks its pages, which is as expensive as the per-operation
+/// buffer setup of overlapped I/O that Registered I/O avoids - registrations are worth it when
+/// they are reused by many operations.
+#[derive(Debug)]
+pub(crate) struct BufferRegistration {
+    id: RIO_BUFFERID,
+}
+
+impl BufferRegistration {
+    /// # Safety
+    ///
+    /// The memory must remain valid until the registration is dropped.
+    pub(crate) unsafe fn new(memory: *const u8, len: usize) -> io::Result<Self> {
+        let len = u32::try_from(len).map_err(|_| {
+            io::Error::InvalidOptions(format!(
+                "cannot register more than {} bytes of memory for Registered I/O at once",
+                u32::MAX
+            ))
+        })?;
+
+        let id =
+            functions().RIORegisterBuffer.expect(FUNCTION_PRESENT)(PCSTR::from_raw(memory), len);
+
+        if id == RIO_INVALID_BUFFERID {
+            return Err(last_error());
+        }
+
+        Ok(Self { id })
+    }
+
+    pub(crate) fn id(&self) -> RIO_BUFFERID {
+        self.id
+    }
+}
+
+impl Drop for BufferRegistration {
+    fn drop(&mut self) {
+        // SAFETY: The registration is ours and nothing refers to it anymore.
+        unsafe { functions().RIODeregisterBuffer.expect(FUNCTION_PRESENT)(self.id) }
+    }
+}
+
+/// The Registered I/O request queue of a socket, together with the completion queue that the
+/// sends and receives of the socket complete into.
+///
+/// The completion queue notifies the completion port of the I/O driver when it has completions
+/// ready to be dequeued. The I/O driver then calls `process_notification()`, which delivers the
+/// results to the callers and arms the next notification if any requests are still in flight.
+///
+/// An armed notification keeps the queue alive. Closing the socket completes all requests in
+/// flight on it, so the queue is released soon after the socket is closed. The socket must be
+/// closed before the queue is released, as closing the completion queue while the request queue
+/// of the socket still refers to it is not allowed.
+#[repr(C)]
+pub(crate) struct RequestQueue {
+    // The notifications of the completion queue carry a pointer to this, which is also a pointer
+    // to the queue itself because this is the first field. The OS uses it to deliver notifications.
+    overlapped: UnsafeCell<OVERLAPPED>,
+
+    completion_queue: Cell<RIO_CQ>,
+    request_queue: Cell<RIO_RQ>,
+
+    // The requests in flight, by the request context they were submitted with.
+    pending: RefCell<HashMap<u64, PendingRequest>>,
+    next_request_id: Cell<u64>,
+
+    notification_armed: Cell<bool>,
+
+    // Shared with the I/O driver, which is not inert while any notifications are armed.
+    armed_notifications: Rc<Cell<usize>>,
+}
+
+struct PendingRequest {
+    // This covers the memory of the buffer, so it is declared first to be released first.
+    transient_registration: Option<BufferRegistration>,
+
+    buffer: PinnedBuffer,
+    result_tx: oneshot::Sender<io::Result<PinnedBuffer>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Direction {
+    Send,
+    Receive,
+}
+
+impl RequestQueue {
+    /// Creates the queues for a socket created with `WSA_FLAG_REGISTERED_IO`, with the completion
+    /// queue notifying the completion port of the I/O driver.
+    pub(crate) fn new(socket: SOCKET, driver: &Driver) -> io::Result<Rc<Self>> {
+        let queue = Rc::new(Self {
+            overlapped: UnsafeCell::default(),
+            completion_queue: Cell::new(RIO_INVALID_CQ),
+            request_queue: Cell::new(RIO_INVALID_RQ),
+            pending: RefCell::new(HashMap::new()),
+            next_request_id: Cell::new(0),
+            notification_armed: Cell::new(false),
+            armed_notifications: driver.armed_rio_notifications(),
+        });
+
+        let notification = RIO_NOTIFICATION_COMPLETION {
+            Type: RIO_IOCP_COMPLETION,
+            Anonymous: RIO_NOTIFICATION_COMPLETION_0 {
+                Iocp: RIO_NOTIFICATION_COMPLETION_0_1 {
+                    IocpHandle: driver.completion_port_handle(),
+                    CompletionKey: RIO_NOTIFICATION_COMPLETION_KEY as *mut c_void,
+                    Overlapped: queue.overlapped.get() as *mut c_void,
+                },
+            },
+        };
+
+        // SAFETY: The OVERLAPPED stays in place until the queue is dropped, which is also when we
+        // close the completion queue.
+        let completion_queue = unsafe {
+            functions()
+                .RIOCreateCompletionQueue
+                .expect(FUNCTION_PRESENT)(COMPLETION_QUEUE_SIZE, &notification)
+        };
+
+        if completion_queue == RIO_INVALID_CQ {
+            return Err(last_error());
+        }
+
+        queue.completion_queue.set(completion_queue);
+
+        // Every request refers to exactly one buffer.
+        // SAFETY: All we need to be concerned about is passing in valid arguments, which we do.
+        let request_queue = unsafe {
+            functions().RIOCreateRequestQueue.expect(FUNCTION_PRESENT)(
+                socket,
+                MAX_REQUESTS_IN_FLIGHT,
+                1,
+                MAX_REQUESTS_IN_FLIGHT,
+                1,
+                completion_queue,
+                completion_queue,
+                ptr::null(),
+            )
+        };
+
+        if request_queue == RIO_INVALID_RQ {
+            return Err(last_error());
+        }
+
+        queue.request_queue.set(request_queue);
+
+        Ok(queue)
+    }
+
+    /// Starts sending the active region of the buffer. The result arrives via the returned
+    /// receiver, with the active region of the buffer set to the bytes sent.
+    pub(crate) fn send(
+        self: &Rc<Self>,
+        buffer: PinnedBuffer,
+    ) -> io::Result<oneshot::Receiver<io::Result<PinnedBuffer>>> {
+        self.submit(Direction::Send, buffer)
+    }
+
+    /// Starts receiving into the active region of the buffer. The result arrives via the returned
+    /// receiver, with the active region of the buffer set to the bytes received.
+    pub(crate) fn receive(
+        self: &Rc<Self>,
+        buffer: PinnedBuffer,
+    ) -> io::Result<oneshot::Receiver<io::Result<PinnedBuffer>>> {
+        self.submit(Direction::Receive, buffer)
+    }
+
+    fn submit(
+        self: &Rc<Self>,
+        direction: Direction,
+        mut buffer: PinnedBuffer,
+    ) -> io::Result<oneshot::Receiver<io::Result<PinnedBuffer>>> {
+        // Buffers from the pool are covered by the registration of their slab. Anything else has
+        // to be registered for the duration of the request.
+        let (rio_buf, transient_registration) = match buffer.rio_buf()? {
+            Some(rio_buf) => (rio_buf, None),
+            None => {
+                let region = buffer.as_mut_slice();
+
+                // SAFETY: The buffer is pinned and stays alive until the request completes, after
+                // the registration has been released.
+                let registration =
+                    unsafe { BufferRegistration::new(region.as_mut_ptr(), region.len())? };
+
+                TRANSIENT_REGISTRATIONS.with(Event::observe_unit);
+
+                let rio_buf = RIO_BUF {
+                    BufferId: registration.id(),
+                    Offset: 0,
+                    Length: region.len() as u32,
+                };
+
+                (rio_buf, Some(registration))
+            }
+        };
+
+        let request_id = self.next_request_id.get();
+        self.next_request_id.set(request_id.wrapping_add(1));
+
+        let function = match direction {
+            Direction::Send => functions().RIOSend,
+            Direction::Receive => functions().RIOReceive,
+        }
+        .expect(FUNCTION_PRESENT);
+
+        // The request is only completed via the completion queue, even if it fails, so there is
+        // nothing for us to do until we dequeue the completion.
+        // SAFETY: The buffer stays alive until we dequeue the completion of the request. The
+        // RIO_BUF itself only needs to be valid for the duration of the call.
+        let submitted = unsafe {
+            function(
+                self.request_queue.get(),
+                &rio_buf,
+                1,
+                0,
+                request_id as *const c_void,
+            )
+        };
+
+        if !submitted.as_bool() {
+            return Err(last_error());
+        }
+
+        let (result_tx, result_rx) = oneshot::channel();
+
+        self.pending.borrow_mut().insert(
+            request_id,
+            PendingRequest {
+                transient_registration,
+                buffer,
+                result_tx,
+            },
+        );
+
+        self.arm_notification();
+
+        Ok(result_rx)
+    }
+
+    /// Asks the completion queue to notify the I/O driver once it has completions ready to be
+    /// dequeued, unless a notification is already armed. The notification arrives right away if
+    /// there are completions in the queue already.
+    fn arm_notification(self: &Rc<Self>) {
+        if self.notification_armed.get() {
+            return;
+        }
+
+        // SAFETY: The completion queue is valid for as long as the queue exists.
+        let result =
+            unsafe { functions().RIONotify.expect(FUNCTION_PRESENT)(self.completion_queue.get()) };
+
+        // This only fails if a notification is already armed or the completion queue is invalid,
+        // neither of which can be the case here.
+        assert_eq!(
+            result, 0,
+            "arming a Registered I/O notification must succeed"
+        );
+
+        self.notification_armed.set(true);
+        self.armed_notifications
+            .set(self.armed_notifications.get() + 1);
+
+        // The notification refers to the queue via the OVERLAPPED pointer, so it holds a reference
+        // to the queue. We take the reference back in `process_notification()`.
+        _ = Rc::into_raw(Rc::clone(self));
+    }
+
+    fn dequeue_completions(&self) {
+        let mut results = [RIORESULT::default(); DEQUEUE_BATCH_SIZE];
+
+        loop {
+            // SAFETY: The array has room for the number of results we ask for.
+            let count = unsafe {
+                functions().RIODequeueCompletion.expect(FUNCTION_PRESENT)(
+                    self.completion_queue.get(),
+                    results.as_mut_ptr(),
+                    results.len() as u32,
+                )
+            };
+
+            assert_ne!(
+                count, RIO_CORRUPT_CQ,
+                "Registered I/O completion queue is corrupt"
+            );
+
+            for result in &results[..count as usize] {
+                self.complete(result);
+            }
+
+            if (count as usize) < results.len() {
+                return;
+            }
+        }
+    }
+
+    fn complete(&self, result: &RIORESULT) {
+        let PendingRequest {
+            transient_registration,
+            mut buffer,
+            result_tx,
+        } = self
+            .pending
+            .borrow_mut()
+            .remove(&result.RequestContext)
+            .expect("every completion belongs to a request in flight");
+
+        // The OS is done with the memory of the buffer.
+        drop(transient_registration);
+
+        let result = if result.Status == 0 {
+            buffer.set_len(result.BytesTransferred as usize);
+            Ok(buffer)
+        } else {
+            Err(io::Error::Winsock {
+                code: SOCKET_ERROR,
+                detail: WSA_ERROR(result.Status),
+            })
+        };
+
+        // The caller may have stopped waiting for the result, which is fine.
+        _ = result_tx.send(result);
+    }
+}
+
+impl fmt::Debug for RequestQueue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestQueue")
+            .field("completion_queue", &self.completion_queue.get())
+            .field("request_queue", &self.request_queue.get())
+            .field("in_flight", &self.pending.borrow().len())
+            .field("notification_armed", &self.notification_armed.get())
+            .finish()
+    }
+}
+
+impl Drop for RequestQueue {
+    fn drop(&mut self) {
+        let completion_queue = self.completion_queue.get();
+
+        if completion_queue != RIO_INVALID_CQ {
+            // SAFETY: No notification is armed because it would keep the queue alive, so the OS
+            // no longer uses the OVERLAPPED once the completion queue is closed.
+            unsafe {
+                functions().RIOCloseCompletionQueue.expect(FUNCTION_PRESENT)(completion_queue)
+            }
+        }
+    }
+}
+
+/// Delivers the results of the requests that completed on the queue that the notification is for
+/// and arms the next notification if any requests are still in flight.
+///
+/// # Safety
+///
+/// The OVERLAPPED pointer must come from a completion notification with the completion key
+/// `RIO_NOTIFICATION_COMPLETION_KEY` that has not been processed yet.
+pub(crate) unsafe fn process_notification(overlapped: *mut OVERLAPPED) {
+    // SAFETY: The OVERLAPPED is the first field of the queue and the notification holds the
+    // reference to the queue that we took in `arm_notification()`, which we now take back.
+    let queue = Rc::from_raw(overlapped as *const RequestQueue);
+
+    queue.notification_armed.set(false);
+    queue
+        .armed_notifications
+        .set(queue.armed_notifications.get() - 1);
+
+    queue.dequeue_completions();
+
+    if !queue.pending.borrow().is_empty() {
+        queue.arm_notification();
+    }
+}
+
+thread_local! {
+    static TRANSIENT_REGISTRATIONS: Event = EventBuilder::new()
+        .name("io_rio_transient_buffer_registrations")
+        .build()
+        .unwrap();
+}
@@ -0,0 +1,88 @@
+use crate::io::IoClass;
+use std::{fmt, sync::Arc, time::Duration};
+use windows::Win32::Foundation::HANDLE;
+
+/// What an I/O operation reported via `RuntimeBuilder::on_slow_io()` was doing.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum IoOperationKind {
+    /// Reading data from a file, socket or pipe.
+    Read,
+
+    /// Writing data to a file, socket or pipe.
+    Write,
+
+    /// Establishing a connection (e.g. a TCP connect or a named pipe client connecting).
+    Connect,
+
+    /// Accepting an incoming connection.
+    Accept,
+
+    /// Operations that do not fit any of the other kinds, such as locking a file or querying a
+    /// device.
+    #[default]
+    Other,
+}
+
+/// Describes an I/O operation that took longer to complete than the threshold configured via
+/// `RuntimeBuilder::on_slow_io()`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SlowIoOperation {
+    /// What the operation was doing.
+    pub kind: IoOperationKind,
+
+    /// The I/O primitive (file handle, socket, ...) the operation was performed on, if known.
+    /// The handle may have been closed by the time the report is received, so only use it to
+    /// tell operations on different primitives apart.
+    pub handle: Option<HANDLE>,
+
+    /// The class of the I/O primitive the operation was performed on.
+    pub class: IoClass,
+
+    /// Time from the submission of the operation to the operating system until the completion
+    /// notification was received. This is measured with low precision (on the order of 15 ms).
+    pub duration: Duration,
+
+    /// Number of bytes transferred by the operation.
+    pub bytes_transferred: usize,
+
+    /// Whether the operation completed successfully.
+    pub succeeded: bool,
+}
+
+// SAFETY: The handle is only an identifier in the report. We never use it for anything.
+unsafe impl Send for SlowIoOperation {}
+unsafe impl Sync for SlowIoOperation {}
+
+/// Invokes a callback for every I/O operation that takes longer than a threshold to complete.
+#[derive(Clone)]
+pub(crate) struct SlowIoHook {
+    threshold: Duration,
+    callback: Arc<dyn Fn(&SlowIoOperation) + Send + Sync + 'static>,
+}
+
+impl SlowIoHook {
+    pub(crate) fn new<F>(threshold: Duration, callback: F) -> Self
+    where
+        F: Fn(&SlowIoOperation) + Send + Sync + 'static,
+    {
+        Self {
+            threshold,
+            callback: Arc::new(callback),
+        }
+    }
+
+    /// Invokes the callback if the operation took longer than the threshold.
+    pub(crate) fn observe(&self, operation: &SlowIoOperation) {
+        if operation.duration > self.threshold {
+            (self.callback)(operation);
+        }
+    }
+}
+
+impl fmt::Debug for SlowIoHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SlowIoHook")
+            .field("threshold", &self.threshold)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,164 @@
+use crate::io;
+use futures::task::AtomicWaker;
+use std::{
+    ffi::c_void,
+    future::poll_fn,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task,
+};
+use tracing::{event, Level};
+use windows::Win32::{
+    Foundation::{BOOLEAN, HANDLE, INVALID_HANDLE_VALUE},
+    System::Threading::{
+        RegisterWaitForSingleObject, UnregisterWaitEx, INFINITE, WT_EXECUTEINWAITTHREAD,
+        WT_EXECUTEONLYONCE,
+    },
+};
+
+/// Waits until any of the handles is signaled and returns the index of the signaled handle. This
+/// is the asynchronous counterpart of `WaitForMultipleObjects()` and accepts the same kinds of
+/// handles (events, processes, threads, semaphores and so on).
+///
+/// The waiting is done by the OS thread pool, which wakes up the task when the first handle is
+/// signaled. If multiple handles are signaled at the same time, the first one to be observed wins.
+///
+/// The handles must remain valid until the returned future completes or is dropped.
+pub async fn wait_for_any(handles: &[HANDLE]) -> io::Result<usize> {
+    if handles.is_empty() {
+        return Err(io::Error::InvalidOptions(
+            "at least one handle is required".to_string(),
+        ));
+    }
+
+    let state = Arc::new(WaitState::new());
+
+    // Each registration is unregistered when dropped, which happens either once we have our
+    // answer or when the future is dropped before that.
+    let _registrations = handles
+        .iter()
+        .enumerate()
+        .map(|(index, handle)| RegisteredWait::new(*handle, index, Arc::clone(&state)))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    Ok(poll_fn(|cx| state.poll(cx)).await)
+}
+
+// Marks that no handle has been signaled yet.
+const NOT_SIGNALED: usize = usize::MAX;
+
+/// The state shared between the waiting task and the callbacks of the OS thread pool.
+#[derive(Debug)]
+struct WaitState {
+    // Index of the first handle that was signaled.
+    signaled: AtomicUsize,
+
+    waker: AtomicWaker,
+}
+
+impl WaitState {
+    fn new() -> Self {
+        Self {
+            signaled: AtomicUsize::new(NOT_SIGNALED),
+            waker: AtomicWaker::new(),
+        }
+    }
+
+    fn signal(&self, index: usize) {
+        // The first to fire wins - if another handle was already signaled, we leave it be.
+        if self
+            .signaled
+            .compare_exchange(NOT_SIGNALED, index, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+        {
+            self.waker.wake();
+        }
+    }
+
+    fn poll(&self, cx: &mut task::Context<'_>) -> task::Poll<usize> {
+        // We register before checking, so a signal that arrives between the check and the
+        // registration is not missed.
+        self.waker.register(cx.waker());
+
+        match self.signaled.load(Ordering::Acquire) {
+            NOT_SIGNALED => task::Poll::Pending,
+            index => task::Poll::Ready(index),
+        }
+    }
+}
+
+/// The data passed to the callback of one registered wait.
+#[derive(Debug)]
+struct WaitContext {
+    index: usize,
+    state: Arc<WaitState>,
+}
+
+/// A wait registered with the OS thread pool, unregistered when dropped.
+#[derive(Debug)]
+struct RegisteredWait {
+    wait_handle: HANDLE,
+
+    // Owned by us but referenced by the callback until the wait is unregistered.
+    context: *mut WaitContext,
+}
+
+impl RegisteredWait {
+    fn new(handle: HANDLE, index: usize, state: Arc<WaitState>) -> io::Result<Self> {
+        let context = Box::into_raw(Box::new(WaitContext { index, state }));
+        let mut wait_handle = HANDLE::default();
+
+        // SAFETY: The context remains valid until the wait is unregistered, which we do before
+        // releasing it. The caller guarantees that the handle remains valid while we wait.
+        let result = unsafe {
+            RegisterWaitForSingleObject(
+                &mut wait_handle,
+                handle,
+                Some(wait_callback),
+                Some(context as *const c_void),
+                INFINITE,
+                // The callback is trivial, so there is no need to hand it off to a worker thread.
+                WT_EXECUTEINWAITTHREAD | WT_EXECUTEONLYONCE,
+            )
+        };
+
+        if let Err(e) = result {
+            // SAFETY: The registration failed, so nothing else is referencing the context.
+            drop(unsafe { Box::from_raw(context) });
+            return Err(e.into());
+        }
+
+        Ok(Self {
+            wait_handle,
+            context,
+        })
+    }
+}
+
+impl Drop for RegisteredWait {
+    fn drop(&mut self) {
+        // We wait for any callback in progress to finish, after which the context is no longer
+        // referenced by the OS. This blocks the thread but only for as long as it takes for the
+        // trivial callback to complete.
+        //
+        // SAFETY: The wait handle is valid because we only unregister it once, here.
+        let result = unsafe { UnregisterWaitEx(self.wait_handle, INVALID_HANDLE_VALUE) };
+
+        match result {
+            // SAFETY: The wait is unregistered, so nothing else is referencing the context.
+            Ok(()) => drop(unsafe { Box::from_raw(self.context) }),
+            // If we cannot unregister, the callback may still run, so we must leak the context.
+            Err(e) => event!(Level::ERROR, message = "failed to unregister wait", error = %e),
+        }
+    }
+}
+
+unsafe extern "system" fn wait_callback(context: *mut c_void, _timed_out: BOOLEAN) {
+    // SAFETY: The context is valid until the wait is unregistered, which waits for this callback
+    // to complete.
+    let context = unsafe { &*(context as *const WaitContext) };
+
+    context.state.signal(context.index);
+}
@@ -1,9 +1,14 @@
 use crate::{collections::BuildPointerHasher, windows::OwnedHandle};
+use crossbeam::queue::SegQueue;
 use std::{
     cell::RefCell,
     collections::HashSet,
     hash::{Hash, Hasher},
-    sync::Weak,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Weak,
+    },
+    task::Waker,
 };
 use windows::Win32::{Foundation::HANDLE, System::IO::PostQueuedCompletionStatus};
 
@@ -18,19 +23,54 @@ pub(crate) const WAKE_UP_COMPLETION_KEY: usize = 0x23546789897;
 ///
 /// The completion packet is simply a completion message without any payload and the completion key
 /// `WAKE_UP_COMPLETION_KEY`. The OVERLAPPED pointer is null for these messages.
+///
+/// Wakeups are coalesced: a packet is only posted if there is not already one on the way that the
+/// target thread has not yet received. No matter how many times the target thread is woken up in
+/// quick succession, it only has to dequeue a single completion packet.
+///
+/// Task wakeups can also be delivered via the waker, to be executed on the target thread after it
+/// wakes up. Any number of task wakeups queued before the target thread wakes up are delivered via
+/// the same single completion packet. See `wake_tasks()`.
 #[derive(Clone, Debug)]
 pub(crate) struct IoWaker {
     completion_port: Weak<OwnedHandle<HANDLE>>,
+    wake_queue: Weak<WakeQueue>,
 }
 
 impl IoWaker {
-    pub(crate) fn new(completion_port: Weak<OwnedHandle<HANDLE>>) -> Self {
-        Self { completion_port }
+    pub(crate) fn new(
+        completion_port: Weak<OwnedHandle<HANDLE>>,
+        wake_queue: Weak<WakeQueue>,
+    ) -> Self {
+        Self {
+            completion_port,
+            wake_queue,
+        }
     }
 
-    /// Wakes up the target thread via the I/O driver by sending a completion packet to its
-    /// completion port. This is a non-blocking operation.
+    /// Wakes up the target thread via the I/O driver, unless a wakeup packet is already on the way
+    /// that the target thread has not yet received. This is a non-blocking operation.
+    ///
+    /// This is for waking up the target thread to pick up work queued for it before the call
+    /// (e.g. tasks spawned from another thread), which it does every time it wakes up. No matter
+    /// how much work is queued in quick succession, the target thread is woken up via a single
+    /// completion packet.
     pub(crate) fn wake(&self) {
+        let Some(wake_queue) = self.wake_queue.upgrade() else {
+            // The target thread is shutting down, so there is nothing to coalesce with.
+            self.post_wake_up();
+            return;
+        };
+
+        // The target thread clears the flag when it receives the packet, before it looks for work.
+        if !wake_queue.wake_posted.swap(true, Ordering::AcqRel) {
+            self.post_wake_up();
+        }
+    }
+
+    // Posts a wakeup packet to the completion port, or adds it to the batch of the current thread
+    // if batching is enabled.
+    fn post_wake_up(&self) {
         if self.try_add_to_batch() {
             return;
         }
@@ -43,6 +83,33 @@ impl IoWaker {
         Self::wake_core(&completion_port);
     }
 
+    /// Wakes up tasks owned by the target thread, calling their wakers on the target thread once it
+    /// wakes up. This is a non-blocking operation.
+    ///
+    /// No matter how many tasks are woken up, the target thread is woken up via a single completion
+    /// packet, which is only posted if there is not already one on the way.
+    pub(crate) fn wake_tasks(&self, wakers: impl IntoIterator<Item = Waker>) {
+        let Some(wake_queue) = self.wake_queue.upgrade() else {
+            // The target thread is shutting down and there is no more I/O driver to deliver the
+            // wakeups. We just wake the tasks right here, right now.
+            wakers.into_iter().for_each(Waker::wake);
+            return;
+        };
+
+        let mut any = false;
+
+        for waker in wakers {
+            wake_queue.wakers.push(waker);
+            any = true;
+        }
+
+        // If a wakeup packet is already on the way, it will deliver our wakers, as the target
+        // thread clears the flag before draining the queue.
+        if any {
+            self.wake();
+        }
+    }
+
     /// Enables I/O wakers to be processed in batches from the current thread. After this, a call
     /// to wake() will merely queue the operation and it will not be submitted until we call the
     /// submit_batch() method. This allows for de-duplication of wake-up calls.
@@ -100,6 +167,31 @@ impl IoWaker {
     }
 }
 
+/// Task wakeups queued via `IoWaker::wake_tasks()` for delivery on the thread that owns the
+/// completion port. Wakeups may be queued from any thread.
+#[derive(Debug, Default)]
+pub(crate) struct WakeQueue {
+    wakers: SegQueue<Waker>,
+
+    // Whether a wakeup packet has been posted that has not yet been received by the target thread.
+    wake_posted: AtomicBool,
+}
+
+impl WakeQueue {
+    /// Wakes up all the tasks whose wakeups have been queued. To be called on the thread that owns
+    /// the completion port whenever it receives a wakeup packet.
+    pub(crate) fn wake_queued_tasks(&self) {
+        // We clear the flag before draining the queue, so any wakeup queued after we have taken a
+        // look at the queue will post a new wakeup packet and not be lost. This also acquires any
+        // work queued by threads that relied on the packet being on the way, as they set the flag.
+        self.wake_posted.swap(false, Ordering::AcqRel);
+
+        while let Some(waker) = self.wakers.pop() {
+            waker.wake();
+        }
+    }
+}
+
 struct WakeRequest {
     completion_port: Weak<OwnedHandle<HANDLE>>,
 }
@@ -122,3 +214,114 @@ thread_local! {
     static BATCH: RefCell<Option<HashSet<WakeRequest, BuildPointerHasher>>> =
         const { RefCell::new(None) };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::CompletionPort;
+    use std::{
+        sync::{atomic::AtomicUsize, Arc},
+        task::Wake,
+        thread,
+    };
+    use windows::Win32::System::IO::{GetQueuedCompletionStatusEx, OVERLAPPED_ENTRY};
+
+    #[derive(Default)]
+    struct CountingWaker {
+        count: AtomicUsize,
+    }
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.count.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn dequeue_packets(completion_port: &CompletionPort) -> usize {
+        let mut entries = [OVERLAPPED_ENTRY::default(); 16];
+        let mut count: u32 = 0;
+
+        // SAFETY: The completion port is valid and we pass valid buffers. Errors just mean there
+        // was nothing to dequeue before the timeout.
+        match unsafe {
+            GetQueuedCompletionStatusEx(
+                *completion_port.as_native_handle(),
+                &mut entries,
+                &mut count,
+                0,
+                false,
+            )
+        } {
+            Ok(()) => {
+                assert!(entries[..count as usize]
+                    .iter()
+                    .all(|entry| entry.lpCompletionKey == WAKE_UP_COMPLETION_KEY));
+                count as usize
+            }
+            Err(_) => 0,
+        }
+    }
+
+    #[test]
+    fn wake_tasks_posts_single_packet() {
+        const WAITERS: usize = 100;
+
+        let completion_port = CompletionPort::new();
+        let io_waker = completion_port.waker();
+
+        let counter = Arc::new(CountingWaker::default());
+        let counter_clone = Arc::clone(&counter);
+
+        // The wakeups come from another thread, as they would when some other thread broadcasts
+        // a notification to tasks waiting on our thread.
+        thread::spawn(move || {
+            for _ in 0..WAITERS {
+                io_waker.wake_tasks([Waker::from(Arc::clone(&counter_clone))]);
+            }
+        })
+        .join()
+        .unwrap();
+
+        // Nothing has been woken up yet - that only happens on the thread that owns the port.
+        assert_eq!(counter.count.load(Ordering::Relaxed), 0);
+
+        assert_eq!(dequeue_packets(&completion_port), 1);
+
+        completion_port.wake_queued_tasks();
+        assert_eq!(counter.count.load(Ordering::Relaxed), WAITERS);
+
+        // Once the wakeups have been delivered, the next batch gets a new packet.
+        completion_port
+            .waker()
+            .wake_tasks([Waker::from(Arc::clone(&counter))]);
+
+        assert_eq!(dequeue_packets(&completion_port), 1);
+
+        completion_port.wake_queued_tasks();
+        assert_eq!(counter.count.load(Ordering::Relaxed), WAITERS + 1);
+    }
+
+    #[test]
+    fn wake_posts_single_packet() {
+        let completion_port = CompletionPort::new();
+        let io_waker = completion_port.waker();
+
+        thread::spawn(move || {
+            for _ in 0..1000 {
+                io_waker.wake();
+            }
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(dequeue_packets(&completion_port), 1);
+
+        // The target thread clears the flag when it receives the packet, after which the next
+        // wakeup gets a new packet.
+        completion_port.wake_queued_tasks();
+        completion_port.waker().wake();
+        completion_port.waker().wake();
+
+        assert_eq!(dequeue_packets(&completion_port), 1);
+    }
+}
@@ -12,6 +12,7 @@ pub mod linked;
 pub mod mem;
 pub mod metrics;
 pub mod net;
+pub mod process;
 pub mod rt;
 pub mod sync;
 pub mod time;
@@ -1,11 +1,15 @@
+mod accounting;
 mod drop_policy;
+mod numa;
 mod pinned_slab;
 mod pinned_slab_chain;
 mod shared_array_pool;
 mod slab_rc;
 pub mod storage;
 
+pub use accounting::*;
 pub use drop_policy::*;
+pub use numa::*;
 pub use pinned_slab::*;
 pub use pinned_slab_chain::*;
 pub use shared_array_pool::*;
@@ -0,0 +1,274 @@
+use pin_project::pin_project;
+use std::{
+    alloc::{GlobalAlloc, Layout},
+    cell::Cell,
+    future::Future,
+    pin::Pin,
+    ptr, task,
+};
+use thiserror::Error;
+
+/// A global allocator wrapper that attributes memory allocations to the task being polled, for
+/// enforcing per-task memory limits via `MemoryLimitExt::with_memory_limit()`.
+///
+/// Install it as the global allocator of the app, wrapping the allocator you would otherwise use:
+///
+/// ```
+/// use folo::mem::AccountingAllocator;
+/// use std::alloc::System;
+///
+/// #[global_allocator]
+/// static ALLOCATOR: AccountingAllocator<System> = AccountingAllocator::new(System);
+/// ```
+///
+/// Allocations made outside any memory-limited future are passed through without accounting,
+/// costing only a thread-local lookup.
+#[derive(Debug)]
+pub struct AccountingAllocator<A> {
+    inner: A,
+}
+
+impl<A> AccountingAllocator<A> {
+    pub const fn new(inner: A) -> Self {
+        Self { inner }
+    }
+}
+
+// SAFETY: We forward all calls to the inner allocator unchanged, only observing the sizes.
+unsafe impl<A: GlobalAlloc> GlobalAlloc for AccountingAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.inner.alloc(layout);
+
+        if !ptr.is_null() {
+            charge(layout.size() as isize);
+        }
+
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.inner.alloc_zeroed(layout);
+
+        if !ptr.is_null() {
+            charge(layout.size() as isize);
+        }
+
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.inner.dealloc(ptr, layout);
+
+        charge(-(layout.size() as isize));
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = self.inner.realloc(ptr, layout, new_size);
+
+        if !new_ptr.is_null() {
+            charge(new_size as isize - layout.size() as isize);
+        }
+
+        new_ptr
+    }
+}
+
+/// The memory usage attributed to one memory-limited future.
+#[derive(Debug, Default)]
+struct MemoryUsage {
+    // Bytes allocated minus bytes freed while polling the future. May go negative if the future
+    // frees memory that was allocated elsewhere.
+    current: Cell<isize>,
+}
+
+/// Returns the number of bytes currently attributed to the innermost memory-limited future that
+/// is being polled on the current thread, or `None` if no memory-limited future is being polled.
+///
+/// This only reports meaningful values if `AccountingAllocator` is the global allocator.
+pub fn current_task_memory() -> Option<usize> {
+    CURRENT_USAGE.with(|current| {
+        let usage = current.get();
+
+        // SAFETY: The pointer is only set while the future that owns the usage is being polled.
+        (!usage.is_null()).then(|| unsafe { (*usage).current.get() }.max(0) as usize)
+    })
+}
+
+fn charge(delta: isize) {
+    // The thread-local may already be gone if we are allocating during thread teardown. Nothing
+    // is being polled at that point, so there is nothing to charge.
+    _ = CURRENT_USAGE.try_with(|current| {
+        let usage = current.get();
+
+        if !usage.is_null() {
+            // SAFETY: The pointer is only set while the future that owns the usage is being
+            // polled, so it is valid here.
+            let usage = unsafe { &*usage };
+            usage.current.set(usage.current.get() + delta);
+        }
+    });
+}
+
+/// The error returned by a future whose memory limit was exceeded.
+#[derive(Clone, Copy, Debug, Error, Eq, PartialEq)]
+#[error("task exceeded its memory limit of {limit_bytes} bytes (used {used_bytes} bytes)")]
+pub struct OutOfMemory {
+    pub limit_bytes: usize,
+    pub used_bytes: usize,
+}
+
+/// Extension methods for capping the memory used by futures.
+pub trait MemoryLimitExt: Future + Sized {
+    /// Attributes the memory allocated while polling the future to it and aborts the future with
+    /// an `OutOfMemory` error if more than `limit_bytes` remain allocated after a poll. The future
+    /// is dropped when aborted.
+    ///
+    /// Typically applied to the future of an entire task when spawning it, to cap the memory of
+    /// each task.
+    ///
+    /// # Accuracy
+    ///
+    /// The accounting is approximate and requires `AccountingAllocator` to be installed as the
+    /// global allocator - without it, the limit is never exceeded.
+    ///
+    /// * Only allocations made on the current thread while the future is being polled are counted.
+    ///   Work the future hands off to other tasks or threads is not attributed to it.
+    /// * The limit is checked after each poll, so a single poll can exceed it by any amount
+    ///   before the future is aborted.
+    /// * Memory freed while polling the future counts against it even if it was allocated
+    ///   elsewhere.
+    /// * If memory-limited futures are nested, allocations are only attributed to the innermost.
+    fn with_memory_limit(self, limit_bytes: usize) -> MemoryLimited<Self> {
+        MemoryLimited {
+            inner: Some(self),
+            limit_bytes,
+            usage: MemoryUsage::default(),
+        }
+    }
+}
+
+impl<F> MemoryLimitExt for F where F: Future {}
+
+/// Future returned by `MemoryLimitExt::with_memory_limit()`.
+#[pin_project]
+#[derive(Debug)]
+pub struct MemoryLimited<F> {
+    // Becomes None when the limit is exceeded and the inner future is dropped.
+    #[pin]
+    inner: Option<F>,
+
+    limit_bytes: usize,
+
+    usage: MemoryUsage,
+}
+
+impl<F> Future for MemoryLimited<F>
+where
+    F: Future,
+{
+    type Output = Result<F::Output, OutOfMemory>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let mut this = self.project();
+
+        let inner = this
+            .inner
+            .as_mut()
+            .as_pin_mut()
+            .expect("future polled after completion");
+
+        // Allocations made during the poll are charged to us.
+        let result = {
+            let _scope = UsageScope::enter(this.usage);
+            inner.poll(cx)
+        };
+
+        let used_bytes = this.usage.current.get().max(0) as usize;
+
+        match result {
+            task::Poll::Ready(output) => task::Poll::Ready(Ok(output)),
+            task::Poll::Pending if used_bytes > *this.limit_bytes => {
+                this.inner.set(None);
+
+                task::Poll::Ready(Err(OutOfMemory {
+                    limit_bytes: *this.limit_bytes,
+                    used_bytes,
+                }))
+            }
+            task::Poll::Pending => task::Poll::Pending,
+        }
+    }
+}
+
+/// Charges the allocations on the current thread to a `MemoryUsage` until dropped. Restores the
+/// previous target when dropped, even if the poll panics, as we may be nested inside another
+/// memory-limited future and must never leave a dangling pointer behind.
+struct UsageScope {
+    previous: *const MemoryUsage,
+}
+
+impl UsageScope {
+    fn enter(usage: &MemoryUsage) -> Self {
+        Self {
+            previous: CURRENT_USAGE.replace(ptr::from_ref(usage)),
+        }
+    }
+}
+
+impl Drop for UsageScope {
+    fn drop(&mut self) {
+        CURRENT_USAGE.set(self.previous);
+    }
+}
+
+thread_local! {
+    // Points to the usage of the memory-limited future being polled on this thread, if any.
+    static CURRENT_USAGE: Cell<*const MemoryUsage> = const { Cell::new(ptr::null()) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{executor::block_on, future::poll_fn, task::noop_waker_ref, FutureExt};
+
+    // The test binary does not use the accounting allocator, so we charge manually to simulate it.
+    fn allocating_future(bytes_per_poll: isize) -> impl Future<Output = ()> {
+        poll_fn(move |cx| {
+            charge(bytes_per_poll);
+            cx.waker().wake_by_ref();
+            task::Poll::<()>::Pending
+        })
+    }
+
+    #[test]
+    fn aborted_when_over_limit() {
+        let mut future = allocating_future(400).with_memory_limit(1000);
+        let mut cx = task::Context::from_waker(noop_waker_ref());
+
+        assert!(future.poll_unpin(&mut cx).is_pending());
+        assert!(future.poll_unpin(&mut cx).is_pending());
+
+        assert_eq!(
+            future.poll_unpin(&mut cx),
+            task::Poll::Ready(Err(OutOfMemory {
+                limit_bytes: 1000,
+                used_bytes: 1200
+            }))
+        );
+    }
+
+    #[test]
+    fn completes_within_limit() {
+        let future = async {
+            charge(500);
+            current_task_memory()
+        }
+        .with_memory_limit(1000);
+
+        assert_eq!(block_on(future), Ok(Some(500)));
+
+        // Outside the future, nothing is being accounted.
+        assert_eq!(current_task_memory(), None);
+        charge(12345);
+    }
+}
@@ -0,0 +1,193 @@
+use std::{alloc::Layout, ffi::c_void};
+use windows::Win32::System::{
+    Memory::{
+        VirtualAllocExNuma, VirtualFree, MEM_COMMIT, MEM_RELEASE, MEM_RESERVE, PAGE_READWRITE,
+    },
+    Threading::{
+        GetCurrentProcess, GetCurrentProcessorNumberEx, GetNumaHighestNodeNumber,
+        GetNumaProcessorNodeEx,
+    },
+};
+
+/// Determines which NUMA node the memory of each slab of a `PinnedSlabChain` comes from.
+///
+/// On systems with a single NUMA node, all placements are equivalent to `Any`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum NumaPlacement {
+    /// The memory comes from the Rust global allocator, without any preference for a NUMA node.
+    #[default]
+    Any,
+
+    /// The memory of every slab comes from the NUMA node of the processor that the thread creating
+    /// the collection is running on.
+    Local,
+
+    /// The memory of consecutive slabs comes from consecutive NUMA nodes, spreading the collection
+    /// evenly across all the NUMA nodes of the system.
+    Interleaved,
+}
+
+impl NumaPlacement {
+    /// Resolves the placement into the sequence of NUMA nodes to allocate slabs on, based on the
+    /// NUMA topology of the system and the processor the current thread is running on.
+    pub(crate) fn resolve(self) -> NumaNodes {
+        let highest_node = highest_numa_node();
+
+        if highest_node == 0 {
+            // There is nothing to choose from, so we use the regular allocator.
+            return NumaNodes::Any;
+        }
+
+        match self {
+            NumaPlacement::Any => NumaNodes::Any,
+            NumaPlacement::Local => NumaNodes::Fixed(current_numa_node()),
+            NumaPlacement::Interleaved => NumaNodes::Interleaved {
+                next: current_numa_node(),
+                highest: highest_node,
+            },
+        }
+    }
+}
+
+/// A `NumaPlacement` resolved against the NUMA topology of the system.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) enum NumaNodes {
+    #[default]
+    Any,
+    Fixed(u32),
+    Interleaved {
+        next: u32,
+        highest: u32,
+    },
+}
+
+impl NumaNodes {
+    /// The NUMA node to allocate the next slab on, if any.
+    pub(crate) fn next(&mut self) -> Option<u32> {
+        match self {
+            NumaNodes::Any => None,
+            NumaNodes::Fixed(node) => Some(*node),
+            NumaNodes::Interleaved { next, highest } => {
+                let node = *next;
+                *next = if node >= *highest { 0 } else { node + 1 };
+                Some(node)
+            }
+        }
+    }
+}
+
+/// Allocates memory for `layout` from the given NUMA node, or via the Rust global allocator if no
+/// node is given. Returns null if the allocation fails.
+///
+/// # Safety
+///
+/// The layout must have a nonzero size and an alignment no greater than the page size. The memory
+/// must be released via `dealloc_on_numa_node()` with the same layout and node.
+pub(crate) unsafe fn alloc_on_numa_node(layout: Layout, node: Option<u32>) -> *mut u8 {
+    let Some(node) = node else {
+        return std::alloc::alloc(layout);
+    };
+
+    // Virtual memory allocations are always aligned to (at least) the page size.
+    debug_assert!(layout.align() <= PAGE_SIZE);
+
+    VirtualAllocExNuma(
+        GetCurrentProcess(),
+        None,
+        layout.size(),
+        MEM_COMMIT | MEM_RESERVE,
+        PAGE_READWRITE.0,
+        node,
+    ) as *mut u8
+}
+
+/// Releases memory allocated via `alloc_on_numa_node()`.
+///
+/// # Safety
+///
+/// The pointer must have been returned by `alloc_on_numa_node()` with the same layout and node.
+pub(crate) unsafe fn dealloc_on_numa_node(ptr: *mut u8, layout: Layout, node: Option<u32>) {
+    if node.is_none() {
+        std::alloc::dealloc(ptr, layout);
+        return;
+    }
+
+    VirtualFree(ptr as *mut c_void, 0, MEM_RELEASE)
+        .expect("releasing memory that we allocated ourselves must succeed");
+}
+
+/// The highest NUMA node number of the system. Zero means the system has a single NUMA node.
+fn highest_numa_node() -> u32 {
+    let mut highest_node = 0;
+
+    // SAFETY: We pass a valid pointer to a local. If the call fails, we treat the system as having
+    // a single NUMA node, which is always a valid assumption to make.
+    match unsafe { GetNumaHighestNodeNumber(&mut highest_node) } {
+        Ok(()) => highest_node,
+        Err(_) => 0,
+    }
+}
+
+/// The NUMA node of the processor that the current thread is running on.
+fn current_numa_node() -> u32 {
+    let mut node: u16 = 0;
+
+    // SAFETY: We pass valid pointers to locals. If the call fails, we fall back to the first node,
+    // which merely makes the placement suboptimal.
+    unsafe {
+        let processor = GetCurrentProcessorNumberEx();
+
+        if GetNumaProcessorNodeEx(&processor, &mut node).is_err() {
+            return 0;
+        }
+    }
+
+    u32::from(node)
+}
+
+// The smallest page size on all supported platforms.
+const PAGE_SIZE: usize = 4096;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interleaved_wraps_around() {
+        let mut nodes = NumaNodes::Interleaved {
+            next: 1,
+            highest: 2,
+        };
+
+        assert_eq!(nodes.next(), Some(1));
+        assert_eq!(nodes.next(), Some(2));
+        assert_eq!(nodes.next(), Some(0));
+        assert_eq!(nodes.next(), Some(1));
+    }
+
+    #[test]
+    fn alloc_roundtrip() {
+        let layout = Layout::array::<u64>(1024).unwrap();
+
+        for placement in [
+            NumaPlacement::Any,
+            NumaPlacement::Local,
+            NumaPlacement::Interleaved,
+        ] {
+            let mut nodes = placement.resolve();
+            let node = nodes.next();
+
+            // SAFETY: The layout is valid for the allocation functions and we release the memory
+            // with the same layout and node.
+            unsafe {
+                let ptr = alloc_on_numa_node(layout, node);
+                assert!(!ptr.is_null());
+
+                ptr.cast::<u64>().add(1023).write(42);
+                assert_eq!(ptr.cast::<u64>().add(1023).read(), 42);
+
+                dealloc_on_numa_node(ptr, layout, node);
+            }
+        }
+    }
+}
@@ -1,6 +1,6 @@
-use crate::mem::DropPolicy;
+use crate::mem::{alloc_on_numa_node, dealloc_on_numa_node, DropPolicy};
 use core::panic;
-use std::alloc::{alloc, dealloc, Layout};
+use std::alloc::{handle_alloc_error, Layout};
 use std::any::type_name;
 use std::mem::{self, MaybeUninit};
 use std::pin::Pin;
@@ -46,6 +46,10 @@ pub struct PinnedSlab<T, const CAPACITY: usize> {
     count: usize,
 
     drop_policy: DropPolicy,
+
+    /// The NUMA node that the memory of the collection was allocated on, if any. See
+    /// `new_on_numa_node()`.
+    numa_node: Option<u32>,
 }
 
 enum Entry<T> {
@@ -60,8 +64,23 @@ impl<T, const CAPACITY: usize> PinnedSlab<T, CAPACITY> {
     /// do not outlive the slab itself. A drop policy may help safeguard this by panicking if the
     /// slab still contains items when it is dropped.
     pub fn new(drop_policy: DropPolicy) -> Self {
-        // SAFETY: MaybeUninit is a ZST, so its layout is guaranteed to match Entry<T>.
-        let ptr = unsafe { alloc(Self::layout()) as *mut MaybeUninit<Entry<T>> };
+        Self::new_on_numa_node(drop_policy, None)
+    }
+
+    /// Creates a new slab with the specified drop policy, with the memory for the items allocated
+    /// on the given NUMA node. If no node is given, the memory comes from the Rust global
+    /// allocator, same as with `new()`.
+    pub fn new_on_numa_node(drop_policy: DropPolicy, numa_node: Option<u32>) -> Self {
+        // SAFETY: The layout has a nonzero size (entries are never zero-sized) and the alignment
+        // of a plain Rust type. We release the memory in `drop()` with the same layout and node.
+        let ptr = unsafe { alloc_on_numa_node(Self::layout(), numa_node) };
+
+        if ptr.is_null() {
+            handle_alloc_error(Self::layout());
+        }
+
+        // MaybeUninit is a ZST, so its layout is guaranteed to match Entry<T>.
+        let ptr = ptr as *mut MaybeUninit<Entry<T>>;
 
         // Initialize them all to `Vacant` to start with.
         for index in 0..CAPACITY {
@@ -82,6 +101,7 @@ impl<T, const CAPACITY: usize> PinnedSlab<T, CAPACITY> {
             next_free_index: 0,
             count: 0,
             drop_policy,
+            numa_node,
         }
     }
 
@@ -90,6 +110,13 @@ impl<T, const CAPACITY: usize> PinnedSlab<T, CAPACITY> {
             .expect("simple flat array layout must be calculable")
     }
 
+    /// The memory that holds the items of the slab, as a pointer to its first byte and its length
+    /// in bytes. Every item of the slab lies within this region, which stays in place for as long
+    /// as the slab exists.
+    pub fn memory_region(&self) -> (*const u8, usize) {
+        (self.ptr as *const u8, Self::layout().size())
+    }
+
     pub fn len(&self) -> usize {
         self.count
     }
@@ -217,6 +244,24 @@ impl<T, const CAPACITY: usize> PinnedSlab<T, CAPACITY> {
         self.count -= 1;
     }
 
+    /// Iterates over the items in the collection, in order of their indexes.
+    pub fn iter(&self) -> impl Iterator<Item = Pin<&T>> + '_ {
+        (0..CAPACITY).filter_map(move |index| {
+            // SAFETY: The index is in bounds and we ensured in the ctor that every entry is
+            // initialized.
+            match unsafe {
+                self.ptr
+                    .add(index)
+                    .as_ref()
+                    .expect("we expect the resulting pointer to always be valid")
+            } {
+                // SAFETY: Items are always guaranteed pinned in this collection.
+                Entry::Occupied { value } => Some(unsafe { Pin::new_unchecked(value) }),
+                Entry::Vacant { .. } => None,
+            }
+        })
+    }
+
     #[cfg(test)]
     pub fn integrity_check(&self) {
         let mut observed_is_vacant: [Option<bool>; CAPACITY] = [None; CAPACITY];
@@ -303,7 +348,7 @@ impl<T, const CAPACITY: usize> Drop for PinnedSlab<T, CAPACITY> {
                 };
             }
 
-            dealloc(self.ptr as *mut u8, Self::layout());
+            dealloc_on_numa_node(self.ptr as *mut u8, Self::layout(), self.numa_node);
         }
 
         // We do this at the end so we clean up the memory first. Mostly to make Miri happy - since
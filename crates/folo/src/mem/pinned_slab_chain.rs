@@ -1,4 +1,4 @@
-use crate::mem::{DropPolicy, PinnedSlab, PinnedSlabInserter};
+use crate::mem::{DropPolicy, NumaNodes, NumaPlacement, PinnedSlab, PinnedSlabInserter};
 use std::{mem::MaybeUninit, pin::Pin};
 
 /// Links up an arbitrary number of PinnedSlabs into a dynamically sized chain that contains any
@@ -24,21 +24,31 @@ use std::{mem::MaybeUninit, pin::Pin};
 ///
 /// The collection itself does not need to be pinned - only the contents are pinned.
 ///
-/// As of today, the collection never shrinks, though future versions may do so.
+/// The collection only shrinks when explicitly asked to via `shrink_to()`.
 #[derive(Debug)]
 pub struct PinnedSlabChain<T, const SLAB_CAPACITY: usize = 128> {
     /// The slabs in the chain. We use a Vec here to allow for dynamic sizing.
-    /// For now, we only grow the Vec but in theory, one could implement shrinking as well.
     slabs: Vec<PinnedSlab<T, SLAB_CAPACITY>>,
 
     drop_policy: DropPolicy,
+
+    /// The NUMA nodes that new slabs are allocated on.
+    numa_nodes: NumaNodes,
 }
 
 impl<T, const SLAB_CAPACITY: usize> PinnedSlabChain<T, SLAB_CAPACITY> {
     pub fn new(drop_policy: DropPolicy) -> Self {
+        Self::with_numa_placement(drop_policy, NumaPlacement::Any)
+    }
+
+    /// Creates a chain whose slabs are allocated on NUMA nodes chosen according to `placement`.
+    /// The placement is resolved when the chain is created, so `NumaPlacement::Local` refers to the
+    /// NUMA node of the processor that the current thread is running on at that time.
+    pub fn with_numa_placement(drop_policy: DropPolicy, placement: NumaPlacement) -> Self {
         Self {
             slabs: Vec::new(),
             drop_policy,
+            numa_nodes: placement.resolve(),
         }
     }
 
@@ -50,6 +60,48 @@ impl<T, const SLAB_CAPACITY: usize> PinnedSlabChain<T, SLAB_CAPACITY> {
         self.slabs.iter().all(|slab| slab.is_empty())
     }
 
+    /// The number of items the chain can hold without growing.
+    pub fn capacity(&self) -> usize {
+        self.slabs.len() * SLAB_CAPACITY
+    }
+
+    /// Releases empty slabs at the end of the chain, as long as the capacity remains at least
+    /// `min_capacity`. Empty slabs in the middle of the chain are kept, as releasing them would
+    /// change the indexes of the items after them.
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        self.shrink_to_with(min_capacity, |_| {});
+    }
+
+    /// Same as `shrink_to()` but calls `on_release` with the index of every slab right before the
+    /// slab is released (see `slab_index_of()`).
+    pub fn shrink_to_with(&mut self, min_capacity: usize, mut on_release: impl FnMut(usize)) {
+        while self.capacity() >= min_capacity + SLAB_CAPACITY
+            && self.slabs.last().is_some_and(|slab| slab.is_empty())
+        {
+            on_release(self.slabs.len() - 1);
+            self.slabs.pop();
+        }
+    }
+
+    /// The index of the slab that holds the item with the given index.
+    pub fn slab_index_of(&self, index: usize) -> usize {
+        ChainIndex::<SLAB_CAPACITY>::from_whole(index).slab
+    }
+
+    /// The memory that holds the items of the slab with the given index (see `slab_index_of()`),
+    /// as a pointer to its first byte and its length in bytes. The memory stays in place until the
+    /// slab is released by `shrink_to()` or the chain is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the slab index is out of bounds.
+    pub fn slab_memory_region(&self, slab_index: usize) -> (*const u8, usize) {
+        self.slabs
+            .get(slab_index)
+            .map(|slab| slab.memory_region())
+            .expect("slab index was out of bounds of slab chain")
+    }
+
     /// # Panics
     ///
     /// Panics if the index is out of bounds or is not associated with an item.
@@ -112,6 +164,11 @@ impl<T, const SLAB_CAPACITY: usize> PinnedSlabChain<T, SLAB_CAPACITY> {
         slab.remove(index.index_in_slab);
     }
 
+    /// Iterates over the items in the chain, in order of their indexes.
+    pub fn iter(&self) -> impl Iterator<Item = Pin<&T>> + '_ {
+        self.slabs.iter().flat_map(|slab| slab.iter())
+    }
+
     fn index_of_slab_with_vacant_slot(&mut self) -> usize {
         if let Some((index, _)) = self
             .slabs
@@ -121,7 +178,10 @@ impl<T, const SLAB_CAPACITY: usize> PinnedSlabChain<T, SLAB_CAPACITY> {
         {
             index
         } else {
-            self.slabs.push(PinnedSlab::new(self.drop_policy));
+            self.slabs.push(PinnedSlab::new_on_numa_node(
+                self.drop_policy,
+                self.numa_nodes.next(),
+            ));
             self.slabs.len() - 1
         }
     }
@@ -222,6 +282,74 @@ mod tests {
         chain.insert(90);
     }
 
+    #[test]
+    fn iter_skips_removed_items() {
+        let mut chain = PinnedSlabChain::<u32, 3>::new(DropPolicy::MayDropItems);
+
+        let a = chain.insert(42);
+        chain.insert(43);
+        chain.insert(44);
+        chain.insert(45);
+
+        chain.remove(a);
+
+        // Spans both slabs, in index order.
+        assert_eq!(chain.iter().map(|x| *x).collect::<Vec<_>>(), [43, 44, 45]);
+    }
+
+    #[test]
+    fn shrink_to_releases_trailing_empty_slabs() {
+        let mut chain = PinnedSlabChain::<u32, 3>::new(DropPolicy::MayDropItems);
+
+        let indexes = (0..7).map(|i| chain.insert(i)).collect::<Vec<_>>();
+        assert_eq!(chain.capacity(), 9);
+
+        // Empties the 2nd and 3rd slab but keeps an item in the 1st.
+        for index in &indexes[1..] {
+            chain.remove(*index);
+        }
+
+        chain.shrink_to(4);
+        assert_eq!(chain.capacity(), 6);
+
+        chain.shrink_to(0);
+        assert_eq!(chain.capacity(), 3);
+        assert_eq!(*chain.get(indexes[0]), 0);
+    }
+
+    #[test]
+    fn shrink_to_with_reports_released_slabs() {
+        let mut chain = PinnedSlabChain::<u32, 3>::new(DropPolicy::MayDropItems);
+
+        let indexes = (0..7).map(|i| chain.insert(i)).collect::<Vec<_>>();
+        assert_eq!(chain.slab_index_of(indexes[6]), 2);
+
+        for index in &indexes[1..] {
+            chain.remove(*index);
+        }
+
+        let mut released = Vec::new();
+        chain.shrink_to_with(0, |slab_index| released.push(slab_index));
+
+        assert_eq!(released, [2, 1]);
+        assert_eq!(chain.capacity(), 3);
+    }
+
+    #[test]
+    fn slab_memory_region_contains_items() {
+        let mut chain = PinnedSlabChain::<u32, 3>::new(DropPolicy::MayDropItems);
+
+        let indexes = (0..4).map(|i| chain.insert(i)).collect::<Vec<_>>();
+
+        for index in indexes {
+            let (start, len) = chain.slab_memory_region(chain.slab_index_of(index));
+            let item = chain.get(index).get_ref() as *const u32 as *const u8;
+
+            assert!(item >= start);
+            assert!(item < start.wrapping_add(len));
+        }
+    }
+
     #[test]
     #[should_panic]
     fn panic_when_empty_oob_get() {
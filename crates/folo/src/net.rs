@@ -1,6 +1,17 @@
+mod named_pipe;
+mod registered_udp_socket;
+mod socket_io;
 mod tcp_connection;
+mod tcp_listener;
 mod tcp_server;
+mod tcp_stream;
+mod udp_socket;
 pub(crate) mod winsock;
 
+pub use named_pipe::*;
+pub use registered_udp_socket::*;
 pub use tcp_connection::*;
+pub use tcp_listener::*;
 pub use tcp_server::*;
+pub use tcp_stream::*;
+pub use udp_socket::*;
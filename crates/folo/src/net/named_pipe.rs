@@ -0,0 +1,383 @@
+use crate::{
+    io::{self, IoClass, IoOperationKind, PendingOperations, PinnedBuffer},
+    rt::{current_async_agent, spawn_sync, SynchronousTaskType},
+    time::sleep,
+    windows::OwnedHandle,
+};
+use negative_impl::negative_impl;
+use std::{ffi::CString, mem, time::Duration};
+use windows::{
+    core::PCSTR,
+    Win32::{
+        Foundation::{
+            ERROR_BROKEN_PIPE, ERROR_NO_DATA, ERROR_PIPE_BUSY, ERROR_PIPE_CONNECTED, HANDLE,
+            STATUS_PIPE_BROKEN,
+        },
+        Storage::FileSystem::{
+            CreateFileA, ReadFile, WriteFile, FILE_FLAGS_AND_ATTRIBUTES,
+            FILE_FLAG_FIRST_PIPE_INSTANCE, FILE_FLAG_OVERLAPPED, FILE_GENERIC_READ,
+            FILE_GENERIC_WRITE, FILE_SHARE_NONE, OPEN_EXISTING, PIPE_ACCESS_DUPLEX,
+        },
+        System::Pipes::{
+            ConnectNamedPipe, CreateNamedPipeA, DisconnectNamedPipe, PIPE_READMODE_BYTE,
+            PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+        },
+    },
+};
+
+/// Size of the buffers the operating system allocates for data in transit through each instance
+/// of a named pipe, in each direction.
+pub const NAMED_PIPE_BUFFER_SIZE_BYTES: u32 = 64 * 1024;
+
+// How long a client waits before trying again to connect to a pipe whose instances are all busy.
+const PIPE_BUSY_RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The server end of a named pipe, accepting connections from clients on the local machine.
+///
+/// The pipe is bound to the I/O driver of the async worker thread that created the server, so
+/// connections must be accepted on that same thread. The accepted connections are bound to the
+/// I/O driver of the same thread.
+#[derive(Debug)]
+pub struct NamedPipeServer {
+    name: CString,
+
+    // The instance of the pipe that the next client connects to.
+    instance: OwnedHandle<HANDLE>,
+}
+
+impl NamedPipeServer {
+    /// Creates a named pipe with the given name, of the form `\\.\pipe\<name>`, and starts
+    /// accepting connections to it.
+    ///
+    /// Fails if a pipe with the same name already exists, even if it was created by another
+    /// process, so other processes cannot intercept the clients of the pipe.
+    pub fn bind(name: &str) -> io::Result<Self> {
+        let name = CString::new(name)
+            .map_err(|_| io::Error::InvalidOptions("pipe name cannot contain null bytes".into()))?;
+
+        let instance = create_instance(&name, FILE_FLAG_FIRST_PIPE_INSTANCE)?;
+
+        Ok(Self { name, instance })
+    }
+
+    /// Waits for the next client to connect and accepts the connection.
+    ///
+    /// If the returned future is dropped before it completes, the pending accept is cancelled. A
+    /// client that connects after that is left for the next call to accept.
+    ///
+    /// Clients that connect and close the pipe again before they are accepted are skipped.
+    pub async fn accept(&mut self) -> io::Result<NamedPipeConnection> {
+        let instance = *self.instance;
+
+        loop {
+            let mut operation = current_async_agent::with_io(|io| {
+                io.new_operation(PinnedBuffer::from_boxed_slice(Box::default()))
+            });
+            operation.set_handle(&instance);
+            operation.set_kind(IoOperationKind::Accept);
+
+            // SAFETY: We are required to pass the OVERLAPPED pointer to the native I/O function.
+            // We do.
+            let result = unsafe {
+                operation.begin(|_, overlapped, _| {
+                    match ConnectNamedPipe(instance, Some(overlapped)) {
+                        Ok(()) => Ok(()),
+                        // The client connected between the creation of the instance and the call.
+                        // The connection is established and no completion notification is going
+                        // to come.
+                        Err(e) if e.code() == ERROR_PIPE_CONNECTED.into() => Ok(()),
+                        Err(e) => Err(e.into()),
+                    }
+                })
+            }
+            .await;
+
+            match result {
+                Ok(_) => break,
+                // A client connected and already closed its end before we got to it, leaving the
+                // instance holding a dead connection. We drop the connection to make the instance
+                // available again and wait for the next client.
+                Err(io::OperationError {
+                    inner: io::Error::Windows(e),
+                    ..
+                }) if e.code() == ERROR_NO_DATA.into() => {
+                    // SAFETY: The instance is a valid pipe handle that we own.
+                    unsafe { DisconnectNamedPipe(instance) }?;
+                }
+                Err(e) => return Err(e.into_inner()),
+            }
+        }
+
+        // The connected instance is taken by the client, so the next client needs a new one.
+        let next_instance = create_instance(&self.name, FILE_FLAGS_AND_ATTRIBUTES(0))?;
+        let connected_instance = mem::replace(&mut self.instance, next_instance);
+
+        Ok(NamedPipeConnection {
+            pipe: PipeHandle::new(connected_instance),
+        })
+    }
+}
+
+#[negative_impl]
+impl !Send for NamedPipeServer {}
+#[negative_impl]
+impl !Sync for NamedPipeServer {}
+
+/// A connection accepted by a `NamedPipeServer`, for exchanging data with the client.
+///
+/// The connection is bound to the I/O driver of the async worker thread that accepted it, so all
+/// I/O on the connection must happen on that same thread. The client is disconnected when this is
+/// dropped.
+#[derive(Debug)]
+pub struct NamedPipeConnection {
+    pipe: PipeHandle,
+}
+
+impl NamedPipeConnection {
+    /// Reads from the pipe into the active region of the buffer.
+    ///
+    /// The buffer is returned with the active region set to the bytes read. An empty active region
+    /// indicates that the client has closed the pipe.
+    pub async fn read(&self, buffer: PinnedBuffer) -> io::Result<PinnedBuffer> {
+        self.pipe.read(buffer).await
+    }
+
+    /// Writes the active region of the buffer into the pipe.
+    ///
+    /// The buffer is returned with the active region set to the bytes written.
+    pub async fn write(&self, buffer: PinnedBuffer) -> io::Result<PinnedBuffer> {
+        self.pipe.write(buffer).await
+    }
+
+    /// Cancels all reads and writes in flight on the connection and waits for them to complete.
+    ///
+    /// The futures of the affected operations resolve with a cancellation error (see
+    /// `io::Error::is_cancelled()`), unless an operation managed to complete before the
+    /// cancellation took effect.
+    pub async fn cancel_all(&self) -> io::Result<()> {
+        self.pipe.cancel_all().await
+    }
+}
+
+impl io::AsyncRead for NamedPipeConnection {
+    async fn read(&mut self, buffer: PinnedBuffer) -> io::Result<PinnedBuffer> {
+        NamedPipeConnection::read(self, buffer).await
+    }
+}
+
+impl io::AsyncWrite for NamedPipeConnection {
+    async fn write(&mut self, buffer: PinnedBuffer) -> io::Result<PinnedBuffer> {
+        NamedPipeConnection::write(self, buffer).await
+    }
+}
+
+#[negative_impl]
+impl !Send for NamedPipeConnection {}
+#[negative_impl]
+impl !Sync for NamedPipeConnection {}
+
+/// The client end of a named pipe, connected to a `NamedPipeServer` (or any other named pipe
+/// server that supports byte mode).
+///
+/// The pipe is bound to the I/O driver of the async worker thread that connected it, so all I/O on
+/// the pipe must happen on that same thread. The pipe is closed when this is dropped.
+#[derive(Debug)]
+pub struct NamedPipeClient {
+    pipe: PipeHandle,
+}
+
+impl NamedPipeClient {
+    /// Connects to the named pipe with the given name, of the form `\\.\pipe\<name>`.
+    ///
+    /// If all the instances of the pipe are busy with other clients, waits until the server makes
+    /// a new one available. Use `time::timeout()` to limit how long to wait.
+    pub async fn connect(name: &str) -> io::Result<Self> {
+        let name = CString::new(name)
+            .map_err(|_| io::Error::InvalidOptions("pipe name cannot contain null bytes".into()))?;
+
+        let handle = loop {
+            let name = name.clone();
+
+            // Opening the pipe may involve the network for pipes on other machines, so we kick it
+            // off to a synchronous worker thread to avoid blocking the async workers.
+            let result = spawn_sync(SynchronousTaskType::Syscall, move || -> io::Result<_> {
+                // SAFETY: The name is a valid null-terminated string that outlives the call and we
+                // take ownership of the returned handle, closing it when dropped.
+                Ok(unsafe {
+                    OwnedHandle::new(CreateFileA(
+                        PCSTR::from_raw(name.as_ptr() as *const u8),
+                        (FILE_GENERIC_READ | FILE_GENERIC_WRITE).0,
+                        FILE_SHARE_NONE,
+                        None,
+                        OPEN_EXISTING,
+                        FILE_FLAG_OVERLAPPED,
+                        None,
+                    )?)
+                })
+            })
+            .await;
+
+            match result {
+                Ok(handle) => break handle,
+                Err(io::Error::Windows(e)) if e.code() == ERROR_PIPE_BUSY.into() => {
+                    sleep(PIPE_BUSY_RETRY_INTERVAL).await;
+                }
+                Err(e) => return Err(e),
+            }
+        };
+
+        current_async_agent::with_io(|io| io.bind_io_primitive(&*handle, IoClass::Other))?;
+
+        Ok(Self {
+            pipe: PipeHandle::new(handle),
+        })
+    }
+
+    /// Reads from the pipe into the active region of the buffer.
+    ///
+    /// The buffer is returned with the active region set to the bytes read. An empty active region
+    /// indicates that the server has closed the pipe.
+    pub async fn read(&self, buffer: PinnedBuffer) -> io::Result<PinnedBuffer> {
+        self.pipe.read(buffer).await
+    }
+
+    /// Writes the active region of the buffer into the pipe.
+    ///
+    /// The buffer is returned with the active region set to the bytes written.
+    pub async fn write(&self, buffer: PinnedBuffer) -> io::Result<PinnedBuffer> {
+        self.pipe.write(buffer).await
+    }
+
+    /// Cancels all reads and writes in flight on the pipe and waits for them to complete.
+    ///
+    /// The futures of the affected operations resolve with a cancellation error (see
+    /// `io::Error::is_cancelled()`), unless an operation managed to complete before the
+    /// cancellation took effect.
+    pub async fn cancel_all(&self) -> io::Result<()> {
+        self.pipe.cancel_all().await
+    }
+}
+
+impl io::AsyncRead for NamedPipeClient {
+    async fn read(&mut self, buffer: PinnedBuffer) -> io::Result<PinnedBuffer> {
+        NamedPipeClient::read(self, buffer).await
+    }
+}
+
+impl io::AsyncWrite for NamedPipeClient {
+    async fn write(&mut self, buffer: PinnedBuffer) -> io::Result<PinnedBuffer> {
+        NamedPipeClient::write(self, buffer).await
+    }
+}
+
+#[negative_impl]
+impl !Send for NamedPipeClient {}
+#[negative_impl]
+impl !Sync for NamedPipeClient {}
+
+/// Either end of a connected named pipe, bound to the I/O driver of the current thread.
+#[derive(Debug)]
+struct PipeHandle {
+    handle: OwnedHandle<HANDLE>,
+
+    // Reads and writes in flight on the pipe, which `cancel_all()` waits for.
+    pending: PendingOperations,
+}
+
+impl PipeHandle {
+    fn new(handle: OwnedHandle<HANDLE>) -> Self {
+        Self {
+            handle,
+            pending: PendingOperations::new(),
+        }
+    }
+
+    async fn read(&self, buffer: PinnedBuffer) -> io::Result<PinnedBuffer> {
+        let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+        operation.track(&self.pending);
+        operation.set_handle(&*self.handle);
+        operation.set_kind(IoOperationKind::Read);
+
+        // SAFETY: We are required to pass the OVERLAPPED pointer to the native I/O function. We do.
+        match unsafe {
+            operation
+                .begin(|buffer, overlapped, immediate_bytes_transferred| {
+                    Ok(ReadFile(
+                        *self.handle,
+                        Some(buffer),
+                        Some(immediate_bytes_transferred as *mut _),
+                        Some(overlapped),
+                    )?)
+                })
+                .await
+        } {
+            Ok(buffer) => Ok(buffer),
+            // Depending on whether the other end goes away before or during the read, we get the
+            // closing of the pipe reported either as a Win32 error or as an NTSTATUS.
+            Err(io::OperationError {
+                inner: io::Error::Windows(external),
+                mut buffer,
+            }) if external.code() == ERROR_BROKEN_PIPE.into()
+                || external.code() == STATUS_PIPE_BROKEN.into() =>
+            {
+                buffer.set_len(0);
+                Ok(buffer)
+            }
+            Err(e) => Err(e.into_inner()),
+        }
+    }
+
+    async fn write(&self, buffer: PinnedBuffer) -> io::Result<PinnedBuffer> {
+        let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+        operation.track(&self.pending);
+        operation.set_handle(&*self.handle);
+        operation.set_kind(IoOperationKind::Write);
+
+        // SAFETY: We are required to pass the OVERLAPPED pointer to the native I/O function. We do.
+        unsafe {
+            operation.begin(|buffer, overlapped, immediate_bytes_transferred| {
+                Ok(WriteFile(
+                    *self.handle,
+                    Some(buffer),
+                    Some(immediate_bytes_transferred as *mut _),
+                    Some(overlapped),
+                )?)
+            })
+        }
+        .await
+        .map_err(io::OperationError::into_inner)
+    }
+
+    async fn cancel_all(&self) -> io::Result<()> {
+        self.pending.cancel_all(*self.handle).await
+    }
+}
+
+/// Creates a new instance of the named pipe for the next client to connect to, bound to the I/O
+/// driver of the current thread.
+fn create_instance(
+    name: &CString,
+    extra_flags: FILE_FLAGS_AND_ATTRIBUTES,
+) -> io::Result<OwnedHandle<HANDLE>> {
+    // Creating a pipe does not touch any storage, so unlike opening files we do not bother
+    // offloading this to a synchronous worker thread.
+    //
+    // SAFETY: The name is a valid null-terminated string that outlives the call and we take
+    // ownership of the returned handle, closing it when dropped.
+    let instance = unsafe {
+        OwnedHandle::new(CreateNamedPipeA(
+            PCSTR::from_raw(name.as_ptr() as *const u8),
+            PIPE_ACCESS_DUPLEX | FILE_FLAG_OVERLAPPED | extra_flags,
+            PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+            PIPE_UNLIMITED_INSTANCES,
+            NAMED_PIPE_BUFFER_SIZE_BYTES,
+            NAMED_PIPE_BUFFER_SIZE_BYTES,
+            0,
+            None,
+        )?)
+    };
+
+    current_async_agent::with_io(|io| io.bind_io_primitive(&*instance, IoClass::Other))?;
+
+    Ok(instance)
+}
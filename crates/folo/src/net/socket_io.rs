@@ -0,0 +1,239 @@
+//! Data transfers on connected sockets, shared by the socket types that support them.
+//!
+//! The socket must be bound to the I/O driver of the current async worker thread. Each operation
+//! is tracked in `pending`, so the owner of the socket can cancel it via `cancel_all()`.
+
+use crate::{
+    io::{self, IoOperationKind, OperationResultFuture, PendingOperations, PinnedBuffer},
+    net::winsock,
+    rt::current_async_agent,
+};
+use std::{any::Any, cell::RefCell, iter, mem, rc::Rc};
+use windows::{
+    core::PSTR,
+    Win32::{
+        Networking::WinSock::{WSARecv, WSASend, SOCKET, WSABUF},
+        System::IO::OVERLAPPED,
+    },
+};
+
+/// Receives the next bytes from the peer into the active region of the buffer.
+pub(super) fn receive(
+    socket: SOCKET,
+    pending: &PendingOperations,
+    buffer: PinnedBuffer,
+) -> OperationResultFuture {
+    let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+    operation.track(pending);
+    operation.set_handle(&socket);
+    operation.set_kind(IoOperationKind::Read);
+
+    // SAFETY: We are required to pass the OVERLAPPED pointer to the completion routine. We do.
+    unsafe {
+        operation.begin(|buffer, overlapped, immediate_bytes_transferred| {
+            let wsabufs = [WSABUF {
+                len: buffer.len() as u32,
+                buf: PSTR::from_raw(buffer.as_mut_ptr()),
+            }];
+
+            let mut flags: u32 = 0;
+
+            winsock::to_io_result(WSARecv(
+                socket,
+                &wsabufs,
+                Some(immediate_bytes_transferred as *mut u32),
+                &mut flags as *mut u32,
+                Some(overlapped),
+                None,
+            ))
+        })
+    }
+}
+
+/// Sends the active region of the buffer to the peer.
+pub(super) fn send(
+    socket: SOCKET,
+    pending: &PendingOperations,
+    buffer: PinnedBuffer,
+) -> OperationResultFuture {
+    let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+    operation.track(pending);
+    operation.set_handle(&socket);
+    operation.set_kind(IoOperationKind::Write);
+
+    // SAFETY: We are required to pass the OVERLAPPED pointer to the completion routine. We do.
+    unsafe {
+        operation.begin(|buffer, overlapped, immediate_bytes_transferred| {
+            let wsabufs = [WSABUF {
+                len: buffer.len() as u32,
+                buf: PSTR::from_raw(buffer.as_mut_ptr()),
+            }];
+
+            winsock::to_io_result(WSASend(
+                socket,
+                &wsabufs,
+                Some(immediate_bytes_transferred as *mut u32),
+                0,
+                Some(overlapped),
+                None,
+            ))
+        })
+    }
+}
+
+/// Receives the next bytes from the peer into the active regions of the buffers as one operation,
+/// filling each buffer before moving on to the next. Returns the total number of bytes received.
+pub(super) async fn receive_vectored(
+    socket: SOCKET,
+    pending: &PendingOperations,
+    buffers: &mut [PinnedBuffer],
+) -> io::Result<usize> {
+    // SAFETY: We are required to pass the OVERLAPPED pointer to the native API. We do.
+    unsafe {
+        transfer_vectored(
+            socket,
+            pending,
+            IoOperationKind::Read,
+            buffers,
+            None,
+            |wsabufs, overlapped, bytes_transferred| {
+                let mut flags: u32 = 0;
+
+                winsock::to_io_result(WSARecv(
+                    socket,
+                    wsabufs,
+                    Some(bytes_transferred as *mut u32),
+                    &mut flags as *mut u32,
+                    Some(overlapped),
+                    None,
+                ))
+            },
+        )
+    }
+    .await
+}
+
+/// Sends the active regions of the buffers to the peer as one operation, in order. Returns the
+/// total number of bytes sent.
+pub(super) async fn send_vectored(
+    socket: SOCKET,
+    pending: &PendingOperations,
+    buffers: &mut [PinnedBuffer],
+) -> io::Result<usize> {
+    // SAFETY: We are required to pass the OVERLAPPED pointer to the native API. We do.
+    unsafe {
+        transfer_vectored(
+            socket,
+            pending,
+            IoOperationKind::Write,
+            buffers,
+            None,
+            |wsabufs, overlapped, bytes_transferred| {
+                winsock::to_io_result(WSASend(
+                    socket,
+                    wsabufs,
+                    Some(bytes_transferred as *mut u32),
+                    0,
+                    Some(overlapped),
+                    None,
+                ))
+            },
+        )
+    }
+    .await
+}
+
+/// Performs a vectored operation on the socket, handing the active regions of all the buffers
+/// to the native API via the callback, which receives the same arguments as the callback of
+/// `Operation::begin()`, except for the buffers being described by the `WSABUF` list.
+///
+/// When this returns, the active region of each buffer is set to the bytes transferred to or from
+/// it. Buffers that the operation did not reach are left with an empty active region. The caller
+/// gets the buffers back even if the operation fails.
+///
+/// If `keep_alive` is set, the operation keeps the value alive until it completes (see
+/// `Operation::keep_alive()`).
+///
+/// # Safety
+///
+/// The callback must call a native I/O API with the OVERLAPPED pointer it receives.
+pub(super) async unsafe fn transfer_vectored<F>(
+    socket: SOCKET,
+    pending: &PendingOperations,
+    kind: IoOperationKind,
+    buffers: &mut [PinnedBuffer],
+    keep_alive: Option<Rc<dyn Any>>,
+    f: F,
+) -> io::Result<usize>
+where
+    F: FnOnce(&[WSABUF], *mut OVERLAPPED, &mut u32) -> io::Result<()>,
+{
+    if buffers.is_empty() {
+        return Ok(0);
+    }
+
+    // We need to hand ownership of the buffers to the I/O driver, so we temporarily leave empty
+    // buffers in their place. An empty boxed slice does not allocate.
+    let mut taken = buffers
+        .iter_mut()
+        .map(|slot| mem::replace(slot, PinnedBuffer::from_boxed_slice(Box::default())));
+
+    let primary = taken
+        .next()
+        .expect("we checked above that there is at least one buffer");
+    let mut extra = taken.collect::<Vec<_>>();
+
+    // The storage of a buffer does not move, so we can describe the extra buffers to the OS
+    // before we hand them over to the operation.
+    let extra_wsabufs = extra
+        .iter_mut()
+        .map(|buffer| WSABUF {
+            len: buffer.len() as u32,
+            buf: PSTR::from_raw(buffer.as_mut_slice().as_mut_ptr()),
+        })
+        .collect::<Vec<_>>();
+
+    let extra = Rc::new(RefCell::new(extra));
+
+    let mut operation = current_async_agent::with_io(|io| io.new_operation(primary));
+    operation.track(pending);
+    operation.set_handle(&socket);
+    operation.set_kind(kind);
+    operation.set_extra_buffers(Rc::clone(&extra));
+
+    if let Some(value) = keep_alive {
+        operation.keep_alive(value);
+    }
+
+    let result = operation
+        .begin(|buffer, overlapped, immediate_bytes_transferred| {
+            let wsabufs = iter::once(WSABUF {
+                len: buffer.len() as u32,
+                buf: PSTR::from_raw(buffer.as_mut_ptr()),
+            })
+            .chain(extra_wsabufs.iter().copied())
+            .collect::<Vec<_>>();
+
+            f(&wsabufs, overlapped, immediate_bytes_transferred)
+        })
+        .await;
+
+    // Whatever the outcome, the caller gets their buffers back.
+    let (primary, result) = match result {
+        Ok(buffer) => (buffer, Ok(())),
+        Err(e) => {
+            let (inner, buffer) = e.into_inner_and_buffer();
+            (buffer, Err(inner))
+        }
+    };
+
+    buffers[0] = primary;
+
+    for (slot, buffer) in buffers[1..].iter_mut().zip(extra.take()) {
+        *slot = buffer;
+    }
+
+    result?;
+
+    Ok(buffers.iter().map(PinnedBuffer::len).sum())
+}
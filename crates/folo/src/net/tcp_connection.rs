@@ -1,14 +1,14 @@
 use crate::{
-    io::{self, OperationResultExt, OperationResultFuture, PinnedBuffer},
-    net::winsock,
-    rt::{current_async_agent, current_runtime, SynchronousTaskType},
+    io::{self, OperationResultExt, OperationResultFuture, PendingOperations, PinnedBuffer},
+    net::{socket_io, winsock},
+    rt::{current_runtime, SynchronousTaskType},
     windows::OwnedHandle,
 };
 use negative_impl::negative_impl;
 use std::sync::Arc;
-use windows::{
-    core::PSTR,
-    Win32::Networking::WinSock::{WSARecv, WSASend, WSASendDisconnect, SOCKET, WSABUF},
+use windows::Win32::{
+    Foundation::HANDLE,
+    Networking::WinSock::{WSASendDisconnect, SOCKET},
 };
 
 #[derive(Debug)]
@@ -16,6 +16,9 @@ pub struct TcpConnection {
     // This is an Arc because some operations (e.g. shutdown) involve synchronous logic and
     // therefore we must share the socket between multiple threads.
     pub(super) socket: Arc<OwnedHandle<SOCKET>>,
+
+    // Receives and sends in flight on the socket, which `cancel_all()` waits for.
+    pub(super) pending: PendingOperations,
 }
 
 impl TcpConnection {
@@ -27,29 +30,7 @@ impl TcpConnection {
     /// You should not call this multiple times concurrently because there is no guarantee that the
     /// continuations will be called in a particular order.
     pub fn receive(&mut self, buffer: PinnedBuffer) -> OperationResultFuture {
-        // SAFETY: We are required to pass the OVERLAPPED pointer to the completion routine. We do.
-        unsafe {
-            current_async_agent::with_io(|io| io.new_operation(buffer)).begin(
-                |buffer, overlapped, immediate_bytes_transferred| {
-                    let wsabuf = WSABUF {
-                        len: buffer.len() as u32,
-                        buf: PSTR::from_raw(buffer.as_mut_ptr()),
-                    };
-
-                    let wsabufs = [wsabuf];
-                    let mut flags: u32 = 0;
-
-                    winsock::to_io_result(WSARecv(
-                        **self.socket,
-                        &wsabufs,
-                        Some(immediate_bytes_transferred as *mut u32),
-                        &mut flags as *mut u32,
-                        Some(overlapped),
-                        None,
-                    ))
-                },
-            )
-        }
+        socket_io::receive(**self.socket, &self.pending, buffer)
     }
 
     /// Sends a buffer of data to the peer.
@@ -59,28 +40,25 @@ impl TcpConnection {
     /// You may call this multiple times concurrently. The buffers will be sent in the order they
     /// are submitted.
     pub fn send(&mut self, buffer: PinnedBuffer) -> OperationResultFuture {
-        // SAFETY: We are required to pass the OVERLAPPED pointer to the completion routine. We do.
-        unsafe {
-            current_async_agent::with_io(|io| io.new_operation(buffer)).begin(
-                |buffer, overlapped, immediate_bytes_transferred| {
-                    let wsabuf = WSABUF {
-                        len: buffer.len() as u32,
-                        buf: PSTR::from_raw(buffer.as_mut_ptr()),
-                    };
+        socket_io::send(**self.socket, &self.pending, buffer)
+    }
 
-                    let wsabufs = [wsabuf];
+    /// Receives the next buffer of data into the active regions of the buffers as one operation,
+    /// filling each buffer before moving on to the next.
+    ///
+    /// When the call returns, the active region of each buffer is set to the bytes read into it.
+    /// Buffers that the data did not reach are left with an empty active region. Returns the total
+    /// number of bytes read, with 0 indicating that the connection was closed.
+    pub async fn receive_vectored(&mut self, buffers: &mut [PinnedBuffer]) -> io::Result<usize> {
+        socket_io::receive_vectored(**self.socket, &self.pending, buffers).await
+    }
 
-                    winsock::to_io_result(WSASend(
-                        **self.socket,
-                        &wsabufs,
-                        Some(immediate_bytes_transferred as *mut u32),
-                        0,
-                        Some(overlapped),
-                        None,
-                    ))
-                },
-            )
-        }
+    /// Sends the active regions of the buffers to the peer as one operation, in order.
+    ///
+    /// When the call returns, the active region of each buffer is set to the bytes sent from it.
+    /// Returns the total number of bytes sent.
+    pub async fn send_vectored(&mut self, buffers: &mut [PinnedBuffer]) -> io::Result<usize> {
+        socket_io::send_vectored(**self.socket, &self.pending, buffers).await
     }
 
     /// Performs a graceful shutdown of the connection, allowing time for all pending data transfers
@@ -115,6 +93,21 @@ impl TcpConnection {
 
         Ok(())
     }
+
+    /// Cancels all receives and sends in flight on the connection and waits for them to complete.
+    ///
+    /// The futures of the affected operations resolve with a cancellation error (see
+    /// `io::Error::is_cancelled()`), unless an operation managed to complete before the
+    /// cancellation took effect, in which case it resolves with its regular result. When this
+    /// returns, the operating system is done with the buffers of all the operations.
+    ///
+    /// The connection is not usable for further data transfers after this, as data may have been
+    /// lost in transit. Drop it once you are done with the results of the cancelled operations.
+    pub async fn cancel_all(&mut self) -> io::Result<()> {
+        self.pending
+            .cancel_all(HANDLE(self.socket.0 as *mut _))
+            .await
+    }
 }
 
 #[negative_impl]
@@ -0,0 +1,175 @@
+use crate::{
+    io::{self, IoClass, IoOperationKind, PinnedBuffer},
+    net::{
+        tcp_server::PENDING_CONNECTION_LIMIT,
+        winsock::{self, SocketAddress},
+        TcpStream,
+    },
+    rt::{current_async_agent, spawn_sync, SynchronousTaskType},
+    windows::OwnedHandle,
+};
+use futures::{stream, Stream};
+use negative_impl::negative_impl;
+use std::{mem, net::SocketAddr};
+use windows::Win32::Networking::WinSock::{
+    bind, listen, setsockopt, AcceptEx, WSASocketA, IPPROTO_TCP, SOCKADDR_IN6, SOCKET, SOCK_STREAM,
+    SOL_SOCKET, SO_UPDATE_ACCEPT_CONTEXT, WSA_FLAG_OVERLAPPED,
+};
+
+// AcceptEx writes the local and remote address into the buffer, each of which requires 16 bytes
+// more than the largest address structure of the protocol.
+const ADDRESS_LENGTH: usize = mem::size_of::<SOCKADDR_IN6>() + 16;
+
+/// Listens for incoming TCP connections on a local address.
+///
+/// The listening socket is bound to the I/O driver of the async worker thread that created the
+/// listener, so connections must be accepted on that same thread. The accepted connections are
+/// bound to the I/O driver of the same thread.
+#[derive(Debug)]
+pub struct TcpListener {
+    socket: OwnedHandle<SOCKET>,
+
+    // The address the socket is bound to, with the actual port if port 0 was requested.
+    local_addr: SocketAddr,
+}
+
+impl TcpListener {
+    /// Starts listening for incoming connections on the local address. Use port 0 to let the OS
+    /// pick a free port, which you can look up via `local_addr()`.
+    pub async fn bind(addr: SocketAddr) -> io::Result<Self> {
+        winsock::ensure_initialized();
+
+        // Creating and configuring the socket are blocking operations, so we kick them off to a
+        // synchronous worker thread to avoid blocking the async workers with these slow calls.
+        let (socket, local_addr) = spawn_sync(SynchronousTaskType::Syscall, move || {
+            let addr = SocketAddress::new(addr);
+
+            // SAFETY: We are required to close the handle once we are done with it,
+            // which we do via OwnedHandle that closes the handle on drop.
+            let socket = unsafe { create_socket(&addr)? };
+
+            // SAFETY: All we need to be concerned about is passing in valid arguments, which we do.
+            unsafe {
+                winsock::to_io_result(bind(*socket, addr.as_ptr(), addr.len()))?;
+
+                // A raw value for the queue length must be wrapped in the SOMAXCONN_HINT macro,
+                // which really is just negation - a negative value means "use the absolute value".
+                winsock::to_io_result(listen(*socket, -PENDING_CONNECTION_LIMIT))?;
+            }
+
+            let local_addr = SocketAddress::local_of(*socket)?.to_socket_addr();
+
+            io::Result::Ok((socket, local_addr))
+        })
+        .await?;
+
+        current_async_agent::with_io(|io| io.bind_io_primitive(&*socket, IoClass::Network))?;
+
+        Ok(Self { socket, local_addr })
+    }
+
+    /// The local address that the listener is bound to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Waits for the next incoming connection and accepts it.
+    ///
+    /// If the returned future is dropped before it completes, the pending accept is cancelled. A
+    /// connection that arrives after that is left for the next call to accept.
+    pub async fn accept(&mut self) -> io::Result<TcpStream> {
+        let family = SocketAddress::new(self.local_addr);
+
+        // AcceptEx takes a socket created in advance to accept the connection on. Creating it is
+        // a blocking operation, so we do it on a synchronous worker thread.
+        let accept_socket = spawn_sync(SynchronousTaskType::Syscall, move || {
+            // SAFETY: We are required to close the handle once we are done with it,
+            // which we do via OwnedHandle that closes the handle on drop.
+            unsafe { create_socket(&family) }
+        })
+        .await?;
+
+        let buffer = PinnedBuffer::from_pool();
+        assert!(buffer.len() >= ADDRESS_LENGTH * 2);
+
+        let listen_socket = *self.socket;
+        let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+        operation.set_handle(&listen_socket);
+        operation.set_kind(IoOperationKind::Accept);
+
+        // SAFETY: We are required to pass the OVERLAPPED pointer to the native I/O function. We do.
+        unsafe {
+            operation.begin(|buffer, overlapped, immediate_bytes_transferred| {
+                // We do not receive any data with the connection, so the buffer only receives the
+                // local and remote address.
+                if AcceptEx(
+                    listen_socket,
+                    *accept_socket,
+                    buffer.as_mut_ptr() as *mut _,
+                    0,
+                    ADDRESS_LENGTH as u32,
+                    ADDRESS_LENGTH as u32,
+                    immediate_bytes_transferred,
+                    overlapped,
+                )
+                .as_bool()
+                {
+                    Ok(())
+                } else {
+                    // AcceptEx sets ERROR_IO_PENDING when waiting for a connection, which we
+                    // detect via the regular GetLastError.
+                    Err(windows::core::Error::from_win32().into())
+                }
+            })
+        }
+        .await
+        .map_err(io::OperationError::into_inner)?;
+
+        // Until this is set, the accepted socket does not inherit the properties of the listening
+        // socket and functions like getpeername() and shutdown() fail on it.
+        // SAFETY: All we need to be concerned about is passing in valid arguments, which we do.
+        winsock::to_io_result(unsafe {
+            setsockopt(
+                *accept_socket,
+                SOL_SOCKET,
+                SO_UPDATE_ACCEPT_CONTEXT,
+                Some(&listen_socket.0.to_ne_bytes()),
+            )
+        })?;
+
+        current_async_agent::with_io(|io| io.bind_io_primitive(&*accept_socket, IoClass::Network))?;
+
+        Ok(TcpStream::from_connected_socket(accept_socket))
+    }
+
+    /// Returns a stream of incoming connections, accepting each one as the stream is polled for
+    /// it (see `accept()`). The stream never ends - errors are yielded in place of connections and
+    /// accepting continues after them.
+    pub fn incoming(&mut self) -> impl Stream<Item = io::Result<TcpStream>> + '_ {
+        stream::unfold(self, |listener| async move {
+            let connection = listener.accept().await;
+            Some((connection, listener))
+        })
+    }
+}
+
+#[negative_impl]
+impl !Send for TcpListener {}
+#[negative_impl]
+impl !Sync for TcpListener {}
+
+/// Creates an overlapped TCP socket for the address family of the address.
+///
+/// # Safety
+///
+/// The caller is responsible for closing the socket, which `OwnedHandle` does on drop.
+unsafe fn create_socket(addr: &SocketAddress) -> io::Result<OwnedHandle<SOCKET>> {
+    Ok(OwnedHandle::new(WSASocketA(
+        addr.family().0 as i32,
+        SOCK_STREAM.0,
+        IPPROTO_TCP.0,
+        None,
+        0,
+        WSA_FLAG_OVERLAPPED,
+    )?))
+}
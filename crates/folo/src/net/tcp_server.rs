@@ -1,5 +1,5 @@
 use crate::{
-    io::{self, OperationResultSharedExt},
+    io::{self, IoClass, IoOperationKind, OperationResultSharedExt, PendingOperations},
     net::{winsock, TcpConnection},
     rt::{current_async_agent, current_runtime, spawn, RemoteJoinHandle, SynchronousTaskType},
     windows::OwnedHandle,
@@ -147,7 +147,8 @@ where
 
         // Bind the socket to the I/O completion port so we can process I/O completions.
         current_async_agent::with_io_shared(|io| {
-            io.bind_io_primitive(&*listen_socket).unwrap();
+            io.bind_io_primitive(&*listen_socket, IoClass::Network)
+                .unwrap();
         });
 
         event!(Level::TRACE, "created TCP socket for accepting connections");
@@ -223,7 +224,7 @@ impl !Sync for TcpServerHandle {}
 const CONCURRENT_ACCEPT_OPERATIONS_PER_DISPATCHER: usize = 8;
 
 // The default assigned by the OS seems to be around 128, which is not enough under high load.
-const PENDING_CONNECTION_LIMIT: i32 = 4096;
+pub(super) const PENDING_CONNECTION_LIMIT: i32 = 4096;
 
 /// The TCP dispatcher manages the listen socket used to receive new connections. When a new
 /// connection is received, it is dispatched to be handled by the user-defined callback on a
@@ -331,6 +332,15 @@ where
                 ?accept_result
             );
 
+            // Once the runtime starts shutting down, no accept can succeed anymore.
+            if matches!(accept_result, Err(io::Error::ShuttingDown)) {
+                event!(
+                    Level::DEBUG,
+                    "TCP dispatcher stopping due to runtime shutdown"
+                );
+                return;
+            }
+
             let Ok(connection_socket) = accept_result else {
                 event!(
                     Level::ERROR,
@@ -347,11 +357,13 @@ where
             // We spawn it on the same async worker that caught the connection.
             _ = spawn(async move {
                 current_async_agent::with_io(|io| {
-                    io.bind_io_primitive(&*connection_socket).unwrap()
+                    io.bind_io_primitive(&*connection_socket, IoClass::Network)
+                        .unwrap()
                 });
 
                 let tcp_connection = TcpConnection {
                     socket: Arc::new(connection_socket),
+                    pending: PendingOperations::new(),
                 };
 
                 _ = (on_accept_clone)(tcp_connection).await;
@@ -427,7 +439,10 @@ impl AcceptOne {
         // NOTE: This is an operation on the **listen socket**, not on the connection socekt, so it
         // is bound to the completion port of the listen socket. Note that we have not yet bound the
         // connection socket to any completion port.
-        let accept_operation = current_async_agent::with_io_shared(|io| io.new_operation(buffer));
+        let mut accept_operation =
+            current_async_agent::with_io_shared(|io| io.new_operation(buffer));
+        accept_operation.set_handle(&**self.listen_socket);
+        accept_operation.set_kind(IoOperationKind::Accept);
 
         event!(Level::TRACE, "waiting for incoming connection to arrive");
 
@@ -0,0 +1,212 @@
+use crate::{
+    io::{self, IoClass, IoOperationKind, PendingOperations, PinnedBuffer},
+    net::{
+        socket_io,
+        winsock::{self, SocketAddress},
+    },
+    rt::{current_async_agent, spawn_sync, SynchronousTaskType},
+    windows::OwnedHandle,
+};
+use negative_impl::negative_impl;
+use std::{ffi::c_void, mem, net::SocketAddr, ptr};
+use windows::Win32::{
+    Foundation::HANDLE,
+    Networking::WinSock::{
+        bind, setsockopt, WSAIoctl, WSASocketA, IPPROTO_TCP, LPFN_CONNECTEX,
+        SIO_GET_EXTENSION_FUNCTION_POINTER, SOCKET, SOCK_STREAM, SOL_SOCKET,
+        SO_UPDATE_CONNECT_CONTEXT, WSAID_CONNECTEX, WSA_FLAG_OVERLAPPED,
+    },
+};
+
+/// A TCP connection to a remote peer, on which asynchronous reads and writes can be performed.
+///
+/// The socket is bound to the I/O driver of the async worker thread that created the stream, so
+/// all I/O on the stream must happen on that same thread.
+#[derive(Debug)]
+pub struct TcpStream {
+    socket: OwnedHandle<SOCKET>,
+
+    // Reads and writes in flight on the socket, which `cancel_all()` waits for.
+    pending: PendingOperations,
+}
+
+impl TcpStream {
+    /// Opens a TCP connection to the remote address.
+    pub async fn connect(addr: SocketAddr) -> io::Result<Self> {
+        winsock::ensure_initialized();
+
+        let remote_addr = SocketAddress::new(addr);
+
+        // Creating the socket and looking up the connect function are blocking operations, so we
+        // kick them off to a synchronous worker thread to avoid blocking the async workers.
+        let (socket, connect_ex) = spawn_sync(SynchronousTaskType::Syscall, move || {
+            // SAFETY: We are required to close the handle once we are done with it,
+            // which we do via OwnedHandle that closes the handle on drop.
+            let socket = unsafe {
+                OwnedHandle::new(WSASocketA(
+                    remote_addr.family().0 as i32,
+                    SOCK_STREAM.0,
+                    IPPROTO_TCP.0,
+                    None,
+                    0,
+                    WSA_FLAG_OVERLAPPED,
+                )?)
+            };
+
+            // ConnectEx requires the socket to be bound first. We let the OS pick the local
+            // address and port, as a regular connect would.
+            let local_addr = SocketAddress::unspecified_like(addr);
+
+            // SAFETY: All we need to be concerned about is passing in valid arguments, which we do.
+            winsock::to_io_result(unsafe { bind(*socket, local_addr.as_ptr(), local_addr.len()) })?;
+
+            let connect_ex = connect_ex_fn(*socket)?;
+
+            io::Result::Ok((socket, connect_ex))
+        })
+        .await?;
+
+        current_async_agent::with_io(|io| io.bind_io_primitive(&*socket, IoClass::Network))?;
+
+        let mut operation = current_async_agent::with_io(|io| {
+            io.new_operation(PinnedBuffer::from_boxed_slice(Box::default()))
+        });
+        operation.set_handle(&*socket);
+        operation.set_kind(IoOperationKind::Connect);
+
+        // SAFETY: We are required to pass the OVERLAPPED pointer to the completion routine. We do.
+        unsafe {
+            operation.begin(|_, overlapped, immediate_bytes_transferred| {
+                let connect_ex =
+                    connect_ex.expect("ConnectEx is always present if the lookup succeeded");
+
+                if connect_ex(
+                    *socket,
+                    remote_addr.as_ptr(),
+                    remote_addr.len(),
+                    ptr::null(),
+                    0,
+                    immediate_bytes_transferred,
+                    overlapped,
+                )
+                .as_bool()
+                {
+                    Ok(())
+                } else {
+                    // ConnectEx sets ERROR_IO_PENDING when the connection is being established
+                    // asynchronously, which we detect via the regular GetLastError.
+                    Err(windows::core::Error::from_win32().into())
+                }
+            })
+        }
+        .await
+        .map_err(io::OperationError::into_inner)?;
+
+        // Until this is set, the socket does not know it is connected and functions like
+        // getpeername() and shutdown() fail on it.
+        // SAFETY: All we need to be concerned about is passing in valid arguments, which we do.
+        winsock::to_io_result(unsafe {
+            setsockopt(*socket, SOL_SOCKET, SO_UPDATE_CONNECT_CONTEXT, None)
+        })?;
+
+        Ok(Self::from_connected_socket(socket))
+    }
+
+    /// Wraps a connected socket that is already bound to the I/O driver of the current thread.
+    pub(super) fn from_connected_socket(socket: OwnedHandle<SOCKET>) -> Self {
+        Self {
+            socket,
+            pending: PendingOperations::new(),
+        }
+    }
+
+    /// Reads the next bytes received from the peer into the active region of the buffer.
+    ///
+    /// The buffer is returned with the active region set to the bytes read. An empty active region
+    /// indicates that the peer has closed the connection.
+    pub async fn read(&mut self, buffer: PinnedBuffer) -> io::Result<PinnedBuffer> {
+        socket_io::receive(*self.socket, &self.pending, buffer)
+            .await
+            .map_err(io::OperationError::into_inner)
+    }
+
+    /// Writes the active region of the buffer to the peer.
+    ///
+    /// The buffer is returned with the active region set to the bytes written, to allow reuse.
+    pub async fn write(&mut self, buffer: PinnedBuffer) -> io::Result<PinnedBuffer> {
+        socket_io::send(*self.socket, &self.pending, buffer)
+            .await
+            .map_err(io::OperationError::into_inner)
+    }
+
+    /// Reads the next bytes received from the peer into the active regions of the buffers as one
+    /// operation, filling each buffer before moving on to the next.
+    ///
+    /// When the call returns, the active region of each buffer is set to the bytes read into it.
+    /// Buffers that the data did not reach are left with an empty active region. Returns the total
+    /// number of bytes read, with 0 indicating that the peer has closed the connection.
+    pub async fn read_vectored(&mut self, buffers: &mut [PinnedBuffer]) -> io::Result<usize> {
+        socket_io::receive_vectored(*self.socket, &self.pending, buffers).await
+    }
+
+    /// Writes the active regions of the buffers to the peer as one operation, in order.
+    ///
+    /// When the call returns, the active region of each buffer is set to the bytes written from it.
+    /// Returns the total number of bytes written.
+    pub async fn write_vectored(&mut self, buffers: &mut [PinnedBuffer]) -> io::Result<usize> {
+        socket_io::send_vectored(*self.socket, &self.pending, buffers).await
+    }
+
+    /// Cancels all reads and writes in flight on the stream and waits for them to complete.
+    ///
+    /// The futures of the affected operations resolve with a cancellation error (see
+    /// `io::Error::is_cancelled()`), unless an operation managed to complete before the
+    /// cancellation took effect. The stream is not usable for further data transfers after this,
+    /// as data may have been lost in transit.
+    pub async fn cancel_all(&mut self) -> io::Result<()> {
+        self.pending
+            .cancel_all(HANDLE(self.socket.0 as *mut _))
+            .await
+    }
+}
+
+impl io::AsyncRead for TcpStream {
+    async fn read(&mut self, buffer: PinnedBuffer) -> io::Result<PinnedBuffer> {
+        TcpStream::read(self, buffer).await
+    }
+}
+
+impl io::AsyncWrite for TcpStream {
+    async fn write(&mut self, buffer: PinnedBuffer) -> io::Result<PinnedBuffer> {
+        TcpStream::write(self, buffer).await
+    }
+}
+
+#[negative_impl]
+impl !Send for TcpStream {}
+#[negative_impl]
+impl !Sync for TcpStream {}
+
+/// ConnectEx is a Winsock extension function, which we must look up at runtime via the socket.
+fn connect_ex_fn(socket: SOCKET) -> io::Result<LPFN_CONNECTEX> {
+    let guid = WSAID_CONNECTEX;
+    let mut connect_ex: LPFN_CONNECTEX = None;
+    let mut bytes_returned: u32 = 0;
+
+    // SAFETY: All we need to be concerned about is passing in valid arguments, which we do.
+    winsock::to_io_result(unsafe {
+        WSAIoctl(
+            socket,
+            SIO_GET_EXTENSION_FUNCTION_POINTER,
+            Some(&guid as *const _ as *const c_void),
+            mem::size_of_val(&guid) as u32,
+            Some(&mut connect_ex as *mut _ as *mut c_void),
+            mem::size_of::<LPFN_CONNECTEX>() as u32,
+            &mut bytes_returned as *mut _,
+            None,
+            None,
+        )
+    })?;
+
+    Ok(connect_ex)
+}
@@ -0,0 +1,311 @@
+use crate::{
+    io::{self, IoClass, IoOperationKind, PendingOperations, PinnedBuffer},
+    net::{
+        socket_io,
+        winsock::{self, SocketAddress},
+    },
+    rt::{current_async_agent, spawn_sync, SynchronousTaskType},
+    windows::OwnedHandle,
+};
+use negative_impl::negative_impl;
+use std::{cell::UnsafeCell, net::SocketAddr, rc::Rc};
+use windows::{
+    core::PSTR,
+    Win32::{
+        Foundation::{HANDLE, STATUS_BUFFER_OVERFLOW},
+        Networking::WinSock::{
+            bind, WSARecvFrom, WSASendTo, WSASocketA, IPPROTO_UDP, SOCKET, SOCK_DGRAM, WSABUF,
+            WSAEMSGSIZE, WSA_FLAG_OVERLAPPED,
+        },
+    },
+};
+
+/// A UDP socket bound to a local address, for sending and receiving datagrams.
+///
+/// The socket is bound to the I/O driver of the async worker thread that created it, so all I/O
+/// on the socket must happen on that same thread.
+#[derive(Debug)]
+pub struct UdpSocket {
+    socket: OwnedHandle<SOCKET>,
+
+    // The address the socket is bound to, with the actual port if port 0 was requested.
+    local_addr: SocketAddr,
+
+    // Sends and receives in flight on the socket, which `cancel_all()` waits for.
+    pending: PendingOperations,
+}
+
+impl UdpSocket {
+    /// Creates a UDP socket bound to the local address. Use port 0 to let the OS pick a free port,
+    /// which you can look up via `local_addr()`.
+    pub async fn bind(addr: SocketAddr) -> io::Result<Self> {
+        winsock::ensure_initialized();
+
+        // Creating and binding the socket are blocking operations, so we kick them off to a
+        // synchronous worker thread to avoid blocking the async workers with these slow calls.
+        let (socket, local_addr) = spawn_sync(SynchronousTaskType::Syscall, move || {
+            let addr = SocketAddress::new(addr);
+
+            // SAFETY: We are required to close the handle once we are done with it,
+            // which we do via OwnedHandle that closes the handle on drop.
+            let socket = unsafe {
+                OwnedHandle::new(WSASocketA(
+                    addr.family().0 as i32,
+                    SOCK_DGRAM.0,
+                    IPPROTO_UDP.0,
+                    None,
+                    0,
+                    WSA_FLAG_OVERLAPPED,
+                )?)
+            };
+
+            // SAFETY: All we need to be concerned about is passing in valid arguments, which we do.
+            winsock::to_io_result(unsafe { bind(*socket, addr.as_ptr(), addr.len()) })?;
+
+            let local_addr = SocketAddress::local_of(*socket)?.to_socket_addr();
+
+            io::Result::Ok((socket, local_addr))
+        })
+        .await?;
+
+        current_async_agent::with_io(|io| io.bind_io_primitive(&*socket, IoClass::Network))?;
+
+        Ok(Self {
+            socket,
+            local_addr,
+            pending: PendingOperations::new(),
+        })
+    }
+
+    /// The local address that the socket is bound to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Sends the active region of the buffer as one datagram to the remote address.
+    ///
+    /// The buffer is returned with the active region set to the bytes sent, to allow reuse.
+    pub async fn send_to(
+        &self,
+        buffer: PinnedBuffer,
+        addr: SocketAddr,
+    ) -> io::Result<PinnedBuffer> {
+        let remote_addr = SocketAddress::new(addr);
+
+        let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+        operation.track(&self.pending);
+        operation.set_handle(&*self.socket);
+        operation.set_kind(IoOperationKind::Write);
+
+        // SAFETY: We are required to pass the OVERLAPPED pointer to the completion routine. We do.
+        unsafe {
+            operation.begin(|buffer, overlapped, immediate_bytes_transferred| {
+                let wsabufs = [WSABUF {
+                    len: buffer.len() as u32,
+                    buf: PSTR::from_raw(buffer.as_mut_ptr()),
+                }];
+
+                // The remote address is captured by the OS during the call, so unlike the buffer
+                // it does not need to outlive the call.
+                winsock::to_io_result(WSASendTo(
+                    *self.socket,
+                    &wsabufs,
+                    Some(immediate_bytes_transferred as *mut u32),
+                    0,
+                    Some(remote_addr.as_ptr()),
+                    remote_addr.len(),
+                    Some(overlapped),
+                    None,
+                ))
+            })
+        }
+        .await
+        .map_err(io::OperationError::into_inner)
+    }
+
+    /// Receives the next datagram into the active region of the buffer.
+    ///
+    /// The buffer is returned with the active region set to the bytes of the datagram, together
+    /// with the address of the sender. If the datagram does not fit into the buffer, the receive
+    /// fails with `io::Error::DatagramTruncated` and the rest of the datagram is lost.
+    pub async fn recv_from(&self, buffer: PinnedBuffer) -> io::Result<(PinnedBuffer, SocketAddr)> {
+        let source = Rc::new(SourceAddress::default());
+
+        let mut operation = current_async_agent::with_io(|io| io.new_operation(buffer));
+        operation.track(&self.pending);
+        operation.set_handle(&*self.socket);
+        operation.set_kind(IoOperationKind::Read);
+
+        // The OS may still write the source address after we stop waiting for the result, so the
+        // operation keeps it alive until the operation completes.
+        operation.keep_alive(source.clone());
+
+        let source_addr = source.addr.get();
+        let source_len = source.len.get();
+
+        // SAFETY: We are required to pass the OVERLAPPED pointer to the completion routine. We do.
+        // The source address is written by the OS until the operation completes and the operation
+        // keeps it valid until then.
+        let result = unsafe {
+            operation.begin(|buffer, overlapped, immediate_bytes_transferred| {
+                let wsabufs = [WSABUF {
+                    len: buffer.len() as u32,
+                    buf: PSTR::from_raw(buffer.as_mut_ptr()),
+                }];
+
+                let mut flags: u32 = 0;
+
+                winsock::to_io_result(WSARecvFrom(
+                    *self.socket,
+                    &wsabufs,
+                    Some(immediate_bytes_transferred as *mut u32),
+                    &mut flags as *mut u32,
+                    Some((*source_addr).as_mut_ptr()),
+                    Some(source_len),
+                    Some(overlapped),
+                    None,
+                ))
+            })
+        }
+        .await;
+
+        let buffer = result.map_err(|e| detect_truncation(e.into_inner()))?;
+
+        // SAFETY: The operation has completed, so the OS is done writing the source address.
+        let source_addr = unsafe { *source_addr };
+
+        Ok((buffer, source_addr.to_socket_addr()))
+    }
+
+    /// Sends the active regions of the buffers as one datagram to the remote address, in order.
+    ///
+    /// When the call returns, the active region of each buffer is set to the bytes sent from it.
+    /// Returns the total number of bytes sent.
+    pub async fn send_to_vectored(
+        &self,
+        buffers: &mut [PinnedBuffer],
+        addr: SocketAddr,
+    ) -> io::Result<usize> {
+        let remote_addr = SocketAddress::new(addr);
+
+        // SAFETY: We are required to pass the OVERLAPPED pointer to the native API. We do.
+        unsafe {
+            socket_io::transfer_vectored(
+                *self.socket,
+                &self.pending,
+                IoOperationKind::Write,
+                buffers,
+                None,
+                |wsabufs, overlapped, bytes_transferred| {
+                    // As in `send_to()`, the remote address does not need to outlive the call.
+                    winsock::to_io_result(WSASendTo(
+                        *self.socket,
+                        wsabufs,
+                        Some(bytes_transferred as *mut u32),
+                        0,
+                        Some(remote_addr.as_ptr()),
+                        remote_addr.len(),
+                        Some(overlapped),
+                        None,
+                    ))
+                },
+            )
+        }
+        .await
+    }
+
+    /// Receives the next datagram into the active regions of the buffers, filling each buffer
+    /// before moving on to the next.
+    ///
+    /// When the call returns, the active region of each buffer is set to the bytes of the datagram
+    /// received into it. Buffers that the datagram did not reach are left with an empty active
+    /// region. Returns the size of the datagram, together with the address of the sender. If the
+    /// datagram does not fit into the buffers, the receive fails with
+    /// `io::Error::DatagramTruncated` and the rest of the datagram is lost.
+    pub async fn recv_from_vectored(
+        &self,
+        buffers: &mut [PinnedBuffer],
+    ) -> io::Result<(usize, SocketAddr)> {
+        let source = Rc::new(SourceAddress::default());
+        let source_addr = source.addr.get();
+        let source_len = source.len.get();
+
+        // SAFETY: We are required to pass the OVERLAPPED pointer to the native API. We do. The
+        // source address is written by the OS until the operation completes and the operation
+        // keeps it valid until then.
+        let bytes_received = unsafe {
+            socket_io::transfer_vectored(
+                *self.socket,
+                &self.pending,
+                IoOperationKind::Read,
+                buffers,
+                Some(source),
+                |wsabufs, overlapped, bytes_transferred| {
+                    let mut flags: u32 = 0;
+
+                    winsock::to_io_result(WSARecvFrom(
+                        *self.socket,
+                        wsabufs,
+                        Some(bytes_transferred as *mut u32),
+                        &mut flags as *mut u32,
+                        Some((*source_addr).as_mut_ptr()),
+                        Some(source_len),
+                        Some(overlapped),
+                        None,
+                    ))
+                },
+            )
+        }
+        .await
+        .map_err(detect_truncation)?;
+
+        // SAFETY: The operation has completed, so the OS is done writing the source address.
+        let source_addr = unsafe { *source_addr };
+
+        Ok((bytes_received, source_addr.to_socket_addr()))
+    }
+
+    /// Cancels all sends and receives in flight on the socket and waits for them to complete.
+    ///
+    /// The futures of the affected operations resolve with a cancellation error (see
+    /// `io::Error::is_cancelled()`), unless an operation managed to complete before the
+    /// cancellation took effect.
+    pub async fn cancel_all(&self) -> io::Result<()> {
+        self.pending
+            .cancel_all(HANDLE(self.socket.0 as *mut _))
+            .await
+    }
+}
+
+#[negative_impl]
+impl !Send for UdpSocket {}
+#[negative_impl]
+impl !Sync for UdpSocket {}
+
+/// Datagrams that do not fit into the buffers are reported as a Winsock error when the receive
+/// completes immediately and as an NTSTATUS when it completes asynchronously. We report both as
+/// `io::Error::DatagramTruncated`.
+pub(super) fn detect_truncation(error: io::Error) -> io::Error {
+    match error {
+        io::Error::Winsock { detail, .. } if detail == WSAEMSGSIZE => io::Error::DatagramTruncated,
+        io::Error::Windows(e) if e.code() == STATUS_BUFFER_OVERFLOW.into() => {
+            io::Error::DatagramTruncated
+        }
+        e => e,
+    }
+}
+
+/// Receives the address of the sender of a datagram from the OS.
+struct SourceAddress {
+    addr: UnsafeCell<SocketAddress>,
+    len: UnsafeCell<i32>,
+}
+
+impl Default for SourceAddress {
+    fn default() -> Self {
+        Self {
+            addr: UnsafeCell::new(SocketAddress::default()),
+            len: UnsafeCell::new(SocketAddress::capacity()),
+        }
+    }
+}
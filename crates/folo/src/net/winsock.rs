@@ -1,6 +1,13 @@
 use crate::io;
-use std::sync::LazyLock;
-use windows::Win32::Networking::WinSock::{WSAGetLastError, WSAStartup, WSADATA};
+use std::{
+    mem,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
+    sync::LazyLock,
+};
+use windows::Win32::Networking::WinSock::{
+    getsockname, WSAGetLastError, WSAStartup, ADDRESS_FAMILY, AF_INET, AF_INET6, IN6_ADDR, IN_ADDR,
+    SOCKADDR, SOCKADDR_IN, SOCKADDR_IN6, SOCKADDR_IN6_0, SOCKADDR_INET, SOCKET, WSADATA,
+};
 
 pub fn ensure_initialized() {
     *WINSOCK_STARTUP;
@@ -28,3 +35,136 @@ pub fn to_io_result(winsock_result: i32) -> io::Result<()> {
         })
     }
 }
+
+/// A socket address in the form expected by Winsock functions, which take a pointer to a generic
+/// `SOCKADDR` and the length of the family-specific structure behind it.
+#[derive(Clone, Copy, Default)]
+pub struct SocketAddress {
+    inner: SOCKADDR_INET,
+}
+
+impl SocketAddress {
+    pub fn new(addr: SocketAddr) -> Self {
+        let mut inner = SOCKADDR_INET::default();
+
+        match addr {
+            SocketAddr::V4(addr) => {
+                let mut ip = IN_ADDR::default();
+                // The address is stored in network byte order, which is the order of the octets.
+                ip.S_un.S_addr = u32::from_ne_bytes(addr.ip().octets());
+
+                inner.Ipv4 = SOCKADDR_IN {
+                    sin_family: AF_INET,
+                    sin_port: addr.port().to_be(),
+                    sin_addr: ip,
+                    sin_zero: [0; 8],
+                };
+            }
+            SocketAddr::V6(addr) => {
+                let mut ip = IN6_ADDR::default();
+                ip.u.Byte = addr.ip().octets();
+
+                let mut scope = SOCKADDR_IN6_0::default();
+                scope.sin6_scope_id = addr.scope_id();
+
+                inner.Ipv6 = SOCKADDR_IN6 {
+                    sin6_family: AF_INET6,
+                    sin6_port: addr.port().to_be(),
+                    sin6_flowinfo: addr.flowinfo(),
+                    sin6_addr: ip,
+                    Anonymous: scope,
+                };
+            }
+        }
+
+        Self { inner }
+    }
+
+    /// The unspecified address of the same family as `addr`, with a port of 0. Binding a socket to
+    /// this lets the OS pick the local address and port.
+    pub fn unspecified_like(addr: SocketAddr) -> Self {
+        match addr {
+            SocketAddr::V4(_) => Self::new((Ipv4Addr::UNSPECIFIED, 0).into()),
+            SocketAddr::V6(_) => Self::new((Ipv6Addr::UNSPECIFIED, 0).into()),
+        }
+    }
+
+    /// The local address that the socket is bound to.
+    pub fn local_of(socket: SOCKET) -> io::Result<Self> {
+        let mut addr = Self::default();
+        let mut len = Self::capacity();
+
+        // SAFETY: The buffer is large enough for an address of any family we support.
+        to_io_result(unsafe { getsockname(socket, addr.as_mut_ptr(), &mut len) })?;
+
+        Ok(addr)
+    }
+
+    pub fn to_socket_addr(self) -> SocketAddr {
+        // SAFETY: We read the member of the union that matches the family of the address.
+        unsafe {
+            if self.family() == AF_INET {
+                let addr = self.inner.Ipv4;
+
+                SocketAddrV4::new(
+                    Ipv4Addr::from(addr.sin_addr.S_un.S_addr.to_ne_bytes()),
+                    u16::from_be(addr.sin_port),
+                )
+                .into()
+            } else {
+                let addr = self.inner.Ipv6;
+
+                SocketAddrV6::new(
+                    Ipv6Addr::from(addr.sin6_addr.u.Byte),
+                    u16::from_be(addr.sin6_port),
+                    addr.sin6_flowinfo,
+                    addr.Anonymous.sin6_scope_id,
+                )
+                .into()
+            }
+        }
+    }
+
+    pub fn family(&self) -> ADDRESS_FAMILY {
+        // SAFETY: The family is at the same place in every member of the union.
+        unsafe { self.inner.si_family }
+    }
+
+    pub fn as_ptr(&self) -> *const SOCKADDR {
+        &self.inner as *const _ as *const _
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut SOCKADDR {
+        &mut self.inner as *mut _ as *mut _
+    }
+
+    /// The size of the storage behind `as_mut_ptr()`, which fits an address of any family.
+    pub fn capacity() -> i32 {
+        mem::size_of::<SOCKADDR_INET>() as i32
+    }
+
+    pub fn len(&self) -> i32 {
+        if self.family() == AF_INET {
+            mem::size_of::<SOCKADDR_IN>() as i32
+        } else {
+            mem::size_of::<SOCKADDR_IN6>() as i32
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn socket_address_round_trips() {
+        let addrs: [SocketAddr; 2] = [
+            "192.0.2.1:1234".parse().unwrap(),
+            "[2001:db8::1%7]:5678".parse().unwrap(),
+        ];
+
+        for addr in addrs {
+            assert_eq!(SocketAddress::new(addr).to_socket_addr(), addr);
+        }
+    }
+}
@@ -0,0 +1,7 @@
+mod child;
+mod command;
+mod inheritable_handle;
+
+pub use child::*;
+pub use command::*;
+pub use inheritable_handle::*;
@@ -0,0 +1,69 @@
+use crate::{io, windows::OwnedHandle};
+use windows::Win32::{
+    Foundation::{HANDLE, WAIT_FAILED, WAIT_OBJECT_0, WAIT_TIMEOUT},
+    System::Threading::{GetExitCodeProcess, GetProcessId, WaitForSingleObject},
+};
+
+/// A child process started via `Command::spawn()`. Dropping this does not terminate the child
+/// process.
+#[derive(Debug)]
+pub struct Child {
+    process: OwnedHandle<HANDLE>,
+}
+
+impl Child {
+    pub(crate) fn new(process: OwnedHandle<HANDLE>) -> Self {
+        Self { process }
+    }
+
+    /// The process ID of the child process.
+    pub fn id(&self) -> u32 {
+        // SAFETY: The handle is valid for as long as we exist.
+        unsafe { GetProcessId(*self.process) }
+    }
+
+    /// Waits for the child process to exit and returns its exit code. If the child process has
+    /// already exited, this completes immediately.
+    ///
+    /// The async worker thread is not blocked while waiting - the OS thread pool waits for the
+    /// child process to exit and wakes up the task when it does.
+    pub async fn wait(&self) -> io::Result<u32> {
+        if let Some(exit_code) = self.try_wait()? {
+            return Ok(exit_code);
+        }
+
+        // The process handle remains valid until the wait completes, as we are borrowed until then.
+        io::wait_for_any(&[*self.process]).await?;
+
+        self.try_wait()?.ok_or_else(|| {
+            io::Error::Internal("child process signaled exit but is still running".to_string())
+        })
+    }
+
+    /// Returns the exit code of the child process if it has exited, without waiting.
+    pub fn try_wait(&self) -> io::Result<Option<u32>> {
+        // We cannot just ask for the exit code, as a process that is still running reports the
+        // exit code STILL_ACTIVE, which a process that has exited may also have returned.
+        //
+        // SAFETY: The handle is valid for as long as we exist.
+        match unsafe { WaitForSingleObject(*self.process, 0) } {
+            WAIT_OBJECT_0 => {}
+            WAIT_TIMEOUT => return Ok(None),
+            WAIT_FAILED => return Err(windows::core::Error::from_win32().into()),
+            other => {
+                return Err(io::Error::Internal(format!(
+                    "unexpected result from waiting for child process: {other:?}"
+                )))
+            }
+        }
+
+        let mut exit_code: u32 = 0;
+
+        // SAFETY: The handle is valid and we pass a valid pointer to a local.
+        unsafe {
+            GetExitCodeProcess(*self.process, &mut exit_code)?;
+        }
+
+        Ok(Some(exit_code))
+    }
+}
@@ -0,0 +1,376 @@
+use crate::{
+    io::{self, inheritable_pipe, LineSplitter, PinnedBuffer, PipeReader},
+    process::{Child, InheritableHandle},
+    rt::{spawn_sync, SynchronousTaskType},
+    windows::OwnedHandle,
+};
+use futures::Stream;
+use std::{ffi::c_void, mem, ptr};
+use windows::{
+    core::{PCWSTR, PWSTR},
+    Win32::{
+        Foundation::{BOOL, HANDLE},
+        System::Threading::{
+            CreateProcessW, DeleteProcThreadAttributeList, InitializeProcThreadAttributeList,
+            UpdateProcThreadAttribute, CREATE_NO_WINDOW, EXTENDED_STARTUPINFO_PRESENT,
+            LPPROC_THREAD_ATTRIBUTE_LIST, PROCESS_INFORMATION, PROC_THREAD_ATTRIBUTE_HANDLE_LIST,
+            STARTF_USESTDHANDLES, STARTUPINFOEXW, STARTUPINFOW,
+        },
+    },
+};
+
+/// Describes a child process to start.
+///
+/// By default, the child process does not have any standard streams, unless they are given via
+/// `stdin()`, `stdout()` and `stderr()`. Standard error is not captured unless merged into
+/// standard output via `merge_stderr()`.
+///
+/// The child process inherits only the handles given to it as its standard streams, never any
+/// other handles of the current process.
+#[derive(Clone, Debug)]
+pub struct Command {
+    program: String,
+    args: Vec<String>,
+    merge_stderr: bool,
+    stdin: Option<InheritableHandle>,
+    stdout: Option<InheritableHandle>,
+    stderr: Option<InheritableHandle>,
+}
+
+impl Command {
+    /// Creates a command for starting `program`, which is located via the search rules of
+    /// `CreateProcessW()` (e.g. the current directory and `PATH`, with `.exe` appended if no
+    /// extension is given).
+    pub fn new(program: impl Into<String>) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+            merge_stderr: false,
+            stdin: None,
+            stdout: None,
+            stderr: None,
+        }
+    }
+
+    /// Adds an argument to pass to the program. The argument is quoted as needed.
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Adds arguments to pass to the program. Each argument is quoted as needed.
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Whether to send standard error of the child process to the same pipe as standard output.
+    /// The lines written to either stream are then received in the order the child process wrote
+    /// them. Lines are only kept intact if the child process writes whole lines at a time - as most
+    /// programs do when writing to a pipe, although output buffering may delay one stream relative
+    /// to the other.
+    ///
+    /// When merged, any handle given via `stderr()` is ignored.
+    pub fn merge_stderr(mut self, merge_stderr: bool) -> Self {
+        self.merge_stderr = merge_stderr;
+        self
+    }
+
+    /// Gives the child process a handle to use as its standard input (e.g. a file opened via
+    /// `fs::File::inheritable_handle()`).
+    pub fn stdin(mut self, handle: InheritableHandle) -> Self {
+        self.stdin = Some(handle);
+        self
+    }
+
+    /// Gives the child process a handle to use as its standard output. Ignored by
+    /// `stdout_lines()`, which captures the standard output itself.
+    pub fn stdout(mut self, handle: InheritableHandle) -> Self {
+        self.stdout = Some(handle);
+        self
+    }
+
+    /// Gives the child process a handle to use as its standard error.
+    pub fn stderr(mut self, handle: InheritableHandle) -> Self {
+        self.stderr = Some(handle);
+        self
+    }
+
+    /// Starts the child process without capturing any of its output and returns a handle to it,
+    /// which can be used to wait for it to exit.
+    pub async fn spawn(&self) -> io::Result<Child> {
+        let command_line = self.command_line();
+        let stdio = self.stdio(self.stdout.clone());
+
+        // Starting a process involves loading the executable from storage, so we kick it off to a
+        // synchronous worker thread to avoid blocking the async workers with this slow call.
+        let process = spawn_sync(SynchronousTaskType::Syscall, move || {
+            start_process(command_line, stdio)
+        })
+        .await?;
+
+        Ok(Child::new(process))
+    }
+
+    /// Starts the child process and returns a stream of the lines it writes to standard output,
+    /// received as they are written. The stream ends when the child process (and any descendants
+    /// it shared its standard output with) has exited.
+    ///
+    /// The line terminators (`\n` or `\r\n`) are not included in the returned lines. If the output
+    /// does not end with a line terminator, the remainder is returned as the last line.
+    pub async fn stdout_lines(&self) -> io::Result<impl Stream<Item = io::Result<String>>> {
+        let (reader, writer) = inheritable_pipe()?;
+
+        let command_line = self.command_line();
+        let stdio = self.stdio(Some(InheritableHandle::new(writer)?));
+
+        // Starting a process involves loading the executable from storage, so we kick it off to a
+        // synchronous worker thread to avoid blocking the async workers with this slow call.
+        //
+        // Our copy of the writing end of the pipe is released once the child process has started,
+        // so the pipe is closed as soon as the child process exits.
+        let process = spawn_sync(SynchronousTaskType::Syscall, move || {
+            start_process(command_line, stdio)
+        })
+        .await?;
+
+        // We do not need to control the child process - the closing of the pipe tells us when it
+        // is done - so we release our handle to it right away.
+        drop(process);
+
+        let state = LinesState {
+            reader,
+            splitter: LineSplitter::new(),
+            finished: false,
+        };
+
+        Ok(futures::stream::unfold(state, |mut state| async move {
+            loop {
+                if let Some(line) = state.splitter.next_line() {
+                    return Some((Ok(line), state));
+                }
+
+                if state.finished {
+                    return None;
+                }
+
+                match state.reader.read(PinnedBuffer::from_pool()).await {
+                    Ok(buffer) if buffer.is_empty() => {
+                        // The child process is gone. Anything after the last line terminator is
+                        // still a line, just an unterminated one.
+                        state.finished = true;
+                        return state.splitter.finish().map(|line| (Ok(line), state));
+                    }
+                    Ok(buffer) => state.splitter.push(buffer.as_slice()),
+                    Err(e) => {
+                        state.finished = true;
+                        return Some((Err(e), state));
+                    }
+                }
+            }
+        }))
+    }
+
+    fn stdio(&self, stdout: Option<InheritableHandle>) -> ChildStdio {
+        ChildStdio {
+            stdin: self.stdin.clone(),
+            stderr: if self.merge_stderr {
+                stdout.clone()
+            } else {
+                self.stderr.clone()
+            },
+            stdout,
+        }
+    }
+
+    fn command_line(&self) -> String {
+        let mut command_line = String::new();
+        append_quoted(&mut command_line, &self.program);
+
+        for arg in &self.args {
+            command_line.push(' ');
+            append_quoted(&mut command_line, arg);
+        }
+
+        command_line
+    }
+}
+
+struct LinesState {
+    reader: PipeReader,
+    splitter: LineSplitter,
+
+    // Set once the pipe has been closed or has failed - there will be no more data.
+    finished: bool,
+}
+
+/// The handles a child process receives as its standard streams.
+struct ChildStdio {
+    stdin: Option<InheritableHandle>,
+    stdout: Option<InheritableHandle>,
+    stderr: Option<InheritableHandle>,
+}
+
+/// Starts a child process and returns a handle to it. The child process does not have the
+/// standard streams for which no handle is given.
+fn start_process(command_line: String, stdio: ChildStdio) -> io::Result<OwnedHandle<HANDLE>> {
+    let mut command_line = command_line
+        .encode_utf16()
+        .chain(Some(0))
+        .collect::<Vec<_>>();
+
+    let [stdin_handle, stdout_handle, stderr_handle] = [&stdio.stdin, &stdio.stdout, &stdio.stderr]
+        .map(|handle| {
+            handle
+                .as_ref()
+                .map_or(HANDLE::default(), InheritableHandle::raw)
+        });
+
+    // By default, a child process inherits every inheritable handle of the parent, including the
+    // pipes of any other child processes being started at the same time. That would keep those
+    // pipes open until our child process exits, so we explicitly limit inheritance to the handles
+    // of our standard streams. The same handle may be used for multiple streams but must only be
+    // listed once.
+    let mut inherited_handles = Vec::with_capacity(3);
+
+    for handle in [stdin_handle, stdout_handle, stderr_handle] {
+        if !handle.is_invalid() && !inherited_handles.contains(&handle) {
+            inherited_handles.push(handle);
+        }
+    }
+
+    let mut attribute_list_size = 0;
+
+    // SAFETY: Querying the required size is always valid. It reports the size via an error.
+    _ = unsafe {
+        InitializeProcThreadAttributeList(
+            LPPROC_THREAD_ATTRIBUTE_LIST(ptr::null_mut()),
+            1,
+            0,
+            &mut attribute_list_size,
+        )
+    };
+
+    // We use u64 as the element type to ensure the list is sufficiently aligned.
+    let mut attribute_list_storage = vec![0_u64; attribute_list_size.div_ceil(8)];
+    let attribute_list = LPPROC_THREAD_ATTRIBUTE_LIST(attribute_list_storage.as_mut_ptr().cast());
+
+    // SAFETY: The storage is of the size requested by the OS and outlives the list, which we
+    // delete before returning. The inherited handles also outlive the list.
+    unsafe {
+        InitializeProcThreadAttributeList(attribute_list, 1, 0, &mut attribute_list_size)?;
+
+        let result = if inherited_handles.is_empty() {
+            Ok(())
+        } else {
+            UpdateProcThreadAttribute(
+                attribute_list,
+                0,
+                PROC_THREAD_ATTRIBUTE_HANDLE_LIST as usize,
+                Some(inherited_handles.as_ptr() as *const c_void),
+                mem::size_of_val(inherited_handles.as_slice()),
+                None,
+                None,
+            )
+            .map_err(io::Error::from)
+        }
+        .and_then(|()| {
+            let startup_info = STARTUPINFOEXW {
+                StartupInfo: STARTUPINFOW {
+                    cb: mem::size_of::<STARTUPINFOEXW>() as u32,
+                    dwFlags: STARTF_USESTDHANDLES,
+                    hStdInput: stdin_handle,
+                    hStdOutput: stdout_handle,
+                    hStdError: stderr_handle,
+                    ..Default::default()
+                },
+                lpAttributeList: attribute_list,
+            };
+
+            let mut process_info = PROCESS_INFORMATION::default();
+
+            CreateProcessW(
+                PCWSTR::null(),
+                PWSTR::from_raw(command_line.as_mut_ptr()),
+                None,
+                None,
+                BOOL::from(!inherited_handles.is_empty()),
+                EXTENDED_STARTUPINFO_PRESENT | CREATE_NO_WINDOW,
+                None,
+                PCWSTR::null(),
+                &startup_info.StartupInfo,
+                &mut process_info,
+            )?;
+
+            // We have no use for the main thread of the child process.
+            drop(OwnedHandle::new(process_info.hThread));
+
+            Ok(OwnedHandle::new(process_info.hProcess))
+        });
+
+        DeleteProcThreadAttributeList(attribute_list);
+
+        result
+    }
+}
+
+/// Appends an argument to a command line, quoting it such that the child process parses it back
+/// into the original argument (using the rules of the Microsoft C runtime).
+fn append_quoted(command_line: &mut String, arg: &str) {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        command_line.push_str(arg);
+        return;
+    }
+
+    command_line.push('"');
+
+    // Backslashes are only special when they precede a quote, so we only know what to do with them
+    // once we see what comes after them.
+    let mut backslashes = 0;
+
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                command_line.push_str(&"\\".repeat(backslashes * 2 + 1));
+                command_line.push('"');
+                backslashes = 0;
+            }
+            _ => {
+                command_line.push_str(&"\\".repeat(backslashes));
+                command_line.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+
+    // The closing quote must not be escaped by any trailing backslashes.
+    command_line.push_str(&"\\".repeat(backslashes * 2));
+    command_line.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_line_quoting() {
+        let command = Command::new("program")
+            .arg("plain")
+            .arg("with space")
+            .arg("")
+            .arg(r#"say "hi""#)
+            .arg(r"C:\path\")
+            .arg(r"C:\dir with space\")
+            .arg(r#"back\"quote"#);
+
+        assert_eq!(
+            command.command_line(),
+            r#"program plain "with space" "" "say \"hi\"" C:\path\ "C:\dir with space\\" "back\\\"quote""#
+        );
+    }
+}
@@ -0,0 +1,36 @@
+use crate::{io, windows::OwnedHandle};
+use std::sync::Arc;
+use windows::Win32::Foundation::{SetHandleInformation, HANDLE, HANDLE_FLAG_INHERIT};
+
+/// A handle that has been explicitly marked as inheritable, for passing to a child process as one
+/// of its standard streams via `Command`.
+///
+/// Handles opened by Folo are not inheritable, so they do not leak into child processes by
+/// accident. Even inheritable handles are only inherited by a child process started via `Command`
+/// if they are given to that specific command - other child processes do not receive them.
+///
+/// Cloning this is cheap - all the clones refer to the same handle, which is closed when the last
+/// clone is dropped.
+#[derive(Clone, Debug)]
+pub struct InheritableHandle {
+    handle: Arc<OwnedHandle<HANDLE>>,
+}
+
+impl InheritableHandle {
+    /// Marks the handle as inheritable. The handle must be a plain synchronous handle that is not
+    /// bound to the I/O driver, as the child process will use it for synchronous I/O.
+    pub(crate) fn new(handle: OwnedHandle<HANDLE>) -> io::Result<Self> {
+        // SAFETY: The handle is valid because we own it.
+        unsafe {
+            SetHandleInformation(*handle, HANDLE_FLAG_INHERIT.0, HANDLE_FLAG_INHERIT)?;
+        }
+
+        Ok(Self {
+            handle: Arc::new(handle),
+        })
+    }
+
+    pub(crate) fn raw(&self) -> HANDLE {
+        **self.handle
+    }
+}
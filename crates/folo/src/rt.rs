@@ -1,26 +1,56 @@
+mod abort;
 mod async_agent;
+mod async_drop_guard;
 mod async_task_engine;
 mod builder;
+mod channel;
+mod collector;
 pub(crate) mod current_async_agent;
 pub(crate) mod current_runtime;
 pub(crate) mod current_sync_agent;
 mod erased_async_task;
 mod functions;
+mod join_all;
 mod local_join;
 mod local_task;
+mod on_cancel;
+pub mod oneshot;
+mod poll_depth;
 mod ready_after_poll;
 mod remote_join;
 mod remote_result_box;
 mod remote_task;
 mod remote_waker;
 mod runtime_client;
+mod run_queue;
+mod runtime_handle;
+mod select;
+mod shutdown_flush;
 mod sync_agent;
+mod sync_join;
 mod types;
 mod waker;
 
+pub use abort::Aborted;
+pub(crate) use abort::{AbortState, Abortable};
+pub use async_drop_guard::*;
 pub use builder::*;
+pub use channel::*;
+pub use collector::*;
 pub use functions::*;
+pub use join_all::*;
 pub use local_join::*;
+pub use on_cancel::*;
+pub use poll_depth::*;
 pub use remote_join::*;
 pub use runtime_client::*;
+pub use run_queue::TaskPriority;
+pub use runtime_handle::*;
+pub use select::*;
+pub use shutdown_flush::*;
+pub use sync_join::*;
 pub(crate) use types::*;
+
+// Sequences of values produced asynchronously, such as `fs::read_dir_stream()` or
+// `net::TcpListener::incoming()`, are exposed as streams with the adapters of `StreamExt`.
+pub use futures::{Stream, StreamExt};
@@ -0,0 +1,145 @@
+use crate::{constants, io::IoWaker, rt::current_async_agent};
+use pin_project::pin_project;
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    task::{self, Waker},
+};
+
+/// The result of joining a task that was aborted via its join handle before it completed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, thiserror::Error)]
+#[error("task was aborted before it completed")]
+pub struct Aborted;
+
+/// Shared between a task and its join handle, allowing the join handle to abort the task from any
+/// thread and observe whether the task has finished.
+#[derive(Debug, Default)]
+pub(crate) struct AbortState {
+    aborted: AtomicBool,
+    finished: AtomicBool,
+
+    // The waker of the task and the I/O waker of the thread that owns it, set once the task has
+    // been polled for the first time and cleared when it finishes. We hold on to the waker only
+    // while the task is running, so that its wake signal can become inert once it has finished.
+    waker: Mutex<Option<(Waker, Option<IoWaker>)>>,
+}
+
+impl AbortState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests the task to be aborted. The task is woken up and drops its future the next time
+    /// it is polled, unless it has already finished by then.
+    pub fn abort(&self) {
+        self.aborted.store(true, Ordering::Release);
+
+        if let Some((waker, io_waker)) = &*self.waker.lock().expect(constants::POISONED_LOCK) {
+            waker.wake_by_ref();
+
+            // The task may be owned by another thread, which may be sleeping while waiting for I/O.
+            if let Some(io_waker) = io_waker {
+                io_waker.wake();
+            }
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Acquire)
+    }
+
+    fn register(&self, waker: &Waker) {
+        let io_waker = current_async_agent::try_with_io(|io| io.waker());
+
+        *self.waker.lock().expect(constants::POISONED_LOCK) = Some((waker.clone(), io_waker));
+    }
+
+    fn finish(&self) {
+        self.finished.store(true, Ordering::Release);
+        self.release_waker();
+    }
+
+    fn release_waker(&self) {
+        // We drop the waker outside the lock, as dropping it may run arbitrary code.
+        let waker = self.waker.lock().expect(constants::POISONED_LOCK).take();
+        drop(waker);
+    }
+}
+
+/// Wraps the future of a task to make it abortable via an `AbortState`, completing with `Aborted`
+/// instead of the result of the future if aborted first.
+#[pin_project(PinnedDrop)]
+#[derive(Debug)]
+pub(crate) struct Abortable<F> {
+    // Cleared when the future completes or is aborted, dropping any state it captured.
+    #[pin]
+    future: Option<F>,
+
+    state: Arc<AbortState>,
+
+    // Whether we have given our waker to the abort state, which we only need to do once because
+    // the waker of a task does not change.
+    registered: bool,
+}
+
+impl<F> Abortable<F> {
+    pub fn new(future: F, state: Arc<AbortState>) -> Self {
+        Self {
+            future: Some(future),
+            state,
+            registered: false,
+        }
+    }
+}
+
+impl<F> Future for Abortable<F>
+where
+    F: Future,
+{
+    type Output = Result<F::Output, Aborted>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let mut this = self.project();
+
+        // We register before checking for abort, so an abort that happens concurrently either
+        // sees our waker and wakes us up or is seen by us below.
+        if !*this.registered {
+            this.state.register(cx.waker());
+            *this.registered = true;
+        }
+
+        if this.state.aborted.load(Ordering::Acquire) {
+            this.future.set(None);
+            this.state.finish();
+            return task::Poll::Ready(Err(Aborted));
+        }
+
+        let future = this
+            .future
+            .as_mut()
+            .as_pin_mut()
+            .expect("a task is never going to be polled after it has completed");
+
+        match future.poll(cx) {
+            task::Poll::Ready(result) => {
+                this.future.set(None);
+                this.state.finish();
+                task::Poll::Ready(Ok(result))
+            }
+            task::Poll::Pending => task::Poll::Pending,
+        }
+    }
+}
+
+#[pin_project::pinned_drop]
+impl<F> PinnedDrop for Abortable<F> {
+    fn drop(self: Pin<&mut Self>) {
+        // If the task is dropped without completing (e.g. on runtime shutdown), we still need to
+        // release the waker so the wake signal of the task can become inert.
+        self.state.release_waker();
+    }
+}
@@ -6,20 +6,22 @@ use crate::{
         async_task_engine::{AsyncTaskEngine, CycleResult},
         current_runtime,
         local_task::LocalTask,
-        LocalJoinHandle,
+        shutdown_flush::ShutdownFlushFn,
+        AbortState, Abortable, LocalJoinHandle, TaskPriority,
     },
-    time::{advance_local_timers, UltraLowPrecisionInstant},
+    time::{advance_local_timers, next_local_timer, UltraLowPrecisionInstant},
 };
 use core_affinity::CoreId;
 use crossbeam::channel;
 use std::{
     cell::{Cell, RefCell},
-    collections::VecDeque,
+    collections::{BTreeMap, VecDeque},
     fmt::{self, Debug, Formatter},
     future::Future,
     pin::Pin,
+    rc::Rc,
     sync::Arc,
-    time::Instant,
+    time::{Duration, Instant},
 };
 use tracing::{event, Level};
 
@@ -51,6 +53,13 @@ pub struct AsyncAgent {
     metrics_tx: Option<channel::Sender<ReportPage>>,
     processor_id: CoreId,
 
+    // How often to poll for cross-thread work, in milliseconds. We do not have cross-thread real
+    // time signals for everything (e.g. tasks spawned from other threads only wake us up once per
+    // batch) and use polling to check for arriving work. This sets our maximum sleep time, although
+    // we will often check much more often if activity on the current thread wakes us up.
+    // See `RuntimeBuilder::spawn_flush_interval()`.
+    cross_thread_poll_interval_ms: u32,
+
     // Becomes None when `run()` has finished and we are safe top drop the AsyncAgent.
     engine: RefCell<Option<AsyncTaskEngine>>,
 
@@ -63,32 +72,81 @@ pub struct AsyncAgent {
     // Tasks that have been enqueued but have not yet been handed over to the async task engine.
     // Includes both locally queued tasks and tasks enqueued from another thread, which are both
     // unified to the `ErasedResultAsyncTask` type.
-    new_tasks: RefCell<VecDeque<Pin<Box<dyn ErasedResultAsyncTask>>>>,
+    new_tasks: RefCell<VecDeque<NewTask>>,
+
+    // Tasks handed over to the async task engine that have not yet completed, maintained by the
+    // engine. Together with `new_tasks`, these are the live tasks of the agent.
+    live_tasks: Rc<Cell<usize>>,
+
+    // Flush callbacks registered via `rt::on_shutdown_flush()`, executed when we are commanded to
+    // terminate, before we start shutting down.
+    shutdown_flushes: RefCell<BTreeMap<u64, ShutdownFlushFn>>,
+    next_shutdown_flush_id: Cell<u64>,
+
+    // Number of flush tasks started in response to the terminate command that have not completed.
+    pending_shutdown_flushes: Rc<Cell<usize>>,
+
+    // Set when we receive the terminate command. We only start shutting down after all the
+    // shutdown flushes have completed.
+    terminate_requested: Cell<bool>,
 
     // If we are shutting down, we try ignore requests to schedule new tasks and do our best to
     // cleanup ASAP.
     shutting_down: Cell<bool>,
 }
 
+// A task waiting to be handed over to the async task engine, with the priority to poll it with.
+type NewTask = (Pin<Box<dyn ErasedResultAsyncTask>>, TaskPriority);
+
 impl AsyncAgent {
+    #[allow(clippy::too_many_arguments)] // Each is a distinct setting from the runtime builder.
     pub fn new(
         command_rx: channel::Receiver<AsyncAgentCommand>,
         metrics_tx: Option<channel::Sender<ReportPage>>,
         io_shared: Arc<io::DriverShared>,
+        io_priority_policy: io::IoPriorityPolicy,
+        max_in_flight_io: Option<usize>,
+        slow_io: Option<io::SlowIoHook>,
+        verify_io_completions: bool,
+        io_dequeue_batch_size: usize,
+        spawn_flush_interval: Duration,
         processor_id: CoreId,
     ) -> Self {
+        let live_tasks = Rc::new(Cell::new(0));
+
         Self {
             command_rx,
             metrics_tx,
             processor_id,
+            // Rounded up, so a sub-millisecond interval does not turn into a busy loop.
+            cross_thread_poll_interval_ms: u32::try_from(
+                spawn_flush_interval.as_nanos().div_ceil(1_000_000),
+            )
+            .unwrap_or(u32::MAX),
             // SAFETY: The async task engine must not be dropped until we get a
             // `CycleResult::Shutdown` from it. We do wait for this in `run()`.
-            engine: RefCell::new(Some(unsafe { AsyncTaskEngine::new() })),
+            engine: RefCell::new(Some(unsafe {
+                AsyncTaskEngine::new(Rc::clone(&live_tasks))
+            })),
             // SAFETY: The I/O driver must not be dropped while there are pending I/O operations.
             // We ensure this by waiting for I/O to complete before returning from `run()`.
-            io: RefCell::new(Some(unsafe { io::Driver::new() })),
+            io: RefCell::new(Some(unsafe {
+                io::Driver::new(
+                    io_priority_policy,
+                    max_in_flight_io,
+                    slow_io,
+                    verify_io_completions,
+                    io_dequeue_batch_size,
+                    processor_id.id,
+                )
+            })),
             io_shared: RefCell::new(Some(io_shared)),
             new_tasks: RefCell::new(VecDeque::new()),
+            live_tasks,
+            shutdown_flushes: RefCell::new(BTreeMap::new()),
+            next_shutdown_flush_id: Cell::new(0),
+            pending_shutdown_flushes: Rc::new(Cell::new(0)),
+            terminate_requested: Cell::new(false),
             shutting_down: Cell::new(false),
         }
     }
@@ -121,6 +179,29 @@ impl AsyncAgent {
         f(io_ref)
     }
 
+    /// Number of live tasks owned by the agent - tasks that have been spawned (whether locally or
+    /// from another thread) and have not yet completed, regardless of whether they are ready to
+    /// be polled or waiting to be woken up.
+    pub fn task_count(&self) -> usize {
+        self.new_tasks.borrow().len() + self.live_tasks.get()
+    }
+
+    pub(crate) fn register_shutdown_flush(&self, flush_fn: ShutdownFlushFn) -> u64 {
+        let id = self.next_shutdown_flush_id.get();
+        self.next_shutdown_flush_id.set(id + 1);
+
+        self.shutdown_flushes.borrow_mut().insert(id, flush_fn);
+        id
+    }
+
+    pub(crate) fn unregister_shutdown_flush(&self, id: u64) {
+        // The entry is already gone if the flush has been started.
+        let flush_fn = self.shutdown_flushes.borrow_mut().remove(&id);
+
+        // Dropped outside the borrow, in case dropping it leads to more (un)registrations.
+        drop(flush_fn);
+    }
+
     /// Spawns a task to execute a future on the current async worker thread.
     ///
     /// # Panics
@@ -128,6 +209,36 @@ impl AsyncAgent {
     /// Panics if the current thread is not an async worker thread. This is possible because there
     /// are more types of runtime threads than async worker threads - e.g. sync worker threads.
     pub fn spawn<F, R>(&self, future: F) -> LocalJoinHandle<R>
+    where
+        F: Future<Output = R> + 'static,
+        R: 'static,
+    {
+        self.spawn_with_priority(TaskPriority::default(), future)
+    }
+
+    /// Spawns a task to execute a future on the current async worker thread, polling it before
+    /// or after other ready tasks depending on its priority.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread is not an async worker thread. This is possible because there
+    /// are more types of runtime threads than async worker threads - e.g. sync worker threads.
+    pub fn spawn_with_priority<F, R>(&self, priority: TaskPriority, future: F) -> LocalJoinHandle<R>
+    where
+        F: Future<Output = R> + 'static,
+        R: 'static,
+    {
+        self.spawn_with_abort_state(priority, future, Arc::new(AbortState::new()))
+    }
+
+    /// Spawns a task that is aborted via the given abort state, which may be shared with a join
+    /// handle on another thread (e.g. when spawning on behalf of `spawn_on_any()`).
+    pub(crate) fn spawn_with_abort_state<F, R>(
+        &self,
+        priority: TaskPriority,
+        future: F,
+        abort: Arc<AbortState>,
+    ) -> LocalJoinHandle<R>
     where
         F: Future<Output = R> + 'static,
         R: 'static,
@@ -157,12 +268,12 @@ impl AsyncAgent {
         // shutdown! The answer is `ErasedTask::clear()` which drops the future and any captured
         // state such as the join handle! The task engine also relies on that capability to drop
         // wakers and break waker reference cycles and we use this to also cancel pending I/O.
-        let mut task = unsafe { LocalTask::new(future) };
-        let join_handle = task.as_mut().join_handle();
+        let mut task = unsafe { LocalTask::new(Abortable::new(future, Arc::clone(&abort))) };
+        let join_handle = LocalJoinHandle::new(task.as_mut().result_rx(), abort);
 
         // We queue up the tasks because we may be being called from within the async task engine
         // itself, so we cannot call back into it immediately.
-        self.new_tasks.borrow_mut().push_back(task);
+        self.new_tasks.borrow_mut().push_back((task, priority));
         join_handle
     }
 
@@ -181,7 +292,7 @@ impl AsyncAgent {
         // * I/O completion arrived on the I/O driver.
         // * An "enqueue new task" command was received from an arbitrary thread.
         // * Some task on the current thread enqueued another task.
-        // * A timer expired (not implemented yet).
+        // * A timer expired. We never sleep past the next timer of the current thread.
         // * A sleeping task was woken up
         //     If it wakes up due to current thread activity, we can just think of it as a
         //     consequence of that activity (e.g. I/O completion). However, a task can also be woken
@@ -231,37 +342,49 @@ impl AsyncAgent {
                     // coordination of worker threads, it is conceivable that somehow we might get
                     // multiple shutdown commands. Just ignore any extra ones - we cannot be
                     // shutting down any harder than we already are.
-                    if !self.shutting_down.get() {
-                        // This *starts* our shutdown - we still need to wait for the async task
-                        // engine to clean up and for pending I/O operations to complete.
-                        event!(
-                            Level::TRACE,
-                            "received terminate command; shutdown process starting"
-                        );
-
-                        self.shutting_down.set(true);
-
-                        // The tasks in this list may own resources that are already referenced by other
-                        // tasks or external entities. We need to accept them into our regular process
-                        // before dropping them - they are not safe to drop just because they are new.
-                        while let Some(erased_task) = self.new_tasks.borrow_mut().pop_front() {
-                            engine.enqueue_erased(erased_task);
-                        }
-
-                        // Start cleaning up the async task engine. This may require some time if there
-                        // are foreign threads holding our wakers. We wait for all wakers to be dropped.
-                        engine.begin_shutdown();
-
-                        // The I/O driver itself does not have a shutdown process - we simply need
-                        // to wait for all pending operations to complete. This will occur naturally
-                        // over time, speeded up by the fact that the async task engine dropped a
-                        // bunch of tasks that were hopefully holding I/O handles that now got
-                        // closed and resulted in pending I/O being canceled (which we still need to
-                        // wait for - a cancellation is just a regular I/O completion for us).
+                    if !self.terminate_requested.get() {
+                        self.terminate_requested.set(true);
+                        self.start_shutdown_flushes();
+                        allow_io_sleep = false;
                     }
                 }
             }
 
+            // Buffered data must be written out before we start dropping tasks, as the buffers may
+            // be owned by those tasks. Therefore we only start shutting down once all the shutdown
+            // flushes have completed.
+            if self.terminate_requested.get()
+                && !self.shutting_down.get()
+                && self.pending_shutdown_flushes.get() == 0
+            {
+                // This *starts* our shutdown - we still need to wait for the async task
+                // engine to clean up and for pending I/O operations to complete.
+                event!(
+                    Level::TRACE,
+                    "received terminate command; shutdown process starting"
+                );
+
+                self.shutting_down.set(true);
+
+                // The tasks in this list may own resources that are already referenced by other
+                // tasks or external entities. We need to accept them into our regular process
+                // before dropping them - they are not safe to drop just because they are new.
+                while let Some((erased_task, priority)) = self.new_tasks.borrow_mut().pop_front() {
+                    engine.enqueue_erased(erased_task, priority);
+                }
+
+                // Start cleaning up the async task engine. This may require some time if there
+                // are foreign threads holding our wakers. We wait for all wakers to be dropped.
+                engine.begin_shutdown();
+
+                // The I/O driver itself does not have a shutdown process - we simply need
+                // to wait for all pending operations to complete. This will occur naturally
+                // over time, speeded up by the fact that the async task engine dropped a
+                // bunch of tasks that were hopefully holding I/O handles that now got
+                // closed and resulted in pending I/O being canceled (which we still need to
+                // wait for - a cancellation is just a regular I/O completion for us).
+            }
+
             // If new tasks have been enqueued but not yet handed over to the engine, we inhibit I/O
             // sleep to get to processing those new tasks ASAP after any pending I/O is completed.
             allow_io_sleep &= self.new_tasks.borrow().is_empty();
@@ -269,7 +392,7 @@ impl AsyncAgent {
             let io_wait_time_ms = if allow_io_sleep {
                 CYCLES_WITH_SLEEP.with(Event::observe_unit);
 
-                CROSS_THREAD_WORK_POLL_INTERVAL_MS
+                io_wait_time_until_next_timer(self.cross_thread_poll_interval_ms)
             } else {
                 CYCLES_WITHOUT_SLEEP.with(Event::observe_unit);
 
@@ -305,8 +428,8 @@ impl AsyncAgent {
             {
                 let mut new_tasks = self.new_tasks.borrow_mut();
 
-                while let Some(erased_task) = new_tasks.pop_front() {
-                    engine.enqueue_erased(erased_task);
+                while let Some((erased_task, priority)) = new_tasks.pop_front() {
+                    engine.enqueue_erased(erased_task, priority);
                 }
             }
 
@@ -356,6 +479,17 @@ impl AsyncAgent {
                 "the shared I/O driver must still be there because it is only removed on shutdown",
             );
 
+            // Operations still in flight may be waiting for something that never happens now
+            // that their tasks are gone (e.g. data arriving on a pipe), so we cancel them instead.
+            // SAFETY: All tasks have been dropped, so no operations are being prepared.
+            unsafe {
+                io.shut_down();
+            }
+
+            // The shared driver may still be used by tasks of other async worker threads, which
+            // are shutting down as well. Their new operations are rejected from now on.
+            io_shared.shut_down();
+
             event!(
                 Level::TRACE,
                 "waiting for isolated and shared I/O drivers to complete pending operations"
@@ -365,7 +499,7 @@ impl AsyncAgent {
             // Really, there is nothing else to do because task execution logic has been shut down
             // already.
             while !io.is_inert() || !io_shared.is_inert() {
-                io.process_completions(CROSS_THREAD_WORK_POLL_INTERVAL_MS);
+                io.process_completions(self.cross_thread_poll_interval_ms);
                 io_shared.process_completions();
 
                 // I/O completions could trigger wakeups of other threads.
@@ -385,6 +519,31 @@ impl AsyncAgent {
         }
     }
 
+    /// Starts a task for each registered shutdown flush, to be completed before we shut down.
+    fn start_shutdown_flushes(&self) {
+        let flushes = std::mem::take(&mut *self.shutdown_flushes.borrow_mut());
+
+        if !flushes.is_empty() {
+            event!(
+                Level::TRACE,
+                message = "executing shutdown flushes",
+                count = flushes.len()
+            );
+        }
+
+        for flush_fn in flushes.into_values() {
+            let pending = Rc::clone(&self.pending_shutdown_flushes);
+            pending.set(pending.get() + 1);
+
+            let flush = flush_fn();
+
+            _ = self.spawn(async move {
+                flush.await;
+                pending.set(pending.get() - 1);
+            });
+        }
+    }
+
     fn process_commands(&self) -> ProcessCommandsResult {
         let mut received_commands = false;
         let mut received_terminate = false;
@@ -397,7 +556,7 @@ impl AsyncAgent {
                     // because remote tasks are expected to always be inert (they hold no resources
                     // that need special cleanup, at least not yet, because they have no local
                     // presence yet).
-                    if self.shutting_down.get() || received_terminate {
+                    if self.terminate_requested.get() || received_terminate {
                         assert!(
                             erased_task.is_inert(),
                             "all remote tasks must be always inert"
@@ -407,7 +566,10 @@ impl AsyncAgent {
 
                     received_commands = true;
                     REMOTE_TASKS.with(Event::observe_unit);
-                    self.new_tasks.borrow_mut().push_back(erased_task);
+                    // Remote tasks do not carry a priority.
+                    self.new_tasks
+                        .borrow_mut()
+                        .push_back((erased_task, TaskPriority::default()));
                 }
                 Ok(AsyncAgentCommand::Terminate) => {
                     // We continue processing commands even after the terminate signal because
@@ -436,11 +598,27 @@ impl AsyncAgent {
     }
 }
 
-/// How often to poll for cross-thread work, in milliseconds. We do not have cross-thread real time
-/// signals for everything (though we have for most things, just not always guaranteed) and use
-/// polling to check for arriving work. This sets our maximum sleep time, although
-/// we will often check much more often if activity on the current thread wakes us up.
-const CROSS_THREAD_WORK_POLL_INTERVAL_MS: u32 = 10;
+/// How long we may wait for I/O without missing the next timer of the current thread, in
+/// milliseconds. Rounded up, as waking up just before the timer is due would be a wasted cycle.
+fn io_wait_time_until_next_timer(poll_interval_ms: u32) -> u32 {
+    io_wait_time_until(next_local_timer(), Instant::now(), poll_interval_ms)
+}
+
+/// How long we may wait for I/O at `now` without missing a timer that fires at `next_timer`, in
+/// milliseconds. Without a timer, we still wake up every `poll_interval_ms` to poll for
+/// cross-thread work.
+fn io_wait_time_until(next_timer: Option<Instant>, now: Instant, poll_interval_ms: u32) -> u32 {
+    let Some(next_timer) = next_timer else {
+        return poll_interval_ms;
+    };
+
+    let until_next_timer = next_timer.saturating_duration_since(now);
+
+    until_next_timer
+        .as_nanos()
+        .div_ceil(1_000_000)
+        .min(poll_interval_ms as u128) as u32
+}
 
 impl Debug for AsyncAgent {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
@@ -521,3 +699,58 @@ thread_local! {
         .build()
         .unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLL_INTERVAL_MS: u32 = 10;
+
+    #[test]
+    fn io_wait_without_timer_is_poll_interval() {
+        assert_eq!(
+            io_wait_time_until(None, Instant::now(), POLL_INTERVAL_MS),
+            POLL_INTERVAL_MS
+        );
+    }
+
+    #[test]
+    fn io_wait_ends_when_timer_is_due() {
+        let now = Instant::now();
+
+        // Rounded up to whole milliseconds, so we never wake up before the timer is due.
+        assert_eq!(
+            io_wait_time_until(
+                Some(now + Duration::from_micros(3_200)),
+                now,
+                POLL_INTERVAL_MS
+            ),
+            4
+        );
+        assert_eq!(
+            io_wait_time_until(Some(now + Duration::from_millis(3)), now, POLL_INTERVAL_MS),
+            3
+        );
+    }
+
+    #[test]
+    fn io_wait_for_overdue_timer_is_zero() {
+        let now = Instant::now();
+
+        assert_eq!(io_wait_time_until(Some(now), now, POLL_INTERVAL_MS), 0);
+        assert_eq!(
+            io_wait_time_until(Some(now), now + Duration::from_millis(5), POLL_INTERVAL_MS),
+            0
+        );
+    }
+
+    #[test]
+    fn io_wait_for_distant_timer_is_poll_interval() {
+        let now = Instant::now();
+
+        assert_eq!(
+            io_wait_time_until(Some(now + Duration::from_secs(60)), now, POLL_INTERVAL_MS),
+            POLL_INTERVAL_MS
+        );
+    }
+}
@@ -0,0 +1,73 @@
+use crate::rt::spawn;
+use std::{fmt, future::Future};
+
+/// Runs an async cleanup action when the guard is dropped, for example to flush or close some
+/// resource when a scope exits, whether by returning normally or by returning early with an error.
+///
+/// As `Drop` cannot be async, the cleanup is spawned as a detached task on the current async
+/// worker thread when the guard is dropped. The task is owned by the runtime, so it will run to
+/// completion even though nobody awaits it.
+///
+/// # Limitations
+///
+/// The cleanup does not run synchronously as part of the drop - it merely starts after the guard
+/// has been dropped, at some point when the async worker gets to it. Code that follows the scope
+/// exit cannot assume that the cleanup has already happened.
+///
+/// If the runtime is shutting down when the guard is dropped, the cleanup may not run at all.
+///
+/// # Panics
+///
+/// Dropping an armed guard panics if the current thread is not an async worker thread owned by a
+/// Folo runtime.
+pub struct AsyncDropGuard<FN, F>
+where
+    FN: FnOnce() -> F,
+    F: Future<Output = ()> + 'static,
+{
+    // Becomes None when the guard is canceled or the cleanup has been spawned.
+    cleanup: Option<FN>,
+}
+
+impl<FN, F> AsyncDropGuard<FN, F>
+where
+    FN: FnOnce() -> F,
+    F: Future<Output = ()> + 'static,
+{
+    /// Creates a guard that will spawn the future returned by `cleanup` when dropped.
+    pub fn new(cleanup: FN) -> Self {
+        Self {
+            cleanup: Some(cleanup),
+        }
+    }
+
+    /// Consumes the guard without running the cleanup action.
+    pub fn cancel(mut self) {
+        self.cleanup = None;
+    }
+}
+
+impl<FN, F> Drop for AsyncDropGuard<FN, F>
+where
+    FN: FnOnce() -> F,
+    F: Future<Output = ()> + 'static,
+{
+    fn drop(&mut self) {
+        if let Some(cleanup) = self.cleanup.take() {
+            // The task continues even though we drop the join handle.
+            _ = spawn(cleanup());
+        }
+    }
+}
+
+impl<FN, F> fmt::Debug for AsyncDropGuard<FN, F>
+where
+    FN: FnOnce() -> F,
+    F: Future<Output = ()> + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncDropGuard")
+            .field("armed", &self.cleanup.is_some())
+            .finish()
+    }
+}
@@ -3,17 +3,22 @@ use crate::{
     constants::{GENERAL_MILLISECONDS_BUCKETS, POISONED_LOCK},
     io::IO_DEQUEUE_BATCH_SIZE,
     mem::{DropPolicy, PinnedSlabChain},
-    metrics::{Event, EventBuilder},
-    rt::{erased_async_task::ErasedResultAsyncTask, waker::WakeSignal},
+    metrics::{Event, EventBuilder, Magnitude},
+    rt::{
+        erased_async_task::ErasedResultAsyncTask,
+        run_queue::{RunQueue, TaskPriority},
+        waker::WakeSignal,
+    },
     time::LowPrecisionInstant,
 };
 use negative_impl::negative_impl;
 use pin_project::pin_project;
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
     collections::{HashSet, VecDeque},
     fmt::{self, Debug, Formatter},
     pin::Pin,
+    rc::Rc,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc, Mutex,
@@ -68,8 +73,9 @@ pub struct AsyncTaskEngine {
     // The active set contains all the tasks we want to poll. This is where all futures start.
     // The items are pinned pointers into the `tasks` collection.
     //
-    // This is a VedDeque because we do not require set characteristics and a deque is faster.
-    active: VecDeque<*mut Task>,
+    // This is a queue ordered by task priority because we do not require set characteristics and
+    // we want to poll higher priority tasks first.
+    active: RunQueue<*mut Task>,
 
     // The inactive set contains all the tasks that are sleeping. We will move them back to the
     // active set after a waker notifies us that a future needs to wake up. Note that the wakeup
@@ -81,7 +87,13 @@ pub struct AsyncTaskEngine {
     // The items are pinned pointers into the `tasks` collection.
     inactive: HashSet<*mut Task, BuildPointerHasher>,
 
-    // The primary mechanism used to signal that a task has awoken and needs to be moved from the
+    // Tasks that have been awakened by a waker called on the current thread, which is the most
+    // common case (e.g. a task yielding or waking up another local task). As only this thread
+    // accesses the queue, no synchronization is needed and we can freely allocate. Boxed because
+    // the wake signals of our tasks point to it, so it must not move when the engine does.
+    local_awakened: Box<RefCell<VecDeque<*mut Task>>>,
+
+    // The primary mechanism used by other threads to signal that a task has awoken and needs to be moved from the
     // inactive queue to the active queue. We ONLY add entries to this list if we can do so without
     // waiting on the lock, to minimize time we spend blocked on cross-thread synchronization. We
     // also only add entries if we do not need to increase the capacity, to avoid allocating the new
@@ -103,6 +115,16 @@ pub struct AsyncTaskEngine {
     // The items are pinned pointers into the `tasks` collection.
     completed: VecDeque<*mut Task>,
 
+    // Inert tasks whose storage is reused for new tasks, to avoid removing them from `tasks` only to
+    // insert new tasks right after. Their wake signals are reset when they are reused. At most
+    // `MAX_RECYCLED_TASKS` are kept, the rest are removed as usual.
+    // The items are pinned pointers into the `tasks` collection.
+    recycled: Vec<*mut Task>,
+
+    // Number of tasks that have been enqueued but have not yet completed. Shared with the owner of
+    // the engine, which can read it even while the engine is busy polling tasks.
+    live_tasks: Rc<Cell<usize>>,
+
     // In shutdown mode, all tasks are considered completed and the only thing we do is wait for
     // them to become inert (which may be driven by uncontrollable actions of foreign threads).
     shutting_down: bool,
@@ -119,23 +141,30 @@ pub struct AsyncTaskEngine {
 // accounting for the possibility that we have a huge batch of IO completions + some random wakes.
 const AWAKENED_CAPACITY: usize = IO_DEQUEUE_BATCH_SIZE + 100;
 
+// Tasks tend to come and go in bursts, so we keep enough inert tasks around to absorb a typical
+// burst without returning the storage to the slab and claiming it again.
+const MAX_RECYCLED_TASKS: usize = 128;
+
 impl AsyncTaskEngine {
     /// # Safety
     ///
     /// You must receive the `CycleResult::Shutdown` result before it is safe to drop the engine.
-    pub unsafe fn new() -> Self {
+    pub unsafe fn new(live_tasks: Rc<Cell<usize>>) -> Self {
         Self {
             // We use MustNotDropItems because the tasks contain elements referenced via raw
             // pointers (e.g. the wake signal) which means their lifetime must be carefully managed.
             // If items are still in the tasks list when the engine is dropped, this indicates that
             // proper cleanup did not happen and other threads may still hold dangling pointers.
             tasks: PinnedSlabChain::new(DropPolicy::MustNotDropItems),
-            active: VecDeque::new(),
+            active: RunQueue::new(),
             inactive: HashSet::with_hasher(BuildPointerHasher::default()),
+            local_awakened: Box::new(RefCell::new(VecDeque::with_capacity(AWAKENED_CAPACITY))),
             #[allow(clippy::arc_with_non_send_sync)] // Clippy false positive? That's a big fat mutex!
             awakened: Arc::new(Mutex::new(VecDeque::with_capacity(AWAKENED_CAPACITY))),
             probe_embedded_wake_signals: Arc::new(AtomicBool::new(false)),
             completed: VecDeque::new(),
+            recycled: Vec::with_capacity(MAX_RECYCLED_TASKS),
+            live_tasks,
             shutting_down: false,
             last_cycle_ended: None,
         }
@@ -144,7 +173,11 @@ impl AsyncTaskEngine {
     /// Enqueues a future whose return type has been erased. It will be polled but no result
     /// will be made available by the async task engine - it is expected that some other mechanism
     /// is used to observe the result.
-    pub fn enqueue_erased(&mut self, erased_task: Pin<Box<dyn ErasedResultAsyncTask>>) {
+    pub fn enqueue_erased(
+        &mut self,
+        erased_task: Pin<Box<dyn ErasedResultAsyncTask>>,
+        priority: TaskPriority,
+    ) {
         // It is possible due to the eventually consistent nature between worker commands that a
         // worker will receive a new task after shutdown has already begun. We expect the worker
         // to perform the necessary filtering to prevent that from ever reaching the task engine.
@@ -153,29 +186,48 @@ impl AsyncTaskEngine {
             "cannot enqueue tasks after shutdown has begun"
         );
 
-        let inserter = self.tasks.begin_insert();
-
-        // SAFETY: We are responsible for not dropping the task until it is inert. We accomplish
-        // this by only removing tasks after they pass through the `completed` list and indicate
-        // that they have become inert. We must also initialize the task with ::initialize() before
-        // it is used.
-        let task = unsafe {
-            Task::new(
-                inserter.index(),
-                erased_task,
-                Arc::clone(&self.awakened),
-                Arc::clone(&self.probe_embedded_wake_signals),
-            )
-        };
+        let task_ptr = if let Some(task_ptr) = self.recycled.pop() {
+            // SAFETY: We know it is pinned because all tasks are always pinned once in
+            // `self.tasks`. Recycled tasks are inert and not in any of our other collections, so
+            // nothing else references the task.
+            let task_pin = unsafe { Pin::new_unchecked(&mut *task_ptr) };
+            task_pin.recycle(erased_task, priority);
 
-        let task_ptr = inserter.insert_raw(task);
+            TASKS_RECYCLED.with(Event::observe_unit);
 
-        // We must initialize it once pinned, to set up the self-referential pointer.
-        // SAFETY: We know it is pinned because all tasks are always pinned once in `self.tasks`.
-        let task_pin = unsafe { Pin::new_unchecked(&mut *task_ptr) };
-        task_pin.initialize();
+            task_ptr
+        } else {
+            let inserter = self.tasks.begin_insert();
+
+            // SAFETY: We are responsible for not dropping the task until it is inert. We accomplish
+            // this by only removing tasks after they pass through the `completed` list and indicate
+            // that they have become inert. We must also initialize the task with ::initialize()
+            // before it is used. The local awakened queue is boxed and the engine outlives its
+            // tasks.
+            let task = unsafe {
+                Task::new(
+                    inserter.index(),
+                    erased_task,
+                    priority,
+                    &*self.local_awakened,
+                    Arc::clone(&self.awakened),
+                    Arc::clone(&self.probe_embedded_wake_signals),
+                )
+            };
+
+            let task_ptr = inserter.insert_raw(task);
+
+            // We must initialize it once pinned, to set up the self-referential pointer.
+            // SAFETY: We know it is pinned because all tasks are always pinned once in
+            // `self.tasks`.
+            let task_pin = unsafe { Pin::new_unchecked(&mut *task_ptr) };
+            task_pin.initialize();
+
+            task_ptr
+        };
 
-        self.active.push_back(task_ptr);
+        self.active.push(priority, task_ptr);
+        self.live_tasks.set(self.live_tasks.get() + 1);
     }
 
     pub fn execute_cycle(&mut self) -> CycleResult {
@@ -195,7 +247,7 @@ impl AsyncTaskEngine {
         // We do not really care why/how the wake signal was sent - same handling for all cases.
         self.activate_awakened_tasks();
 
-        while let Some(task_ptr) = self.active.pop_front() {
+        while let Some(task_ptr) = self.active.pop() {
             // SAFETY: This comes from a pinned slab and we are responsible for dropping tasks, which
             // we never do until they progress through the lifecycle into the `completed` list.
             let task = unsafe { Pin::new_unchecked(&*task_ptr) };
@@ -206,6 +258,7 @@ impl AsyncTaskEngine {
                 task::Poll::Ready(()) => {
                     TASKS_COMPLETED.with(Event::observe_unit);
                     self.completed.push_back(task_ptr);
+                    self.live_tasks.set(self.live_tasks.get() - 1);
                 }
                 task::Poll::Pending => {
                     TASK_INACTIVATED.with(Event::observe_unit);
@@ -240,37 +293,40 @@ impl AsyncTaskEngine {
     fn has_work_to_do(&self) -> bool {
         // Work for us means either a) some task is active; b) a wakeup signal has been received.
         !self.active.is_empty()
+            || !self.local_awakened.borrow().is_empty()
             || !self.awakened.lock().expect(POISONED_LOCK).is_empty()
             || self.probe_embedded_wake_signals.load(Ordering::Relaxed)
     }
 
     // Moves any awakened tasks into the active set. Returns whether any tasks were moved.
     fn activate_awakened_tasks(&mut self) {
-        // There are two ways to activate tasks:
+        // There are three ways to activate tasks:
         // 1. by probing the embedded wake signals.
-        // 2. by receiving an explicit wake signal via the `awakened` set.
+        // 2. by receiving an explicit wake signal via the `local_awakened` queue.
+        // 3. by receiving an explicit wake signal via the `awakened` queue.
         //
-        // Note that the same task may be awakened via both channels simultaneously, and that
+        // Note that the same task may be awakened via multiple channels simultaneously, and that
         // explicit wake signals may be sent when the task is already active (the signal
         // may come from some caller who has no idea if it is already awake or not).
 
+        {
+            // Activating a task does not call any wakers, so nothing can try to use the queue
+            // while we hold the borrow.
+            let mut local_awakened = self.local_awakened.borrow_mut();
+
+            while let Some(task_ptr) = local_awakened.pop_front() {
+                Self::activate(&mut self.inactive, &mut self.active, task_ptr);
+            }
+        }
+
         {
             // Hard lock here - hopefully any competing threads do not hold it too long.
-            // 99% of notifications will be from the same thread, so this should be low cost.
+            // Most notifications from the same thread go via the local queue instead.
             let mut awakened = self.awakened.lock().expect(POISONED_LOCK);
 
             // We copy here so the loop does not keep a reference to the awakened set.
             while let Some(task_ptr) = awakened.pop_front() {
-                // It is theoretically possible for a completed task to be awakened, in which case
-                // we do nothing. We detect this by ensuring that the task was in the "inactive" set
-                // before we react to the wake notification. This also eliminates spurious wakes.
-                if self.inactive.remove(&task_ptr) {
-                    self.active.push_back(task_ptr);
-
-                    TASK_ACTIVATED_VIA_SET.with(Event::observe_unit);
-                } else {
-                    TASK_ACTIVATED_SPURIOUS.with(Event::observe_unit);
-                }
+                Self::activate(&mut self.inactive, &mut self.active, task_ptr);
             }
         }
 
@@ -285,7 +341,7 @@ impl AsyncTaskEngine {
 
                 if task.wake_signal.consume_awakened() {
                     TASK_ACTIVATED_VIA_SIGNAL.with(Event::observe_unit);
-                    self.active.push_back(*task_ptr);
+                    self.active.push(task.priority, *task_ptr);
                     false
                 } else {
                     true
@@ -294,6 +350,32 @@ impl AsyncTaskEngine {
         }
     }
 
+    fn activate(
+        inactive: &mut HashSet<*mut Task, BuildPointerHasher>,
+        active: &mut RunQueue<*mut Task>,
+        task_ptr: *mut Task,
+    ) {
+        // It is theoretically possible for a completed task to be awakened, in which case we do
+        // nothing. We detect this by ensuring that the task was in the "inactive" set before we
+        // react to the wake notification. This also eliminates spurious wakes, including a task
+        // being woken up through multiple channels, so a task is never activated twice.
+        //
+        // A wake-up of a completed task may also be dequeued after the task has been recycled, in
+        // which case it activates the new task that reuses the storage. This is merely a spurious
+        // poll of the new task, which is harmless.
+        if inactive.remove(&task_ptr) {
+            // SAFETY: This comes from a pinned slab and we are responsible for dropping tasks,
+            // which we never do until they progress through the lifecycle into the `completed`
+            // list.
+            let priority = unsafe { (*task_ptr).priority };
+            active.push(priority, task_ptr);
+
+            TASK_ACTIVATED_VIA_SET.with(Event::observe_unit);
+        } else {
+            TASK_ACTIVATED_SPURIOUS.with(Event::observe_unit);
+        }
+    }
+
     fn drop_inert_tasks(&mut self) {
         self.completed.retain(|task_ptr| {
             // SAFETY: This comes from a pinned slab and we are responsible for dropping tasks, which
@@ -303,8 +385,12 @@ impl AsyncTaskEngine {
             let is_inert = task.is_inert();
 
             if is_inert {
-                TASKS_DROPPED.with(Event::observe_unit);
-                self.tasks.remove(task.index);
+                if !self.shutting_down && self.recycled.len() < MAX_RECYCLED_TASKS {
+                    self.recycled.push(*task_ptr);
+                } else {
+                    TASKS_DROPPED.with(Event::observe_unit);
+                    self.tasks.remove(task.index);
+                }
             }
 
             !is_inert
@@ -321,9 +407,21 @@ impl AsyncTaskEngine {
 
         self.shutting_down = true;
 
+        // Recycled tasks are already inert, so we can release them right away.
+        for task_ptr in self.recycled.drain(..) {
+            // SAFETY: This comes from a pinned slab and we are responsible for dropping tasks,
+            // which we never do until they progress through the lifecycle into the `completed`
+            // list and then to the `recycled` list.
+            let index = unsafe { (*task_ptr).index };
+
+            TASKS_DROPPED.with(Event::observe_unit);
+            self.tasks.remove(index);
+        }
+
         // All tasks are considered completed - we never poll them again.
         TASKS_CANCELED_ON_SHUTDOWN
             .with(|x| x.observe((self.active.len() + self.inactive.len()) as i64));
+        self.live_tasks.set(0);
 
         // We call `clear()` on all tasks that we are canceling. This will drop the maximum amount
         // of internal state such as any captured variables that may be holding on to join handles
@@ -335,7 +433,7 @@ impl AsyncTaskEngine {
         // We call .count() to force the iterator to be evaluated. We do not care about the count.
         _ = self
             .active
-            .drain(..)
+            .drain()
             .chain(self.inactive.drain())
             .map(|task_ptr| {
                 // SAFETY: This comes from a pinned slab and we are responsible for dropping tasks, which
@@ -393,6 +491,9 @@ pub(super) struct Task {
     // Used for dropping the task once we are done with it.
     index: usize,
 
+    // Determines the order in which the task is polled relative to other ready tasks.
+    priority: TaskPriority,
+
     #[pin]
     wake_signal: WakeSignal,
 }
@@ -402,19 +503,44 @@ impl Task {
     ///
     /// The task must be initialized via ::initialize() once it has been pinned.
     /// The task must not be dropped until it is inert.
+    /// The local awakened queue must outlive the task.
     unsafe fn new(
         index: usize,
         inner: Pin<Box<dyn ErasedResultAsyncTask>>,
+        priority: TaskPriority,
+        local_awakened_queue: *const RefCell<VecDeque<*mut Task>>,
         awakened_queue: Arc<Mutex<VecDeque<*mut Task>>>,
         probe_embedded_wake_signals: Arc<AtomicBool>,
     ) -> Self {
         Self {
             inner: RefCell::new(inner),
             index,
-            wake_signal: WakeSignal::new(awakened_queue, probe_embedded_wake_signals),
+            priority,
+            wake_signal: WakeSignal::new(
+                local_awakened_queue,
+                awakened_queue,
+                probe_embedded_wake_signals,
+            ),
         }
     }
 
+    /// Reuses the storage of an inert task for a new task, which replaces the completed one. The
+    /// task keeps its place in the slab, so the self-referential pointer remains valid.
+    fn recycle(
+        self: Pin<&mut Self>,
+        inner: Pin<Box<dyn ErasedResultAsyncTask>>,
+        priority: TaskPriority,
+    ) {
+        let this = self.project();
+
+        // This drops the completed task, which is inert so nothing references it anymore.
+        *this.inner.get_mut() = inner;
+        *this.priority = priority;
+
+        // Wake-ups of the completed task may still be queued - see `WakeSignal::reset()`.
+        this.wake_signal.reset();
+    }
+
     /// The task is self-referential, so must be initialized once pinned.
     fn initialize(self: Pin<&mut Self>) {
         // SAFETY: We are not unpinning anything here, just writing some harmless pointers.
@@ -430,9 +556,20 @@ impl Task {
 
         let mut context = task::Context::from_waker(waker);
 
+        // More than one wake-up per poll means wakers are being signaled redundantly, e.g. by a
+        // future that keeps waking itself up.
+        WAKES_PER_POLL.with(|x| x.observe(self.wake_signal.consume_wake_count() as Magnitude));
+
+        self.wake_signal.begin_poll();
+
         // We are only accessing the erased task in poll() which is only called by the current
         // thread and never recursively, so we are not at risk of conflicting borrows.
-        self.inner.borrow_mut().as_mut().poll(&mut context)
+        let result = self.inner.borrow_mut().as_mut().poll(&mut context);
+
+        // If the task was woken up during the poll, this reschedules it for the next cycle.
+        self.wake_signal.end_poll(result.is_pending());
+
+        result
     }
 
     fn is_inert(&self) -> bool {
@@ -443,6 +580,7 @@ impl Task {
 impl Debug for Task {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         f.debug_struct("Task")
+            .field("priority", &self.priority)
             .field("wake_signal", &self.wake_signal)
             .finish()
     }
@@ -484,6 +622,17 @@ thread_local! {
         .build()
         .unwrap();
 
+    static TASKS_RECYCLED: Event = EventBuilder::new()
+        .name("rt_async_tasks_recycled")
+        .build()
+        .unwrap();
+
+    static WAKES_PER_POLL: Event = EventBuilder::new()
+        .name("rt_async_task_wakes_per_poll")
+        .buckets(&[0, 1, 2, 4, 8])
+        .build()
+        .unwrap();
+
     static CYCLE_INTERVAL: Event = EventBuilder::new()
         .name("rt_async_cycle_interval_millis")
         .buckets(GENERAL_MILLISECONDS_BUCKETS)
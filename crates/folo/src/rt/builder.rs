@@ -1,28 +1,55 @@
 use std::collections::HashMap;
-use std::fmt::{self, Debug, Formatter};
+use std::env::{self, VarError};
+use std::fmt::{self, Debug, Display, Formatter};
+use std::num::NonZeroUsize;
 use std::rc::Rc;
+use std::str::FromStr;
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 use std::thread;
+use std::time::Duration;
 
 use crossbeam::channel;
 use crossbeam::queue::SegQueue;
 use tracing::{event, Level};
 
 use super::sync_agent::{SyncAgent, SyncAgentCommand};
-use super::{current_sync_agent, ErasedSyncTask};
+use super::{current_sync_agent, poll_depth, ErasedSyncTask};
+use crate::fs::WriteBufferBudget;
 use crate::io::{self, IoWaker};
+use crate::mem::NumaPlacement;
 use crate::metrics::ReportPage;
 use crate::rt::async_agent::{AsyncAgent, AsyncAgentCommand};
 use crate::rt::{current_async_agent, current_runtime, CoreClient, RuntimeClient};
 
+/// Environment variable that sets the number of async worker threads the runtime starts.
+/// See `RuntimeBuilder::worker_threads()`.
+pub const ENV_WORKER_THREADS: &str = "FOLO_WORKER_THREADS";
+
+/// Environment variable that sets how many I/O completion notifications an async worker thread
+/// dequeues at once. See `RuntimeBuilder::io_dequeue_batch_size()`.
+pub const ENV_IO_BATCH_SIZE: &str = "FOLO_IO_BATCH_SIZE";
+
+/// Environment variable that limits the number of file I/O operations in flight per async worker
+/// thread. See `RuntimeBuilder::max_in_flight_io()`.
+pub const ENV_MAX_INFLIGHT_IO: &str = "FOLO_MAX_INFLIGHT_IO";
+
 /// The thing with synchronous worker threads is that they often get blocked and spend time doing
 /// essentially nothing due to offloading blocking I/O onto these threads. Therefore, we spawn many
 /// of them to ensure that we can keep processing synchronous work when a large batch comes in.
 /// In the future we might replace this with a more dynamically sizing thread pool but for now the
-/// fixed size might be acceptable.
+/// fixed size might be acceptable. Can be changed via `RuntimeBuilder::sync_workers_per_processor()`.
 const SYNC_WORKERS_PER_PROCESSOR: usize = 2;
 
+/// By default, every task spawned onto an async worker thread from another thread wakes up the
+/// worker (though wakeups posted before the worker gets around to processing them are coalesced).
+/// Can be changed via `RuntimeBuilder::spawn_wake_batch_size()`.
+const SPAWN_WAKE_BATCH_SIZE: usize = 1;
+
+/// The longest an idle async worker thread sleeps before checking for cross-thread work that did
+/// not wake it up. Can be changed via `RuntimeBuilder::spawn_flush_interval()`.
+const SPAWN_FLUSH_INTERVAL: Duration = Duration::from_millis(10);
+
 struct ThreadStartResult<AgentReady, R> {
     join_handle: std::thread::JoinHandle<()>,
     start_tx: oneshot::Sender<AgentStartArguments>,
@@ -35,6 +62,19 @@ pub struct RuntimeBuilder {
     ad_hoc_entrypoint: bool,
     metrics_tx: Option<channel::Sender<ReportPage>>,
     max_processors: Option<usize>,
+    worker_threads: Option<usize>,
+    io_priority_policy: io::IoPriorityPolicy,
+    buffer_numa_placement: NumaPlacement,
+    max_poll_depth: Option<usize>,
+    max_in_flight_io: Option<usize>,
+    slow_io: Option<io::SlowIoHook>,
+    verify_io_completions: bool,
+    completion_concurrency: Option<usize>,
+    max_buffered_write_bytes: Option<usize>,
+    io_dequeue_batch_size: usize,
+    sync_workers_per_processor: usize,
+    spawn_wake_batch_size: usize,
+    spawn_flush_interval: Duration,
 }
 
 impl RuntimeBuilder {
@@ -44,9 +84,60 @@ impl RuntimeBuilder {
             ad_hoc_entrypoint: false,
             metrics_tx: None,
             max_processors: None,
+            worker_threads: None,
+            io_priority_policy: io::IoPriorityPolicy::default(),
+            buffer_numa_placement: NumaPlacement::Local,
+            max_poll_depth: None,
+            max_in_flight_io: None,
+            slow_io: None,
+            verify_io_completions: false,
+            completion_concurrency: None,
+            max_buffered_write_bytes: None,
+            io_dequeue_batch_size: io::IO_DEQUEUE_BATCH_SIZE,
+            sync_workers_per_processor: SYNC_WORKERS_PER_PROCESSOR,
+            spawn_wake_batch_size: SPAWN_WAKE_BATCH_SIZE,
+            spawn_flush_interval: SPAWN_FLUSH_INTERVAL,
         }
     }
 
+    /// Creates a builder with defaults read from environment variables, allowing operators to tune
+    /// the runtime without recompiling. Options set explicitly on the returned builder override
+    /// the values read from the environment.
+    ///
+    /// Supported environment variables:
+    ///
+    /// * `FOLO_WORKER_THREADS` - see `worker_threads()`.
+    /// * `FOLO_IO_BATCH_SIZE` - see `io_dequeue_batch_size()`.
+    /// * `FOLO_MAX_INFLIGHT_IO` - see `max_in_flight_io()`.
+    ///
+    /// Environment variables that are not set are ignored. Returns an error if a variable is set
+    /// to a value that is not valid for the corresponding option.
+    pub fn from_env() -> io::Result<Self> {
+        Self::from_lookup(|name| env::var(name))
+    }
+
+    /// Creates a builder with defaults read via `lookup`, which behaves like `std::env::var()`.
+    fn from_lookup<F>(lookup: F) -> io::Result<Self>
+    where
+        F: Fn(&str) -> Result<String, VarError>,
+    {
+        let mut builder = Self::new();
+
+        if let Some(worker_threads) = parse_var::<NonZeroUsize>(&lookup, ENV_WORKER_THREADS)? {
+            builder = builder.worker_threads(worker_threads.get());
+        }
+
+        if let Some(batch_size) = parse_var::<NonZeroUsize>(&lookup, ENV_IO_BATCH_SIZE)? {
+            builder = builder.io_dequeue_batch_size(batch_size.get());
+        }
+
+        if let Some(max_in_flight_io) = parse_var::<NonZeroUsize>(&lookup, ENV_MAX_INFLIGHT_IO)? {
+            builder = builder.max_in_flight_io(max_in_flight_io.get());
+        }
+
+        Ok(builder)
+    }
+
     /// Registers a function to call when initializing every created worker thread.
     pub fn worker_init<F>(mut self, f: F) -> Self
     where
@@ -86,6 +177,151 @@ impl RuntimeBuilder {
         self
     }
 
+    /// Sets the exact number of async worker threads the runtime starts, each pinned to its own
+    /// processor and with its own I/O driver. The threads use the first N processors of the
+    /// system. By default, there is one async worker thread per processor.
+    ///
+    /// Unlike `max_processors()`, building the runtime fails if the system does not have enough
+    /// processors to give each thread its own, so the topology is exactly as requested.
+    pub fn worker_threads(mut self, worker_threads: usize) -> Self {
+        self.worker_threads = Some(worker_threads);
+        self
+    }
+
+    /// Sets the order in which each async worker thread dispatches the I/O completions it receives
+    /// in one batch. For example, this can be used to dispatch network completions ahead of disk
+    /// completions or high priority operations ahead of bulk operations. By default, completions
+    /// are dispatched in the order they are received.
+    pub fn io_priority_policy(mut self, policy: io::IoPriorityPolicy) -> Self {
+        self.io_priority_policy = policy;
+        self
+    }
+
+    /// Sets which NUMA nodes the memory of the buffer pool of each worker thread comes from (see
+    /// `PinnedBuffer::from_pool()`). By default, each worker thread allocates its buffers on the
+    /// NUMA node of the processor it is pinned to, minimizing cross-node memory traffic during
+    /// I/O. On systems with a single NUMA node, this has no effect.
+    ///
+    /// The placement applies to buffers the worker thread allocates after `worker_init()` returns.
+    pub fn buffer_numa_placement(mut self, placement: NumaPlacement) -> Self {
+        self.buffer_numa_placement = placement;
+        self
+    }
+
+    /// Limits how deeply `PollDepthGuard`s may be nested when polled on async worker threads.
+    /// When the limit is exceeded, the guard completes with `PollDepthExceeded` instead of polling
+    /// its inner future. By default, there is no limit.
+    pub fn max_poll_depth(mut self, max_poll_depth: usize) -> Self {
+        self.max_poll_depth = Some(max_poll_depth);
+        self
+    }
+
+    /// Limits the number of file I/O operations that each async worker thread keeps in flight at
+    /// the same time. Operations over the limit are queued and submitted as earlier operations
+    /// complete, in order of their `IoPriority`. By default, there is no limit.
+    ///
+    /// Whether limited or not, if the operating system rejects operations due to resource
+    /// exhaustion, fewer operations are admitted until the ones in flight start completing.
+    pub fn max_in_flight_io(mut self, max_in_flight_io: usize) -> Self {
+        self.max_in_flight_io = Some(max_in_flight_io);
+        self
+    }
+
+    /// Registers a function to call whenever an I/O operation takes longer than `threshold` to
+    /// complete, measured from submission to the operating system until the completion
+    /// notification is received. Operations that complete immediately are never reported.
+    ///
+    /// The callback is invoked on the async worker thread that processes the completion, in the
+    /// middle of processing I/O, so it must be cheap - do not block or perform heavy work in it.
+    pub fn on_slow_io<F>(mut self, threshold: Duration, callback: F) -> Self
+    where
+        F: Fn(&io::SlowIoOperation) + Send + Sync + 'static,
+    {
+        self.slow_io = Some(io::SlowIoHook::new(threshold, callback));
+        self
+    }
+
+    /// Enables verification of I/O completion notifications. Before delivering the result of an
+    /// I/O operation, the async worker threads check that the operating system no longer considers
+    /// the operation to be in progress. Notifications for operations that are still in progress
+    /// are set aside (and counted in the `io_spurious_completions_ignored` metrics) and checked
+    /// again each time the worker processes completions, with the result delivered once the
+    /// operating system reports the operation as completed or another notification arrives for it.
+    ///
+    /// This guards against delivering garbage results due to misbehaving drivers or defects in
+    /// native code that posts to the completion ports, at the cost of an extra memory read per
+    /// completion. By default, completion notifications are trusted.
+    pub fn verify_io_completions(mut self) -> Self {
+        self.verify_io_completions = true;
+        self
+    }
+
+    /// Sets how many threads the operating system allows to process completions of multithreaded
+    /// I/O operations (e.g. accepting TCP connections) at the same time. The completions are
+    /// processed by the async worker threads, of which there is one per processor, so a value
+    /// greater than the number of processors has no effect. A lower value reduces the number of
+    /// worker threads woken up to process a burst of completions, at the cost of less parallelism.
+    /// By default, this is the number of processors used by the runtime.
+    ///
+    /// This does not affect thread-isolated I/O, which each async worker thread processes on its
+    /// own completion port with a concurrency of 1.
+    pub fn completion_concurrency(mut self, completion_concurrency: usize) -> Self {
+        self.completion_concurrency = Some(completion_concurrency);
+        self
+    }
+
+    /// Sets the maximum number of I/O completion notifications that each async worker thread
+    /// dequeues from the operating system at a time. A smaller batch lets the worker get back to
+    /// its tasks sooner, reducing latency, while a larger batch reduces the overhead per completion
+    /// for workloads that complete many operations at once. By default, this is
+    /// `io::IO_DEQUEUE_BATCH_SIZE`.
+    ///
+    /// This does not affect multithreaded I/O, which is dequeued in batches of the default size.
+    pub fn io_dequeue_batch_size(mut self, io_dequeue_batch_size: usize) -> Self {
+        self.io_dequeue_batch_size = io_dequeue_batch_size;
+        self
+    }
+
+    /// Sets the number of synchronous worker threads started for each processor, which execute the
+    /// closures passed to `spawn_blocking()` and `spawn_sync()`. This is how many blocking calls
+    /// can be in progress on a processor at the same time before further calls have to wait for a
+    /// free thread. By default, there are two synchronous worker threads per processor.
+    pub fn sync_workers_per_processor(mut self, sync_workers_per_processor: usize) -> Self {
+        self.sync_workers_per_processor = sync_workers_per_processor;
+        self
+    }
+
+    /// Limits the total number of bytes buffered by all the `fs::BufWriter`s of the runtime, on
+    /// all threads. A write that would exceed the limit forces the writer to flush its buffer and
+    /// if that does not free up enough space, the write bypasses the buffer. By default, there is
+    /// no limit.
+    pub fn max_buffered_write_bytes(mut self, max_buffered_write_bytes: usize) -> Self {
+        self.max_buffered_write_bytes = Some(max_buffered_write_bytes);
+        self
+    }
+
+    /// Sets how many tasks spawned onto an async worker thread from other threads are batched
+    /// together before the worker thread is woken up to pick them up. When many tasks are spawned
+    /// in quick succession, a larger batch reduces the number of wakeups posted to the worker
+    /// threads, at the cost of the tasks in an incomplete batch waiting longer to start.
+    ///
+    /// No task is left behind - the tasks of an incomplete batch are picked up whenever the
+    /// worker thread wakes up for another reason, at the latest after `spawn_flush_interval()`.
+    /// By default, every spawned task wakes up the worker thread.
+    pub fn spawn_wake_batch_size(mut self, spawn_wake_batch_size: usize) -> Self {
+        self.spawn_wake_batch_size = spawn_wake_batch_size;
+        self
+    }
+
+    /// Sets the longest an idle async worker thread sleeps before checking for tasks spawned onto
+    /// it from other threads that did not wake it up (see `spawn_wake_batch_size()`). This is also
+    /// the upper bound on how long the worker thread waits for I/O at a time. Rounded up to whole
+    /// milliseconds. By default, this is 10 milliseconds.
+    pub fn spawn_flush_interval(mut self, spawn_flush_interval: Duration) -> Self {
+        self.spawn_flush_interval = spawn_flush_interval;
+        self
+    }
+
     fn start_async_agent(
         &self,
         processor_id: core_affinity::CoreId,
@@ -95,6 +331,14 @@ impl RuntimeBuilder {
     {
         let worker_init = Arc::clone(&self.worker_init);
         let metrics_tx = self.metrics_tx.clone();
+        let io_priority_policy = self.io_priority_policy;
+        let buffer_numa_placement = self.buffer_numa_placement;
+        let max_poll_depth = self.max_poll_depth;
+        let max_in_flight_io = self.max_in_flight_io;
+        let slow_io = self.slow_io.clone();
+        let verify_io_completions = self.verify_io_completions;
+        let io_dequeue_batch_size = self.io_dequeue_batch_size;
+        let spawn_flush_interval = self.spawn_flush_interval;
         let (start_tx, start_rx) = oneshot::channel::<AgentStartArguments>();
         let (ready_tx, ready_rx) = oneshot::channel::<AsyncAgentReady>();
         let (command_tx, command_rx) = channel::unbounded::<AsyncAgentCommand>();
@@ -102,12 +346,26 @@ impl RuntimeBuilder {
         let join_handle = thread::Builder::new()
             .name(format!("async-{}", worker_index))
             .spawn(move || {
+                // We pin the thread to its processor before creating any thread-local state. The
+                // OS allocates physical memory on the NUMA node of the processor that first touches
+                // it, so this ensures the I/O driver structures and the buffer pool of the thread
+                // are allocated from memory local to the processor. On systems with a single NUMA
+                // node, this makes no difference.
+                core_affinity::set_for_current(processor_id);
+                io::set_pool_numa_placement(buffer_numa_placement);
+
                 worker_init();
 
                 let agent = Rc::new(AsyncAgent::new(
                     command_rx,
                     metrics_tx,
                     io_shared,
+                    io_priority_policy,
+                    max_in_flight_io,
+                    slow_io,
+                    verify_io_completions,
+                    io_dequeue_batch_size,
+                    spawn_flush_interval,
                     processor_id,
                 ));
 
@@ -124,9 +382,9 @@ impl RuntimeBuilder {
                     .recv()
                     .expect("runtime startup process failed in infallible code");
 
-                core_affinity::set_for_current(processor_id);
                 current_async_agent::set(Rc::clone(&agent));
                 current_runtime::set(start.runtime_client);
+                poll_depth::set_limit(max_poll_depth);
 
                 agent.run();
             })?;
@@ -148,6 +406,7 @@ impl RuntimeBuilder {
     ) -> std::io::Result<ThreadStartResult<SyncAgentReady, channel::Sender<SyncAgentCommand>>> {
         let worker_init = Arc::clone(&self.worker_init);
         let metrics_tx = self.metrics_tx.clone();
+        let buffer_numa_placement = self.buffer_numa_placement;
         let (start_tx, start_rx) = oneshot::channel::<AgentStartArguments>();
         let (ready_tx, ready_rx) = oneshot::channel::<SyncAgentReady>();
         let (command_tx, command_rx) = channel::unbounded::<SyncAgentCommand>();
@@ -155,6 +414,10 @@ impl RuntimeBuilder {
         let join_handle = thread::Builder::new()
             .name(format!("sync-{}-{}", processor_id.id, worker_index))
             .spawn(move || {
+                // See start_async_agent() for why we pin the thread before doing anything else.
+                core_affinity::set_for_current(processor_id);
+                io::set_pool_numa_placement(buffer_numa_placement);
+
                 (worker_init)();
 
                 let agent = Rc::new(SyncAgent::new(
@@ -175,8 +438,6 @@ impl RuntimeBuilder {
                     .recv()
                     .expect("runtime startup process failed in infallible code");
 
-                core_affinity::set_for_current(processor_id);
-
                 current_sync_agent::set(Rc::clone(&agent));
                 current_runtime::set(start.runtime_client);
 
@@ -206,15 +467,70 @@ impl RuntimeBuilder {
             processor_ids.truncate(max_processors);
         }
 
+        if let Some(worker_threads) = self.worker_threads {
+            if worker_threads == 0 || worker_threads > processor_ids.len() {
+                return Err(io::Error::InvalidOptions(format!(
+                    "worker_threads must be between 1 and {} but was {worker_threads}",
+                    processor_ids.len()
+                )));
+            }
+
+            processor_ids.truncate(worker_threads);
+        }
+
         let processor_ids = processor_ids.into_boxed_slice();
 
         // We will spawn one agent of each type (async + sync) for each processor.
         let processor_count = processor_ids.len();
 
         let async_worker_count = processor_count;
-        let sync_worker_count = SYNC_WORKERS_PER_PROCESSOR * processor_count;
+        let sync_worker_count = self.sync_workers_per_processor * processor_count;
+
+        // Zero would mean "one per processor" to the OS, which is not what the caller would expect
+        // if they limited the runtime to fewer processors.
+        let completion_concurrency = match self.completion_concurrency.unwrap_or(processor_count) {
+            0 => {
+                return Err(io::Error::InvalidOptions(
+                    "completion_concurrency must be at least 1".to_string(),
+                ))
+            }
+            n => u32::try_from(n).unwrap_or(u32::MAX),
+        };
 
-        event!(Level::INFO, processor_count);
+        if self.io_dequeue_batch_size == 0 {
+            return Err(io::Error::InvalidOptions(
+                "io_dequeue_batch_size must be at least 1".to_string(),
+            ));
+        }
+
+        // With a limit of zero, no operation would ever be admitted and all limited I/O would
+        // wait forever.
+        if self.max_in_flight_io == Some(0) {
+            return Err(io::Error::InvalidOptions(
+                "max_in_flight_io must be at least 1".to_string(),
+            ));
+        }
+
+        if self.sync_workers_per_processor == 0 {
+            return Err(io::Error::InvalidOptions(
+                "sync_workers_per_processor must be at least 1".to_string(),
+            ));
+        }
+
+        if self.spawn_wake_batch_size == 0 {
+            return Err(io::Error::InvalidOptions(
+                "spawn_wake_batch_size must be at least 1".to_string(),
+            ));
+        }
+
+        // A worker thread that never sleeps would spin instead of waiting for work.
+        if self.spawn_flush_interval.is_zero() {
+            return Err(io::Error::InvalidOptions(
+                "spawn_flush_interval must be greater than zero".to_string(),
+            ));
+        }
+
+        event!(Level::INFO, processor_count, completion_concurrency);
 
         let mut join_handles = Vec::with_capacity(sync_worker_count + async_worker_count);
         let mut core_processors = HashMap::new();
@@ -222,7 +538,13 @@ impl RuntimeBuilder {
         // SAFETY: The shared I/O driver must be shut down only after all operations have been
         // shut down. The async worker agents guarantee this by ensuring they do not shut down
         // and release the Arc until the driver signals that it has become inert.
-        let io_shared = Arc::new(unsafe { io::DriverShared::new() });
+        let io_shared = Arc::new(unsafe {
+            io::DriverShared::new(
+                completion_concurrency,
+                self.verify_io_completions,
+                self.slow_io.clone(),
+            )
+        });
 
         // # Async workers & Sync workers
 
@@ -252,7 +574,7 @@ impl RuntimeBuilder {
             let mut sync_command_txs = Vec::with_capacity(sync_worker_count);
             let mut sync_ready_rxs = Vec::with_capacity(sync_worker_count);
 
-            for worker_index in 0..SYNC_WORKERS_PER_PROCESSOR {
+            for worker_index in 0..self.sync_workers_per_processor {
                 let ThreadStartResult {
                     join_handle,
                     start_tx,
@@ -291,6 +613,7 @@ impl RuntimeBuilder {
                 sync_command_txs.into_boxed_slice(),
                 sync_task_queue,
                 sync_priority_task_queue,
+                self.spawn_wake_batch_size,
             );
 
             core_processors.insert(processor_id, proc);
@@ -309,6 +632,9 @@ impl RuntimeBuilder {
             processor_ids.clone(),
             join_handles.into_boxed_slice(),
             Arc::clone(&is_stopping),
+            Arc::new(WriteBufferBudget::new(
+                self.max_buffered_write_bytes.unwrap_or(usize::MAX),
+            )),
         );
 
         // In most cases, the entrypoint thread is merely parked. However, for interoperability
@@ -352,6 +678,32 @@ impl Debug for RuntimeBuilder {
     }
 }
 
+/// Reads and parses an environment variable, returning `None` if it is not set.
+fn parse_var<T>(
+    lookup: impl Fn(&str) -> Result<String, VarError>,
+    name: &str,
+) -> io::Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let value = match lookup(name) {
+        Ok(value) => value,
+        Err(VarError::NotPresent) => return Ok(None),
+        Err(VarError::NotUnicode(_)) => {
+            return Err(io::Error::InvalidOptions(format!(
+                "{name} is not valid Unicode"
+            )))
+        }
+    };
+
+    value
+        .trim()
+        .parse()
+        .map(Some)
+        .map_err(|e| io::Error::InvalidOptions(format!("{name} has invalid value '{value}': {e}")))
+}
+
 /// A signal that an async agent is ready to start, providing inputs required for the runtime start.
 #[derive(Debug)]
 struct AsyncAgentReady {
@@ -368,3 +720,54 @@ struct SyncAgentReady {}
 struct AgentStartArguments {
     runtime_client: RuntimeClient,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // All environment manipulation happens in one test, as tests run in parallel.
+    #[test]
+    fn from_env() {
+        let from_vars = |vars: &[(&str, &str)]| {
+            let vars = vars
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<HashMap<_, _>>();
+
+            RuntimeBuilder::from_lookup(|name| vars.get(name).cloned().ok_or(VarError::NotPresent))
+        };
+
+        let builder = from_vars(&[]).unwrap();
+        assert_eq!(builder.worker_threads, None);
+        assert_eq!(builder.io_dequeue_batch_size, io::IO_DEQUEUE_BATCH_SIZE);
+        assert_eq!(builder.max_in_flight_io, None);
+
+        let vars = [
+            (ENV_WORKER_THREADS, "3"),
+            (ENV_IO_BATCH_SIZE, " 16 "),
+            (ENV_MAX_INFLIGHT_IO, "64"),
+        ];
+
+        let builder = from_vars(&vars).unwrap();
+        assert_eq!(builder.worker_threads, Some(3));
+        assert_eq!(builder.io_dequeue_batch_size, 16);
+        assert_eq!(builder.max_in_flight_io, Some(64));
+
+        // Explicit options override the environment.
+        let builder = from_vars(&vars).unwrap().worker_threads(5);
+        assert_eq!(builder.worker_threads, Some(5));
+        assert_eq!(builder.max_in_flight_io, Some(64));
+
+        for invalid in [
+            (ENV_WORKER_THREADS, "lots"),
+            (ENV_WORKER_THREADS, "0"),
+            (ENV_IO_BATCH_SIZE, "0"),
+            (ENV_MAX_INFLIGHT_IO, "-1"),
+        ] {
+            assert!(matches!(
+                from_vars(&[invalid]),
+                Err(io::Error::InvalidOptions(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use crate::{constants::POISONED_LOCK, io::IoWaker, rt::current_async_agent};
+use crossbeam::queue::SegQueue;
+use negative_impl::negative_impl;
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    task::{self, Waker},
+};
+
+/// Creates a channel for sending values from any thread to a task on the current async worker
+/// thread. The receiver is bound to the current thread, while the sender can be cloned and sent
+/// to any thread, including threads not owned by the Folo runtime.
+///
+/// Sending a value wakes up the receiving task via the I/O driver of its thread, so the receiver
+/// does not have to poll for new values.
+///
+/// # Panics
+///
+/// Panics if the current thread is not an async worker thread owned by the Folo runtime.
+pub fn channel<T: Send>() -> (Sender<T>, Receiver<T>) {
+    let io_waker = current_async_agent::with_io(|io| io.waker());
+
+    let shared = Arc::new(Shared {
+        queue: SegQueue::new(),
+        receiver_waker: Mutex::new(None),
+        senders: AtomicUsize::new(1),
+        receiver_alive: AtomicBool::new(true),
+        io_waker,
+    });
+
+    (
+        Sender {
+            shared: Arc::clone(&shared),
+        },
+        Receiver { shared },
+    )
+}
+
+#[derive(Debug)]
+struct Shared<T> {
+    queue: SegQueue<T>,
+
+    // The task waiting in `recv()` for the next value, if any.
+    receiver_waker: Mutex<Option<Waker>>,
+
+    senders: AtomicUsize,
+    receiver_alive: AtomicBool,
+
+    // Delivers the wakeup of the receiving task to the thread that owns the receiver.
+    io_waker: IoWaker,
+}
+
+impl<T> Shared<T> {
+    fn wake_receiver(&self) {
+        let waker = self.receiver_waker.lock().expect(POISONED_LOCK).take();
+
+        if let Some(waker) = waker {
+            self.io_waker.wake_tasks([waker]);
+        }
+    }
+}
+
+/// The sending half of a channel created via `channel()`. Can be cloned to send from multiple
+/// threads.
+#[derive(Debug)]
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Queues a value for the receiver and wakes up the receiving task if it is waiting. This does
+    /// not block.
+    ///
+    /// Returns the value back as an error if the receiver has been dropped.
+    pub fn send(&self, value: T) -> Result<(), T> {
+        if !self.shared.receiver_alive.load(Ordering::Acquire) {
+            return Err(value);
+        }
+
+        self.shared.queue.push(value);
+        self.shared.wake_receiver();
+
+        Ok(())
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::Relaxed);
+
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        // The receiver needs to find out that no more values are coming.
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.shared.wake_receiver();
+        }
+    }
+}
+
+/// The receiving half of a channel created via `channel()`, bound to the async worker thread that
+/// created the channel.
+#[derive(Debug)]
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Receiver<T> {
+    /// Waits for the next value sent via the channel. Returns `None` once all the senders have
+    /// been dropped and all the values sent before that have been received.
+    pub fn recv(&self) -> impl Future<Output = Option<T>> + '_ {
+        Recv { receiver: self }
+    }
+
+    /// Takes the next value sent via the channel if there is one, without waiting.
+    pub fn try_recv(&self) -> Option<T> {
+        self.shared.queue.pop()
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.receiver_alive.store(false, Ordering::Release);
+    }
+}
+
+#[negative_impl]
+impl<T> !Send for Receiver<T> {}
+#[negative_impl]
+impl<T> !Sync for Receiver<T> {}
+
+struct Recv<'r, T> {
+    receiver: &'r Receiver<T>,
+}
+
+impl<T> Future for Recv<'_, T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context) -> task::Poll<Self::Output> {
+        let shared = &self.receiver.shared;
+
+        if let Some(value) = shared.queue.pop() {
+            return task::Poll::Ready(Some(value));
+        }
+
+        *shared.receiver_waker.lock().expect(POISONED_LOCK) = Some(cx.waker().clone());
+
+        // A value may have been sent (or the last sender dropped) after we looked at the queue
+        // but before we registered the waker, in which case nobody is going to wake us up.
+        if let Some(value) = shared.queue.pop() {
+            return task::Poll::Ready(Some(value));
+        }
+
+        if shared.senders.load(Ordering::Acquire) == 0 {
+            // The last sender may have sent a value right before going away.
+            return task::Poll::Ready(shared.queue.pop());
+        }
+
+        task::Poll::Pending
+    }
+}
@@ -0,0 +1,93 @@
+use crate::{
+    io::IoWaker,
+    rt::{current_async_agent, remote_waker::RemoteWaker},
+};
+use futures::{
+    channel::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    Stream, StreamExt,
+};
+use negative_impl::negative_impl;
+use std::{pin::Pin, task};
+
+/// Collects results produced by any number of tasks, which may be running on any thread, for
+/// consumption by a single consumer in the order they arrive.
+///
+/// This is convenient when spawning many tasks whose results are to be processed as soon as they
+/// are available, in no particular order - the alternative being to keep a join handle for each
+/// task and awaiting them in turn.
+///
+/// Producers push results via a `CollectorHandle`, which can be cloned and sent to other threads.
+/// Once all handles have been dropped and all results consumed, `next()` returns `None`.
+///
+/// The consumer must remain on the thread that created it. It is woken up when results arrive,
+/// even if the thread is waiting for I/O at the time.
+#[derive(Debug)]
+pub struct Collector<T> {
+    rx: UnboundedReceiver<T>,
+
+    // If created on an async worker thread, we use this to wake up the thread from I/O sleep when
+    // a result arrives from another thread.
+    io_waker: Option<IoWaker>,
+}
+
+impl<T> Collector<T> {
+    /// Creates a collector together with the first handle for pushing results into it.
+    pub fn new() -> (Self, CollectorHandle<T>) {
+        let (tx, rx) = mpsc::unbounded();
+
+        let collector = Self {
+            rx,
+            io_waker: current_async_agent::try_with_io(|io| io.waker()),
+        };
+
+        (collector, CollectorHandle { tx })
+    }
+
+    /// Waits for the next result. Returns `None` once all handles have been dropped and every
+    /// pushed result has been consumed.
+    pub async fn next(&mut self) -> Option<T> {
+        StreamExt::next(self).await
+    }
+}
+
+impl<T> Stream for Collector<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Option<T>> {
+        match self.io_waker.clone() {
+            None => self.rx.poll_next_unpin(cx),
+            Some(io_waker) => {
+                let composite_waker = RemoteWaker::new(io_waker, cx.waker().clone()).into();
+                let mut composite_cx = task::Context::from_waker(&composite_waker);
+                self.rx.poll_next_unpin(&mut composite_cx)
+            }
+        }
+    }
+}
+
+#[negative_impl]
+impl<T> !Send for Collector<T> {}
+#[negative_impl]
+impl<T> !Sync for Collector<T> {}
+
+/// Pushes results into a `Collector`. Clone the handle to give one to each producer.
+#[derive(Debug)]
+pub struct CollectorHandle<T> {
+    tx: UnboundedSender<T>,
+}
+
+impl<T> CollectorHandle<T> {
+    /// Pushes a result into the collector. If the collector has been dropped, the result is
+    /// dropped as well.
+    pub fn push(&self, value: T) {
+        _ = self.tx.unbounded_send(value);
+    }
+}
+
+impl<T> Clone for CollectorHandle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+        }
+    }
+}
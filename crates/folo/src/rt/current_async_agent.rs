@@ -18,6 +18,15 @@ where
     })
 }
 
+/// Executes a closure that receives the current thread's async agent, if the current thread is an
+/// async worker thread owned by the Folo runtime.
+pub fn try_with<F, R>(f: F) -> Option<R>
+where
+    F: FnOnce(&AsyncAgent) -> R,
+{
+    CURRENT_AGENT.with_borrow(|agent| agent.as_ref().map(|agent| f(agent)))
+}
+
 /// Executes a closure that receives the current thread's I/O driver for the runtime that owns the
 /// current thread. This is the mechanism used to start I/O operations. Only available on async
 /// worker threads because only those threads can perform I/O using the Folo runtime.
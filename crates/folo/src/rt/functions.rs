@@ -3,7 +3,7 @@
 use super::SynchronousTaskType;
 use crate::rt::{
     current_async_agent, current_runtime, ready_after_poll::ReadyAfterPoll, LocalJoinHandle,
-    RemoteJoinHandle,
+    RemoteJoinHandle, RuntimeBuilder, SyncJoinHandle, TaskPriority,
 };
 use std::future::Future;
 
@@ -20,6 +20,20 @@ where
     current_async_agent::with(|agent| agent.spawn(future))
 }
 
+/// Spawns a task to execute a future on the current async worker thread with the given priority.
+/// Whenever multiple tasks are ready to be polled, higher priority tasks are polled first.
+///
+/// # Panics
+///
+/// Panics if the current thread is not an async worker thread owned by a Folo runtime.
+pub fn spawn_with_priority<F, R>(priority: TaskPriority, future: F) -> LocalJoinHandle<R>
+where
+    F: Future<Output = R> + 'static,
+    R: 'static,
+{
+    current_async_agent::with(|agent| agent.spawn_with_priority(priority, future))
+}
+
 /// Spawns a task to execute a future on any worker thread owned by the same Folo runtime
 /// as the current thread. The future is provided by a closure.
 ///
@@ -37,6 +51,29 @@ where
     current_runtime::with(|runtime| runtime.spawn_on_any(future_fn))
 }
 
+/// Spawns a task to execute a future on the async worker thread with the given reactor ID, owned
+/// by the same Folo runtime as the current thread. The future is provided by a closure, which is
+/// executed on the target thread, and any I/O it performs uses the I/O driver of that thread.
+///
+/// Tasks spawned on the same reactor never run concurrently with each other, so they can share
+/// single-threaded state without locking. Use `current_reactor_id()` to find out the ID of the
+/// current thread or `RuntimeClient::reactor_ids()` to list all of them.
+///
+/// The future itself does not have to be thread-safe. However, the closure must be.
+///
+/// # Panics
+///
+/// Panics if the current thread is not owned by a Folo runtime or if there is no async worker
+/// thread with the given reactor ID.
+pub fn spawn_on<FN, F, R>(reactor_id: usize, future_fn: FN) -> RemoteJoinHandle<R>
+where
+    FN: FnOnce() -> F + Send + 'static,
+    F: Future<Output = R> + 'static,
+    R: Send + 'static,
+{
+    current_runtime::with(|runtime| runtime.spawn_on(reactor_id, future_fn))
+}
+
 /// Spawns a task to execute a future on every worker thread.
 ///
 /// There are two layers of callbacks involved here, with the overall sequence being:
@@ -61,7 +98,7 @@ where
 /// Spawns a task on a synchronous worker thread suitable for the specific type of synchronous
 /// work requested, returning the result via a join handle suitable for use in asynchronous
 /// tasks.
-pub fn spawn_sync<F, R>(task_type: SynchronousTaskType, f: F) -> RemoteJoinHandle<R>
+pub fn spawn_sync<F, R>(task_type: SynchronousTaskType, f: F) -> SyncJoinHandle<R>
 where
     F: FnOnce() -> R + Send + 'static,
     R: Send + 'static,
@@ -69,10 +106,29 @@ where
     current_runtime::with(|runtime| runtime.spawn_sync(task_type, f))
 }
 
+/// Runs a blocking closure (e.g. a blocking syscall or a call into a synchronous library) on a
+/// synchronous worker thread, returning the result via a join handle that the calling task can
+/// await. The async worker thread keeps running its other tasks while the closure executes.
+///
+/// The synchronous worker threads of the current processor are shared by all blocking work, so
+/// if they are all busy, the closure waits for one to become free. The number of threads is set
+/// via `RuntimeBuilder::sync_workers_per_processor()`.
+///
+/// # Panics
+///
+/// Panics if the current thread is not an async worker thread owned by the Folo runtime.
+pub fn spawn_blocking<F, R>(f: F) -> SyncJoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    spawn_sync(SynchronousTaskType::Syscall, f)
+}
+
 /// Spawns a task on a synchronous worker thread suitable for the specific type of synchronous
 /// work requested, returning the result via a join handle suitable for use in asynchronous
 /// tasks.
-pub fn spawn_sync_on_any<F, R>(task_type: SynchronousTaskType, f: F) -> RemoteJoinHandle<R>
+pub fn spawn_sync_on_any<F, R>(task_type: SynchronousTaskType, f: F) -> SyncJoinHandle<R>
 where
     F: FnOnce() -> R + Send + 'static,
     R: Send + 'static,
@@ -80,9 +136,76 @@ where
     current_runtime::with(|runtime| runtime.spawn_sync_on_any(task_type, f))
 }
 
+/// Returns the ID of the reactor (async worker thread) the current task is running on. Each
+/// reactor has its own I/O driver, to which the I/O primitives opened on the reactor are bound.
+///
+/// # Panics
+///
+/// Panics if the current thread is not an async worker thread owned by the Folo runtime.
+pub fn current_reactor_id() -> usize {
+    current_async_agent::with_io(|io| io.reactor_id())
+}
+
+/// Returns the number of live tasks on the current async worker thread. This counts all the tasks
+/// that have been spawned on the thread and have not yet completed, including both tasks that are
+/// ready to be polled and tasks waiting to be woken up, as well as the calling task itself.
+///
+/// This can be used for admission control or to pick the least loaded worker to spawn work on.
+///
+/// # Panics
+///
+/// Panics if the current thread is not an async worker thread owned by the Folo runtime.
+pub fn current_task_count() -> usize {
+    current_async_agent::with(|agent| agent.task_count())
+}
+
 /// Yields control back to the async task runtime to allow other tasks to run.
 /// There is no guarantee that other tasks will run in any particular order.
 /// Even the same task that called this may be scheduled again immediately.
+///
+/// The task reschedules itself via its own waker, without involving the I/O driver, so this is
+/// cheap enough to call in every iteration of a long-running compute loop to avoid starving the
+/// other tasks on the same async worker thread.
 pub fn yield_now() -> impl Future<Output = ()> {
     ReadyAfterPoll::default()
 }
+
+/// Runs a future to completion on a new Folo runtime with a single async worker thread, blocking
+/// the current thread until the future completes and returning its output. The future is provided
+/// by a closure, so the future itself does not have to be thread-safe.
+///
+/// The runtime is shut down before this returns, with all its threads (and their I/O drivers)
+/// torn down. Any other tasks the future spawned are dropped at that point if still unfinished.
+///
+/// This is intended as a simple entrypoint for programs that do not need the `#[folo::main]`
+/// macro. It must not be called from a thread owned by a Folo runtime.
+///
+/// # Panics
+///
+/// Panics if the runtime cannot be started or if the future panics.
+pub fn block_on<FN, F, R>(future_fn: FN) -> R
+where
+    FN: FnOnce() -> F + Send + 'static,
+    F: Future<Output = R> + 'static,
+    R: Send + 'static,
+{
+    let runtime = RuntimeBuilder::new().max_processors(1).build().unwrap();
+    let runtime_clone = runtime.clone();
+
+    let (result_tx, result_rx) = oneshot::channel();
+
+    runtime.spawn_on_any(move || async move {
+        // The receiver is only dropped if the caller panicked, in which case nobody cares.
+        _ = result_tx.send(future_fn().await);
+
+        runtime_clone.stop();
+    });
+
+    // If the future panics, the worker thread dies and we panic from here, so we only get past
+    // this point if the future has completed and sent its result.
+    runtime.wait();
+
+    result_rx
+        .try_recv()
+        .expect("the future completed before the runtime stopped")
+}
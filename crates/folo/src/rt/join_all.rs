@@ -0,0 +1,246 @@
+use crossbeam::queue::SegQueue;
+use futures::task::AtomicWaker;
+use std::{
+    fmt,
+    future::Future,
+    mem,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{self, Wake, Waker},
+};
+
+/// Drives all the futures to completion concurrently and completes with their outputs, in the
+/// same order as the futures were provided.
+///
+/// Each future is given its own waker, so when one of them is woken up, only that one is polled
+/// again. This makes progress on whichever futures are ready, unlike awaiting the futures one by
+/// one, which only notices that a future has completed once all the futures before it have.
+///
+/// ```ignore
+/// let tasks = files.into_iter().map(|file| spawn_on_any(|| folo::fs::read(file)));
+/// let contents = join_all(tasks).await;
+/// ```
+pub fn join_all<I>(futures: I) -> JoinAll<I::Item>
+where
+    I: IntoIterator,
+    I::Item: Future,
+{
+    let slots = futures.into_iter().map(Slot::Pending).collect::<Box<[_]>>();
+
+    let wakers = Arc::new(JoinWakers {
+        ready: SegQueue::new(),
+        queued: (0..slots.len()).map(|_| AtomicBool::new(true)).collect(),
+        parent: AtomicWaker::new(),
+    });
+
+    // All the futures start out as woken up, so the first poll polls every one of them.
+    for index in 0..slots.len() {
+        wakers.ready.push(index);
+    }
+
+    let slot_wakers = (0..slots.len())
+        .map(|index| {
+            Waker::from(Arc::new(SlotWaker {
+                wakers: Arc::clone(&wakers),
+                index,
+            }))
+        })
+        .collect();
+
+    JoinAll {
+        remaining: slots.len(),
+        slots,
+        wakers,
+        slot_wakers,
+    }
+}
+
+/// Future returned by `join_all()`.
+pub struct JoinAll<F>
+where
+    F: Future,
+{
+    // The futures are pinned in place in the boxed slice, which we never reallocate.
+    slots: Box<[Slot<F>]>,
+
+    // Number of futures that have not yet completed.
+    remaining: usize,
+
+    wakers: Arc<JoinWakers>,
+
+    // One per future, in the same order as the slots.
+    slot_wakers: Box<[Waker]>,
+}
+
+impl<F> Future for JoinAll<F>
+where
+    F: Future,
+{
+    type Output = Vec<F::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        // SAFETY: We never move the futures out of their slots, we only drop them in place.
+        let this = unsafe { self.get_unchecked_mut() };
+
+        // We register before taking the woken futures, so a future woken up while we are polling
+        // the others wakes us up again and is not missed.
+        this.wakers.parent.register(cx.waker());
+
+        // We only poll the futures that were woken up before we started. Any that are woken up
+        // while we poll (e.g. ones that yield) are polled the next time, so we do not hog the
+        // thread if some of the futures keep waking themselves up.
+        for _ in 0..this.wakers.ready.len() {
+            let Some(index) = this.wakers.ready.pop() else {
+                break;
+            };
+
+            // We clear the flag before polling, so a wakeup during the poll queues it again.
+            this.wakers.queued[index].store(false, Ordering::Release);
+
+            let Slot::Pending(future) = &mut this.slots[index] else {
+                continue;
+            };
+
+            // SAFETY: See above.
+            let future = unsafe { Pin::new_unchecked(future) };
+            let mut slot_cx = task::Context::from_waker(&this.slot_wakers[index]);
+
+            if let task::Poll::Ready(result) = future.poll(&mut slot_cx) {
+                this.slots[index] = Slot::Done(result);
+                this.remaining -= 1;
+            }
+        }
+
+        if this.remaining > 0 {
+            return task::Poll::Pending;
+        }
+
+        let results = mem::take(&mut this.slots)
+            .into_vec()
+            .into_iter()
+            .map(|slot| match slot {
+                Slot::Done(result) => result,
+                Slot::Pending(_) => unreachable!("all the futures have completed"),
+            })
+            .collect();
+
+        task::Poll::Ready(results)
+    }
+}
+
+impl<F> fmt::Debug for JoinAll<F>
+where
+    F: Future,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JoinAll")
+            .field("futures", &self.slots.len())
+            .field("remaining", &self.remaining)
+            .finish()
+    }
+}
+
+enum Slot<F>
+where
+    F: Future,
+{
+    Pending(F),
+    Done(F::Output),
+}
+
+#[derive(Debug)]
+struct JoinWakers {
+    // Indexes of the futures that have been woken up and need to be polled.
+    ready: SegQueue<usize>,
+
+    // Whether each future is already in the ready queue, so repeated wakeups queue it only once.
+    queued: Box<[AtomicBool]>,
+
+    // The waker of the task awaiting the join.
+    parent: AtomicWaker,
+}
+
+struct SlotWaker {
+    wakers: Arc<JoinWakers>,
+    index: usize,
+}
+
+impl Wake for SlotWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        if !self.wakers.queued[self.index].swap(true, Ordering::AcqRel) {
+            self.wakers.ready.push(self.index);
+        }
+
+        self.wakers.parent.wake();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{
+        future::{self, poll_fn},
+        task::noop_waker_ref,
+        FutureExt,
+    };
+    use std::{cell::Cell, rc::Rc};
+
+    #[test]
+    fn empty_completes_immediately() {
+        let mut join = join_all(Vec::<future::Ready<u32>>::new());
+
+        let mut cx = task::Context::from_waker(noop_waker_ref());
+        assert_eq!(join.poll_unpin(&mut cx), task::Poll::Ready(vec![]));
+    }
+
+    #[test]
+    fn only_woken_futures_are_polled() {
+        let polls = Rc::new([Cell::new(0), Cell::new(0), Cell::new(0)]);
+        let wakers = Rc::new([const { Cell::new(None::<Waker>) }; 3]);
+
+        let futures = (0..3).map(|index| {
+            let polls = Rc::clone(&polls);
+            let wakers = Rc::clone(&wakers);
+
+            poll_fn(move |cx| {
+                polls[index].set(polls[index].get() + 1);
+
+                if polls[index].get() == 2 {
+                    return task::Poll::Ready(index * 10);
+                }
+
+                wakers[index].set(Some(cx.waker().clone()));
+                task::Poll::Pending
+            })
+        });
+
+        let mut join = join_all(futures);
+        let mut cx = task::Context::from_waker(noop_waker_ref());
+
+        let poll_counts = || polls.iter().map(Cell::get).collect::<Vec<_>>();
+
+        assert_eq!(join.poll_unpin(&mut cx), task::Poll::Pending);
+        assert_eq!(poll_counts(), [1, 1, 1]);
+
+        // Waking twice still polls only once.
+        let waker = wakers[2].take().unwrap();
+        waker.wake_by_ref();
+        waker.wake();
+
+        assert_eq!(join.poll_unpin(&mut cx), task::Poll::Pending);
+        assert_eq!(poll_counts(), [1, 1, 2]);
+
+        wakers[1].take().unwrap().wake();
+        wakers[0].take().unwrap().wake();
+
+        assert_eq!(join.poll_unpin(&mut cx), task::Poll::Ready(vec![0, 10, 20]));
+        assert_eq!(poll_counts(), [2, 2, 2]);
+    }
+}
@@ -1,25 +1,51 @@
-use crate::sync::once_event;
+use crate::{
+    rt::{AbortState, Aborted},
+    sync::once_event,
+};
 use futures::FutureExt;
 use negative_impl::negative_impl;
-use std::{future::Future, pin::Pin, task};
+use std::{future::Future, pin::Pin, sync::Arc, task};
 
 /// Allows a unit of work to be awaited and its result to be observed on the same thread as it is
 /// scheduled on.
 ///
-/// Awaiting this is optional - the task will continue even if you drop the join handle.
+/// Awaiting the handle yields the result of the task or `Aborted` if the task was aborted before it
+/// completed.
+///
+/// Awaiting this is optional - the task will continue even if you drop the join handle. To stop
+/// the task, use `abort()`.
 #[derive(Debug)]
 pub struct LocalJoinHandle<R> {
-    rx: once_event::EmbeddedReceiver<R>,
+    rx: once_event::EmbeddedReceiver<Result<R, Aborted>>,
+    abort: Arc<AbortState>,
 }
 
 impl<R> LocalJoinHandle<R> {
-    pub(crate) fn new(rx: once_event::EmbeddedReceiver<R>) -> Self {
-        Self { rx }
+    pub(crate) fn new(
+        rx: once_event::EmbeddedReceiver<Result<R, Aborted>>,
+        abort: Arc<AbortState>,
+    ) -> Self {
+        Self { rx, abort }
+    }
+
+    /// Aborts the task. The future of the task is dropped the next time the task would be polled,
+    /// so it does not run any further, unless it has already completed by then.
+    pub fn abort(&self) {
+        self.abort.abort();
+    }
+
+    /// Whether the task has completed or has been aborted and will not run any further.
+    pub fn is_finished(&self) -> bool {
+        self.abort.is_finished()
+    }
+
+    pub(crate) fn abort_state(&self) -> &Arc<AbortState> {
+        &self.abort
     }
 }
 
 impl<R> Future for LocalJoinHandle<R> {
-    type Output = R;
+    type Output = Result<R, Aborted>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
         self.rx.poll_unpin(cx)
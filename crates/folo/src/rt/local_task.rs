@@ -1,6 +1,5 @@
 use crate::{
     rt::erased_async_task::ErasedResultAsyncTask,
-    rt::LocalJoinHandle,
     sync::once_event::{self, OnceEvent, OnceEventEmbeddedStorage},
 };
 use negative_impl::negative_impl;
@@ -78,13 +77,11 @@ where
         instance
     }
 
-    pub fn join_handle(self: Pin<&mut Self>) -> LocalJoinHandle<R> {
-        LocalJoinHandle::new(
-            self.project()
-                .result_rx
-                .take()
-                .expect("join handle for task can only be acquired once"),
-        )
+    pub fn result_rx(self: Pin<&mut Self>) -> once_event::EmbeddedReceiver<R> {
+        self.project()
+            .result_rx
+            .take()
+            .expect("join handle for task can only be acquired once")
     }
 
     pub fn is_inert(&self) -> bool {
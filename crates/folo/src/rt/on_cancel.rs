@@ -0,0 +1,118 @@
+use pin_project::{pin_project, pinned_drop};
+use std::{fmt, future::Future, pin::Pin, task};
+
+/// Extension methods for attaching cancellation cleanup logic to futures.
+pub trait OnCancelExt: Future + Sized {
+    /// Runs `cleanup` if and only if the future is dropped before it has completed (i.e. it has
+    /// been canceled). If the future completes, the cleanup is discarded without being called.
+    ///
+    /// This is useful for undoing partial work of a canceled operation, such as removing a
+    /// partially written temporary file.
+    fn on_cancel<C>(self, cleanup: C) -> OnCancel<Self, C>
+    where
+        C: FnOnce(),
+    {
+        OnCancel {
+            inner: self,
+            cleanup: Some(cleanup),
+        }
+    }
+}
+
+impl<F> OnCancelExt for F where F: Future {}
+
+/// Future returned by `OnCancelExt::on_cancel()`.
+#[pin_project(PinnedDrop)]
+pub struct OnCancel<F, C>
+where
+    C: FnOnce(),
+{
+    #[pin]
+    inner: F,
+
+    // Becomes None when the inner future has completed, disarming the cleanup.
+    cleanup: Option<C>,
+}
+
+impl<F, C> Future for OnCancel<F, C>
+where
+    F: Future,
+    C: FnOnce(),
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let this = self.project();
+
+        match this.inner.poll(cx) {
+            task::Poll::Ready(result) => {
+                *this.cleanup = None;
+                task::Poll::Ready(result)
+            }
+            task::Poll::Pending => task::Poll::Pending,
+        }
+    }
+}
+
+impl<F, C> fmt::Debug for OnCancel<F, C>
+where
+    C: FnOnce(),
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OnCancel")
+            .field("armed", &self.cleanup.is_some())
+            .finish()
+    }
+}
+
+#[pinned_drop]
+impl<F, C> PinnedDrop for OnCancel<F, C>
+where
+    C: FnOnce(),
+{
+    fn drop(self: Pin<&mut Self>) {
+        if let Some(cleanup) = self.project().cleanup.take() {
+            cleanup();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{executor::block_on, future, task::noop_waker_ref, FutureExt};
+    use std::cell::Cell;
+
+    #[test]
+    fn cleanup_runs_when_canceled() {
+        let canceled = Cell::new(false);
+
+        let mut future = future::pending::<()>().on_cancel(|| canceled.set(true));
+
+        let mut cx = task::Context::from_waker(noop_waker_ref());
+        assert!(future.poll_unpin(&mut cx).is_pending());
+        assert!(!canceled.get());
+
+        drop(future);
+        assert!(canceled.get());
+    }
+
+    #[test]
+    fn cleanup_runs_when_dropped_before_first_poll() {
+        let canceled = Cell::new(false);
+
+        drop(future::ready(()).on_cancel(|| canceled.set(true)));
+
+        assert!(canceled.get());
+    }
+
+    #[test]
+    fn cleanup_does_not_run_on_completion() {
+        let canceled = Cell::new(false);
+
+        let future = future::ready(42).on_cancel(|| canceled.set(true));
+
+        assert_eq!(block_on(future), 42);
+        assert!(!canceled.get());
+    }
+}
@@ -0,0 +1,169 @@
+//! A channel for sending a single value, typically the response to a request, from any thread to a
+//! task on an async worker thread.
+
+use crate::{constants::POISONED_LOCK, io::IoWaker, rt::current_async_agent};
+use negative_impl::negative_impl;
+use std::{
+    future::Future,
+    mem,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{self, Waker},
+};
+
+/// The result of receiving from a oneshot channel whose sender was dropped without sending a value.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, thiserror::Error)]
+#[error("sender was dropped without sending a value")]
+pub struct Canceled;
+
+/// Creates a channel for sending a single value to a task on the current async worker thread. The
+/// receiver is bound to the current thread, while the sender can be sent to any thread.
+///
+/// The receiver is a future that resolves once the value is sent or the sender is dropped. The
+/// receiving task is woken up via the I/O driver of its thread.
+///
+/// # Panics
+///
+/// Panics if the current thread is not an async worker thread owned by the Folo runtime.
+pub fn channel<T: Send>() -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State::Empty(None)),
+        io_waker: current_async_agent::with_io(|io| io.waker()),
+    });
+
+    (
+        Sender {
+            shared: Some(Arc::clone(&shared)),
+        },
+        Receiver { shared },
+    )
+}
+
+#[derive(Debug)]
+struct Shared<T> {
+    state: Mutex<State<T>>,
+
+    // Delivers the wakeup of the receiving task to the thread that owns the receiver.
+    io_waker: IoWaker,
+}
+
+#[derive(Debug)]
+enum State<T> {
+    // Nothing has been sent yet. Holds the waker of the receiving task once it has been polled.
+    Empty(Option<Waker>),
+
+    Sent(T),
+
+    // The sender was dropped without sending.
+    Canceled,
+
+    // The receiver has received the result or has been dropped.
+    Closed,
+}
+
+impl<T> Shared<T> {
+    // Replaces the empty state, waking up the receiving task. If the receiver is already gone,
+    // the new state is returned back.
+    fn complete(&self, new_state: State<T>) -> Result<(), State<T>> {
+        let waker = {
+            let mut state = self.state.lock().expect(POISONED_LOCK);
+
+            match &mut *state {
+                State::Empty(waker) => {
+                    let waker = waker.take();
+                    *state = new_state;
+                    waker
+                }
+                State::Closed => return Err(new_state),
+                State::Sent(_) | State::Canceled => {
+                    unreachable!("the sender completes the channel at most once")
+                }
+            }
+        };
+
+        if let Some(waker) = waker {
+            self.io_waker.wake_tasks([waker]);
+        }
+
+        Ok(())
+    }
+}
+
+/// The sending half of a oneshot channel. Dropping it without sending a value makes the receiver
+/// resolve with `Canceled`.
+#[derive(Debug)]
+pub struct Sender<T> {
+    // Only taken when the value is sent.
+    shared: Option<Arc<Shared<T>>>,
+}
+
+impl<T> Sender<T> {
+    /// Sends the value and wakes up the receiving task. This does not block.
+    ///
+    /// Returns the value back as an error if the receiver has been dropped.
+    pub fn send(mut self, value: T) -> Result<(), T> {
+        let shared = self
+            .shared
+            .take()
+            .expect("only taken when sending, which consumes the sender");
+
+        shared
+            .complete(State::Sent(value))
+            .map_err(|state| match state {
+                State::Sent(value) => value,
+                _ => unreachable!("we get back the state we provided"),
+            })
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        if let Some(shared) = self.shared.take() {
+            // If the receiver is already gone, there is nobody to tell.
+            _ = shared.complete(State::Canceled);
+        }
+    }
+}
+
+/// The receiving half of a oneshot channel, bound to the async worker thread that created the
+/// channel. Resolves with the sent value or with `Canceled` if the sender was dropped without
+/// sending one.
+#[derive(Debug)]
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Future for Receiver<T> {
+    type Output = Result<T, Canceled>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context) -> task::Poll<Self::Output> {
+        let mut state = self.shared.state.lock().expect(POISONED_LOCK);
+
+        match &mut *state {
+            State::Empty(waker) => {
+                *waker = Some(cx.waker().clone());
+                task::Poll::Pending
+            }
+            State::Sent(_) => match mem::replace(&mut *state, State::Closed) {
+                State::Sent(value) => task::Poll::Ready(Ok(value)),
+                _ => unreachable!("we just matched the state"),
+            },
+            State::Canceled => {
+                *state = State::Closed;
+                task::Poll::Ready(Err(Canceled))
+            }
+            State::Closed => panic!("oneshot receiver polled after completion"),
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        *self.shared.state.lock().expect(POISONED_LOCK) = State::Closed;
+    }
+}
+
+#[negative_impl]
+impl<T> !Send for Receiver<T> {}
+#[negative_impl]
+impl<T> !Sync for Receiver<T> {}
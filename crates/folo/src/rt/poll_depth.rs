@@ -0,0 +1,126 @@
+use pin_project::pin_project;
+use std::{cell::Cell, future::Future, pin::Pin, task};
+use thiserror::Error;
+
+/// Returned by `PollDepthGuard` instead of polling the inner future when the poll nesting depth
+/// on the current thread exceeds the limit configured via `RuntimeBuilder::max_poll_depth()`.
+#[derive(Debug, Error)]
+#[error("maximum poll depth of {limit} exceeded")]
+pub struct PollDepthExceeded {
+    pub limit: usize,
+}
+
+/// Guards against stack overflow when polling deeply nested futures (e.g. recursive combinators
+/// in dynamically composed future graphs).
+///
+/// Every time a guard is polled, it increments a thread-local nesting depth counter for the
+/// duration of the inner poll. If the counter exceeds the limit configured for the current thread,
+/// the guard completes with `PollDepthExceeded` instead of polling the inner future, so the task
+/// can fail cleanly rather than crashing the worker thread.
+///
+/// Only nesting of guarded futures is counted - wrap the recursive step of a future graph in a
+/// guard to protect it. If no limit is configured, the guard only tracks the depth.
+#[pin_project]
+#[derive(Debug)]
+pub struct PollDepthGuard<F> {
+    #[pin]
+    inner: F,
+}
+
+/// Wraps a future in a `PollDepthGuard`.
+pub fn guard_poll_depth<F>(future: F) -> PollDepthGuard<F>
+where
+    F: Future,
+{
+    PollDepthGuard { inner: future }
+}
+
+impl<F> Future for PollDepthGuard<F>
+where
+    F: Future,
+{
+    type Output = Result<F::Output, PollDepthExceeded>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let depth = DEPTH.get() + 1;
+
+        if let Some(limit) = LIMIT.get() {
+            if depth > limit {
+                return task::Poll::Ready(Err(PollDepthExceeded { limit }));
+            }
+        }
+
+        DEPTH.set(depth);
+        let _reset = ResetDepthOnDrop { depth: depth - 1 };
+
+        self.project().inner.poll(cx).map(Ok)
+    }
+}
+
+// Restores the previous depth even if the inner poll panics.
+struct ResetDepthOnDrop {
+    depth: usize,
+}
+
+impl Drop for ResetDepthOnDrop {
+    fn drop(&mut self) {
+        DEPTH.set(self.depth);
+    }
+}
+
+/// Sets the maximum poll depth for guarded futures polled on the current thread.
+pub(crate) fn set_limit(limit: Option<usize>) {
+    LIMIT.set(limit);
+}
+
+thread_local! {
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+    static LIMIT: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{executor::block_on, FutureExt};
+
+    type NestedFuture = Pin<Box<dyn Future<Output = Result<usize, PollDepthExceeded>>>>;
+
+    fn nested(levels: usize) -> NestedFuture {
+        Box::pin(
+            guard_poll_depth(async move {
+                if levels == 0 {
+                    Ok(0)
+                } else {
+                    nested(levels - 1).await.map(|depth| depth + 1)
+                }
+            })
+            .map(|result| result.and_then(|inner| inner)),
+        )
+    }
+
+    #[test]
+    fn within_limit() {
+        set_limit(Some(10));
+
+        assert_eq!(block_on(nested(9)).unwrap(), 9);
+        assert_eq!(DEPTH.get(), 0);
+
+        set_limit(None);
+    }
+
+    #[test]
+    fn exceeds_limit() {
+        set_limit(Some(10));
+
+        let result = block_on(nested(1000));
+        assert!(matches!(result, Err(PollDepthExceeded { limit: 10 })));
+        assert_eq!(DEPTH.get(), 0);
+
+        set_limit(None);
+    }
+
+    #[test]
+    fn no_limit() {
+        assert_eq!(block_on(nested(100)).unwrap(), 100);
+    }
+}
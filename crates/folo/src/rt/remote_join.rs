@@ -1,7 +1,7 @@
 use super::remote_waker::RemoteWaker;
 use crate::{
     io::IoWaker,
-    rt::{remote_result_box::RemoteResultBox, LocalJoinHandle},
+    rt::{remote_result_box::RemoteResultBox, AbortState, Aborted, LocalJoinHandle},
 };
 use futures::{channel::oneshot, FutureExt};
 use std::future::Future;
@@ -12,55 +12,51 @@ use std::{pin::Pin, task};
 ///
 /// You can convert a `LocalJoinHandle` into a `RemoteJoinHandle` using `Into::into`.
 ///
-/// Awaiting this is optional - the task will continue even if you drop the join handle.
+/// Awaiting the handle yields the result of the task or `Aborted` if the task was aborted before it
+/// completed.
+///
+/// Awaiting this is optional - the task will continue even if you drop the join handle. To stop
+/// the task, use `abort()`.
 #[derive(Debug)]
 pub struct RemoteJoinHandle<R>
 where
     R: Send + 'static,
 {
     model: ImplementationModel<R>,
+    abort: Arc<AbortState>,
 }
 
 impl<R> RemoteJoinHandle<R>
 where
     R: Send + 'static,
 {
-    pub(crate) fn new(result: Arc<RemoteResultBox<R>>, io_waker: Option<IoWaker>) -> Self {
+    pub(crate) fn new(
+        result: Arc<RemoteResultBox<Result<R, Aborted>>>,
+        io_waker: Option<IoWaker>,
+        abort: Arc<AbortState>,
+    ) -> Self {
         Self {
             model: ImplementationModel::RemoteTask { result, io_waker },
+            abort,
         }
     }
 
-    pub(crate) fn from_local(local: LocalJoinHandle<R>) -> Self {
-        // We add a new task to await the result on the current thread, after which we publish
-        // it in a thread-safe manner to whoever wants to consume this object.
-
-        // TODO: This is probably not the most efficient way to do this, what with spawning
-        // a new task here and allocating a channel and so forth. We could probably improve this
-        // with some "direct wiring" between the two endpoints. Worry about it later - it works.
-
-        let (tx, rx) = oneshot::channel::<R>();
-
-        _ = crate::rt::spawn(async {
-            let result = local.await;
-
-            // If the join handle was dropped, this will return an error, which is fine.
-            _ = tx.send(result);
-        });
+    /// Aborts the task. The future of the task is dropped the next time the task would be polled,
+    /// so it does not run any further, unless it has already completed by then. This can be called
+    /// from any thread.
+    pub fn abort(&self) {
+        self.abort.abort();
+    }
 
-        Self {
-            model: ImplementationModel::LocalJoinHandle { result_rx: rx },
+    /// Whether the task has completed or has been aborted and will not run any further.
+    pub fn is_finished(&self) -> bool {
+        match &self.model {
+            ImplementationModel::LocalJoinHandle { .. } => self.abort.is_finished(),
+            ImplementationModel::RemoteTask { result, .. } => result.is_set(),
         }
     }
-}
-
-impl<R> Future for RemoteJoinHandle<R>
-where
-    R: Send + 'static,
-{
-    type Output = R;
 
-    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+    fn poll_join(&mut self, cx: &mut task::Context<'_>) -> task::Poll<Result<R, Aborted>> {
         match &mut self.model {
             ImplementationModel::LocalJoinHandle { ref mut result_rx } => {
                 match result_rx.poll_unpin(cx) {
@@ -91,19 +87,56 @@ where
             }
         }
     }
+
+    pub(crate) fn from_local(local: LocalJoinHandle<R>) -> Self {
+        // We add a new task to await the result on the current thread, after which we publish
+        // it in a thread-safe manner to whoever wants to consume this object.
+
+        // TODO: This is probably not the most efficient way to do this, what with spawning
+        // a new task here and allocating a channel and so forth. We could probably improve this
+        // with some "direct wiring" between the two endpoints. Worry about it later - it works.
+
+        let (tx, rx) = oneshot::channel::<Result<R, Aborted>>();
+
+        // Aborting the remote join handle aborts the local task, not the task forwarding its result.
+        let abort = Arc::clone(local.abort_state());
+
+        _ = crate::rt::spawn(async {
+            let result = local.await;
+
+            // If the join handle was dropped, this will return an error, which is fine.
+            _ = tx.send(result);
+        });
+
+        Self {
+            model: ImplementationModel::LocalJoinHandle { result_rx: rx },
+            abort,
+        }
+    }
+}
+
+impl<R> Future for RemoteJoinHandle<R>
+where
+    R: Send + 'static,
+{
+    type Output = Result<R, Aborted>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        self.poll_join(cx)
+    }
 }
 
 #[derive(Debug)]
 enum ImplementationModel<R> {
     // We are wrapping a `LocalJoinHandle`, which will send the result via oneshot channel.
     LocalJoinHandle {
-        result_rx: oneshot::Receiver<R>,
+        result_rx: oneshot::Receiver<Result<R, Aborted>>,
     },
 
     // We are observing a `RemoteTask` to obtain the result from it. We use a special waker to
     // also wake up our thread from I/O sleep if it is sleeping.
     RemoteTask {
-        result: Arc<RemoteResultBox<R>>,
+        result: Arc<RemoteResultBox<Result<R, Aborted>>>,
         io_waker: Option<IoWaker>,
     },
 }
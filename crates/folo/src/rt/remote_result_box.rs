@@ -76,6 +76,14 @@ impl<R> RemoteResultBox<R> {
         }
     }
 
+    /// Whether the result has been set, regardless of whether it has been consumed yet.
+    pub fn is_set(&self) -> bool {
+        matches!(
+            *self.result.lock().expect(constants::POISONED_LOCK),
+            TaskResult::Ready(_) | TaskResult::Consumed
+        )
+    }
+
     // We expose a poll-like API for getting the result, as the ResultBox is only intended to be
     // read from a future's poll() function (via a join handle).
     pub fn poll(&self, waker: &Waker) -> Option<R> {
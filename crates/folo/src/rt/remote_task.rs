@@ -1,8 +1,8 @@
 use crate::{
     io::IoWaker,
     rt::{
-        erased_async_task::ErasedResultAsyncTask, remote_result_box::RemoteResultBox,
-        RemoteJoinHandle,
+        erased_async_task::ErasedResultAsyncTask, remote_result_box::RemoteResultBox, AbortState,
+        Aborted, RemoteJoinHandle,
     },
 };
 use std::{cell::RefCell, future::Future, pin::Pin, sync::Arc, task};
@@ -41,10 +41,22 @@ where
             result: Arc::new(RemoteResultBox::new()),
         }
     }
+}
 
-    pub fn join_handle(&self, io_waker: Option<IoWaker>) -> RemoteJoinHandle<R> {
+impl<F, R> RemoteTask<F, Result<R, Aborted>>
+where
+    F: Future<Output = Result<R, Aborted>> + Send + 'static,
+    R: Send + 'static,
+{
+    /// The task is aborted via the abort state of the local task that the future of this task
+    /// spawns to do the actual work.
+    pub fn join_handle(
+        &self,
+        io_waker: Option<IoWaker>,
+        abort: Arc<AbortState>,
+    ) -> RemoteJoinHandle<R> {
         // TODO: Protect this so only one join handle can be taken.
-        RemoteJoinHandle::new(Arc::clone(&self.result), io_waker)
+        RemoteJoinHandle::new(Arc::clone(&self.result), io_waker, abort)
     }
 }
 
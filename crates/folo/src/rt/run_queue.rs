@@ -0,0 +1,154 @@
+use std::collections::VecDeque;
+
+/// Determines the order in which ready tasks on the same async worker thread are polled. Tasks
+/// with a higher priority are polled before tasks with a lower priority whenever both are ready,
+/// e.g. to handle interactive requests ahead of bulk background work.
+///
+/// Lower priority tasks are not starved - after a number of higher priority tasks have been polled
+/// in a row while lower priority tasks were waiting, a lower priority task gets its turn.
+///
+/// The priority applies for the entire lifetime of the task, including when it is woken up from
+/// another thread.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum TaskPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+/// After this many items of higher priority have been taken in a row while an item of some lower
+/// priority was waiting, the waiting item is taken next.
+const STARVATION_LIMIT: usize = 8;
+
+/// The queue of ready tasks of the async task engine, ordered by priority and within the same
+/// priority by arrival order.
+#[derive(Debug)]
+pub(super) struct RunQueue<T> {
+    // Indexed by `TaskPriority as usize`.
+    queues: [VecDeque<T>; 3],
+
+    // Indexed by `TaskPriority as usize`. How many items of higher priority have been taken in a
+    // row while an item of this priority was waiting.
+    bypassed: [usize; 3],
+}
+
+impl<T> RunQueue<T> {
+    pub fn new() -> Self {
+        Self {
+            queues: [VecDeque::new(), VecDeque::new(), VecDeque::new()],
+            bypassed: [0; 3],
+        }
+    }
+
+    pub fn push(&mut self, priority: TaskPriority, item: T) {
+        self.queues[priority as usize].push_back(item);
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        let highest = self.queues.iter().rposition(|queue| !queue.is_empty())?;
+
+        // If several lower priorities have waited too long, the higher of them goes first. The
+        // others keep waiting and go next, so with all priorities busy, every one is served.
+        let chosen = (0..highest)
+            .rev()
+            .find(|&priority| {
+                !self.queues[priority].is_empty() && self.bypassed[priority] >= STARVATION_LIMIT
+            })
+            .unwrap_or(highest);
+
+        for (priority, queue) in self.queues.iter().enumerate() {
+            if priority == chosen || queue.is_empty() {
+                self.bypassed[priority] = 0;
+            } else if priority < chosen {
+                self.bypassed[priority] += 1;
+            }
+        }
+
+        self.queues[chosen].pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.queues.iter().map(VecDeque::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queues.iter().all(VecDeque::is_empty)
+    }
+
+    /// Removes all items, regardless of priority.
+    pub fn drain(&mut self) -> impl Iterator<Item = T> + '_ {
+        self.bypassed = [0; 3];
+        self.queues.iter_mut().flat_map(|queue| queue.drain(..))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn higher_priority_first() {
+        let mut queue = RunQueue::new();
+
+        queue.push(TaskPriority::Low, 1);
+        queue.push(TaskPriority::Normal, 2);
+        queue.push(TaskPriority::High, 3);
+        queue.push(TaskPriority::Normal, 4);
+
+        assert_eq!(queue.len(), 4);
+
+        assert_eq!(queue.pop(), Some(3));
+        assert_eq!(queue.pop(), Some(2));
+        assert_eq!(queue.pop(), Some(4));
+        assert_eq!(queue.pop(), Some(1));
+        assert_eq!(queue.pop(), None);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn lower_priority_not_starved() {
+        let mut queue = RunQueue::new();
+
+        queue.push(TaskPriority::Low, 0);
+
+        for i in 1..=STARVATION_LIMIT * 2 {
+            queue.push(TaskPriority::High, i);
+        }
+
+        let order = std::iter::from_fn(|| queue.pop()).collect::<Vec<_>>();
+
+        // The low priority item gets its turn after the limit is reached, not after all the high
+        // priority items have been taken.
+        assert_eq!(order.iter().position(|&i| i == 0), Some(STARVATION_LIMIT));
+        assert_eq!(order.len(), STARVATION_LIMIT * 2 + 1);
+    }
+
+    #[test]
+    fn middle_priority_not_starved_by_busy_neighbors() {
+        let mut queue = RunQueue::new();
+
+        for priority in [TaskPriority::Low, TaskPriority::Normal, TaskPriority::High] {
+            queue.push(priority, priority);
+        }
+
+        let mut taken = Vec::new();
+
+        // Every priority stays busy - each item taken is immediately replaced by another.
+        for _ in 0..(STARVATION_LIMIT + 2) * 3 {
+            let priority = queue.pop().unwrap();
+            taken.push(priority);
+            queue.push(priority, priority);
+        }
+
+        let count = |priority| taken.iter().filter(|&&p| p == priority).count();
+
+        assert!(count(TaskPriority::Normal) >= 2);
+        assert!(count(TaskPriority::Low) >= 2);
+        assert!(count(TaskPriority::High) > count(TaskPriority::Normal));
+
+        // Both waiting priorities get their turn as soon as the limit is reached.
+        assert_eq!(taken[STARVATION_LIMIT], TaskPriority::Normal);
+        assert_eq!(taken[STARVATION_LIMIT + 1], TaskPriority::Low);
+    }
+}
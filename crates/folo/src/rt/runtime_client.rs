@@ -2,7 +2,7 @@ use std::any::type_name;
 use std::cell::Cell;
 use std::collections::HashMap;
 use std::future::Future;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::{fmt, thread};
 
@@ -12,13 +12,17 @@ use crossbeam::queue::SegQueue;
 use tracing::{event, Level};
 
 use crate::constants::{self, GENERAL_MILLISECONDS_BUCKETS};
+use crate::fs::WriteBufferBudget;
 use crate::io::IoWaker;
 use crate::metrics::{Event, EventBuilder};
 use crate::rt::async_agent::AsyncAgentCommand;
 use crate::rt::remote_result_box::RemoteResultBox;
 use crate::rt::remote_task::RemoteTask;
 use crate::rt::sync_agent::SyncAgentCommand;
-use crate::rt::{current_async_agent, ErasedSyncTask, RemoteJoinHandle};
+use crate::rt::{
+    current_async_agent, AbortState, ErasedSyncTask, LocalJoinHandle, RemoteJoinHandle,
+    RuntimeHandle, SyncJoinHandle, TaskPriority,
+};
 use crate::time::UltraLowPrecisionInstant;
 
 // TODO: In a real implementation we should split this up into multiple layers:
@@ -39,6 +43,11 @@ pub(super) struct CoreClient {
     async_command_tx: channel::Sender<AsyncAgentCommand>,
     async_io_waker: IoWaker,
 
+    // Tasks spawned from other threads wake up the async agent once per batch of this many tasks.
+    // The count is shared by all clones, as the clones all command the same agent.
+    spawn_wake_batch_size: usize,
+    spawns_since_wake: Arc<AtomicUsize>,
+
     // We often prefer to give work to the same processor, so we split
     // the sync command architecture up by the processor ID.
     sync_command_txs: Box<[channel::Sender<SyncAgentCommand>]>,
@@ -62,11 +71,14 @@ impl CoreClient {
         sync_command_txs: Box<[channel::Sender<SyncAgentCommand>]>,
         sync_task_queue: Arc<SegQueue<ErasedSyncTask>>,
         sync_priority_task_queue: Arc<SegQueue<ErasedSyncTask>>,
+        spawn_wake_batch_size: usize,
     ) -> Self {
         Self {
             processor_id,
             async_command_tx,
             async_io_waker,
+            spawn_wake_batch_size,
+            spawns_since_wake: Arc::new(AtomicUsize::new(0)),
             sync_command_txs,
             sync_task_queue,
             sync_priority_task_queue,
@@ -86,8 +98,16 @@ impl CoreClient {
             erased_task: Box::pin(task),
         });
 
-        // Wake up the agent if it might be sleeping and waiting for I/O.
-        self.async_io_waker.wake();
+        // Wake up the agent if it might be sleeping and waiting for I/O, once per batch of tasks.
+        // The agent picks up the tasks of an incomplete batch when it next polls for cross-thread
+        // work, so they are not left behind. When many batches are completed in quick succession,
+        // only the first one posts a wakeup - the agent picks up all the tasks enqueued before it
+        // processes the wakeup.
+        let spawns_since_wake = self.spawns_since_wake.fetch_add(1, Ordering::Relaxed) + 1;
+
+        if spawns_since_wake.is_multiple_of(self.spawn_wake_batch_size) {
+            self.async_io_waker.wake();
+        }
     }
 
     fn terminate(&self) {
@@ -186,6 +206,12 @@ pub struct RuntimeClient {
 
     // This can be used by cleanup logic to detect that the runtime is not usable anymore.
     is_stopping: Arc<AtomicBool>,
+
+    // Set by `stop()`, so runtime handles can refuse to spawn tasks that would never be executed.
+    stop_requested: Arc<AtomicBool>,
+
+    // Shared by all the buffered writers of the runtime, on any thread.
+    write_buffer_budget: Arc<WriteBufferBudget>,
 }
 
 impl RuntimeClient {
@@ -195,17 +221,68 @@ impl RuntimeClient {
         processor_ids: Box<[CoreId]>,
         join_handles: Box<[thread::JoinHandle<()>]>,
         is_stopping: Arc<AtomicBool>,
+        write_buffer_budget: Arc<WriteBufferBudget>,
     ) -> Self {
         Self {
             core_clients,
             processor_ids,
             join_handles: Arc::new(Mutex::new(Some(join_handles))),
             is_stopping,
+            stop_requested: Arc::new(AtomicBool::new(false)),
+            write_buffer_budget,
         }
     }
 
     /// Spawns a task to execute a future on any worker thread, creating the future via closure.
     pub fn spawn_on_any<FN, F, R>(&self, future_fn: FN) -> RemoteJoinHandle<R>
+    where
+        FN: FnOnce() -> F + Send + 'static,
+        F: Future<Output = R> + 'static,
+        R: Send + 'static,
+    {
+        let processor_id = self.processor_ids[next_async_worker(self.processor_ids.len())];
+        self.spawn_on_processor(processor_id, future_fn)
+    }
+
+    /// Spawns a task to execute a future on the async worker thread with the given reactor ID,
+    /// creating the future via closure on that thread. I/O primitives opened by the task are bound
+    /// to the I/O driver of that thread.
+    ///
+    /// Use this to keep related tasks (e.g. all the work on one connection) on the same thread, so
+    /// they can share single-threaded state. See `reactor_ids()` for the valid IDs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no async worker thread with the given reactor ID.
+    pub fn spawn_on<FN, F, R>(&self, reactor_id: usize, future_fn: FN) -> RemoteJoinHandle<R>
+    where
+        FN: FnOnce() -> F + Send + 'static,
+        F: Future<Output = R> + 'static,
+        R: Send + 'static,
+    {
+        let processor_id = *self
+            .processor_ids
+            .iter()
+            .find(|processor_id| processor_id.id == reactor_id)
+            .unwrap_or_else(|| panic!("no async worker thread with reactor ID {reactor_id}"));
+
+        self.spawn_on_processor(processor_id, future_fn)
+    }
+
+    /// The reactor IDs of all the async worker threads of the runtime, which can be used to spawn
+    /// tasks on a specific thread via `spawn_on()`.
+    pub fn reactor_ids(&self) -> Box<[usize]> {
+        self.processor_ids
+            .iter()
+            .map(|processor_id| processor_id.id)
+            .collect()
+    }
+
+    fn spawn_on_processor<FN, F, R>(
+        &self,
+        processor_id: CoreId,
+        future_fn: FN,
+    ) -> RemoteJoinHandle<R>
     where
         FN: FnOnce() -> F + Send + 'static,
         F: Future<Output = R> + 'static,
@@ -217,20 +294,25 @@ impl RuntimeClient {
         // thread-safe future (although the return value has to be). Therefore, we kajigger it
         // around via a remote join handle from the same thread, to allow a single-threaded future
         // to execute, as long as the closure that creates it is thread-safe.
+        //
+        // The local task shares its abort state with the join handle we return, so aborting the
+        // join handle aborts the task doing the actual work.
+        let abort = Arc::new(AbortState::new());
+        let task_abort = Arc::clone(&abort);
+
         let thread_safe_wrapper_future = async move {
             REMOTE_SPAWN_DELAY.with(|x| x.observe_millis(started.elapsed()));
 
             // TODO: This seems inefficient. Surely we can do better?
             // This is: RemoteJoinHandle -> RemoteJoinHandle -> LocalJoinHandle -> LocalJoinHandle
             // Desired is: RemoteJoinHandle -> LocalJoinHandle
-            let join_handle: RemoteJoinHandle<R> = crate::rt::spawn(future_fn()).into();
+            let join_handle: RemoteJoinHandle<R> =
+                spawn_local_with_abort_state(future_fn(), task_abort).into();
             join_handle.await
         };
 
         let task = RemoteTask::new(thread_safe_wrapper_future);
-        let join_handle = task.join_handle(self.current_thread_io_waker());
-
-        let processor_id = self.processor_ids[next_async_worker(self.processor_ids.len())];
+        let join_handle = task.join_handle(self.current_thread_io_waker(), abort);
         self.core_clients[&processor_id].enqueue_async_task(task);
 
         join_handle
@@ -265,18 +347,24 @@ impl RuntimeClient {
             // thread-safe future (although the return value has to be). Therefore, we kajigger it
             // around via a remote join handle from the same thread, to allow a single-threaded future
             // to execute, as long as the closure that creates it is thread-safe.
+            //
+            // As in `spawn_on_any()`, the local task shares its abort state with the join handle.
+            let abort = Arc::new(AbortState::new());
+            let task_abort = Arc::clone(&abort);
+
             let thread_safe_wrapper_future = async move {
                 REMOTE_SPAWN_DELAY.with(|x| x.observe_millis(started.elapsed()));
 
                 // TODO: This seems inefficient. Surely we can do better?
                 // This is: RemoteJoinHandle -> RemoteJoinHandle -> LocalJoinHandle -> LocalJoinHandle
                 // Desired is: RemoteJoinHandle -> LocalJoinHandle
-                let join_handle: RemoteJoinHandle<R> = crate::rt::spawn(future_fn()).into();
+                let join_handle: RemoteJoinHandle<R> =
+                    spawn_local_with_abort_state(future_fn(), task_abort).into();
                 join_handle.await
             };
 
             let task = RemoteTask::new(thread_safe_wrapper_future);
-            let join_handle = task.join_handle(self.current_thread_io_waker());
+            let join_handle = task.join_handle(self.current_thread_io_waker(), abort);
             proc.enqueue_async_task(task);
             join_handles.push(join_handle);
         }
@@ -287,7 +375,7 @@ impl RuntimeClient {
     /// Spawns a task on a synchronous worker thread suitable for the specific type of synchronous
     /// work requested, returning the result via a join handle suitable for use in asynchronous
     /// tasks.
-    pub fn spawn_sync<F, R>(&self, task_type: SynchronousTaskType, f: F) -> RemoteJoinHandle<R>
+    pub fn spawn_sync<F, R>(&self, task_type: SynchronousTaskType, f: F) -> SyncJoinHandle<R>
     where
         F: FnOnce() -> R + Send + 'static,
         R: Send + 'static,
@@ -355,7 +443,7 @@ impl RuntimeClient {
             _ => unreachable!(),
         }
 
-        RemoteJoinHandle::new(result_box_rx, self.current_thread_io_waker())
+        SyncJoinHandle::new(result_box_rx, self.current_thread_io_waker())
     }
 
     /// Spawns a task on a synchronous worker thread suitable for the specific type of synchronous
@@ -367,7 +455,7 @@ impl RuntimeClient {
         &self,
         task_type: SynchronousTaskType,
         f: F,
-    ) -> RemoteJoinHandle<R>
+    ) -> SyncJoinHandle<R>
     where
         F: FnOnce() -> R + Send + 'static,
         R: Send + 'static,
@@ -440,7 +528,7 @@ impl RuntimeClient {
             _ => unreachable!(),
         }
 
-        RemoteJoinHandle::new(result_box_rx, self.current_thread_io_waker())
+        SyncJoinHandle::new(result_box_rx, self.current_thread_io_waker())
     }
 
     /// Submits any tasks that have been queued for submission. We expect this to be called by
@@ -459,11 +547,27 @@ impl RuntimeClient {
     ///
     /// This returns immediately. To wait for the runtime to stop, use `wait()`.
     pub fn stop(&self) {
+        self.stop_requested.store(true, Ordering::Relaxed);
+
         for proc in self.core_clients.values() {
             proc.terminate();
         }
     }
 
+    /// Returns a handle that can be used to spawn tasks on the runtime from any thread, including
+    /// threads not owned by the runtime. See `RuntimeHandle`.
+    pub fn handle(&self) -> RuntimeHandle {
+        RuntimeHandle::new(self.clone())
+    }
+
+    pub(super) fn is_stop_requested(&self) -> bool {
+        self.stop_requested.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn write_buffer_budget(&self) -> &Arc<WriteBufferBudget> {
+        &self.write_buffer_budget
+    }
+
     /// Returns `true` if the runtime has been asked to stop.
     /// If so, enqueued tasks are unlikely to actually execute.
     pub fn is_stopping(&self) -> bool {
@@ -524,6 +628,8 @@ impl fmt::Debug for RuntimeClient {
             .field("processor_ids", &self.processor_ids)
             .field("join_handles", &self.join_handles)
             .field("is_stopping", &self.is_stopping)
+            .field("stop_requested", &self.stop_requested)
+            .field("write_buffer_budget", &self.write_buffer_budget)
             .finish()
     }
 }
@@ -557,6 +663,18 @@ thread_local! {
     static NEXT_SYNC_PROCESSOR_INDEX: Cell<usize> = const { Cell::new(0) };
 }
 
+/// Spawns a local task on the current async worker thread on behalf of a remote join handle, which
+/// aborts the task via the given abort state.
+fn spawn_local_with_abort_state<F, R>(future: F, abort: Arc<AbortState>) -> LocalJoinHandle<R>
+where
+    F: Future<Output = R> + 'static,
+    R: 'static,
+{
+    current_async_agent::with(|agent| {
+        agent.spawn_with_abort_state(TaskPriority::default(), future, abort)
+    })
+}
+
 fn next_async_worker(max: usize) -> usize {
     let next = NEXT_ASYNC_WORKER_INDEX.get();
     NEXT_ASYNC_WORKER_INDEX.set((next + 1) % max);
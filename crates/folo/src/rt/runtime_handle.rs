@@ -0,0 +1,79 @@
+use crate::{
+    io,
+    rt::{RemoteJoinHandle, RuntimeClient},
+};
+use std::future::Future;
+
+/// A handle for spawning tasks on a Folo runtime from any thread, including threads that are not
+/// owned by the runtime (e.g. a thread on which a native library invokes a callback).
+///
+/// The handle can be cloned, stored and sent to other threads. Once the runtime has been asked to
+/// stop, the handle refuses to spawn tasks, as they would never be executed.
+///
+/// Obtain one via `RuntimeClient::handle()`.
+#[derive(Clone, Debug)]
+pub struct RuntimeHandle {
+    client: RuntimeClient,
+}
+
+impl RuntimeHandle {
+    pub(super) fn new(client: RuntimeClient) -> Self {
+        Self { client }
+    }
+
+    /// Spawns a task to execute a future on any async worker thread of the runtime, creating the
+    /// future via closure on the worker thread.
+    ///
+    /// Returns an error if the runtime has been asked to stop.
+    pub fn spawn_on_any<FN, F, R>(&self, future_fn: FN) -> io::Result<RemoteJoinHandle<R>>
+    where
+        FN: FnOnce() -> F + Send + 'static,
+        F: Future<Output = R> + 'static,
+        R: Send + 'static,
+    {
+        if self.client.is_stop_requested() {
+            return Err(io::Error::LogicError(
+                "cannot spawn tasks on a runtime that has been asked to stop".to_string(),
+            ));
+        }
+
+        Ok(self.client.spawn_on_any(future_fn))
+    }
+
+    /// Spawns a task to execute a future on the async worker thread with the given reactor ID,
+    /// creating the future via closure on that thread. See `RuntimeClient::spawn_on()`.
+    ///
+    /// Returns an error if the runtime has been asked to stop.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no async worker thread with the given reactor ID.
+    pub fn spawn_on<FN, F, R>(
+        &self,
+        reactor_id: usize,
+        future_fn: FN,
+    ) -> io::Result<RemoteJoinHandle<R>>
+    where
+        FN: FnOnce() -> F + Send + 'static,
+        F: Future<Output = R> + 'static,
+        R: Send + 'static,
+    {
+        if self.client.is_stop_requested() {
+            return Err(io::Error::LogicError(
+                "cannot spawn tasks on a runtime that has been asked to stop".to_string(),
+            ));
+        }
+
+        Ok(self.client.spawn_on(reactor_id, future_fn))
+    }
+
+    /// The reactor IDs of all the async worker threads of the runtime, for use with `spawn_on()`.
+    pub fn reactor_ids(&self) -> Box<[usize]> {
+        self.client.reactor_ids()
+    }
+
+    /// Returns `true` if the runtime has been asked to stop.
+    pub fn is_stopping(&self) -> bool {
+        self.client.is_stop_requested()
+    }
+}
@@ -0,0 +1,264 @@
+use futures::task::AtomicWaker;
+use pin_project::pin_project;
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    task::{self, Wake, Waker},
+};
+
+/// Polls all the futures in a tuple and completes with the output of the first one to complete,
+/// dropping the rest right away (cancelling any I/O operations they have started).
+///
+/// The futures must all have the same output type. To race futures with different output types,
+/// map their outputs into a common type first (e.g. via `FutureExt::map()` into an enum).
+///
+/// Each future is given its own waker, so when one of them is woken up, only that one is polled
+/// again. If several futures are ready at the same time, the first one in the tuple wins.
+///
+/// ```ignore
+/// let message = select((
+///     receiver.recv().map(Event::Message),
+///     sleep(Duration::from_secs(1)).map(|()| Event::Idle),
+/// ))
+/// .await;
+/// ```
+pub fn select<B>(branches: B) -> Select<B>
+where
+    B: SelectBranches,
+{
+    let wakers = Arc::new(SelectWakers {
+        // All the branches start out as woken up, so the first poll polls every one of them.
+        woken: AtomicU32::new(u32::MAX >> (32 - B::LEN)),
+        parent: AtomicWaker::new(),
+    });
+
+    let branch_wakers = (0..B::LEN)
+        .map(|index| {
+            Waker::from(Arc::new(BranchWaker {
+                wakers: Arc::clone(&wakers),
+                index,
+            }))
+        })
+        .collect();
+
+    Select {
+        branches: Some(branches),
+        wakers,
+        branch_wakers,
+    }
+}
+
+/// A tuple of futures that can be raced against each other via `select()`. This is implemented
+/// for tuples of 2 to 8 futures that all have the same output type.
+pub trait SelectBranches {
+    /// The output type shared by all the futures in the tuple.
+    type Output;
+
+    /// Number of futures in the tuple.
+    const LEN: usize;
+
+    /// Polls the future at `index` in the tuple.
+    fn poll_branch(
+        self: Pin<&mut Self>,
+        index: usize,
+        cx: &mut task::Context<'_>,
+    ) -> task::Poll<Self::Output>;
+}
+
+macro_rules! impl_select_branches {
+    ($len:literal; $($index:tt $future:ident),+) => {
+        impl<O, $($future),+> SelectBranches for ($($future,)+)
+        where
+            $($future: Future<Output = O>),+
+        {
+            type Output = O;
+
+            const LEN: usize = $len;
+
+            fn poll_branch(
+                self: Pin<&mut Self>,
+                index: usize,
+                cx: &mut task::Context<'_>,
+            ) -> task::Poll<O> {
+                // SAFETY: The futures are pinned together with the tuple - we never move them out.
+                let this = unsafe { self.get_unchecked_mut() };
+
+                match index {
+                    // SAFETY: See above.
+                    $($index => unsafe { Pin::new_unchecked(&mut this.$index) }.poll(cx),)+
+                    _ => unreachable!("index out of bounds of the select tuple"),
+                }
+            }
+        }
+    };
+}
+
+impl_select_branches!(2; 0 F0, 1 F1);
+impl_select_branches!(3; 0 F0, 1 F1, 2 F2);
+impl_select_branches!(4; 0 F0, 1 F1, 2 F2, 3 F3);
+impl_select_branches!(5; 0 F0, 1 F1, 2 F2, 3 F3, 4 F4);
+impl_select_branches!(6; 0 F0, 1 F1, 2 F2, 3 F3, 4 F4, 5 F5);
+impl_select_branches!(7; 0 F0, 1 F1, 2 F2, 3 F3, 4 F4, 5 F5, 6 F6);
+impl_select_branches!(8; 0 F0, 1 F1, 2 F2, 3 F3, 4 F4, 5 F5, 6 F6, 7 F7);
+
+/// Future returned by `select()`.
+#[pin_project]
+pub struct Select<B> {
+    // Cleared once we have a result, dropping the futures that did not complete.
+    #[pin]
+    branches: Option<B>,
+
+    wakers: Arc<SelectWakers>,
+
+    // One per branch, in the same order as the futures in the tuple.
+    branch_wakers: Box<[Waker]>,
+}
+
+impl<B> Future for Select<B>
+where
+    B: SelectBranches,
+{
+    type Output = B::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let mut this = self.project();
+
+        // We register before taking the woken branches, so a branch woken up while we are polling
+        // the others wakes us up again and is not missed.
+        this.wakers.parent.register(cx.waker());
+        let woken = this.wakers.woken.swap(0, Ordering::AcqRel);
+
+        let mut branches = this
+            .branches
+            .as_mut()
+            .as_pin_mut()
+            .expect("a select is never going to be polled after it has completed");
+
+        for (index, branch_waker) in this.branch_wakers.iter().enumerate() {
+            if woken & (1 << index) == 0 {
+                continue;
+            }
+
+            let mut branch_cx = task::Context::from_waker(branch_waker);
+
+            if let task::Poll::Ready(result) = branches.as_mut().poll_branch(index, &mut branch_cx)
+            {
+                this.branches.set(None);
+                return task::Poll::Ready(result);
+            }
+        }
+
+        task::Poll::Pending
+    }
+}
+
+impl<B> fmt::Debug for Select<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Select")
+            .field("completed", &self.branches.is_none())
+            .field("branches", &self.branch_wakers.len())
+            .finish()
+    }
+}
+
+#[derive(Debug)]
+struct SelectWakers {
+    // One bit per branch, set when the branch has been woken up and needs to be polled.
+    woken: AtomicU32,
+
+    // The waker of the task awaiting the select.
+    parent: AtomicWaker,
+}
+
+struct BranchWaker {
+    wakers: Arc<SelectWakers>,
+    index: usize,
+}
+
+impl Wake for BranchWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.wakers
+            .woken
+            .fetch_or(1 << self.index, Ordering::AcqRel);
+        self.wakers.parent.wake();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{
+        future::{self, poll_fn},
+        task::noop_waker_ref,
+        FutureExt,
+    };
+    use std::{cell::Cell, pin::pin, rc::Rc};
+
+    #[test]
+    fn first_ready_branch_wins() {
+        let mut select = pin!(select((
+            future::pending::<u32>(),
+            future::ready(2),
+            future::ready(3)
+        )));
+
+        let mut cx = task::Context::from_waker(noop_waker_ref());
+        assert_eq!(select.poll_unpin(&mut cx), task::Poll::Ready(2));
+    }
+
+    #[test]
+    fn only_woken_branch_is_polled() {
+        let first_polls = Rc::new(Cell::new(0));
+        let second_polls = Rc::new(Cell::new(0));
+        let second_waker = Rc::new(Cell::new(None::<Waker>));
+
+        let first = poll_fn({
+            let first_polls = Rc::clone(&first_polls);
+
+            move |_| {
+                first_polls.set(first_polls.get() + 1);
+                task::Poll::<u32>::Pending
+            }
+        });
+
+        let second = poll_fn({
+            let second_polls = Rc::clone(&second_polls);
+            let second_waker = Rc::clone(&second_waker);
+
+            move |cx| {
+                second_polls.set(second_polls.get() + 1);
+
+                if second_polls.get() == 2 {
+                    return task::Poll::Ready(2);
+                }
+
+                second_waker.set(Some(cx.waker().clone()));
+                task::Poll::Pending
+            }
+        });
+
+        let mut select = pin!(select((first, second)));
+        let mut cx = task::Context::from_waker(noop_waker_ref());
+
+        assert_eq!(select.poll_unpin(&mut cx), task::Poll::Pending);
+        assert_eq!((first_polls.get(), second_polls.get()), (1, 1));
+
+        // Nothing was woken up, so nothing is polled.
+        assert_eq!(select.poll_unpin(&mut cx), task::Poll::Pending);
+        assert_eq!((first_polls.get(), second_polls.get()), (1, 1));
+
+        second_waker.take().unwrap().wake();
+
+        assert_eq!(select.poll_unpin(&mut cx), task::Poll::Ready(2));
+        assert_eq!((first_polls.get(), second_polls.get()), (1, 2));
+    }
+}
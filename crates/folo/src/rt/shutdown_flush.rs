@@ -0,0 +1,52 @@
+use crate::rt::current_async_agent;
+use negative_impl::negative_impl;
+use std::{future::Future, pin::Pin};
+
+pub(crate) type ShutdownFlushFn = Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = ()>>>>;
+
+/// Registers a flush callback with the shutdown sequence of the current async worker thread. When
+/// the runtime is stopped, the future returned by the callback is executed to completion before
+/// the tasks of the worker thread are dropped, giving buffered writers a chance to write out any
+/// data they are still holding.
+///
+/// The flush is performed on the async worker thread that registered it. Asynchronous I/O is
+/// available during the flush but synchronous worker threads are shutting down at the same time,
+/// so the flush must not depend on `spawn_sync()` tasks being executed. The shutdown of the worker
+/// thread waits for the flush to complete, so it must not wait for anything that never happens.
+///
+/// The callback is unregistered when the returned registration is dropped, so the owner of the
+/// buffered data should keep the registration alive for as long as it holds data that would need
+/// to be flushed.
+///
+/// # Panics
+///
+/// Panics if the current thread is not an async worker thread owned by a Folo runtime.
+pub fn on_shutdown_flush<FN, F>(flush_fn: FN) -> ShutdownFlushRegistration
+where
+    FN: FnOnce() -> F + 'static,
+    F: Future<Output = ()> + 'static,
+{
+    let flush_fn: ShutdownFlushFn = Box::new(move || Box::pin(flush_fn()));
+
+    let id = current_async_agent::with(|agent| agent.register_shutdown_flush(flush_fn));
+
+    ShutdownFlushRegistration { id }
+}
+
+/// Keeps a flush callback registered via `on_shutdown_flush()`, unregistering it when dropped.
+#[derive(Debug)]
+pub struct ShutdownFlushRegistration {
+    id: u64,
+}
+
+#[negative_impl]
+impl !Send for ShutdownFlushRegistration {}
+#[negative_impl]
+impl !Sync for ShutdownFlushRegistration {}
+
+impl Drop for ShutdownFlushRegistration {
+    fn drop(&mut self) {
+        // If the agent is already gone, there is nothing left to unregister from.
+        current_async_agent::try_with(|agent| agent.unregister_shutdown_flush(self.id));
+    }
+}
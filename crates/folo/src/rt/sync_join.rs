@@ -0,0 +1,57 @@
+use super::remote_waker::RemoteWaker;
+use crate::{io::IoWaker, rt::remote_result_box::RemoteResultBox};
+use std::{future::Future, pin::Pin, sync::Arc, task};
+
+/// Allows a task running on a synchronous worker thread to be awaited and its result to be
+/// observed on any thread.
+///
+/// Synchronous tasks always run to completion, so unlike the join handles of async tasks, this
+/// cannot abort the task and awaiting it yields the result of the task directly.
+///
+/// Awaiting this is optional - the task will run even if you drop the join handle.
+#[derive(Debug)]
+pub struct SyncJoinHandle<R>
+where
+    R: Send + 'static,
+{
+    result: Arc<RemoteResultBox<R>>,
+
+    // Used to also wake up the awaiting thread from I/O sleep if it is sleeping.
+    io_waker: Option<IoWaker>,
+}
+
+impl<R> SyncJoinHandle<R>
+where
+    R: Send + 'static,
+{
+    pub(crate) fn new(result: Arc<RemoteResultBox<R>>, io_waker: Option<IoWaker>) -> Self {
+        Self { result, io_waker }
+    }
+
+    /// Whether the task has completed.
+    pub fn is_finished(&self) -> bool {
+        self.result.is_set()
+    }
+}
+
+impl<R> Future for SyncJoinHandle<R>
+where
+    R: Send + 'static,
+{
+    type Output = R;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let poll_result = match &self.io_waker {
+            None => self.result.poll(cx.waker()),
+            Some(io_waker) => {
+                let composite_waker = RemoteWaker::new(io_waker.clone(), cx.waker().clone());
+                self.result.poll(&composite_waker.into())
+            }
+        };
+
+        match poll_result {
+            Some(result) => task::Poll::Ready(result),
+            None => task::Poll::Pending,
+        }
+    }
+}
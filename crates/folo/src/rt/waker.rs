@@ -1,7 +1,7 @@
 use crate::rt::async_task_engine::Task;
 use negative_impl::negative_impl;
 use std::{
-    cell::UnsafeCell,
+    cell::{RefCell, UnsafeCell},
     collections::VecDeque,
     pin::Pin,
     sync::{
@@ -19,11 +19,20 @@ use std::{
 ///
 /// ```ignore
 /// let mut context = Context::from_waker(self.wake_signal.waker());
-/// self.something.poll(&mut context);
+///
+/// self.wake_signal.begin_poll();
+/// let result = self.something.poll(&mut context);
+/// self.wake_signal.end_poll(result.is_pending());
 ///
 /// let awakened = self.wake_signal.consume_awakened();
 /// ```
 ///
+/// Wake-ups that arrive while a poll is in progress are not delivered immediately. Instead, they
+/// are counted and `end_poll()` reschedules the task once if the poll returned `Pending`, no matter
+/// how many wake-ups arrived meanwhile. Wake-ups that arrived before the poll started are covered
+/// by the poll itself, so `begin_poll()` discards them. This ensures that a task woken up during
+/// its own poll is polled again exactly once, without losing the wake-up or polling it twice.
+///
 /// # Safety
 ///
 /// The signal must be pinned at all times after calling `waker()`.
@@ -40,6 +49,15 @@ pub(crate) struct WakeSignal {
     // The task that we are waking up. We will insert this pointer into a list of awakened tasks.
     task_ptr: *mut Task,
 
+    // The queue of tasks that have been awakened from the thread that owns the task engine. As it
+    // is only ever touched by that thread, we do not need to synchronize access to it, making this
+    // the cheapest way to wake up a task. Owned by the task engine, which outlives all its tasks
+    // and therefore all wake signals.
+    local_awakened_queue: *const RefCell<VecDeque<*mut Task>>,
+
+    // Identifies the thread that owns the task engine, as returned by `current_thread_marker()`.
+    owner_thread: usize,
+
     // The queue of tasks that have been awakened by a signal. If we can lock the mutex without
     // blocking and if there is room in the queue, we add our task. Otherwise, we update
     // the signal itself and set the "probe signals to find awakened ones" flag.
@@ -56,9 +74,21 @@ pub(crate) struct WakeSignal {
     /// This seems independent from any other memory operations, so we use Relaxed ordering.
     waker_count: AtomicUsize,
 
-    /// Release ordering when setting, acquire ordering when consuming - we are passing a flag
-    /// and expect memory writes before passing the flag to be synchronized.
-    awakened: AtomicBool,
+    /// Number of wake-up notifications received via the signal itself since the last time they
+    /// were consumed. Release ordering when incrementing, acquire ordering when consuming - we are
+    /// passing a flag and expect memory writes before passing the flag to be synchronized.
+    wake_count: AtomicUsize,
+
+    /// Number of times any of our wakers was used since the last `consume_wake_count()`, no matter
+    /// which path delivered the wake-up. Nothing else resets this. It is only a statistic and does
+    /// not pass any data between threads, so we use Relaxed ordering.
+    wakes_since_consumed: AtomicUsize,
+
+    /// Whether the task is being polled right now, between `begin_poll()` and `end_poll()`. The
+    /// flag and the wake count are accessed with SeqCst ordering while a poll may be in progress,
+    /// as whether a wake-up is delivered by the waker or by `end_poll()` depends on the order in
+    /// which the two threads observe each other's writes to both of them.
+    poll_in_progress: AtomicBool,
 
     /// The real waker that we construct on first use. We hand out references to this.
     /// This is self-referential and we need to initialize it lazily once we are pinned.
@@ -71,16 +101,26 @@ pub(crate) struct WakeSignal {
 }
 
 impl WakeSignal {
-    pub(crate) fn new(
+    /// Creates a wake signal owned by the current thread.
+    ///
+    /// # Safety
+    ///
+    /// The local awakened queue must remain valid for as long as the wake signal exists.
+    pub(crate) unsafe fn new(
+        local_awakened_queue: *const RefCell<VecDeque<*mut Task>>,
         awakened_queue: Arc<Mutex<VecDeque<*mut Task>>>,
         probe_embedded_wake_signals: Arc<AtomicBool>,
     ) -> Self {
         Self {
             task_ptr: std::ptr::null_mut(),
+            local_awakened_queue,
+            owner_thread: current_thread_marker(),
             awakened_queue,
             probe_embedded_wake_signals,
             waker_count: AtomicUsize::new(0),
-            awakened: AtomicBool::new(false),
+            wake_count: AtomicUsize::new(0),
+            wakes_since_consumed: AtomicUsize::new(0),
+            poll_in_progress: AtomicBool::new(false),
             waker: UnsafeCell::new(None),
             _phantom_pinned: std::marker::PhantomPinned,
         }
@@ -92,14 +132,43 @@ impl WakeSignal {
         self.task_ptr = task_ptr;
     }
 
-    /// Returns whether the signal has received a wake-up notification. If so, resets the signal
-    /// to a not awakened state.
+    /// Returns whether the signal has received a wake-up notification via the signal itself. If
+    /// so, resets the signal to a not awakened state.
     pub(crate) fn consume_awakened(&self) -> bool {
-        // Most of the time, the flag will be false so we at first probe it with Relaxed ordering.
-        // If it is false, we can return early. If it is true, we need to ensure that we see all
-        // memory operations that happened before the flag was set, so we use Acquire ordering and
-        // load it one more time.
-        self.awakened.load(Ordering::Relaxed) && self.awakened.swap(false, Ordering::Acquire)
+        // Most of the time, the count will be zero so we at first probe it with Relaxed ordering.
+        // If it is zero, we can return early. If it is not, we need to ensure that we see all
+        // memory operations that happened before the count was incremented, so we use Acquire
+        // ordering and load it one more time.
+        self.wake_count.load(Ordering::Relaxed) > 0
+            && self.wake_count.swap(0, Ordering::Acquire) > 0
+    }
+
+    /// Returns the number of times the task was woken up since the last time this was called,
+    /// regardless of how the wake-ups were delivered, and resets the count. A count greater than
+    /// one means the task was woken up repeatedly without being polled in between.
+    pub(crate) fn consume_wake_count(&self) -> usize {
+        self.wakes_since_consumed.swap(0, Ordering::Relaxed)
+    }
+
+    /// Marks the start of a poll of the task. Wake-ups received before this are covered by the
+    /// poll and discarded, while wake-ups received from now on are held until `end_poll()`.
+    pub(crate) fn begin_poll(&self) {
+        // The flag goes first - a wake-up arriving in between is counted and then discarded, which
+        // is fine as the poll has not yet started.
+        self.poll_in_progress.store(true, Ordering::SeqCst);
+        self.wake_count.swap(0, Ordering::SeqCst);
+    }
+
+    /// Marks the end of a poll of the task. If the task was woken up while the poll was in
+    /// progress and the poll returned `Pending`, the task is rescheduled once.
+    pub(crate) fn end_poll(&self, is_pending: bool) {
+        self.poll_in_progress.store(false, Ordering::SeqCst);
+
+        if self.wake_count.swap(0, Ordering::SeqCst) > 0 && is_pending {
+            // The poll is over, so this takes the regular path. The wake-ups were already counted
+            // when they arrived, so we do not count this one.
+            self.deliver_wake();
+        }
     }
 
     /// Returns whether the signal is inert, meaning that no wakers are currently active and it is
@@ -112,6 +181,42 @@ impl WakeSignal {
         self.waker_count.load(Ordering::Relaxed) <= 1
     }
 
+    /// Returns the signal to its initial state so it can be reused for a new task without being
+    /// dropped and recreated. The waker previously returned by `waker()` is dropped - a fresh one is
+    /// created on next use.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the signal is not inert. Any wakers cloned from the signal must be gone before
+    /// the signal can be reset, as they would otherwise wake up whatever task reuses the signal.
+    ///
+    /// # Stale wake-ups
+    ///
+    /// The task pointer is kept, so wake-ups delivered before the reset may still be sitting in the
+    /// local awakened queue or the awakened queue, from where they will activate whatever task now
+    /// uses the signal. The owner of the queues must treat these as spurious wake-ups, which are
+    /// harmless as polling a task that was not actually woken up is always allowed.
+    pub(crate) fn reset(self: Pin<&mut Self>) {
+        assert!(
+            self.is_inert(),
+            "wake signal can only be reset when inert but {} wakers are active",
+            self.waker_count.load(Ordering::Relaxed)
+        );
+
+        // SAFETY: We are not moving anything out of the signal, just resetting fields in place.
+        let this = unsafe { Pin::into_inner_unchecked(self) };
+
+        // Dropping the original waker (if it was ever created) decrements the count to zero.
+        // Having exclusive access to the signal means nobody holds a reference to that waker.
+        *this.waker.get_mut() = None;
+
+        debug_assert_eq!(this.waker_count.load(Ordering::Relaxed), 0);
+
+        *this.wake_count.get_mut() = 0;
+        *this.wakes_since_consumed.get_mut() = 0;
+        *this.poll_in_progress.get_mut() = false;
+    }
+
     /// # Safety
     ///
     /// Once the waker has been used, the signal comes out of the inert state and is not valid to
@@ -144,6 +249,46 @@ impl WakeSignal {
     }
 
     fn wake(&self) {
+        self.wakes_since_consumed.fetch_add(1, Ordering::Relaxed);
+
+        self.deliver_wake();
+    }
+
+    fn deliver_wake(&self) {
+        // A wake-up that arrives while the task is being polled is picked up by `end_poll()` once
+        // the poll returns, so all we need to do is count it.
+        if self.poll_in_progress.load(Ordering::SeqCst) {
+            self.wake_count.fetch_add(1, Ordering::SeqCst);
+
+            if self.poll_in_progress.load(Ordering::SeqCst) {
+                return;
+            }
+
+            // The poll ended while we were counting. If `end_poll()` took the count, it reschedules
+            // the task on our behalf. Otherwise, we take the count back and deliver the wake-up
+            // the regular way.
+            if self.wake_count.swap(0, Ordering::SeqCst) == 0 {
+                return;
+            }
+        }
+
+        // Most wakeups come from the thread that owns the task (e.g. a task yielding or one local
+        // task waking up another), in which case we can skip all the cross-thread signaling. The
+        // queue is only borrowed briefly by the engine itself, so failing to borrow it is rare.
+        if current_thread_marker() == self.owner_thread {
+            // SAFETY: The queue outlives us, see field comment. We are on the owning thread, which
+            // is the only thread that ever accesses it.
+            let local_awakened_queue = unsafe { &*self.local_awakened_queue };
+
+            if let Ok(mut local_awakened_queue) = local_awakened_queue.try_borrow_mut() {
+                // A task woken up multiple times may be pushed multiple times, the same as with
+                // the shared queue below. The engine only activates tasks that are inactive, so
+                // the duplicates are discarded as spurious wakeups.
+                local_awakened_queue.push_back(self.task_ptr);
+                return;
+            }
+        }
+
         if let Ok(mut awakened_set) = self.awakened_queue.try_lock() {
             // We only add if we can do so without increasing capacity, because increasing capacity
             // from an arbitrary thread may require reallocation, which we do not want to do on a
@@ -157,9 +302,9 @@ impl WakeSignal {
             }
         }
 
-        // We release the awakened flag here, which means when someone acquires it
+        // We release the wake count here, which means when someone acquires it
         // they will see all the memory operations that happened up to this point.
-        self.awakened.store(true, Ordering::Release);
+        self.wake_count.fetch_add(1, Ordering::Release);
 
         self.probe_embedded_wake_signals
             .store(true, Ordering::Release);
@@ -218,9 +363,29 @@ unsafe fn resurrect_signal_ptr(ptr: *const ()) -> &'static WakeSignal {
     &*(ptr as *const WakeSignal)
 }
 
+thread_local! {
+    static THREAD_MARKER: u8 = const { 0 };
+}
+
+/// Returns a value unique to the current thread among all live threads, which is cheaper to obtain
+/// than the thread ID. As the owning thread of a wake signal outlives the signal, the value can
+/// never be reused by another thread while a signal still refers to it.
+fn current_thread_marker() -> usize {
+    THREAD_MARKER.with(|marker| marker as *const u8 as usize)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::{mem, thread};
+
+    // Wakeups from the owning thread take the fast path via the local queue, so wakeups that are
+    // to exercise the other paths need to come from a different thread.
+    fn wake_from_other_thread(waker: &Waker) {
+        thread::scope(|s| {
+            s.spawn(|| waker.wake_by_ref());
+        });
+    }
 
     #[test]
     fn awaken_via_embedded_signal() {
@@ -231,10 +396,15 @@ mod tests {
         // We hold the lock - the signal cannot use the set.
         let _awakened_set_lock_guard = awakened_queue.lock().unwrap();
 
-        let signal = WakeSignal::new(
-            Arc::clone(&awakened_queue),
-            Arc::clone(&probe_embedded_wake_signals),
-        );
+        let local_awakened_queue = RefCell::new(VecDeque::new());
+
+        let signal = unsafe {
+            WakeSignal::new(
+                &local_awakened_queue,
+                Arc::clone(&awakened_queue),
+                Arc::clone(&probe_embedded_wake_signals),
+            )
+        };
         let signal = unsafe { Pin::new_unchecked(&signal) };
 
         let waker = unsafe { signal.waker() };
@@ -253,10 +423,21 @@ mod tests {
 
         assert!(!signal.consume_awakened());
 
-        waker.wake_by_ref();
+        wake_from_other_thread(waker);
         assert!(probe_embedded_wake_signals.load(Ordering::Relaxed));
         assert!(signal.consume_awakened());
 
+        // Consuming the signal does not consume the count of wake-ups.
+        assert_eq!(signal.consume_wake_count(), 1);
+
+        // Repeated wakeups accumulate until consumed.
+        wake_from_other_thread(waker);
+        wake_from_other_thread(&waker_clone);
+        wake_from_other_thread(waker);
+        assert_eq!(signal.consume_wake_count(), 3);
+        assert_eq!(signal.consume_wake_count(), 0);
+        assert!(signal.consume_awakened());
+
         assert!(!signal.is_inert());
 
         // Once we drop the clone, we are again inert because only the original remains.
@@ -272,10 +453,15 @@ mod tests {
         let awakened_queue = Arc::new(Mutex::new(VecDeque::with_capacity(10)));
         let probe_embedded_wake_signals = Arc::new(AtomicBool::new(false));
 
-        let signal = WakeSignal::new(
-            Arc::clone(&awakened_queue),
-            Arc::clone(&probe_embedded_wake_signals),
-        );
+        let local_awakened_queue = RefCell::new(VecDeque::new());
+
+        let signal = unsafe {
+            WakeSignal::new(
+                &local_awakened_queue,
+                Arc::clone(&awakened_queue),
+                Arc::clone(&probe_embedded_wake_signals),
+            )
+        };
         let signal = unsafe { Pin::new_unchecked(&signal) };
 
         let waker = unsafe { signal.waker() };
@@ -294,7 +480,7 @@ mod tests {
 
         assert!(!signal.consume_awakened());
 
-        waker.wake_by_ref();
+        wake_from_other_thread(waker);
         // It should not have set the embedded signal here because we use the awakened set.
         assert!(!probe_embedded_wake_signals.load(Ordering::Relaxed));
         assert!(!signal.consume_awakened());
@@ -309,6 +495,52 @@ mod tests {
         assert!(signal.is_inert());
     }
 
+    #[test]
+    fn awaken_via_local_queue() {
+        #[allow(clippy::arc_with_non_send_sync)] // False positive? Or needs more annotations in type layers?
+        let awakened_queue = Arc::new(Mutex::new(VecDeque::with_capacity(10)));
+        let probe_embedded_wake_signals = Arc::new(AtomicBool::new(false));
+        let local_awakened_queue = RefCell::new(VecDeque::new());
+
+        let signal = unsafe {
+            WakeSignal::new(
+                &local_awakened_queue,
+                Arc::clone(&awakened_queue),
+                Arc::clone(&probe_embedded_wake_signals),
+            )
+        };
+        let signal = unsafe { Pin::new_unchecked(&signal) };
+
+        let waker = unsafe { signal.waker() };
+
+        // A wakeup from the owning thread only touches the local queue - there is no signaling
+        // that another thread would need to observe.
+        waker.wake_by_ref();
+        waker.wake_by_ref();
+        assert_eq!(local_awakened_queue.borrow().len(), 2);
+        assert!(awakened_queue.lock().unwrap().is_empty());
+        assert!(!probe_embedded_wake_signals.load(Ordering::Relaxed));
+        assert!(!signal.consume_awakened());
+
+        // While the engine is looking at the local queue, we fall back to the shared queue.
+        {
+            let _local_awakened_queue_borrow = local_awakened_queue.borrow_mut();
+            waker.wake_by_ref();
+        }
+
+        assert_eq!(awakened_queue.lock().unwrap().len(), 1);
+
+        // A wakeup from a different thread never touches the local queue.
+        wake_from_other_thread(waker);
+        assert_eq!(local_awakened_queue.borrow().len(), 2);
+        assert_eq!(awakened_queue.lock().unwrap().len(), 2);
+
+        // Every wake-up is counted, whichever queue it went to.
+        assert_eq!(signal.consume_wake_count(), 4);
+
+        assert!(signal.is_inert());
+    }
+
     #[test]
     fn awaken_via_full_awakened_set() {
         // Capacity is 0 so the queue is not allowed to allocate (== is never used).
@@ -316,10 +548,15 @@ mod tests {
         let awakened_queue = Arc::new(Mutex::new(VecDeque::with_capacity(0)));
         let probe_embedded_wake_signals = Arc::new(AtomicBool::new(false));
 
-        let signal = WakeSignal::new(
-            Arc::clone(&awakened_queue),
-            Arc::clone(&probe_embedded_wake_signals),
-        );
+        let local_awakened_queue = RefCell::new(VecDeque::new());
+
+        let signal = unsafe {
+            WakeSignal::new(
+                &local_awakened_queue,
+                Arc::clone(&awakened_queue),
+                Arc::clone(&probe_embedded_wake_signals),
+            )
+        };
         let signal = unsafe { Pin::new_unchecked(&signal) };
 
         let waker = unsafe { signal.waker() };
@@ -338,7 +575,7 @@ mod tests {
 
         assert!(!signal.consume_awakened());
 
-        waker.wake_by_ref();
+        wake_from_other_thread(waker);
         // Even though it could lock the set, it could not use it because it was at capacity.
         assert!(probe_embedded_wake_signals.load(Ordering::Relaxed));
         assert!(signal.consume_awakened());
@@ -351,4 +588,168 @@ mod tests {
         assert_eq!(signal.waker_count.load(Ordering::Relaxed), 1);
         assert!(signal.is_inert());
     }
+
+    #[test]
+    fn reset_inert_signal() {
+        #[allow(clippy::arc_with_non_send_sync)] // False positive? Or needs more annotations in type layers?
+        let awakened_queue = Arc::new(Mutex::new(VecDeque::with_capacity(0)));
+        let probe_embedded_wake_signals = Arc::new(AtomicBool::new(false));
+
+        let local_awakened_queue = RefCell::new(VecDeque::new());
+
+        let mut signal = unsafe {
+            WakeSignal::new(
+                &local_awakened_queue,
+                Arc::clone(&awakened_queue),
+                Arc::clone(&probe_embedded_wake_signals),
+            )
+        };
+        let mut signal = unsafe { Pin::new_unchecked(&mut signal) };
+
+        let waker = unsafe { signal.as_ref().waker() };
+        wake_from_other_thread(waker);
+
+        assert!(signal.is_inert());
+        assert_eq!(signal.waker_count.load(Ordering::Relaxed), 1);
+
+        // The signal is awakened but we never consume it - the reset takes care of that.
+        signal.as_mut().reset();
+
+        assert_eq!(signal.waker_count.load(Ordering::Relaxed), 0);
+        assert!(!signal.consume_awakened());
+
+        // The signal can now be used afresh, as if newly created.
+        let waker = unsafe { signal.as_ref().waker() };
+        assert_eq!(signal.waker_count.load(Ordering::Relaxed), 1);
+
+        let waker_clone = waker.clone();
+        assert!(!signal.is_inert());
+
+        wake_from_other_thread(&waker_clone);
+        drop(waker_clone);
+        assert!(signal.consume_awakened());
+        assert!(signal.is_inert());
+    }
+
+    #[test]
+    #[should_panic]
+    fn reset_active_signal_panics() {
+        #[allow(clippy::arc_with_non_send_sync)] // False positive? Or needs more annotations in type layers?
+        let awakened_queue = Arc::new(Mutex::new(VecDeque::with_capacity(0)));
+        let probe_embedded_wake_signals = Arc::new(AtomicBool::new(false));
+
+        // The local queue must outlive the leaked signal.
+        let local_awakened_queue = Box::leak(Box::new(RefCell::new(VecDeque::new())));
+
+        // We leak the signal because it is not inert when the test panics, so dropping it would
+        // fail an assertion while already panicking.
+        let signal = Box::leak(Box::new(unsafe {
+            WakeSignal::new(
+                local_awakened_queue,
+                Arc::clone(&awakened_queue),
+                Arc::clone(&probe_embedded_wake_signals),
+            )
+        }));
+        let mut signal = unsafe { Pin::new_unchecked(signal) };
+
+        let waker = unsafe { signal.as_ref().waker() };
+
+        // The clone may have been handed to another thread, so the signal is not inert.
+        mem::forget(waker.clone());
+
+        signal.as_mut().reset();
+    }
+
+    #[test]
+    fn wake_during_poll_reschedules_once() {
+        #[allow(clippy::arc_with_non_send_sync)] // False positive? Or needs more annotations in type layers?
+        let awakened_queue = Arc::new(Mutex::new(VecDeque::with_capacity(10)));
+        let probe_embedded_wake_signals = Arc::new(AtomicBool::new(false));
+
+        let local_awakened_queue = RefCell::new(VecDeque::new());
+
+        let signal = unsafe {
+            WakeSignal::new(
+                &local_awakened_queue,
+                Arc::clone(&awakened_queue),
+                Arc::clone(&probe_embedded_wake_signals),
+            )
+        };
+        let signal = unsafe { Pin::new_unchecked(&signal) };
+
+        let waker = unsafe { signal.waker() };
+
+        // A wake-up before the poll is covered by the poll.
+        signal.begin_poll();
+        signal.end_poll(true);
+        assert!(local_awakened_queue.borrow().is_empty());
+
+        // Repeated wake-ups during the poll reschedule the task once.
+        signal.begin_poll();
+        waker.wake_by_ref();
+        waker.wake_by_ref();
+        wake_from_other_thread(waker);
+        assert!(local_awakened_queue.borrow().is_empty());
+        signal.end_poll(true);
+        assert_eq!(local_awakened_queue.borrow_mut().drain(..).count(), 1);
+
+        // Each wake-up is counted once, even though the task is only rescheduled once, and the
+        // next poll does not reset the count.
+        signal.begin_poll();
+        signal.end_poll(true);
+        assert_eq!(signal.consume_wake_count(), 3);
+
+        // A task that completed is not rescheduled.
+        signal.begin_poll();
+        waker.wake_by_ref();
+        signal.end_poll(false);
+        assert!(local_awakened_queue.borrow().is_empty());
+
+        assert!(awakened_queue.lock().unwrap().is_empty());
+        assert!(!signal.consume_awakened());
+    }
+
+    #[test]
+    fn wake_racing_end_of_poll_is_delivered_once() {
+        #[allow(clippy::arc_with_non_send_sync)] // False positive? Or needs more annotations in type layers?
+        let awakened_queue = Arc::new(Mutex::new(VecDeque::with_capacity(10)));
+        let probe_embedded_wake_signals = Arc::new(AtomicBool::new(false));
+
+        let local_awakened_queue = RefCell::new(VecDeque::new());
+
+        let signal = unsafe {
+            WakeSignal::new(
+                &local_awakened_queue,
+                Arc::clone(&awakened_queue),
+                Arc::clone(&probe_embedded_wake_signals),
+            )
+        };
+        let signal = unsafe { Pin::new_unchecked(&signal) };
+
+        let waker = unsafe { signal.waker() };
+        let barrier = std::sync::Barrier::new(2);
+
+        for _ in 0..10_000 {
+            signal.begin_poll();
+
+            // The wake-up lands before, during or after the end of the poll, depending on timing.
+            thread::scope(|s| {
+                s.spawn(|| {
+                    barrier.wait();
+                    waker.wake_by_ref();
+                });
+
+                barrier.wait();
+                signal.end_poll(true);
+            });
+
+            // Whichever way the wake-up went, it must have been delivered exactly once.
+            let delivered = local_awakened_queue.borrow_mut().drain(..).count()
+                + awakened_queue.lock().unwrap().drain(..).count()
+                + usize::from(signal.consume_awakened());
+
+            assert_eq!(delivered, 1);
+            assert_eq!(signal.consume_wake_count(), 1);
+        }
+    }
 }
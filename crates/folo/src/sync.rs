@@ -1,4 +1,8 @@
+mod condvar;
+mod mutex;
 pub mod once_event;
 mod semaphores;
 
-pub use semaphores::*;
\ No newline at end of file
+pub use condvar::*;
+pub use mutex::*;
+pub use semaphores::*;
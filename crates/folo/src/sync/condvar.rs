@@ -0,0 +1,191 @@
+use crate::{constants::POISONED_LOCK, sync::MutexGuard};
+use futures::task::AtomicWaker;
+use std::{
+    collections::VecDeque,
+    future, mem,
+    sync::{
+        atomic::{self, AtomicBool},
+        Arc,
+    },
+    task::Poll,
+};
+
+/// An asynchronous condition variable for use with `Mutex`, allowing tasks to wait until some
+/// condition on the state protected by the mutex becomes true. The tasks may be on any threads.
+///
+/// Waiting tasks may be woken up even if the condition they are waiting for is not true, either
+/// because the notifier did not check the specific condition or because another task already
+/// acted on the state before the woken task reacquired the lock. Callers must always re-check
+/// the condition in a loop:
+///
+/// ```ignore
+/// let mut queue = mutex.lock().await;
+///
+/// while queue.is_empty() {
+///     queue = condvar.wait(queue).await;
+/// }
+/// ```
+///
+/// To avoid missing notifications, the state must only be changed while holding the lock and the
+/// condition must be checked while holding the lock before starting to wait.
+#[derive(Debug, Default)]
+pub struct Condvar {
+    // Tasks waiting for a notification, in the order they started waiting.
+    waiters: std::sync::Mutex<VecDeque<Arc<Waiter>>>,
+}
+
+#[derive(Debug, Default)]
+struct Waiter {
+    notified: AtomicBool,
+    waker: AtomicWaker,
+}
+
+impl Waiter {
+    fn notify(&self) {
+        self.notified.store(true, atomic::Ordering::Release);
+        self.waker.wake();
+    }
+}
+
+impl Condvar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Releases the lock, waits for a notification and reacquires the lock before returning.
+    ///
+    /// The task is registered as a waiter before the lock is released, so a notification sent by
+    /// another task after it acquires the lock is never missed.
+    pub async fn wait<'m, T: ?Sized>(&self, guard: MutexGuard<'m, T>) -> MutexGuard<'m, T> {
+        let mutex = guard.mutex();
+
+        let waiter = Arc::new(Waiter::default());
+        self.waiters
+            .lock()
+            .expect(POISONED_LOCK)
+            .push_back(Arc::clone(&waiter));
+
+        drop(guard);
+
+        // If we are dropped before being notified, we remove ourselves from the queue. If we were
+        // notified, we pass the notification on so it is not lost.
+        let registration = Registration {
+            condvar: self,
+            waiter: &waiter,
+        };
+
+        future::poll_fn(|cx| {
+            waiter.waker.register(cx.waker());
+
+            if waiter.notified.load(atomic::Ordering::Acquire) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+
+        // We got the notification and it is ours to act on.
+        registration.disarm();
+
+        mutex.lock().await
+    }
+
+    /// Wakes up the task that has been waiting the longest, if any task is waiting.
+    pub fn notify_one(&self) {
+        let waiter = self.waiters.lock().expect(POISONED_LOCK).pop_front();
+
+        if let Some(waiter) = waiter {
+            waiter.notify();
+        }
+    }
+
+    /// Wakes up all the waiting tasks.
+    pub fn notify_all(&self) {
+        let waiters = mem::take(&mut *self.waiters.lock().expect(POISONED_LOCK));
+
+        for waiter in waiters {
+            waiter.notify();
+        }
+    }
+}
+
+struct Registration<'a> {
+    condvar: &'a Condvar,
+    waiter: &'a Arc<Waiter>,
+}
+
+impl Registration<'_> {
+    fn disarm(self) {
+        mem::forget(self);
+    }
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        let mut waiters = self.condvar.waiters.lock().expect(POISONED_LOCK);
+
+        if let Some(index) = waiters.iter().position(|w| Arc::ptr_eq(w, self.waiter)) {
+            waiters.remove(index);
+            return;
+        }
+
+        drop(waiters);
+
+        // We are no longer in the queue, so a notification was already sent to us. As we are not
+        // going to act on it, we hand it over to the next waiter.
+        if self.waiter.notified.load(atomic::Ordering::Acquire) {
+            self.condvar.notify_one();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::Mutex;
+    use futures::{task::noop_waker_ref, FutureExt};
+    use std::{pin::pin, task};
+
+    #[test]
+    fn notify_one_wakes_waiter() {
+        let mutex = Mutex::new(0);
+        let condvar = Condvar::new();
+
+        let mut cx = task::Context::from_waker(noop_waker_ref());
+
+        let guard = mutex.try_lock().unwrap();
+        let mut wait = pin!(condvar.wait(guard));
+        assert!(wait.poll_unpin(&mut cx).is_pending());
+
+        // The lock was released by the waiter, so we can update the state.
+        *mutex.try_lock().unwrap() = 1;
+        condvar.notify_one();
+
+        let task::Poll::Ready(guard) = wait.poll_unpin(&mut cx) else {
+            panic!("waiter should have been notified");
+        };
+
+        assert_eq!(*guard, 1);
+    }
+
+    #[test]
+    fn notification_passed_on_when_waiter_dropped() {
+        let mutex = Mutex::new(());
+        let condvar = Condvar::new();
+
+        let mut cx = task::Context::from_waker(noop_waker_ref());
+
+        let mut first = Box::pin(condvar.wait(mutex.try_lock().unwrap()));
+        assert!(first.poll_unpin(&mut cx).is_pending());
+
+        let mut second = pin!(condvar.wait(mutex.try_lock().unwrap()));
+        assert!(second.poll_unpin(&mut cx).is_pending());
+
+        // The notification goes to the first waiter, which gives up before acting on it.
+        condvar.notify_one();
+        drop(first);
+
+        assert!(second.poll_unpin(&mut cx).is_ready());
+    }
+}
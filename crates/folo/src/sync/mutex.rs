@@ -0,0 +1,314 @@
+use crate::constants::POISONED_LOCK;
+use std::{
+    cell::UnsafeCell,
+    collections::VecDeque,
+    future::Future,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    task::{self, Waker},
+};
+
+/// An asynchronous mutual exclusion lock that can be shared between tasks on any threads. Waiting
+/// for the lock parks the task instead of blocking the thread.
+///
+/// The lock is fair - waiting tasks acquire it in the order they started waiting. When the lock is
+/// released while tasks are waiting, it is handed over directly to the task that has been waiting
+/// the longest, so a task that keeps reacquiring the lock cannot starve the others.
+#[derive(Debug, Default)]
+pub struct Mutex<T: ?Sized> {
+    state: std::sync::Mutex<MutexState>,
+    value: UnsafeCell<T>,
+}
+
+#[derive(Debug, Default)]
+struct MutexState {
+    // Remains set while the lock is being handed over to a waiter, so nobody can cut in line.
+    locked: bool,
+
+    // Tasks that found the lock held, in the order they started waiting.
+    waiters: VecDeque<Waiter>,
+
+    // The waiter that the lock has been handed over to, which has not yet taken it.
+    handed_over_to: Option<WaiterId>,
+
+    next_waiter_id: WaiterId,
+}
+
+type WaiterId = u64;
+
+#[derive(Debug)]
+struct Waiter {
+    id: WaiterId,
+    waker: Waker,
+}
+
+// SAFETY: Access to the value is serialized by the lock, so it is enough for the value to be Send.
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+// SAFETY: Access to the value is serialized by the lock, so it is enough for the value to be Send.
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+
+impl<T> Mutex<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            state: std::sync::Mutex::new(MutexState::default()),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Waits until the lock can be acquired and acquires it. The lock is released when the
+    /// returned guard is dropped.
+    pub fn lock(&self) -> impl Future<Output = MutexGuard<'_, T>> {
+        Lock {
+            mutex: self,
+            waiter_id: None,
+        }
+    }
+
+    /// Acquires the lock if it is not held, without waiting. Succeeds even if tasks are waiting
+    /// for the lock, as long as it is not held when this is called.
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        let mut state = self.state.lock().expect(POISONED_LOCK);
+
+        if state.locked {
+            return None;
+        }
+
+        state.locked = true;
+        Some(MutexGuard {
+            mutex: self,
+            _value: PhantomData,
+        })
+    }
+
+    fn unlock(&self) {
+        let next = {
+            let mut state = self.state.lock().expect(POISONED_LOCK);
+            Self::hand_over(&mut state)
+        };
+
+        if let Some(waker) = next {
+            waker.wake();
+        }
+    }
+
+    /// Hands the lock over to the longest waiting task or releases it if nobody is waiting.
+    /// Returns the waker of the task that got the lock, to be woken up once the state is unlocked.
+    fn hand_over(state: &mut MutexState) -> Option<Waker> {
+        let Some(next) = state.waiters.pop_front() else {
+            state.locked = false;
+            state.handed_over_to = None;
+            return None;
+        };
+
+        state.handed_over_to = Some(next.id);
+        Some(next.waker)
+    }
+}
+
+struct Lock<'m, T: ?Sized> {
+    mutex: &'m Mutex<T>,
+
+    // Set once we have joined the queue of waiters.
+    waiter_id: Option<WaiterId>,
+}
+
+impl<'m, T: ?Sized> Future for Lock<'m, T> {
+    type Output = MutexGuard<'m, T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context) -> task::Poll<Self::Output> {
+        let mutex = self.mutex;
+        let mut state = mutex.state.lock().expect(POISONED_LOCK);
+
+        let acquired = match self.waiter_id {
+            Some(id) if state.handed_over_to == Some(id) => {
+                state.handed_over_to = None;
+                true
+            }
+            Some(id) => {
+                // We are still waiting in line - the waker may have changed since we joined.
+                if let Some(waiter) = state.waiters.iter_mut().find(|waiter| waiter.id == id) {
+                    waiter.waker.clone_from(cx.waker());
+                }
+
+                false
+            }
+            None if !state.locked => {
+                state.locked = true;
+                true
+            }
+            None => {
+                let id = state.next_waiter_id;
+                state.next_waiter_id += 1;
+
+                state.waiters.push_back(Waiter {
+                    id,
+                    waker: cx.waker().clone(),
+                });
+
+                drop(state);
+                self.waiter_id = Some(id);
+
+                false
+            }
+        };
+
+        if !acquired {
+            return task::Poll::Pending;
+        }
+
+        // The guard is responsible for the lock from now on.
+        self.waiter_id = None;
+
+        task::Poll::Ready(MutexGuard {
+            mutex,
+            _value: PhantomData,
+        })
+    }
+}
+
+impl<T: ?Sized> Drop for Lock<'_, T> {
+    fn drop(&mut self) {
+        let Some(id) = self.waiter_id else {
+            return;
+        };
+
+        let next = {
+            let mut state = self.mutex.state.lock().expect(POISONED_LOCK);
+
+            if state.handed_over_to == Some(id) {
+                // We were given the lock but are no longer interested, so we pass it on.
+                Mutex::<T>::hand_over(&mut state)
+            } else {
+                state.waiters.retain(|waiter| waiter.id != id);
+                None
+            }
+        };
+
+        if let Some(waker) = next {
+            waker.wake();
+        }
+    }
+}
+
+/// Grants access to the value protected by a `Mutex`, releasing the lock when dropped.
+#[derive(Debug)]
+pub struct MutexGuard<'m, T: ?Sized> {
+    mutex: &'m Mutex<T>,
+
+    // The guard grants shared access to the value, so it may only be shared between threads if the
+    // value may be.
+    _value: PhantomData<&'m T>,
+}
+
+impl<'m, T: ?Sized> MutexGuard<'m, T> {
+    /// The mutex this guard holds the lock of.
+    pub(crate) fn mutex(&self) -> &'m Mutex<T> {
+        self.mutex
+    }
+}
+
+impl<T: ?Sized> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: The guard exists only while the lock is held, so nobody else is accessing it.
+        unsafe { &*self.mutex.value.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: The guard exists only while the lock is held, so nobody else is accessing it.
+        unsafe { &mut *self.mutex.value.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.unlock();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{task::noop_waker_ref, FutureExt};
+    use std::pin::pin;
+
+    #[test]
+    fn lock_waits_until_released() {
+        let mutex = Mutex::new(1);
+
+        let mut guard = mutex.try_lock().unwrap();
+        *guard += 1;
+
+        assert!(mutex.try_lock().is_none());
+
+        let mut cx = task::Context::from_waker(noop_waker_ref());
+        let mut lock = pin!(mutex.lock());
+        assert!(lock.poll_unpin(&mut cx).is_pending());
+
+        drop(guard);
+
+        let task::Poll::Ready(guard) = lock.poll_unpin(&mut cx) else {
+            panic!("lock should be acquirable after release");
+        };
+
+        assert_eq!(*guard, 2);
+    }
+
+    #[test]
+    fn waiters_acquire_in_order() {
+        let mutex = Mutex::new(());
+        let mut cx = task::Context::from_waker(noop_waker_ref());
+
+        let guard = mutex.try_lock().unwrap();
+
+        let mut first_in_line = pin!(mutex.lock());
+        let mut second_in_line = pin!(mutex.lock());
+        assert!(first_in_line.poll_unpin(&mut cx).is_pending());
+        assert!(second_in_line.poll_unpin(&mut cx).is_pending());
+
+        drop(guard);
+
+        // The lock is handed over to the task that started waiting first, even if it is not the
+        // first to check, and nobody else can take it meanwhile.
+        assert!(second_in_line.poll_unpin(&mut cx).is_pending());
+        assert!(mutex.try_lock().is_none());
+
+        let task::Poll::Ready(guard) = first_in_line.poll_unpin(&mut cx) else {
+            panic!("lock should be handed over to the first waiter");
+        };
+
+        assert!(second_in_line.poll_unpin(&mut cx).is_pending());
+        drop(guard);
+
+        assert!(second_in_line.poll_unpin(&mut cx).is_ready());
+    }
+
+    #[test]
+    fn dropped_waiter_passes_lock_on() {
+        let mutex = Mutex::new(());
+        let mut cx = task::Context::from_waker(noop_waker_ref());
+
+        let guard = mutex.try_lock().unwrap();
+
+        let mut first = Box::pin(mutex.lock());
+        let mut second = pin!(mutex.lock());
+        assert!(first.poll_unpin(&mut cx).is_pending());
+        assert!(second.poll_unpin(&mut cx).is_pending());
+
+        // The lock is handed over to the first waiter, which gives up before taking it.
+        drop(guard);
+        drop(first);
+
+        assert!(second.poll_unpin(&mut cx).is_ready());
+    }
+}
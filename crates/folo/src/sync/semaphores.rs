@@ -1,3 +1,5 @@
+use crate::constants::POISONED_LOCK;
+use futures::task::AtomicWaker;
 use negative_impl::negative_impl;
 use std::{
     cell::{Cell, RefCell},
@@ -5,11 +7,16 @@ use std::{
     future::Future,
     pin::Pin,
     rc::Rc,
+    sync::{
+        atomic::{self, AtomicBool},
+        Arc,
+    },
     task::{self, Waker},
 };
 
 /// Controls access to a thread-local resource, granting only a limited number of concurrent tasks
 /// access.
+#[derive(Debug)]
 pub struct LocalSemaphore<const MAX: usize> {
     current: Cell<usize>,
     awaiting: Rc<RefCell<VecDeque<Waker>>>,
@@ -80,3 +87,227 @@ impl<'s, const MAX: usize> Drop for LocalSemaphoreGuard<'s, MAX> {
         self.semaphore.release_one();
     }
 }
+
+/// Limits the number of tasks that can access a resource concurrently, e.g. to bound how many
+/// I/O operations are in flight at the same time. The tasks may be on any threads.
+///
+/// The semaphore is fair - waiting tasks get permits in the order they started waiting. A released
+/// permit is handed over directly to the task that has been waiting the longest.
+#[derive(Debug)]
+pub struct Semaphore {
+    state: std::sync::Mutex<SemaphoreState>,
+}
+
+#[derive(Debug)]
+struct SemaphoreState {
+    // Permits not held by anyone. Permits are only available if nobody is waiting for them.
+    available: usize,
+
+    // Tasks waiting for a permit, in the order they started waiting.
+    waiters: VecDeque<Arc<PermitWaiter>>,
+}
+
+#[derive(Debug, Default)]
+struct PermitWaiter {
+    // Set (while holding the state lock) when a permit is handed over to the waiter.
+    granted: AtomicBool,
+    waker: AtomicWaker,
+}
+
+impl Semaphore {
+    /// Creates a semaphore with the given number of permits.
+    pub fn new(permits: usize) -> Self {
+        Self {
+            state: std::sync::Mutex::new(SemaphoreState {
+                available: permits,
+                waiters: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Waits until a permit is available and takes it. The permit is returned to the semaphore
+    /// when the returned guard is dropped.
+    pub fn acquire(&self) -> impl Future<Output = SemaphorePermit<'_>> {
+        AcquirePermit {
+            semaphore: self,
+            waiter: None,
+        }
+    }
+
+    /// Takes a permit if one is available, without waiting.
+    pub fn try_acquire(&self) -> Option<SemaphorePermit<'_>> {
+        let mut state = self.state.lock().expect(POISONED_LOCK);
+
+        if state.available == 0 {
+            return None;
+        }
+
+        state.available -= 1;
+        Some(SemaphorePermit { semaphore: self })
+    }
+
+    /// Number of permits that can be taken without waiting.
+    pub fn available_permits(&self) -> usize {
+        self.state.lock().expect(POISONED_LOCK).available
+    }
+
+    /// Hands the permit over to the longest waiting task or makes it available if nobody is
+    /// waiting.
+    fn release(&self) {
+        let next = {
+            let mut state = self.state.lock().expect(POISONED_LOCK);
+
+            let next = state.waiters.pop_front();
+
+            match &next {
+                Some(waiter) => waiter.granted.store(true, atomic::Ordering::Release),
+                None => state.available += 1,
+            }
+
+            next
+        };
+
+        if let Some(waiter) = next {
+            waiter.waker.wake();
+        }
+    }
+}
+
+struct AcquirePermit<'s> {
+    semaphore: &'s Semaphore,
+
+    // Set once we have joined the queue of waiters.
+    waiter: Option<Arc<PermitWaiter>>,
+}
+
+impl<'s> Future for AcquirePermit<'s> {
+    type Output = SemaphorePermit<'s>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context) -> task::Poll<Self::Output> {
+        let semaphore = self.semaphore;
+
+        if let Some(waiter) = &self.waiter {
+            // We register before checking, so a permit granted in between is not missed.
+            waiter.waker.register(cx.waker());
+
+            if !waiter.granted.load(atomic::Ordering::Acquire) {
+                return task::Poll::Pending;
+            }
+
+            // The permit is responsible for the grant from now on.
+            self.waiter = None;
+            return task::Poll::Ready(SemaphorePermit { semaphore });
+        }
+
+        let mut state = semaphore.state.lock().expect(POISONED_LOCK);
+
+        if state.available > 0 {
+            state.available -= 1;
+            return task::Poll::Ready(SemaphorePermit { semaphore });
+        }
+
+        let waiter = Arc::new(PermitWaiter::default());
+        waiter.waker.register(cx.waker());
+        state.waiters.push_back(Arc::clone(&waiter));
+
+        drop(state);
+        self.waiter = Some(waiter);
+
+        task::Poll::Pending
+    }
+}
+
+impl Drop for AcquirePermit<'_> {
+    fn drop(&mut self) {
+        let Some(waiter) = self.waiter.take() else {
+            return;
+        };
+
+        let granted = {
+            let mut state = self.semaphore.state.lock().expect(POISONED_LOCK);
+
+            // The flag is only set while holding the state lock, so it cannot change under us.
+            let granted = waiter.granted.load(atomic::Ordering::Acquire);
+
+            if !granted {
+                state.waiters.retain(|other| !Arc::ptr_eq(other, &waiter));
+            }
+
+            granted
+        };
+
+        // We were given a permit but are no longer interested, so we pass it on.
+        if granted {
+            self.semaphore.release();
+        }
+    }
+}
+
+/// A permit taken from a `Semaphore`, returned to the semaphore when dropped.
+#[derive(Debug)]
+pub struct SemaphorePermit<'s> {
+    semaphore: &'s Semaphore,
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        self.semaphore.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{task::noop_waker_ref, FutureExt};
+    use std::pin::pin;
+
+    #[test]
+    fn acquire_beyond_permits_waits() {
+        let semaphore = Semaphore::new(2);
+        let mut cx = task::Context::from_waker(noop_waker_ref());
+
+        let first = semaphore.try_acquire().unwrap();
+        let _second = semaphore.acquire().now_or_never().unwrap();
+        assert_eq!(semaphore.available_permits(), 0);
+
+        let mut third = pin!(semaphore.acquire());
+        let mut fourth = pin!(semaphore.acquire());
+        assert!(third.poll_unpin(&mut cx).is_pending());
+        assert!(fourth.poll_unpin(&mut cx).is_pending());
+        assert!(semaphore.try_acquire().is_none());
+
+        // The released permit goes to the task that started waiting first.
+        drop(first);
+        assert_eq!(semaphore.available_permits(), 0);
+        assert!(fourth.poll_unpin(&mut cx).is_pending());
+
+        let task::Poll::Ready(third) = third.poll_unpin(&mut cx) else {
+            panic!("permit should be handed over to the first waiter");
+        };
+
+        drop(third);
+        assert!(fourth.poll_unpin(&mut cx).is_ready());
+    }
+
+    #[test]
+    fn dropped_waiter_passes_permit_on() {
+        let semaphore = Semaphore::new(1);
+        let mut cx = task::Context::from_waker(noop_waker_ref());
+
+        let permit = semaphore.try_acquire().unwrap();
+
+        let mut first = Box::pin(semaphore.acquire());
+        let mut second = pin!(semaphore.acquire());
+        assert!(first.poll_unpin(&mut cx).is_pending());
+        assert!(second.poll_unpin(&mut cx).is_pending());
+
+        // The permit is handed over to the first waiter, which gives up before taking it.
+        drop(permit);
+        drop(first);
+
+        assert!(second.poll_unpin(&mut cx).is_ready());
+
+        // Nobody is waiting anymore, so the permit becomes available once more.
+        assert_eq!(semaphore.available_permits(), 1);
+    }
+}
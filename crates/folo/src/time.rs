@@ -8,6 +8,7 @@ mod error;
 mod low_precision;
 mod periodic_timer;
 mod stopwatch;
+mod timeout;
 mod timers;
 mod ultra_low_precision;
 
@@ -19,5 +20,6 @@ pub use error::*;
 pub use low_precision::*;
 pub use periodic_timer::*;
 pub use stopwatch::*;
+pub use timeout::*;
 pub(crate) use timers::*;
 pub use ultra_low_precision::*;
\ No newline at end of file
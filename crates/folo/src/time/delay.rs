@@ -10,6 +10,14 @@ use negative_impl::negative_impl;
 use super::timers::TimerKey;
 use super::Clock;
 
+/// Asynchronously delays for the specified duration, using the real clock.
+///
+/// The delay is tracked by the timers of the current thread, which the async worker consults to
+/// decide how long it may wait for I/O, so the task is woken up on time without any polling.
+pub fn sleep(duration: Duration) -> Delay {
+    Delay::with_clock(&Clock::new(), duration)
+}
+
 /// Asynchronously delays for the specified duration.
 #[derive(Debug)]
 pub struct Delay {
@@ -53,6 +61,16 @@ impl Delay {
     }
 }
 
+impl Drop for Delay {
+    fn drop(&mut self) {
+        // A delay dropped before it finishes (e.g. when a timeout is not needed after all) must
+        // not leave its timer behind, as that would keep the waker of the task alive until then.
+        if let Some(key) = self.current_timer.take() {
+            self.clock.unregister_timer(key);
+        }
+    }
+}
+
 impl Future for Delay {
     type Output = ();
 
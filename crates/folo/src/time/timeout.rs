@@ -0,0 +1,120 @@
+use super::{sleep, Delay};
+use pin_project::pin_project;
+use std::{future::Future, pin::Pin, task, time::Duration};
+
+/// The result of a `timeout()` whose duration elapsed before the future completed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, thiserror::Error)]
+#[error("deadline elapsed before the future completed")]
+pub struct Elapsed;
+
+/// Polls a future until it completes or until the duration elapses, whichever comes first.
+///
+/// If the duration elapses first, the future is dropped right away, cancelling any I/O operations
+/// it has started. If the future completes first, the timer is released right away.
+pub fn timeout<F>(duration: Duration, future: F) -> Timeout<F>
+where
+    F: Future,
+{
+    Timeout {
+        future: Some(future),
+        delay: Some(sleep(duration)),
+    }
+}
+
+/// Future returned by `timeout()`.
+#[pin_project]
+#[derive(Debug)]
+pub struct Timeout<F> {
+    // Both are cleared once we have a result, dropping the future and releasing the timer.
+    #[pin]
+    future: Option<F>,
+    delay: Option<Delay>,
+}
+
+impl<F> Future for Timeout<F>
+where
+    F: Future,
+{
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let mut this = self.project();
+
+        let future = this
+            .future
+            .as_mut()
+            .as_pin_mut()
+            .expect("a timeout is never going to be polled after it has completed");
+
+        // The future goes first, so a future that is ready wins even if the timeout has elapsed.
+        if let task::Poll::Ready(result) = future.poll(cx) {
+            this.future.set(None);
+            *this.delay = None;
+            return task::Poll::Ready(Ok(result));
+        }
+
+        let delay = this
+            .delay
+            .as_mut()
+            .expect("the delay is only removed together with the future");
+
+        match Pin::new(delay).poll(cx) {
+            task::Poll::Ready(()) => {
+                this.future.set(None);
+                *this.delay = None;
+                task::Poll::Ready(Err(Elapsed))
+            }
+            task::Poll::Pending => task::Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::timers::{Timers, LOCAL_TIMERS};
+    use futures::task::noop_waker;
+    use std::pin::pin;
+
+    #[test]
+    fn completed_future_releases_timer() {
+        let waker = noop_waker();
+        let mut cx = task::Context::from_waker(&waker);
+
+        let timers_before = LOCAL_TIMERS.with_borrow(Timers::len);
+
+        // The future is not ready on the first poll, so the timer gets registered.
+        let mut polled = false;
+        let future = std::future::poll_fn(|_| {
+            if polled {
+                task::Poll::Ready(42)
+            } else {
+                polled = true;
+                task::Poll::Pending
+            }
+        });
+
+        let mut timeout = pin!(timeout(Duration::from_secs(60), future));
+
+        assert!(timeout.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(LOCAL_TIMERS.with_borrow(Timers::len), timers_before + 1);
+
+        assert_eq!(timeout.as_mut().poll(&mut cx), task::Poll::Ready(Ok(42)));
+        assert_eq!(LOCAL_TIMERS.with_borrow(Timers::len), timers_before);
+    }
+
+    #[test]
+    fn dropped_delay_releases_timer() {
+        let waker = noop_waker();
+        let mut cx = task::Context::from_waker(&waker);
+
+        let timers_before = LOCAL_TIMERS.with_borrow(Timers::len);
+
+        let mut delay = sleep(Duration::from_secs(60));
+        assert!(Pin::new(&mut delay).poll(&mut cx).is_pending());
+        assert_eq!(LOCAL_TIMERS.with_borrow(Timers::len), timers_before + 1);
+
+        drop(delay);
+        assert_eq!(LOCAL_TIMERS.with_borrow(Timers::len), timers_before);
+    }
+}
@@ -41,6 +41,11 @@ pub(crate) fn advance_local_timers(now: Instant) {
     LOCAL_TIMERS.with_borrow_mut(|timer_manager| timer_manager.advance_timers(now));
 }
 
+/// Returns when the earliest thread-local timer fires, if any timers are registered.
+pub(crate) fn next_local_timer() -> Option<Instant> {
+    LOCAL_TIMERS.with_borrow(Timers::next_tick)
+}
+
 /// The management of one-shot timers, inspired by [glommio runtime](https://github.com/DataDog/glommio/blob/d3f6e7a2ee7fb071ada163edcf90fc3286424c31/glommio/src/reactor.rs#L80)
 ///
 /// The timers managed by this collection are one-shot, meaning after they fire they won't be fired again.
@@ -87,6 +92,11 @@ impl Timers {
         self.wakers.remove(&id);
     }
 
+    /// Returns when the earliest registered timer fires.
+    pub fn next_tick(&self) -> Option<Instant> {
+        self.wakers.first_key_value().map(|(key, _)| key.tick())
+    }
+
     /// Advance timers that are ready to be woken.
     ///
     /// Later, the signature of this method can be easily expanded to return more
@@ -166,6 +176,20 @@ mod tests {
         assert!(!LOCAL_TIMERS.with_borrow(|t| t.contains(id2)));
     }
 
+    #[test]
+    fn next_tick_is_earliest_timer() {
+        let anchor = Instant::now();
+        let mut timers = Timers::new();
+        assert_eq!(timers.next_tick(), None);
+
+        let later = timers.register(anchor + Duration::from_secs(2), noop_waker());
+        let earlier = timers.register(anchor + Duration::from_secs(1), noop_waker());
+        assert_eq!(timers.next_tick(), Some(earlier.tick()));
+
+        timers.unregister(earlier);
+        assert_eq!(timers.next_tick(), Some(later.tick()));
+    }
+
     #[test]
     fn timer_resolution_ensure_correct_value() {
         assert_eq!(TIMER_RESOLUTION, Duration::from_millis(1));
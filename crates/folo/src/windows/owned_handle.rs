@@ -82,8 +82,13 @@ where
         // Alternatively, we may be in the middle of shutting down but the runtime might still exist
         // in a semi-functional state (reference exists but runtime is not accepting any more tasks).
         // In this case we just perform the drop synchronously because there is not much else to do.
+        //
+        // We also perform the drop synchronously if we are not on an async worker thread (e.g. if
+        // the handle was moved into a task on a synchronous worker thread), as there is nothing to
+        // gain from moving the work to another thread - blocking is fine here.
         if !crate::rt::current_runtime::is_some()
             || crate::rt::current_runtime::with(|x| x.is_stopping())
+            || !crate::rt::current_async_agent::is_some()
         {
             unsafe {
                 (*thread_safe).free();
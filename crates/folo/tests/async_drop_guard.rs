@@ -0,0 +1,38 @@
+use folo::rt::AsyncDropGuard;
+use folo_testing::{init_test_worker, temp_path};
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn cleanup_runs_on_scope_exit() {
+    let path = temp_path("cleanup_runs_on_scope_exit");
+    _ = std::fs::remove_file(&path);
+
+    let (done_tx, done_rx) = oneshot::channel();
+
+    {
+        let path = path.clone();
+
+        let _guard = AsyncDropGuard::new(move || async move {
+            std::fs::write(&path, b"flushed").unwrap();
+            done_tx.send(()).unwrap();
+        });
+    }
+
+    done_rx.await.unwrap();
+
+    assert_eq!(std::fs::read(&path).unwrap(), b"flushed");
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn canceled_cleanup_does_not_run() {
+    let (done_tx, done_rx) = oneshot::channel::<()>();
+
+    let guard = AsyncDropGuard::new(move || async move {
+        done_tx.send(()).unwrap();
+    });
+
+    guard.cancel();
+
+    // The cleanup closure is dropped without being called, which drops the sender.
+    assert!(done_rx.await.is_err());
+}
@@ -0,0 +1,47 @@
+use folo::rt::{spawn_on_any, Collector};
+use folo_testing::init_test_worker;
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn collect_from_many_producers() {
+    const PRODUCERS: usize = 100;
+    const RESULTS_PER_PRODUCER: usize = 10;
+
+    let (mut collector, handle) = Collector::new();
+
+    for producer in 0..PRODUCERS {
+        let handle = handle.clone();
+
+        // The join handles are dropped - the tasks keep running and we get the results via the
+        // collector instead.
+        _ = spawn_on_any(move || async move {
+            for i in 0..RESULTS_PER_PRODUCER {
+                handle.push(producer * RESULTS_PER_PRODUCER + i);
+                folo::rt::yield_now().await;
+            }
+        });
+    }
+
+    // Only the producers hold handles now, so the collector closes once they are all done.
+    drop(handle);
+
+    let mut results = Vec::new();
+
+    while let Some(result) = collector.next().await {
+        results.push(result);
+    }
+
+    // The results arrive in no particular order but every one of them arrives.
+    results.sort_unstable();
+    assert_eq!(
+        results,
+        (0..PRODUCERS * RESULTS_PER_PRODUCER).collect::<Vec<_>>()
+    );
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn collector_without_producers_is_closed() {
+    let (mut collector, handle) = Collector::<u32>::new();
+    drop(handle);
+
+    assert_eq!(collector.next().await, None);
+}
@@ -0,0 +1,1413 @@
+use folo::{io, rt::StreamExt};
+use folo_testing::{init_test_worker, temp_path};
+use futures::future;
+use std::{
+    cell::RefCell,
+    path::PathBuf,
+    rc::Rc,
+    thread,
+    time::{Duration, Instant},
+};
+use windows::{
+    core::PCSTR,
+    Win32::{
+        Foundation::{CloseHandle, ERROR_FILE_NOT_FOUND},
+        Storage::FileSystem::PIPE_ACCESS_OUTBOUND,
+        System::Pipes::{CreateNamedPipeA, PIPE_TYPE_BYTE},
+    },
+};
+
+fn create_test_file(name: &str, len: usize) -> PathBuf {
+    let path = temp_path(name);
+    std::fs::write(&path, vec![0xAB; len]).unwrap();
+    path
+}
+
+#[test]
+fn block_on_reads_file() {
+    let path = create_test_file("block_on_reads_file", 1234);
+    let task_path = path.clone();
+
+    let contents = folo::rt::block_on(|| async move { folo::fs::read(task_path).await });
+
+    assert_eq!(contents.unwrap(), vec![0xAB; 1234]);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn read_limited_file_at_limit() {
+    let path = create_test_file("read_limited_file_at_limit", 1234);
+
+    let contents = folo::fs::read_limited(&path, 1234).await.unwrap();
+
+    assert_eq!(contents.len(), 1234);
+    assert!(contents.iter().all(|b| *b == 0xAB));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn read_limited_file_over_limit() {
+    let path = create_test_file("read_limited_file_over_limit", 1235);
+
+    let result = folo::fs::read_limited(&path, 1234).await;
+
+    assert!(matches!(
+        result,
+        Err(io::Error::FileTooLarge { max_bytes: 1234 })
+    ));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn read_into_undersized_buffer_is_short_read() {
+    let path = create_test_file("read_into_undersized_buffer_is_short_read", 1000);
+
+    let buffer = io::PinnedBuffer::from_boxed_slice(vec![0; 400].into());
+    let (buffer, bytes_read) = folo::fs::read_into(&path, buffer).await.unwrap();
+
+    assert_eq!(bytes_read, 400);
+    assert_eq!(buffer.capacity(), 400);
+    assert!(buffer.as_slice().iter().all(|b| *b == 0xAB));
+
+    // The same buffer can be reused for reading a file that is smaller than the buffer.
+    std::fs::write(&path, vec![0xCD; 100]).unwrap();
+
+    let (buffer, bytes_read) = folo::fs::read_into(&path, buffer.use_all()).await.unwrap();
+
+    assert_eq!(bytes_read, 100);
+    assert_eq!(buffer.as_slice(), [0xCD; 100]);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn read_growable_grows_past_initial_buffer() {
+    // Larger than the initial buffer, so the buffer has to grow a few times.
+    let path = create_test_file("read_growable_grows_past_initial_buffer", 300_000);
+
+    let contents = folo::fs::read_growable(&path).await.unwrap();
+
+    assert_eq!(contents.len(), 300_000);
+    assert!(contents.iter().all(|b| *b == 0xAB));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn read_empty_file() {
+    let path = create_test_file("read_empty_file", 0);
+
+    assert!(folo::fs::read(&path).await.unwrap().is_empty());
+    assert!(folo::fs::read_growable(&path).await.unwrap().is_empty());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn metadata_matches_std() {
+    let path = create_test_file("metadata_matches_std", 1234);
+
+    let metadata = folo::fs::metadata(&path).await.unwrap();
+    let expected = std::fs::metadata(&path).unwrap();
+
+    assert_eq!(metadata.len(), 1234);
+    assert_eq!(metadata.len(), expected.len());
+    assert!(metadata.is_file());
+    assert!(!metadata.is_dir());
+    assert_eq!(metadata.modified(), expected.modified().unwrap());
+    assert_eq!(metadata.compressed_len(), 1234);
+    assert!(!metadata.is_compressed());
+
+    let dir_metadata = folo::fs::metadata(std::env::temp_dir()).await.unwrap();
+    assert!(dir_metadata.is_dir());
+    assert_eq!(dir_metadata.compressed_len(), 0);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn read_missing_file_reports_os_error() {
+    let path = temp_path("read_missing_file_reports_os_error");
+
+    let error = folo::fs::read(&path).await.unwrap_err();
+
+    assert_eq!(error.raw_os_error(), Some(ERROR_FILE_NOT_FOUND.0 as i32));
+    assert_eq!(
+        std::io::Error::from(error).kind(),
+        std::io::ErrorKind::NotFound
+    );
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn write_then_read_round_trip() {
+    const LEN: usize = 10 * 1024 * 1024;
+
+    // The existing file is longer than what we write, so it must be truncated.
+    let path = create_test_file("write_then_read_round_trip", LEN + 1234);
+
+    let contents = (0..LEN).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+
+    let bytes_written = folo::fs::write(&path, contents.clone()).await.unwrap();
+    assert_eq!(bytes_written, LEN);
+
+    assert_eq!(folo::fs::read(&path).await.unwrap(), contents);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn read_resilient_reads_file() {
+    let path = create_test_file("read_resilient_reads_file", 1234);
+
+    let contents = folo::fs::read_resilient(&path, folo::fs::ReopenPolicy::default())
+        .await
+        .unwrap();
+
+    assert_eq!(contents.len(), 1234);
+    assert!(contents.iter().all(|b| *b == 0xAB));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn warm_cache_leaves_files_intact() {
+    let paths = [
+        create_test_file("warm_cache_leaves_files_intact_1", 3 * 1024 * 1024 + 1),
+        create_test_file("warm_cache_leaves_files_intact_2", 0),
+    ];
+
+    folo::fs::warm_cache(&paths, folo::fs::WarmCacheOptions::default())
+        .await
+        .unwrap();
+
+    // Whether a later read is faster depends on the OS cache, which we cannot observe, so we only
+    // verify that warming left the files intact.
+    let contents = folo::fs::read(&paths[0]).await.unwrap();
+
+    assert_eq!(contents.len(), 3 * 1024 * 1024 + 1);
+    assert!(contents.iter().all(|b| *b == 0xAB));
+
+    for path in paths {
+        std::fs::remove_file(&path).unwrap();
+    }
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn wait_for_file_already_exists() {
+    let path = create_test_file("wait_for_file_already_exists", 1);
+
+    folo::fs::wait_for_file(&path).await.unwrap();
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn wait_for_file_resolves_when_created() {
+    let path = temp_path("wait_for_file_resolves_when_created");
+    _ = std::fs::remove_file(&path);
+
+    let started = Instant::now();
+
+    let creator = folo::rt::spawn_on_any({
+        let path = path.clone();
+        move || async move {
+            // Give the waiting task a head start so it actually has to wait.
+            thread::sleep(Duration::from_millis(100));
+            std::fs::write(&path, b"hello").unwrap();
+        }
+    });
+
+    folo::fs::wait_for_file(&path).await.unwrap();
+
+    // If we were polling or missed the notification, we would not be this prompt.
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    creator.await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn wait_for_file_missing_directory_is_error() {
+    let path = temp_path("missing_directory").join("file");
+
+    assert!(folo::fs::wait_for_file(&path).await.is_err());
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn file_read_len_at() {
+    let path = create_test_file("file_read_len_at", 0);
+    std::fs::write(&path, (0..=255).cycle().take(1000).collect::<Vec<u8>>()).unwrap();
+
+    let file = folo::fs::File::open(&path).await.unwrap();
+
+    let buffer = file.read_len_at(10, 50).await.unwrap();
+    assert_eq!(buffer.as_slice(), (10..60).collect::<Vec<u8>>());
+
+    // Reads that cross the end of the file are cut short.
+    let buffer = file.read_len_at(900, 200).await.unwrap();
+    assert_eq!(buffer.len(), 100);
+    assert_eq!(buffer.as_slice()[0], (900 % 256) as u8);
+
+    let buffer = file.read_len_at(1000, 10).await.unwrap();
+    assert!(buffer.is_empty());
+
+    drop(file);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn boxed_slice_read_round_trip() {
+    let path = temp_path("boxed_slice_read_round_trip");
+    std::fs::write(&path, b"boxed slice contents").unwrap();
+
+    let file = folo::fs::File::open(&path).await.unwrap();
+
+    let slice = vec![0; 100].into_boxed_slice();
+    let original_ptr = slice.as_ptr();
+
+    let buffer = file
+        .read_at(0, io::PinnedBuffer::from_boxed_slice(slice))
+        .await
+        .unwrap();
+    assert_eq!(buffer.len(), 20);
+
+    // We get back the same allocation, in its full extent, with the data the read put there.
+    let slice = buffer.into_inner_boxed_slice();
+    assert_eq!(slice.as_ptr(), original_ptr);
+    assert_eq!(slice.len(), 100);
+    assert_eq!(&slice[..20], b"boxed slice contents");
+    assert!(slice[20..].iter().all(|b| *b == 0));
+
+    drop(file);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn file_read_into_buffers() {
+    let path = temp_path("file_read_into_buffers");
+    std::fs::write(&path, (0..40).collect::<Vec<u8>>()).unwrap();
+
+    let file = folo::fs::File::open(&path).await.unwrap();
+
+    let mut buffers =
+        [5, 10, 15].map(|len| io::PinnedBuffer::from_boxed_slice(vec![0; len].into_boxed_slice()));
+
+    let bytes_read = file.read_into_buffers(3, &mut buffers).await.unwrap();
+
+    assert_eq!(bytes_read, 30);
+    assert_eq!(buffers[0].as_slice(), (3..8).collect::<Vec<u8>>());
+    assert_eq!(buffers[1].as_slice(), (8..18).collect::<Vec<u8>>());
+    assert_eq!(buffers[2].as_slice(), (18..33).collect::<Vec<u8>>());
+
+    drop(file);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn read_parallel_assembles_ranges() {
+    let path = temp_path("read_parallel_assembles_ranges");
+    let expected = (0..10_000).map(|i| (i % 251) as u8).collect::<Vec<u8>>();
+    std::fs::write(&path, &expected).unwrap();
+
+    // Does not divide evenly, so the last range is shorter than the others.
+    let options = folo::fs::ParallelReadOptions {
+        threshold_bytes: 0,
+        concurrency: 3,
+    };
+
+    let contents = folo::fs::read_parallel(&path, options).await.unwrap();
+    assert_eq!(contents, expected);
+
+    // Below the threshold, the file is read in one go.
+    let options = folo::fs::ParallelReadOptions {
+        threshold_bytes: 1_000_000,
+        concurrency: 3,
+    };
+
+    let contents = folo::fs::read_parallel(&path, options).await.unwrap();
+    assert_eq!(contents, expected);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn read_parallel_empty_file() {
+    let path = create_test_file("read_parallel_empty_file", 0);
+
+    let options = folo::fs::ParallelReadOptions {
+        threshold_bytes: 0,
+        concurrency: 3,
+    };
+
+    let contents = folo::fs::read_parallel(&path, options).await.unwrap();
+    assert!(contents.is_empty());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn file_lock_exclusive_contended() {
+    let path = create_test_file("file_lock_exclusive_contended", 1);
+
+    let first = folo::fs::File::open(&path).await.unwrap();
+    let second = Rc::new(folo::fs::File::open(&path).await.unwrap());
+
+    let events = Rc::new(RefCell::new(Vec::new()));
+
+    let first_guard = first.lock_exclusive().await.unwrap();
+    events.borrow_mut().push("first locked");
+
+    // The lock is held via another handle, so we cannot get it immediately.
+    assert!(second.try_lock_exclusive().await.unwrap().is_none());
+
+    let waiter = folo::rt::spawn({
+        let second = Rc::clone(&second);
+        let events = Rc::clone(&events);
+
+        async move {
+            let _guard = second.lock_exclusive().await.unwrap();
+            events.borrow_mut().push("second locked");
+        }
+    });
+
+    // Give the waiter plenty of opportunity to (incorrectly) acquire the lock.
+    for _ in 0..10 {
+        folo::rt::yield_now().await;
+    }
+
+    events.borrow_mut().push("first unlocking");
+    drop(first_guard);
+
+    waiter.await.unwrap();
+
+    assert_eq!(
+        *events.borrow(),
+        ["first locked", "first unlocking", "second locked"]
+    );
+
+    // Now that nobody holds the lock, it can be acquired immediately.
+    assert!(first.try_lock_exclusive().await.unwrap().is_some());
+
+    drop(first);
+    drop(second);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn alternate_data_streams() {
+    let path = create_test_file("alternate_data_streams", 10);
+
+    // Only the default stream exists, which is not listed.
+    assert!(folo::fs::streams(&path).await.unwrap().is_empty());
+
+    let stream_path = format!("{}:extra", path.to_str().unwrap());
+    std::fs::write(&stream_path, b"hidden contents").unwrap();
+
+    let expected = [folo::fs::StreamInfo {
+        name: "extra".to_string(),
+        size: 15,
+    }];
+
+    assert_eq!(folo::fs::streams(&path).await.unwrap(), expected);
+
+    // A path that already names a stream lists the streams of its file.
+    assert_eq!(folo::fs::streams(&stream_path).await.unwrap(), expected);
+
+    assert_eq!(
+        folo::fs::read(&stream_path).await.unwrap(),
+        b"hidden contents"
+    );
+
+    // The default stream is unaffected.
+    assert_eq!(folo::fs::read(&path).await.unwrap().len(), 10);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn create_dir_all_nested() {
+    let root = temp_path("create_dir_all_nested");
+    _ = std::fs::remove_dir_all(&root);
+
+    let deepest = root.join("a").join("b").join("c");
+
+    folo::fs::create_dir_all(&deepest).await.unwrap();
+
+    assert!(root.is_dir());
+    assert!(root.join("a").is_dir());
+    assert!(root.join("a").join("b").is_dir());
+    assert!(deepest.is_dir());
+
+    // Everything already exists, which is fine.
+    folo::fs::create_dir_all(&deepest).await.unwrap();
+
+    // Unlike create_dir_all(), create_dir() requires the directory to not exist yet.
+    assert!(folo::fs::create_dir(&deepest).await.is_err());
+    folo::fs::create_dir(deepest.join("d")).await.unwrap();
+    assert!(deepest.join("d").is_dir());
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn copy_dir_nested_tree() {
+    let root = temp_path("copy_dir_nested_tree");
+    _ = std::fs::remove_dir_all(&root);
+
+    let from = root.join("from");
+    let to = root.join("to");
+
+    std::fs::create_dir_all(from.join("a").join("b")).unwrap();
+    std::fs::create_dir_all(from.join("empty")).unwrap();
+    std::fs::write(from.join("top.txt"), b"top").unwrap();
+    std::fs::write(from.join("a").join("middle.txt"), b"middle").unwrap();
+    std::fs::write(from.join("a").join("b").join("bottom.txt"), b"bottom").unwrap();
+
+    let report = folo::fs::copy_dir(&from, &to, folo::fs::CopyDirOptions::default())
+        .await
+        .unwrap();
+
+    assert!(report.errors.is_empty(), "{:?}", report.errors);
+    assert_eq!(report.files_copied, 3);
+    assert_eq!(report.bytes_copied, 15);
+
+    assert_eq!(std::fs::read(to.join("top.txt")).unwrap(), b"top");
+    assert_eq!(
+        std::fs::read(to.join("a").join("middle.txt")).unwrap(),
+        b"middle"
+    );
+    assert_eq!(
+        std::fs::read(to.join("a").join("b").join("bottom.txt")).unwrap(),
+        b"bottom"
+    );
+    assert!(to.join("empty").is_dir());
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn copy_dir_missing_source_is_error() {
+    let root = temp_path("copy_dir_missing_source_is_error");
+    _ = std::fs::remove_dir_all(&root);
+
+    let result = folo::fs::copy_dir(
+        root.join("from"),
+        root.join("to"),
+        folo::fs::CopyDirOptions::default(),
+    )
+    .await;
+
+    assert!(result.is_err());
+
+    // Nothing is created if there is nothing to copy.
+    assert!(!root.exists());
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn temp_dir_cleanup() {
+    let temp_dir = folo::fs::TempDir::new().await.unwrap();
+    let path = temp_dir.path().to_path_buf();
+
+    assert!(path.is_dir());
+
+    std::fs::create_dir(path.join("nested")).unwrap();
+    std::fs::write(path.join("file.txt"), b"contents").unwrap();
+    std::fs::write(path.join("nested").join("file.txt"), b"contents").unwrap();
+
+    // Every temporary directory is unique.
+    let other_temp_dir = folo::fs::TempDir::new().await.unwrap();
+    assert_ne!(other_temp_dir.path(), path);
+
+    temp_dir.cleanup().await.unwrap();
+    assert!(!path.exists());
+
+    // Dropping without cleanup still deletes the directory, just less efficiently.
+    let other_path = other_temp_dir.path().to_path_buf();
+    drop(other_temp_dir);
+    assert!(!other_path.exists());
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn disk_space_of_temp_volume() {
+    let space = folo::fs::disk_space(std::env::temp_dir()).await.unwrap();
+
+    assert!(space.total_bytes > 0);
+    assert!(space.free_bytes > 0);
+    assert!(space.free_bytes <= space.total_bytes);
+    assert!(space.available_bytes <= space.free_bytes);
+
+    // A file resolves to the volume it is on.
+    let path = create_test_file("disk_space_of_temp_volume", 1);
+    let file_space = folo::fs::disk_space(&path).await.unwrap();
+    assert_eq!(file_space.total_bytes, space.total_bytes);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn sparse_file_chunks() {
+    use std::os::windows::{fs::FileExt, io::AsRawHandle};
+    use windows::Win32::{
+        Foundation::HANDLE,
+        System::{Ioctl::FSCTL_SET_SPARSE, IO::DeviceIoControl},
+    };
+
+    const HOLE_END: u64 = 10 * 1024 * 1024;
+
+    let path = temp_path("sparse_file_chunks");
+
+    {
+        let file = std::fs::File::create(&path).unwrap();
+
+        // SAFETY: The handle is valid for the duration of the call.
+        unsafe {
+            DeviceIoControl(
+                HANDLE(file.as_raw_handle()),
+                FSCTL_SET_SPARSE,
+                None,
+                0,
+                None,
+                0,
+                None,
+                None,
+            )
+            .unwrap();
+        }
+
+        // Writing past the end of a sparse file leaves a hole in between.
+        file.seek_write(&[1; 4096], 0).unwrap();
+        file.seek_write(&[2; 4096], HOLE_END).unwrap();
+    }
+
+    let file = folo::fs::File::open(&path).await.unwrap();
+
+    let ranges = file.read_allocated_ranges().await.unwrap();
+    assert_eq!(ranges.len(), 2, "{ranges:?}");
+    assert_eq!(ranges[0].offset, 0);
+    assert!(ranges[0].len >= 4096);
+    assert!(ranges[1].offset <= HOLE_END);
+    assert!(ranges[1].offset + ranges[1].len >= HOLE_END + 4096);
+
+    // The middle of the file is not allocated.
+    assert!(ranges[0].offset + ranges[0].len < HOLE_END / 2);
+    assert!(ranges[1].offset > HOLE_END / 2);
+
+    let mut chunks = Box::pin(file.chunks(1024 * 1024));
+    let mut contents = Vec::new();
+
+    while let Some(chunk) = futures::StreamExt::next(&mut chunks).await {
+        contents.extend_from_slice(chunk.unwrap().as_slice());
+    }
+
+    drop(chunks);
+
+    assert_eq!(contents.len() as u64, HOLE_END + 4096);
+    assert!(contents[..4096].iter().all(|b| *b == 1));
+    assert!(contents[4096..HOLE_END as usize].iter().all(|b| *b == 0));
+    assert!(contents[HOLE_END as usize..].iter().all(|b| *b == 2));
+
+    drop(file);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn read_utf16le_with_bom() {
+    const TEXT: &str = "Größe: 42 €\r\n";
+
+    let path = temp_path("read_utf16le_with_bom");
+
+    let mut bytes = vec![0xFF, 0xFE];
+    bytes.extend(TEXT.encode_utf16().flat_map(u16::to_le_bytes));
+    std::fs::write(&path, bytes).unwrap();
+
+    let explicit = folo::fs::read_to_string_with_encoding(&path, folo::fs::Encoding::Utf16Le)
+        .await
+        .unwrap();
+    assert_eq!(explicit, TEXT);
+
+    let detected = folo::fs::read_to_string_with_encoding(&path, folo::fs::Encoding::Auto)
+        .await
+        .unwrap();
+    assert_eq!(detected, TEXT);
+
+    // It is not valid UTF-8, so the default must reject it.
+    assert!(folo::fs::read_to_string(&path).await.is_err());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn chunks_with_readahead_preserves_order() {
+    let path = temp_path("chunks_with_readahead_preserves_order");
+
+    // Every byte is different from its neighbors, so chunks out of order would be detected.
+    let expected = (0..100_000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    std::fs::write(&path, &expected).unwrap();
+
+    let file = folo::fs::File::open(&path).await.unwrap();
+
+    let mut chunks = Box::pin(file.chunks_with_readahead(4096, 8));
+    let mut contents = Vec::new();
+    let mut chunk_count = 0;
+
+    while let Some(chunk) = futures::StreamExt::next(&mut chunks).await {
+        contents.extend_from_slice(chunk.unwrap().as_slice());
+        chunk_count += 1;
+    }
+
+    drop(chunks);
+
+    assert_eq!(chunk_count, 100_000_usize.div_ceil(4096));
+    assert!(contents == expected);
+
+    drop(file);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn rotating_log_rotate() {
+    let dir = folo::fs::TempDir::new().await.unwrap();
+    let path = dir.path().join("app.log");
+
+    let log = folo::fs::RotatingLog::open(&path).await.unwrap();
+
+    log.write(b"before rotation\n").await.unwrap();
+    let rotated = log.rotate().await.unwrap();
+    log.write(b"after rotation\n").await.unwrap();
+
+    // Writes racing with a rotation land entirely in one of the files.
+    let (first, rotated_again, second) = futures::join!(
+        log.write(b"racing 1\n"),
+        log.rotate(),
+        log.write(b"racing 2\n")
+    );
+    first.unwrap();
+    second.unwrap();
+    let rotated_again = rotated_again.unwrap();
+
+    drop(log);
+
+    assert_ne!(rotated, path);
+    assert_eq!(rotated.parent(), path.parent());
+    assert_eq!(std::fs::read(&rotated).unwrap(), b"before rotation\n");
+
+    let mut all = std::fs::read_to_string(&rotated_again).unwrap();
+    assert!(all.starts_with("after rotation\n"), "{all}");
+    all.push_str(&std::fs::read_to_string(&path).unwrap());
+
+    assert_eq!(all.matches("racing 1\n").count(), 1, "{all}");
+    assert_eq!(all.matches("racing 2\n").count(), 1, "{all}");
+
+    dir.cleanup().await.unwrap();
+}
+
+// Opening a raw volume requires administrator privileges, so run with `--ignored` as admin.
+#[folo::test(worker_init_fn = init_test_worker)]
+#[ignore = "requires administrator"]
+async fn read_first_sector_of_volume() {
+    let volume = folo::fs::File::open_device(r"\\.\C:").await.unwrap();
+
+    let sector_size = volume.sector_size().unwrap();
+    assert!(volume.len().await.unwrap() >= sector_size as u64);
+
+    // We allocate extra space so we can position the active region on an aligned address.
+    let mut buffer =
+        io::PinnedBuffer::from_boxed_slice(vec![0; sector_size * 2].into_boxed_slice());
+    let misalignment = buffer.as_slice().as_ptr().align_offset(sector_size);
+    buffer.set_len(0);
+    buffer.set_start(misalignment);
+    buffer.set_len(sector_size);
+
+    // Reading from the middle of a sector is not allowed.
+    let result = volume
+        .read_at(1, io::PinnedBuffer::from_boxed_slice(vec![0; 1].into()))
+        .await;
+    assert!(matches!(result, Err(io::Error::InvalidOptions(_))));
+
+    let buffer = volume.read_at(0, buffer).await.unwrap();
+    assert_eq!(buffer.len(), sector_size);
+}
+
+#[derive(Debug, PartialEq, serde::Deserialize)]
+struct Measurement {
+    sensor: String,
+    value: f64,
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn records_from_ndjson() {
+    let path = temp_path("records_from_ndjson");
+    std::fs::write(
+        &path,
+        "{\"sensor\":\"a\",\"value\":1.5}\r\n\nnot json\n{\"sensor\":\"b\",\"value\":-2}",
+    )
+    .unwrap();
+
+    let records = folo::fs::records(&path, |line| serde_json::from_str::<Measurement>(line))
+        .await
+        .unwrap();
+    let records = futures::StreamExt::collect::<Vec<_>>(records).await;
+
+    assert_eq!(records.len(), 3);
+    assert_eq!(
+        *records[0].as_ref().unwrap(),
+        Measurement {
+            sensor: "a".to_string(),
+            value: 1.5
+        }
+    );
+
+    // A record that fails to parse does not stop the stream.
+    assert!(matches!(records[1], Err(io::Error::Other(_))));
+
+    assert_eq!(
+        *records[2].as_ref().unwrap(),
+        Measurement {
+            sensor: "b".to_string(),
+            value: -2.0
+        }
+    );
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn cancel_all_cancels_pending_reads() {
+    // Nobody ever writes into the pipe, so reads from it only complete when cancelled.
+    let pipe_name = format!(r"\\.\pipe\folo_fs_test_{}_cancel_all", std::process::id());
+    let pipe_name_cstr = std::ffi::CString::new(pipe_name.as_str()).unwrap();
+
+    // SAFETY: The name is a valid null-terminated string and we close the pipe at the end.
+    let server = unsafe {
+        CreateNamedPipeA(
+            PCSTR::from_raw(pipe_name_cstr.as_ptr() as *const u8),
+            PIPE_ACCESS_OUTBOUND,
+            PIPE_TYPE_BYTE,
+            1,
+            0,
+            0,
+            0,
+            None,
+        )
+        .unwrap()
+    };
+
+    let file = folo::fs::File::open(&pipe_name).await.unwrap();
+
+    let reads = future::join_all(
+        (0..4).map(|_| file.read_at(0, io::PinnedBuffer::from_boxed_slice(vec![0; 16].into()))),
+    );
+
+    // The reads are polled first, so they are all in flight by the time we cancel them.
+    let (results, cancel_result) = future::join(reads, file.cancel_all()).await;
+
+    cancel_result.unwrap();
+
+    assert_eq!(results.len(), 4);
+    assert!(results
+        .into_iter()
+        .all(|result| result.is_err_and(|e| e.is_cancelled())));
+
+    // SAFETY: Nothing is using the pipe anymore.
+    unsafe {
+        CloseHandle(server).unwrap();
+    }
+}
+
+#[test]
+fn cancel_all_cancels_reads_waiting_for_admission() {
+    // Only two of the reads are submitted, the rest wait for the in-flight operation limit.
+    let folo = folo::rt::RuntimeBuilder::new()
+        .worker_init(init_test_worker)
+        .max_processors(1)
+        .max_in_flight_io(2)
+        .build()
+        .unwrap();
+
+    let task = folo.spawn_on_any(|| async {
+        // Nobody ever writes into the pipe, so reads from it only complete when cancelled.
+        let pipe_name = format!(
+            r"\\.\pipe\folo_fs_test_{}_cancel_all_queued",
+            std::process::id()
+        );
+        let pipe_name_cstr = std::ffi::CString::new(pipe_name.as_str()).unwrap();
+
+        // SAFETY: The name is a valid null-terminated string and we close the pipe at the end.
+        let server = unsafe {
+            CreateNamedPipeA(
+                PCSTR::from_raw(pipe_name_cstr.as_ptr() as *const u8),
+                PIPE_ACCESS_OUTBOUND,
+                PIPE_TYPE_BYTE,
+                1,
+                0,
+                0,
+                0,
+                None,
+            )
+            .unwrap()
+        };
+
+        let file = folo::fs::File::open(&pipe_name).await.unwrap();
+
+        let reads = future::join_all(
+            (0..4).map(|_| file.read_at(0, io::PinnedBuffer::from_boxed_slice(vec![0; 16].into()))),
+        );
+
+        // If the queued reads were submitted after the cancellation, they would never complete.
+        let (results, cancel_result) = future::join(reads, file.cancel_all()).await;
+
+        cancel_result.unwrap();
+
+        assert_eq!(results.len(), 4);
+        assert!(results
+            .into_iter()
+            .all(|result| result.is_err_and(|e| e.is_cancelled())));
+
+        // SAFETY: Nothing is using the pipe anymore.
+        unsafe {
+            CloseHandle(server).unwrap();
+        }
+    });
+
+    futures::executor::block_on(task).unwrap();
+
+    folo.stop();
+    folo.wait();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn mmap_flush_range_reaches_file() {
+    let path = create_test_file("mmap_flush_range_reaches_file", 8192);
+
+    let mut mmap = folo::fs::MmapMut::open(&path).await.unwrap();
+    assert_eq!(mmap.len(), 8192);
+
+    mmap[4096..4100].copy_from_slice(b"folo");
+    mmap.flush(4096..4100).await.unwrap();
+
+    // Read via a separate handle, which does not go through our view.
+    let contents = std::fs::read(&path).unwrap();
+    assert_eq!(&contents[4096..4100], b"folo");
+    assert!(contents[..4096].iter().all(|&b| b == 0xAB));
+
+    mmap[0] = 0;
+    mmap.flush_async(0..1).unwrap();
+
+    drop(mmap);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn walk_dir_junction_loop_terminates() {
+    let root = temp_path("walk_dir_junction_loop_terminates");
+    _ = std::fs::remove_dir_all(&root);
+
+    std::fs::create_dir_all(root.join("a")).unwrap();
+    std::fs::write(root.join("a").join("file.txt"), b"file").unwrap();
+
+    // Unlike symbolic links, junctions can be created without elevated privileges.
+    let loop_path = root.join("a").join("loop");
+    let status = std::process::Command::new("cmd")
+        .arg("/C")
+        .arg("mklink")
+        .arg("/J")
+        .arg(&loop_path)
+        .arg(&root)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let entries = folo::fs::walk_dir(&root, folo::fs::WalkDirOptions::default())
+        .await
+        .unwrap();
+
+    assert_eq!(entries.len(), 3);
+    let junction = entries.iter().find(|e| e.path == loop_path).unwrap();
+    assert!(junction.is_reparse_point);
+    assert!(junction.is_dir);
+
+    let entries = folo::fs::walk_dir(
+        &root,
+        folo::fs::WalkDirOptions {
+            follow_reparse_points: true,
+        },
+    )
+    .await
+    .unwrap();
+
+    // The junction leads back to the root, which has already been walked.
+    assert_eq!(entries.len(), 3);
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn read_dir_lists_entries_with_types() {
+    let root = temp_path("read_dir_lists_entries_with_types");
+    _ = std::fs::remove_dir_all(&root);
+
+    // Longer than MAX_PATH, to make sure long paths are supported.
+    let deep = root.join("d".repeat(200)).join("e".repeat(200));
+    std::fs::create_dir_all(&deep).unwrap();
+    std::fs::write(deep.join("file.txt"), b"file").unwrap();
+    std::fs::create_dir(deep.join("subdir")).unwrap();
+
+    let junction_path = deep.join("junction");
+    let status = std::process::Command::new("cmd")
+        .arg("/C")
+        .arg("mklink")
+        .arg("/J")
+        .arg(&junction_path)
+        .arg(&root)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let mut entries = folo::fs::read_dir(&deep).await.unwrap();
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let names = entries
+        .iter()
+        .map(|e| e.file_name().to_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["file.txt", "junction", "subdir"]);
+
+    assert_eq!(entries[0].file_type, folo::fs::FileType::File);
+    assert_eq!(
+        entries[1].file_type,
+        folo::fs::FileType::ReparsePoint { is_dir: true }
+    );
+    assert_eq!(entries[2].file_type, folo::fs::FileType::Dir);
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn read_dir_stream_filters_entries() {
+    let root = temp_path("read_dir_stream_filters_entries");
+    _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).unwrap();
+
+    // More than fit into one batch of the listing.
+    for i in 0..150 {
+        std::fs::write(root.join(format!("{i:03}.txt")), b"text").unwrap();
+        std::fs::write(root.join(format!("{i:03}.log")), b"log").unwrap();
+    }
+
+    std::fs::create_dir(root.join("dir.txt")).unwrap();
+
+    let mut names = folo::fs::read_dir_stream(&root)
+        .map(|entry| entry.unwrap())
+        .filter(|entry| {
+            future::ready(
+                entry.file_type.is_file() && entry.path.extension().is_some_and(|ext| ext == "txt"),
+            )
+        })
+        .map(|entry| entry.file_name().to_str().unwrap().to_string())
+        .collect::<Vec<_>>()
+        .await;
+    names.sort();
+
+    let expected = (0..150).map(|i| format!("{i:03}.txt")).collect::<Vec<_>>();
+    assert_eq!(names, expected);
+
+    let error = folo::fs::read_dir_stream(root.join("missing"))
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(error.len(), 1);
+    assert!(error[0].is_err());
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn set_compression_shrinks_compressible_file() {
+    const LEN: usize = 1024 * 1024;
+
+    // A single repeated byte compresses very well.
+    let path = create_test_file("set_compression_shrinks_compressible_file", LEN);
+
+    let file = folo::fs::File::open(&path).await.unwrap();
+
+    let before = file.metadata().await.unwrap();
+    assert_eq!(before.len(), LEN as u64);
+    assert_eq!(before.compressed_len(), LEN as u64);
+    assert!(!before.is_compressed());
+
+    file.set_compression(true).await.unwrap();
+
+    let after = file.metadata().await.unwrap();
+    assert_eq!(after.len(), LEN as u64);
+    assert!(after.compressed_len() < after.len());
+    assert!(after.is_compressed());
+
+    file.set_compression(false).await.unwrap();
+    assert!(!file.metadata().await.unwrap().is_compressed());
+
+    drop(file);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn write_many_writes_all_files() {
+    const FILE_COUNT: usize = 200;
+
+    let root = temp_path("write_many_writes_all_files");
+    _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).unwrap();
+
+    let mut items = (0..FILE_COUNT)
+        .map(|i| {
+            (
+                root.join(format!("{i}.txt")),
+                format!("contents of {i}").into_bytes(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    // One of the files cannot be written because its directory does not exist.
+    items.insert(
+        FILE_COUNT / 2,
+        (root.join("missing").join("file.txt"), b"lost".to_vec()),
+    );
+
+    let results = folo::fs::write_many(items.clone(), folo::fs::WriteManyOptions::default()).await;
+
+    assert_eq!(results.len(), items.len());
+
+    for ((path, contents), result) in items.iter().zip(results) {
+        if path.parent() == Some(root.as_path()) {
+            assert_eq!(result.unwrap(), contents.len());
+            assert_eq!(&std::fs::read(path).unwrap(), contents);
+        } else {
+            assert!(result.is_err());
+        }
+    }
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn buf_writers_stay_within_budget() {
+    const BUDGET: usize = 1000;
+    const WRITERS_PER_THREAD: usize = 3;
+    const WRITES_PER_WRITER: usize = 50;
+    const CHUNK_LEN: usize = 100;
+
+    let folo = folo::rt::RuntimeBuilder::new()
+        .worker_init(init_test_worker)
+        .max_processors(2)
+        .max_buffered_write_bytes(BUDGET)
+        .build()
+        .unwrap();
+
+    let mut next_thread = 0;
+
+    let tasks = folo.spawn_on_all(|| {
+        let thread_index = next_thread;
+        next_thread += 1;
+
+        move || async move {
+            let paths = (0..WRITERS_PER_THREAD)
+                .map(|i| {
+                    temp_path(&format!(
+                        "buf_writers_stay_within_budget_{thread_index}_{i}"
+                    ))
+                })
+                .collect::<Vec<_>>();
+
+            // Together, the buffers of all the writers are far larger than the budget.
+            let mut writers = Vec::new();
+
+            for path in &paths {
+                writers.push(folo::fs::BufWriter::create(path, 600).await.unwrap());
+            }
+
+            for _ in 0..WRITES_PER_WRITER {
+                for (i, writer) in writers.iter_mut().enumerate() {
+                    writer.write(&[i as u8; CHUNK_LEN]).await.unwrap();
+
+                    assert!(folo::fs::buffered_write_bytes() <= BUDGET);
+                }
+
+                assert!(writers.iter().map(|w| w.buffered_len()).sum::<usize>() <= BUDGET);
+            }
+
+            for writer in &mut writers {
+                writer.flush().await.unwrap();
+                assert_eq!(writer.buffered_len(), 0);
+            }
+
+            drop(writers);
+
+            for (i, path) in paths.iter().enumerate() {
+                let contents = std::fs::read(path).unwrap();
+                assert_eq!(contents, vec![i as u8; CHUNK_LEN * WRITES_PER_WRITER]);
+
+                std::fs::remove_file(path).unwrap();
+            }
+        }
+    });
+
+    for task in tasks {
+        futures::executor::block_on(task).unwrap();
+    }
+
+    futures::executor::block_on(folo.spawn_on_any(|| async {
+        assert_eq!(folo::fs::buffered_write_bytes(), 0);
+    }))
+    .unwrap();
+
+    folo.stop();
+    folo.wait();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn periodic_durability_commits_in_batches() {
+    const FILES: usize = 3;
+    const COMMITS_PER_FILE: usize = 5;
+    const INTERVAL: Duration = Duration::from_millis(20);
+
+    let durability = folo::fs::PeriodicDurability::new(INTERVAL);
+
+    let paths = (0..FILES)
+        .map(|i| temp_path(&format!("periodic_durability_commits_in_batches_{i}")))
+        .collect::<Vec<_>>();
+
+    let start = Instant::now();
+
+    future::join_all(paths.iter().enumerate().map(|(i, path)| {
+        let durability = &durability;
+
+        async move {
+            let mut writer = folo::fs::BufWriter::create(path, 100).await.unwrap();
+
+            for commit in 0..COMMITS_PER_FILE {
+                writer.write(&[commit as u8; 10]).await.unwrap();
+
+                let flushes_before = durability.flushes();
+                durability.commit(&mut writer).await.unwrap();
+
+                // The commit only completes once a flush that started after it has completed.
+                assert!(durability.flushes() > flushes_before, "file {i}");
+            }
+        }
+    }))
+    .await;
+
+    // Every commit waits for the next flush, so the sequential commits of a file take at least one
+    // interval each. The files commit at the same time, so they share the flushes.
+    assert!(start.elapsed() >= INTERVAL * COMMITS_PER_FILE as u32);
+    assert!(durability.flushes() >= COMMITS_PER_FILE as u64);
+    assert!(durability.flushes() < (COMMITS_PER_FILE * FILES) as u64);
+
+    for path in &paths {
+        let expected = (0..COMMITS_PER_FILE)
+            .flat_map(|commit| [commit as u8; 10])
+            .collect::<Vec<_>>();
+
+        assert_eq!(std::fs::read(path).unwrap(), expected);
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn files_are_bound_to_reactor_that_opened_them() {
+    let folo = folo::rt::RuntimeBuilder::new()
+        .worker_init(init_test_worker)
+        .max_processors(2)
+        .build()
+        .unwrap();
+
+    let path = create_test_file("files_are_bound_to_reactor_that_opened_them", 10);
+
+    let tasks = folo.spawn_on_all({
+        let path = path.clone();
+
+        move || {
+            let path = path.clone();
+
+            move || async move {
+                let file = folo::fs::File::open(&path).await.unwrap();
+                (folo::rt::current_reactor_id(), file.reactor_id())
+            }
+        }
+    });
+
+    let reactor_ids = tasks
+        .into_vec()
+        .into_iter()
+        .map(|task| {
+            let (current, bound) = futures::executor::block_on(task).unwrap();
+            assert_eq!(current, bound);
+            bound
+        })
+        .collect::<Vec<_>>();
+
+    // Each reactor has its own I/O driver, so the files are bound to different reactors.
+    assert_eq!(reactor_ids.len(), 2);
+    assert_ne!(reactor_ids[0], reactor_ids[1]);
+
+    folo.stop();
+    folo.wait();
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn copy_between_files() {
+    // Larger than one buffer, so the copy takes several rounds of reads and writes.
+    let source_path = create_test_file("copy_between_files_source", 300_000);
+    let destination_path = temp_path("copy_between_files_destination");
+
+    let mut source = folo::fs::File::open(&source_path).await.unwrap();
+    let mut destination = folo::fs::File::create(&destination_path).await.unwrap();
+
+    let copied = io::copy(&mut source, &mut destination).await.unwrap();
+    assert_eq!(copied, 300_000);
+
+    drop(destination);
+    assert_eq!(
+        std::fs::read(&destination_path).unwrap(),
+        vec![0xAB; 300_000]
+    );
+
+    std::fs::remove_file(&source_path).unwrap();
+    std::fs::remove_file(&destination_path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn file_vectored_write_and_read() {
+    let path = temp_path("file_vectored_write_and_read");
+
+    let mut file = folo::fs::File::create(&path).await.unwrap();
+
+    // A header and a body, written in one call.
+    let mut buffers = [
+        io::PinnedBuffer::from_boxed_slice(Box::new(*b"HEAD")),
+        io::PinnedBuffer::from_boxed_slice(Box::new(*b"body!!")),
+    ];
+
+    let written = file.write_vectored(&mut buffers).await.unwrap();
+    assert_eq!(written, 10);
+
+    // Sequential writes continue after the vectored write.
+    let written = file.write_vectored(&mut buffers[..1]).await.unwrap();
+    assert_eq!(written, 4);
+
+    drop(file);
+    assert_eq!(std::fs::read(&path).unwrap(), b"HEADbody!!HEAD");
+
+    let mut file = folo::fs::File::open(&path).await.unwrap();
+
+    // The file runs out in the middle of the second buffer, so the third one is not reached.
+    let mut buffers = [
+        io::PinnedBuffer::from_boxed_slice(vec![0; 4].into_boxed_slice()),
+        io::PinnedBuffer::from_boxed_slice(vec![0; 100].into_boxed_slice()),
+        io::PinnedBuffer::from_boxed_slice(vec![0; 100].into_boxed_slice()),
+    ];
+
+    let read = file.read_vectored(&mut buffers).await.unwrap();
+    assert_eq!(read, 14);
+    assert_eq!(buffers[0].as_slice(), b"HEAD");
+    assert_eq!(buffers[1].as_slice(), b"body!!HEAD");
+    assert!(buffers[2].is_empty());
+
+    drop(file);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn failed_vectored_write_returns_buffers() {
+    let path = create_test_file("failed_vectored_write_returns_buffers", 10);
+
+    // The file is only open for reading, so the write fails.
+    let file = folo::fs::File::open(&path).await.unwrap();
+
+    let mut body = io::PinnedBuffer::from_boxed_slice(Box::new(*b"xxbody!!"));
+    body.set_len(6);
+    body.set_start(2);
+
+    let mut buffers = [io::PinnedBuffer::from_boxed_slice(Box::new(*b"HEAD")), body];
+
+    assert!(file.write_from_buffers(0, &mut buffers).await.is_err());
+
+    // The caller gets the buffers back as they were.
+    assert_eq!(buffers[0].as_slice(), b"HEAD");
+    assert_eq!(buffers[1].as_slice(), b"body!!");
+    assert_eq!(buffers[1].start(), 2);
+
+    drop(file);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn concurrent_appenders_do_not_tear_lines() {
+    const LINES_PER_APPENDER: usize = 100;
+
+    let path = temp_path("concurrent_appenders_do_not_tear_lines");
+    _ = std::fs::remove_file(&path);
+
+    let first = folo::fs::File::open_append(&path).await.unwrap();
+    let second = folo::fs::File::open_append(&path).await.unwrap();
+
+    let append_lines = |file: folo::fs::File, name: &'static str| async move {
+        for i in 0..LINES_PER_APPENDER {
+            let line = format!("{name} line {i:04} end\n").into_bytes();
+            let buffer = io::PinnedBuffer::from_boxed_slice(line.into_boxed_slice());
+
+            // The offset is ignored, each line goes to the end of the file.
+            file.write_at(0, buffer).await.unwrap();
+        }
+    };
+
+    futures::join!(append_lines(first, "first"), append_lines(second, "second"));
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    let lines = contents.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), LINES_PER_APPENDER * 2);
+
+    for name in ["first", "second"] {
+        let mut appender_lines = lines
+            .iter()
+            .filter(|line| line.starts_with(name))
+            .collect::<Vec<_>>();
+        appender_lines.sort();
+
+        let expected = (0..LINES_PER_APPENDER)
+            .map(|i| format!("{name} line {i:04} end"))
+            .collect::<Vec<_>>();
+
+        assert_eq!(appender_lines, expected.iter().collect::<Vec<_>>());
+    }
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn synced_data_survives_reopen() {
+    let path = temp_path("synced_data_survives_reopen");
+
+    let file = folo::fs::File::create(&path).await.unwrap();
+
+    let buffer = io::PinnedBuffer::from_boxed_slice(Box::new(*b"durable data"));
+    file.write_at(0, buffer).await.unwrap();
+
+    file.sync_all().await.unwrap();
+    file.sync_data().await.unwrap();
+    drop(file);
+
+    let file = folo::fs::File::open(&path).await.unwrap();
+    let buffer = io::PinnedBuffer::from_boxed_slice(vec![0; 100].into_boxed_slice());
+    let buffer = file.read_at(0, buffer).await.unwrap();
+    assert_eq!(buffer.as_slice(), b"durable data");
+
+    // Flushing requires write access, which a file opened for reading does not have.
+    assert!(file.sync_all().await.is_err());
+
+    drop(file);
+    std::fs::remove_file(&path).unwrap();
+}
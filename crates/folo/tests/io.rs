@@ -0,0 +1,248 @@
+use folo::{
+    io::{AsyncReadExt, PinnedBuffer, RegisteredHandle},
+    windows::OwnedHandle,
+};
+use folo_testing::{init_test_worker, temp_path};
+use std::{thread, time::Duration};
+use windows::{
+    core::PCSTR,
+    Win32::{
+        Foundation::{CloseHandle, HANDLE},
+        Storage::FileSystem::{
+            CreateFileA, FILE_FLAG_OVERLAPPED, FILE_GENERIC_READ, FILE_GENERIC_WRITE,
+            FILE_SHARE_NONE, OPEN_EXISTING, PIPE_ACCESS_DUPLEX,
+        },
+        System::{
+            Pipes::{CreateNamedPipeA, PIPE_TYPE_BYTE},
+            Threading::{CreateEventW, SetEvent},
+        },
+    },
+};
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn wait_for_any_returns_signaled_index() {
+    // SAFETY: We close the events at the end of the test, after nothing is using them anymore.
+    let events = [(); 3].map(|_| unsafe { CreateEventW(None, true, false, None).unwrap() });
+
+    // Handles are not thread-safe as far as Rust is concerned, so we smuggle the raw value.
+    let signaled_event = events[1].0 as usize;
+
+    let signaler = thread::spawn(move || {
+        // Give the waiting task a moment to start waiting, to avoid making the test too easy.
+        thread::sleep(Duration::from_millis(10));
+
+        // SAFETY: The event remains open until the signaler thread has been joined.
+        unsafe {
+            SetEvent(HANDLE(signaled_event as *mut _)).unwrap();
+        }
+    });
+
+    let index = folo::io::wait_for_any(&events).await.unwrap();
+    assert_eq!(index, 1);
+
+    signaler.join().unwrap();
+
+    for event in events {
+        // SAFETY: Nothing is using the events anymore.
+        unsafe {
+            CloseHandle(event).unwrap();
+        }
+    }
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn anon_pipe_round_trip() {
+    let (reader, writer) = folo::io::anon_pipe().unwrap();
+
+    // Much more than fits into the pipe buffer, so the writer has to wait for the reader.
+    let data: Vec<u8> = (0..4 * folo::io::PIPE_BUFFER_SIZE_BYTES as usize)
+        .map(|i| i as u8)
+        .collect();
+
+    let writer_task = folo::rt::spawn({
+        let data = data.clone();
+
+        async move {
+            for chunk in data.chunks(10_000) {
+                writer
+                    .write(folo::io::PinnedBuffer::from_boxed_slice(chunk.into()))
+                    .await
+                    .unwrap();
+            }
+
+            // Dropping the writer closes the pipe, which ends the reading loop below.
+        }
+    });
+
+    let mut received = Vec::new();
+
+    loop {
+        let buffer = reader
+            .read(folo::io::PinnedBuffer::from_pool())
+            .await
+            .unwrap();
+
+        if buffer.is_empty() {
+            break;
+        }
+
+        received.extend_from_slice(buffer.as_slice());
+    }
+
+    writer_task.await.unwrap();
+
+    assert_eq!(received, data);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn read_to_end_reads_pipe_until_closed() {
+    let (mut reader, writer) = folo::io::anon_pipe().unwrap();
+
+    // Much more than the initial buffer of read_to_end(), so the buffer has to grow several times.
+    let data: Vec<u8> = (0..1_000_000).map(|i| i as u8).collect();
+
+    let writer_task = folo::rt::spawn({
+        let data = data.clone();
+
+        async move {
+            for chunk in data.chunks(10_000) {
+                writer
+                    .write(folo::io::PinnedBuffer::from_boxed_slice(chunk.into()))
+                    .await
+                    .unwrap();
+            }
+        }
+    });
+
+    let received = reader.read_to_end().await.unwrap();
+
+    writer_task.await.unwrap();
+
+    assert_eq!(received, data);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn read_to_end_limited_rejects_long_stream() {
+    let (mut reader, writer) = folo::io::anon_pipe().unwrap();
+
+    let writer_task = folo::rt::spawn(async move {
+        // The reader gives up once it has seen too much, closing the pipe under our feet.
+        _ = writer
+            .write(folo::io::PinnedBuffer::from_boxed_slice(
+                vec![0; 1000].into(),
+            ))
+            .await;
+    });
+
+    assert!(matches!(
+        reader.read_to_end_limited(999).await,
+        Err(folo::io::Error::StreamTooLong { max_bytes: 999 })
+    ));
+
+    drop(reader);
+    writer_task.await.unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn buf_reader_read_line_matches_std() {
+    let path = temp_path("buf_reader_read_line_matches_std");
+
+    // Lines of varying lengths, some longer than the buffer, with a final line without a newline.
+    let contents = (0..100)
+        .map(|i| format!("line {i} {}\r\n", "x".repeat(i % 37)))
+        .collect::<String>()
+        + "no newline at the end";
+    std::fs::write(&path, &contents).unwrap();
+
+    let mut expected = Vec::new();
+    let mut std_reader = std::io::BufReader::new(std::fs::File::open(&path).unwrap());
+
+    loop {
+        let mut line = String::new();
+
+        if std::io::BufRead::read_line(&mut std_reader, &mut line).unwrap() == 0 {
+            break;
+        }
+
+        expected.push(line);
+    }
+
+    // A small buffer, so lines span multiple refills.
+    let file = folo::fs::File::open(&path).await.unwrap();
+    let mut reader = folo::io::BufReader::with_capacity(16, file);
+    let mut actual = Vec::new();
+
+    loop {
+        let mut line = String::new();
+
+        if reader.read_line(&mut line).await.unwrap() == 0 {
+            break;
+        }
+
+        actual.push(line);
+    }
+
+    assert_eq!(actual, expected);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn registered_handles_transfer_data_through_driver() {
+    // Anonymous pipes do not support overlapped I/O, so we use a named pipe with a unique name.
+    let pipe_name = std::ffi::CString::new(format!(
+        r"\\.\pipe\folo_io_test_{}_registered_handles",
+        std::process::id()
+    ))
+    .unwrap();
+
+    // SAFETY: The name is a valid null-terminated string and we take ownership of the handles,
+    // which are closed when the registered handles are dropped.
+    let (server, client) = unsafe {
+        let server = CreateNamedPipeA(
+            PCSTR::from_raw(pipe_name.as_ptr() as *const u8),
+            PIPE_ACCESS_DUPLEX | FILE_FLAG_OVERLAPPED,
+            PIPE_TYPE_BYTE,
+            1,
+            0,
+            0,
+            0,
+            None,
+        )
+        .unwrap();
+
+        let client = CreateFileA(
+            PCSTR::from_raw(pipe_name.as_ptr() as *const u8),
+            (FILE_GENERIC_READ | FILE_GENERIC_WRITE).0,
+            FILE_SHARE_NONE,
+            None,
+            OPEN_EXISTING,
+            FILE_FLAG_OVERLAPPED,
+            None,
+        )
+        .unwrap();
+
+        (OwnedHandle::new(server), OwnedHandle::new(client))
+    };
+
+    let server = RegisteredHandle::new(server).unwrap();
+    let client = RegisteredHandle::new(client).unwrap();
+
+    let written = client
+        .write(0, PinnedBuffer::from_boxed_slice(Box::new(*b"hello")))
+        .await
+        .unwrap();
+    assert_eq!(written.as_slice(), b"hello");
+
+    let empty_buffer = || PinnedBuffer::from_boxed_slice(vec![0; 16].into_boxed_slice());
+
+    let read = server.read(0, empty_buffer()).await.unwrap();
+    assert_eq!(read.as_slice(), b"hello");
+
+    // A read that waits for data that never comes can be cancelled.
+    let (result, cancel_result) =
+        futures::join!(server.read(0, empty_buffer()), server.cancel_all());
+
+    cancel_result.unwrap();
+    assert!(result.unwrap_err().is_cancelled());
+}
@@ -0,0 +1,54 @@
+use folo::mem::{AccountingAllocator, MemoryLimitExt, OutOfMemory};
+use folo_testing::init_test_worker;
+use std::alloc::System;
+
+#[global_allocator]
+static ALLOCATOR: AccountingAllocator<System> = AccountingAllocator::new(System);
+
+const LIMIT_BYTES: usize = 4 * 1024 * 1024;
+const BLOCK_SIZE_BYTES: usize = 1024 * 1024;
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn task_over_memory_limit_is_aborted() {
+    let result = folo::rt::spawn_on_any(|| {
+        async {
+            let mut blocks = Vec::new();
+
+            loop {
+                blocks.push(vec![0_u8; BLOCK_SIZE_BYTES]);
+                folo::rt::yield_now().await;
+            }
+        }
+        .with_memory_limit(LIMIT_BYTES)
+    })
+    .await
+    .unwrap();
+
+    let OutOfMemory {
+        limit_bytes,
+        used_bytes,
+    } = result.unwrap_err();
+
+    assert_eq!(limit_bytes, LIMIT_BYTES);
+    assert!(used_bytes > LIMIT_BYTES);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn task_within_memory_limit_completes() {
+    let result = folo::rt::spawn_on_any(|| {
+        async {
+            let block = vec![1_u8; BLOCK_SIZE_BYTES];
+            folo::rt::yield_now().await;
+
+            let used = folo::mem::current_task_memory().unwrap();
+            assert!(used >= BLOCK_SIZE_BYTES);
+
+            block.len()
+        }
+        .with_memory_limit(LIMIT_BYTES)
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(result, Ok(BLOCK_SIZE_BYTES));
+}
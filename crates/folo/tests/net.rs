@@ -0,0 +1,640 @@
+use folo::{
+    io::{self, OperationResultExt},
+    net::{
+        NamedPipeClient, NamedPipeServer, RegisteredUdpSocket, TcpConnection, TcpListener,
+        TcpServerBuilder, TcpStream, UdpSocket,
+    },
+    rt::{RuntimeBuilder, StreamExt},
+    time,
+};
+use folo_testing::init_test_worker;
+use futures::FutureExt;
+use std::{
+    io::{Read, Write},
+    net::{self as std_net, Ipv4Addr},
+    process, thread,
+    time::Duration,
+};
+
+const PORT: u16 = 37117;
+
+#[test]
+fn tcp_echo_with_completion_concurrency() {
+    // Connections are accepted via the shared completion port, whose concurrency we limit here.
+    let folo = RuntimeBuilder::new()
+        .worker_init(init_test_worker)
+        .max_processors(2)
+        .completion_concurrency(1)
+        .build()
+        .unwrap();
+
+    let (started_tx, started_rx) = oneshot::channel();
+    let (stop_tx, stop_rx) = oneshot::channel::<()>();
+
+    let server_task = folo.spawn_on_any(move || async move {
+        let mut server = TcpServerBuilder::new()
+            .port(PORT.try_into().unwrap())
+            .on_accept(echo_once)
+            .build()
+            .await
+            .unwrap();
+
+        started_tx.send(()).unwrap();
+
+        _ = stop_rx.await;
+        server.stop();
+    });
+
+    started_rx.recv().unwrap();
+
+    let client = thread::spawn(|| {
+        let mut stream = std_net::TcpStream::connect(("127.0.0.1", PORT)).unwrap();
+        stream.write_all(b"hello").unwrap();
+
+        let mut response = [0; 5];
+        stream.read_exact(&mut response).unwrap();
+        response
+    });
+
+    assert_eq!(&client.join().unwrap(), b"hello");
+
+    stop_tx.send(()).unwrap();
+    futures::executor::block_on(server_task).unwrap();
+
+    folo.stop();
+    folo.wait();
+}
+
+#[test]
+fn tcp_connection_vectored_echo() {
+    const VECTORED_PORT: u16 = PORT + 1;
+
+    let folo = RuntimeBuilder::new()
+        .worker_init(init_test_worker)
+        .build()
+        .unwrap();
+
+    let (started_tx, started_rx) = oneshot::channel();
+    let (stop_tx, stop_rx) = oneshot::channel::<()>();
+
+    let server_task = folo.spawn_on_any(move || async move {
+        let mut server = TcpServerBuilder::new()
+            .port(VECTORED_PORT.try_into().unwrap())
+            .on_accept(echo_once_vectored)
+            .build()
+            .await
+            .unwrap();
+
+        started_tx.send(()).unwrap();
+
+        _ = stop_rx.await;
+        server.stop();
+    });
+
+    started_rx.recv().unwrap();
+
+    let client = thread::spawn(|| {
+        let mut stream = std_net::TcpStream::connect(("127.0.0.1", VECTORED_PORT)).unwrap();
+        stream.write_all(b"HEADbody!!").unwrap();
+
+        let mut response = [0; 10];
+        stream.read_exact(&mut response).unwrap();
+        response
+    });
+
+    assert_eq!(&client.join().unwrap(), b"HEADbody!!");
+
+    stop_tx.send(()).unwrap();
+    futures::executor::block_on(server_task).unwrap();
+
+    folo.stop();
+    folo.wait();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn tcp_stream_exchanges_bytes_with_listener() {
+    let listener = std_net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+
+        let mut request = [0; 5];
+        stream.read_exact(&mut request).unwrap();
+        stream.write_all(&request).unwrap();
+    });
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    let written = stream
+        .write(io::PinnedBuffer::from_boxed_slice(Box::new(*b"hello")))
+        .await
+        .unwrap();
+    assert_eq!(written.len(), 5);
+
+    let mut response = Vec::new();
+
+    while response.len() < 5 {
+        let buffer = stream.read(io::PinnedBuffer::from_pool()).await.unwrap();
+        assert!(
+            !buffer.is_empty(),
+            "connection closed before the echo arrived"
+        );
+        response.extend_from_slice(buffer.as_slice());
+    }
+
+    assert_eq!(response, b"hello");
+
+    server.join().unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn tcp_stream_vectored_write_and_read() {
+    let listener = std_net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+
+        let mut request = [0; 10];
+        stream.read_exact(&mut request).unwrap();
+        stream.write_all(&request).unwrap();
+    });
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    // A header and a body, sent in one operation.
+    let mut buffers = [
+        io::PinnedBuffer::from_boxed_slice(Box::new(*b"HEAD")),
+        io::PinnedBuffer::from_boxed_slice(Box::new(*b"body!!")),
+    ];
+
+    let written = stream.write_vectored(&mut buffers).await.unwrap();
+    assert_eq!(written, 10);
+    assert_eq!(buffers[0].as_slice(), b"HEAD");
+    assert_eq!(buffers[1].as_slice(), b"body!!");
+
+    let mut response = Vec::new();
+
+    while response.len() < 10 {
+        // The third buffer is never reached because the echo is smaller than the first two.
+        let mut buffers = [
+            io::PinnedBuffer::from_boxed_slice(vec![0; 4].into_boxed_slice()),
+            io::PinnedBuffer::from_boxed_slice(vec![0; 16].into_boxed_slice()),
+            io::PinnedBuffer::from_boxed_slice(vec![0; 16].into_boxed_slice()),
+        ];
+
+        let read = stream.read_vectored(&mut buffers).await.unwrap();
+        assert!(read > 0, "connection closed before the echo arrived");
+        assert_eq!(buffers.iter().map(|b| b.len()).sum::<usize>(), read);
+        assert!(buffers[2].is_empty());
+
+        // Each buffer is filled before the next one is used.
+        if !buffers[1].is_empty() {
+            assert_eq!(buffers[0].len(), 4);
+        }
+
+        for buffer in &buffers {
+            response.extend_from_slice(buffer.as_slice());
+        }
+    }
+
+    assert_eq!(response, b"HEADbody!!");
+
+    server.join().unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn tcp_listener_accepts_loopback_connection() {
+    let mut listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0).into())
+        .await
+        .unwrap();
+    let addr = listener.local_addr();
+
+    let (server, client) = futures::join!(listener.accept(), TcpStream::connect(addr));
+    let (mut server, mut client) = (server.unwrap(), client.unwrap());
+
+    client
+        .write(io::PinnedBuffer::from_boxed_slice(Box::new(*b"ping")))
+        .await
+        .unwrap();
+    let request = read_at_least(&mut server, 4).await;
+    assert_eq!(request, b"ping");
+
+    server
+        .write(io::PinnedBuffer::from_boxed_slice(
+            request.into_boxed_slice(),
+        ))
+        .await
+        .unwrap();
+    let response = read_at_least(&mut client, 4).await;
+    assert_eq!(response, b"ping");
+}
+
+/// Reads from the stream until at least `len` bytes have arrived, as TCP may deliver the bytes
+/// written by the peer in any number of pieces.
+async fn read_at_least(stream: &mut TcpStream, len: usize) -> Vec<u8> {
+    let mut received = Vec::new();
+
+    while received.len() < len {
+        let buffer = stream.read(io::PinnedBuffer::from_pool()).await.unwrap();
+        assert!(
+            !buffer.is_empty(),
+            "connection closed before all the data arrived"
+        );
+        received.extend_from_slice(buffer.as_slice());
+    }
+
+    received
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn tcp_listener_accept_can_be_cancelled() {
+    let mut listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0).into())
+        .await
+        .unwrap();
+    let addr = listener.local_addr();
+
+    assert!(time::timeout(Duration::from_millis(20), listener.accept())
+        .await
+        .is_err());
+
+    // The listener remains usable after the cancelled accept.
+    let (server, client) = futures::join!(listener.accept(), TcpStream::connect(addr));
+    server.unwrap();
+    client.unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn dropped_reads_are_cancelled() {
+    const DROPPED_READS: usize = 1000;
+
+    let mut listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0).into())
+        .await
+        .unwrap();
+    let addr = listener.local_addr();
+
+    let (server, client) = futures::join!(listener.accept(), TcpStream::connect(addr));
+    let (mut server, mut client) = (server.unwrap(), client.unwrap());
+
+    // Each read is started and then dropped while it is still waiting for data. The OS owns the
+    // buffer of each read until the cancellation completes, so this must not free it early.
+    for _ in 0..DROPPED_READS {
+        let read = server.read(io::PinnedBuffer::from_pool()).now_or_never();
+        assert!(read.is_none());
+    }
+
+    // If any of the dropped reads were still in flight, they would swallow this data.
+    client
+        .write(io::PinnedBuffer::from_boxed_slice(Box::new(*b"ping")))
+        .await
+        .unwrap();
+
+    let request = time::timeout(
+        Duration::from_secs(10),
+        server.read(io::PinnedBuffer::from_pool()),
+    )
+    .await
+    .expect("data was swallowed by a dropped read")
+    .unwrap();
+    assert_eq!(request.as_slice(), b"ping");
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn tcp_listener_incoming_yields_connections() {
+    const CONNECTIONS: usize = 3;
+
+    let mut listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0).into())
+        .await
+        .unwrap();
+    let addr = listener.local_addr();
+
+    let server = listener
+        .incoming()
+        .take(CONNECTIONS)
+        .for_each(|connection| async move {
+            let mut connection = connection.unwrap();
+
+            let request = connection
+                .read(io::PinnedBuffer::from_pool())
+                .await
+                .unwrap();
+            connection.write(request).await.unwrap();
+        });
+
+    let clients = async {
+        for _ in 0..CONNECTIONS {
+            let mut client = TcpStream::connect(addr).await.unwrap();
+
+            client
+                .write(io::PinnedBuffer::from_boxed_slice(Box::new(*b"ping")))
+                .await
+                .unwrap();
+            let response = client.read(io::PinnedBuffer::from_pool()).await.unwrap();
+            assert_eq!(response.as_slice(), b"ping");
+        }
+    };
+
+    futures::join!(server, clients);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn udp_socket_exchanges_datagram() {
+    let sender = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0).into())
+        .await
+        .unwrap();
+    let receiver = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0).into())
+        .await
+        .unwrap();
+
+    sender
+        .send_to(
+            io::PinnedBuffer::from_boxed_slice(Box::new(*b"hello")),
+            receiver.local_addr(),
+        )
+        .await
+        .unwrap();
+
+    let (datagram, source) = receiver
+        .recv_from(io::PinnedBuffer::from_pool())
+        .await
+        .unwrap();
+
+    assert_eq!(datagram.as_slice(), b"hello");
+    assert_eq!(source, sender.local_addr());
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn udp_socket_vectored_datagram() {
+    let sender = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0).into())
+        .await
+        .unwrap();
+    let receiver = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0).into())
+        .await
+        .unwrap();
+
+    // A header and a body, sent as one datagram.
+    let mut buffers = [
+        io::PinnedBuffer::from_boxed_slice(Box::new(*b"HEAD")),
+        io::PinnedBuffer::from_boxed_slice(Box::new(*b"body!!")),
+    ];
+
+    let sent = sender
+        .send_to_vectored(&mut buffers, receiver.local_addr())
+        .await
+        .unwrap();
+    assert_eq!(sent, 10);
+
+    // The datagram runs out in the middle of the second buffer, so the third one is not reached.
+    let mut buffers = [
+        io::PinnedBuffer::from_boxed_slice(vec![0; 4].into_boxed_slice()),
+        io::PinnedBuffer::from_boxed_slice(vec![0; 100].into_boxed_slice()),
+        io::PinnedBuffer::from_boxed_slice(vec![0; 100].into_boxed_slice()),
+    ];
+
+    let (received, source) = receiver.recv_from_vectored(&mut buffers).await.unwrap();
+
+    assert_eq!(received, 10);
+    assert_eq!(buffers[0].as_slice(), b"HEAD");
+    assert_eq!(buffers[1].as_slice(), b"body!!");
+    assert!(buffers[2].is_empty());
+    assert_eq!(source, sender.local_addr());
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn udp_socket_reports_truncated_datagram() {
+    let sender = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0).into())
+        .await
+        .unwrap();
+    let receiver = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0).into())
+        .await
+        .unwrap();
+
+    sender
+        .send_to(
+            io::PinnedBuffer::from_boxed_slice(vec![0; 100].into()),
+            receiver.local_addr(),
+        )
+        .await
+        .unwrap();
+
+    let result = receiver
+        .recv_from(io::PinnedBuffer::from_boxed_slice(vec![0; 10].into()))
+        .await;
+
+    assert!(matches!(result, Err(io::Error::DatagramTruncated)));
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn registered_udp_socket_exchanges_datagrams() {
+    let peer = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0).into())
+        .await
+        .unwrap();
+    let socket = RegisteredUdpSocket::connect(peer.local_addr())
+        .await
+        .unwrap();
+
+    assert_eq!(socket.peer_addr(), peer.local_addr());
+
+    // Buffers from the pool are covered by the registration of the pool, whereas other buffers
+    // are registered for each operation. Both must work the same.
+    let mut pooled = io::PinnedBuffer::from_pool();
+    pooled.as_mut_slice_with_len(5).copy_from_slice(b"hello");
+
+    let boxed = io::PinnedBuffer::from_boxed_slice(Box::new(*b"hello"));
+
+    for buffer in [pooled, boxed] {
+        let sent = socket.send(buffer).await.unwrap();
+        assert_eq!(sent.len(), 5);
+
+        let (datagram, source) = peer.recv_from(io::PinnedBuffer::from_pool()).await.unwrap();
+
+        assert_eq!(datagram.as_slice(), b"hello");
+        assert_eq!(source, socket.local_addr());
+    }
+
+    for buffer in [
+        io::PinnedBuffer::from_pool(),
+        io::PinnedBuffer::from_boxed_slice(vec![0; 100].into()),
+    ] {
+        peer.send_to(
+            io::PinnedBuffer::from_boxed_slice(Box::new(*b"world")),
+            socket.local_addr(),
+        )
+        .await
+        .unwrap();
+
+        let datagram = socket.recv(buffer).await.unwrap();
+        assert_eq!(datagram.as_slice(), b"world");
+    }
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn registered_udp_socket_reports_truncated_datagram() {
+    let peer = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0).into())
+        .await
+        .unwrap();
+    let socket = RegisteredUdpSocket::connect(peer.local_addr())
+        .await
+        .unwrap();
+
+    peer.send_to(
+        io::PinnedBuffer::from_boxed_slice(vec![0; 100].into()),
+        socket.local_addr(),
+    )
+    .await
+    .unwrap();
+
+    let result = socket
+        .recv(io::PinnedBuffer::from_boxed_slice(vec![0; 10].into()))
+        .await;
+
+    assert!(matches!(result, Err(io::Error::DatagramTruncated)));
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn registered_udp_socket_dropped_with_receive_in_flight() {
+    let peer = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0).into())
+        .await
+        .unwrap();
+    let socket = RegisteredUdpSocket::connect(peer.local_addr())
+        .await
+        .unwrap();
+
+    // Nothing is sent to the socket, so the receive stays in flight until the socket is closed.
+    // The runtime can only shut down once the receive has completed, which this test relies on.
+    assert!(socket
+        .recv(io::PinnedBuffer::from_pool())
+        .now_or_never()
+        .is_none());
+
+    drop(socket);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn named_pipe_exchanges_bytes_in_both_directions() {
+    let name = format!(r"\\.\pipe\folo_net_test_{}_loopback", process::id());
+    let mut server = NamedPipeServer::bind(&name).unwrap();
+
+    // A second server with the same name is rejected, so nobody can intercept our clients.
+    assert!(NamedPipeServer::bind(&name).is_err());
+
+    for _ in 0..2 {
+        let (connection, client) = futures::join!(server.accept(), NamedPipeClient::connect(&name));
+        let connection = connection.unwrap();
+        let client = client.unwrap();
+
+        client
+            .write(io::PinnedBuffer::from_boxed_slice(Box::new(*b"ping")))
+            .await
+            .unwrap();
+        let request = connection
+            .read(io::PinnedBuffer::from_pool())
+            .await
+            .unwrap();
+        assert_eq!(request.as_slice(), b"ping");
+
+        connection
+            .write(io::PinnedBuffer::from_boxed_slice(Box::new(*b"pong")))
+            .await
+            .unwrap();
+        let response = client.read(io::PinnedBuffer::from_pool()).await.unwrap();
+        assert_eq!(response.as_slice(), b"pong");
+
+        // Closing the client shows up as the end of the data on the server side.
+        drop(client);
+        let end = connection
+            .read(io::PinnedBuffer::from_pool())
+            .await
+            .unwrap();
+        assert!(end.is_empty());
+    }
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn named_pipe_skips_client_that_disconnected_before_accept() {
+    let name = format!(r"\\.\pipe\folo_net_test_{}_early_leaver", process::id());
+    let mut server = NamedPipeServer::bind(&name).unwrap();
+
+    // This client is gone by the time the server starts waiting for it.
+    drop(NamedPipeClient::connect(&name).await.unwrap());
+
+    let (connection, client) = futures::join!(server.accept(), NamedPipeClient::connect(&name));
+    let connection = connection.unwrap();
+    let client = client.unwrap();
+
+    client
+        .write(io::PinnedBuffer::from_boxed_slice(Box::new(*b"ping")))
+        .await
+        .unwrap();
+    let request = connection
+        .read(io::PinnedBuffer::from_pool())
+        .await
+        .unwrap();
+    assert_eq!(request.as_slice(), b"ping");
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn named_pipe_accepts_client_that_connected_first() {
+    let name = format!(r"\\.\pipe\folo_net_test_{}_early_client", process::id());
+    let mut server = NamedPipeServer::bind(&name).unwrap();
+
+    // The client connects before the server starts waiting, so the connection is reported
+    // immediately instead of via a completion notification.
+    let client = NamedPipeClient::connect(&name).await.unwrap();
+    let connection = time::timeout(Duration::from_secs(10), server.accept())
+        .await
+        .unwrap()
+        .unwrap();
+
+    connection
+        .write(io::PinnedBuffer::from_boxed_slice(Box::new(*b"hello")))
+        .await
+        .unwrap();
+    let greeting = client.read(io::PinnedBuffer::from_pool()).await.unwrap();
+    assert_eq!(greeting.as_slice(), b"hello");
+}
+
+#[test]
+fn zero_completion_concurrency_is_error() {
+    assert!(matches!(
+        RuntimeBuilder::new().completion_concurrency(0).build(),
+        Err(io::Error::InvalidOptions(_))
+    ));
+}
+
+async fn echo_once(mut connection: TcpConnection) -> io::Result<()> {
+    let buffer = connection
+        .receive(io::PinnedBuffer::from_pool())
+        .await
+        .into_inner()?;
+
+    connection.send(buffer).await.into_inner()?;
+
+    Ok(())
+}
+
+async fn echo_once_vectored(mut connection: TcpConnection) -> io::Result<()> {
+    let mut buffers = [
+        io::PinnedBuffer::from_boxed_slice(vec![0; 4].into_boxed_slice()),
+        io::PinnedBuffer::from_boxed_slice(vec![0; 16].into_boxed_slice()),
+    ];
+    let mut received = 0;
+
+    // The data may arrive in pieces, each of which we echo as soon as it arrives.
+    while received < 10 {
+        let read = connection.receive_vectored(&mut buffers).await?;
+        assert!(read > 0, "connection closed before the request arrived");
+        received += read;
+
+        connection.send_vectored(&mut buffers).await?;
+
+        for buffer in &mut buffers {
+            buffer.set_len(0);
+            buffer.set_start(0);
+            buffer.set_len(buffer.capacity());
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use folo::process::Command;
+use folo_testing::{init_test_worker, temp_path};
+use futures::StreamExt;
+use std::{
+    os::windows::{fs::OpenOptionsExt, io::AsRawHandle},
+    time::{Duration, Instant},
+};
+use windows::Win32::Foundation::{SetHandleInformation, HANDLE, HANDLE_FLAG_INHERIT};
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn stdout_lines_arrive_incrementally() {
+    // `ping` is the traditional way to sleep in a batch script - each extra ping takes a second.
+    let command = Command::new("cmd.exe").args([
+        "/C",
+        "echo one&ping -n 2 127.0.0.1 >nul&echo two&ping -n 2 127.0.0.1 >nul&echo three",
+    ]);
+
+    let mut lines = Box::pin(command.stdout_lines().await.unwrap());
+
+    let mut received = Vec::new();
+
+    while let Some(line) = lines.next().await {
+        received.push((line.unwrap(), Instant::now()));
+    }
+
+    let texts = received
+        .iter()
+        .map(|(line, _)| line.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(texts, ["one", "two", "three"]);
+
+    // If the lines only arrived when the child exited, they would all arrive at the same time.
+    let first_received = received[0].1;
+    let last_received = received[2].1;
+    assert!(last_received.duration_since(first_received) >= Duration::from_secs(1));
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn stdout_lines_merged_stderr_and_partial_line() {
+    let command = Command::new("cmd.exe")
+        .args(["/C", "echo out&1>&2 echo err&<nul set /p =partial"])
+        .merge_stderr(true);
+
+    let lines = command
+        .stdout_lines()
+        .await
+        .unwrap()
+        .map(Result::unwrap)
+        .collect::<Vec<_>>()
+        .await;
+
+    // The output does not end with a line terminator but the remainder is still a line.
+    assert_eq!(lines, ["out", "err", "partial"]);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn stdout_lines_missing_program_is_error() {
+    let command = Command::new("folo_program_that_does_not_exist.exe");
+
+    assert!(command.stdout_lines().await.is_err());
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn spawn_wait_returns_exit_code() {
+    let child = Command::new("cmd.exe")
+        .args(["/C", "exit 42"])
+        .spawn()
+        .await
+        .unwrap();
+
+    assert_eq!(child.wait().await.unwrap(), 42);
+
+    // Once the child has exited, waiting again completes immediately with the same exit code.
+    assert_eq!(child.try_wait().unwrap(), Some(42));
+    assert_eq!(child.wait().await.unwrap(), 42);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn child_inherits_only_given_handles() {
+    let input_path = temp_path("child_inherits_only_given_handles_input");
+    std::fs::write(&input_path, "banana\r\napple\r\n").unwrap();
+
+    let other_path = temp_path("child_inherits_only_given_handles_other");
+
+    // A file that does not allow any sharing, so it cannot be deleted while the child holds a
+    // handle to it. We mark it inheritable, so it would leak into the child if given the chance.
+    let other = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .share_mode(0)
+        .open(&other_path)
+        .unwrap();
+
+    // SAFETY: The handle is valid for as long as the file is open.
+    unsafe {
+        SetHandleInformation(
+            HANDLE(other.as_raw_handle()),
+            HANDLE_FLAG_INHERIT.0,
+            HANDLE_FLAG_INHERIT,
+        )
+        .unwrap();
+    }
+
+    let input = folo::fs::File::open(&input_path).await.unwrap();
+
+    // The child keeps running for a while after sorting its input.
+    let command = Command::new("cmd.exe")
+        .args(["/C", "sort&ping -n 3 127.0.0.1 >nul"])
+        .stdin(input.inheritable_handle().unwrap());
+
+    let lines = command.stdout_lines().await.unwrap();
+
+    // If the child had inherited the other file, we could not delete it while the child runs.
+    drop(other);
+    std::fs::remove_file(&other_path).unwrap();
+
+    let lines = lines.map(Result::unwrap).collect::<Vec<_>>().await;
+    assert_eq!(lines, ["apple", "banana"]);
+
+    drop(command);
+    drop(input);
+    std::fs::remove_file(&input_path).unwrap();
+}
@@ -1,14 +1,28 @@
-use folo::rt::{RemoteJoinHandle, RuntimeBuilder};
-use folo_testing::init_test_worker;
-use futures::{task::noop_waker, FutureExt};
+use folo::{
+    io::PinnedBuffer,
+    rt::{RemoteJoinHandle, RuntimeBuilder},
+};
+use folo_testing::{init_test_worker, temp_path};
+use futures::{future, task::noop_waker, FutureExt};
 use std::{
+    cell::RefCell,
     future::Future,
+    path::PathBuf,
     pin::Pin,
+    rc::Rc,
     sync::{Arc, Mutex},
     task::{self, Waker},
     thread,
     time::Duration,
 };
+use windows::{
+    core::PCSTR,
+    Win32::{
+        Foundation::CloseHandle,
+        Storage::FileSystem::PIPE_ACCESS_OUTBOUND,
+        System::Pipes::{CreateNamedPipeA, PIPE_TYPE_BYTE},
+    },
+};
 
 #[folo::test(worker_init_fn = init_test_worker)]
 async fn runtime_stops_with_deadlocked_tasks() {
@@ -26,10 +40,10 @@ async fn runtime_stops_with_deadlocked_tasks() {
     let task1 = folo::rt::spawn_on_any(move || async {
         let task2 = init_rx.await.unwrap();
         _ = tx.send(());
-        task2.await;
+        task2.await.unwrap();
     });
     let task2 = folo::rt::spawn_on_any(move || async {
-        task1.await;
+        task1.await.unwrap();
     });
 
     init_tx.send(task2).unwrap();
@@ -210,6 +224,148 @@ fn runtime_stops_with_external_dependency() {
     folo.wait();
 }
 
+#[test]
+fn runtime_stop_flushes_buffered_writer() {
+    let folo = RuntimeBuilder::new()
+        .worker_init(folo_testing::init_test_worker)
+        .build()
+        .unwrap();
+
+    let path = temp_file_path("runtime_stop_flushes_buffered_writer");
+    let path_clone = path.clone();
+
+    let (started_tx, started_rx) = oneshot::channel();
+
+    // A writer that buffers data in memory and only writes it out when flushed. It never gets
+    // around to flushing on its own before the runtime is stopped.
+    _ = folo.spawn_on_any(move || async move {
+        let buffer = Rc::new(RefCell::new(Vec::new()));
+
+        let buffer_clone = Rc::clone(&buffer);
+        let _registration = folo::rt::on_shutdown_flush(move || async move {
+            std::fs::write(path_clone, &*buffer_clone.borrow()).unwrap();
+        });
+
+        buffer.borrow_mut().extend_from_slice(b"unflushed data");
+        _ = started_tx.send(());
+
+        futures::future::pending::<()>().await;
+    });
+
+    started_rx.recv().unwrap();
+
+    folo.stop();
+    folo.wait();
+
+    assert_eq!(std::fs::read(&path).unwrap(), b"unflushed data");
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn runtime_stop_skips_dropped_flush_registration() {
+    let folo = RuntimeBuilder::new()
+        .worker_init(folo_testing::init_test_worker)
+        .build()
+        .unwrap();
+
+    let path = temp_file_path("runtime_stop_skips_dropped_flush_registration");
+    let path_clone = path.clone();
+
+    let (done_tx, done_rx) = oneshot::channel();
+
+    _ = folo.spawn_on_any(move || async move {
+        let registration = folo::rt::on_shutdown_flush(move || async move {
+            std::fs::write(path_clone, b"should not be written").unwrap();
+        });
+
+        // The writer goes away before shutdown, so there is nothing left to flush.
+        drop(registration);
+        _ = done_tx.send(());
+    });
+
+    done_rx.recv().unwrap();
+
+    folo.stop();
+    folo.wait();
+
+    assert!(!path.exists());
+}
+
+#[test]
+fn runtime_stop_cancels_pending_reads() {
+    // Nobody ever writes into the pipes, so reads from them only complete when cancelled. We
+    // repeat the scenario a few times, as a shutdown that releases the memory of an operation
+    // before the OS is done with it tends to only show up as sporadic crashes.
+    for iteration in 0..10 {
+        let pipe_name = format!(
+            r"\\.\pipe\folo_shutdown_test_{}_{iteration}",
+            std::process::id()
+        );
+        let pipe_name_cstr = std::ffi::CString::new(pipe_name.as_str()).unwrap();
+
+        // SAFETY: The name is a valid null-terminated string and we close the pipe at the end.
+        let server = unsafe {
+            CreateNamedPipeA(
+                PCSTR::from_raw(pipe_name_cstr.as_ptr() as *const u8),
+                PIPE_ACCESS_OUTBOUND,
+                PIPE_TYPE_BYTE,
+                1,
+                0,
+                0,
+                0,
+                None,
+            )
+            .unwrap()
+        };
+
+        let folo = RuntimeBuilder::new()
+            .worker_init(folo_testing::init_test_worker)
+            .worker_threads(1)
+            .build()
+            .unwrap();
+
+        let (started_tx, started_rx) = oneshot::channel();
+
+        _ = folo.spawn_on_any(move || async move {
+            // The file outlives the task, so closing the handle does not cancel the reads for
+            // us - only the runtime shutting down can get them to complete.
+            let file: &'static folo::fs::File =
+                Box::leak(Box::new(folo::fs::File::open(&pipe_name).await.unwrap()));
+
+            let reads = future::join_all(
+                (0..4).map(|_| file.read_at(0, PinnedBuffer::from_boxed_slice(vec![0; 16].into()))),
+            );
+
+            // The reads are polled first, so they are all in flight by the time we signal.
+            future::join(reads, async {
+                _ = started_tx.send(());
+            })
+            .await;
+        });
+
+        started_rx.recv().unwrap();
+
+        // If the pending reads are not cancelled, this never returns.
+        folo.stop();
+        folo.wait();
+
+        // SAFETY: Nothing is using the pipe anymore.
+        unsafe {
+            CloseHandle(server).unwrap();
+        }
+    }
+}
+
+fn temp_file_path(name: &str) -> PathBuf {
+    let path = temp_path(name);
+
+    // Leftovers from a previous run with the same process ID would confuse the test.
+    _ = std::fs::remove_file(&path);
+
+    path
+}
+
 /// A future that progresses or completes (and wakes up the last poller) when manually commanded.
 struct ManualFuture {
     state: Mutex<ManualFutureState>,
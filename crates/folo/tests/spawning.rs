@@ -1,5 +1,19 @@
-use folo::rt::{spawn, spawn_on_any, yield_now, RuntimeBuilder};
-use std::rc::Rc;
+use folo::rt::{
+    current_task_count, join_all, spawn, spawn_blocking, spawn_on_any, spawn_with_priority,
+    yield_now, Aborted, RuntimeBuilder, TaskPriority,
+};
+use folo_testing::init_test_worker;
+use futures::FutureExt;
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
 
 #[test]
 fn spawning() {
@@ -36,3 +50,434 @@ async fn single_threaded_logic() -> Option<()> {
     assert_eq!(42, *rc);
     Some(())
 }
+
+#[test]
+fn worker_threads_sets_thread_count() {
+    let folo = RuntimeBuilder::new()
+        .worker_init(init_test_worker)
+        .worker_threads(2)
+        .build()
+        .unwrap();
+
+    let tasks = folo.spawn_on_all(|| || async { folo::rt::current_reactor_id() });
+
+    let mut reactor_ids = tasks
+        .into_vec()
+        .into_iter()
+        .map(|task| futures::executor::block_on(task).unwrap())
+        .collect::<Vec<_>>();
+    reactor_ids.sort_unstable();
+    reactor_ids.dedup();
+
+    // Each thread runs its own reactor.
+    assert_eq!(reactor_ids.len(), 2);
+
+    folo.stop();
+    folo.wait();
+}
+
+#[test]
+fn spawn_blocking_does_not_stall_worker() {
+    let folo = RuntimeBuilder::new()
+        .worker_init(init_test_worker)
+        .worker_threads(1)
+        .sync_workers_per_processor(1)
+        .build()
+        .unwrap();
+
+    let progress = folo.spawn_on_any(|| async {
+        let progress = Rc::new(RefCell::new(0));
+
+        // Another task on the same worker, which must keep running while the closure blocks.
+        let progress_clone = Rc::clone(&progress);
+        _ = spawn(async move {
+            loop {
+                *progress_clone.borrow_mut() += 1;
+                yield_now().await;
+            }
+        });
+
+        let result = spawn_blocking(|| {
+            thread::sleep(Duration::from_millis(200));
+            42
+        })
+        .await;
+
+        assert_eq!(result, 42);
+
+        let progress = *progress.borrow();
+        progress
+    });
+
+    assert!(futures::executor::block_on(progress).unwrap() > 0);
+
+    folo.stop();
+    folo.wait();
+}
+
+#[test]
+fn spawn_on_pins_tasks_to_reactor() {
+    let folo = RuntimeBuilder::new()
+        .worker_init(init_test_worker)
+        .worker_threads(2)
+        .build()
+        .unwrap();
+
+    let reactor_ids = folo.reactor_ids();
+    assert_eq!(reactor_ids.len(), 2);
+    let target = reactor_ids[1];
+
+    // Incremented while a task is inside its critical section, which must never be seen by the
+    // other task if they are never running at the same time.
+    let in_section = Arc::new(AtomicUsize::new(0));
+
+    let tasks = (0..2)
+        .map(|_| {
+            let in_section = Arc::clone(&in_section);
+            folo.spawn_on(target, move || async move {
+                for _ in 0..20 {
+                    assert_eq!(in_section.fetch_add(1, Ordering::SeqCst), 0);
+                    thread::sleep(Duration::from_millis(1));
+                    in_section.fetch_sub(1, Ordering::SeqCst);
+
+                    yield_now().await;
+                }
+
+                folo::rt::current_reactor_id()
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for task in tasks {
+        assert_eq!(futures::executor::block_on(task).unwrap(), target);
+    }
+
+    folo.stop();
+    folo.wait();
+}
+
+#[test]
+fn sync_workers_per_processor_zero_is_invalid() {
+    let result = RuntimeBuilder::new().sync_workers_per_processor(0).build();
+
+    assert!(matches!(result, Err(folo::io::Error::InvalidOptions(_))));
+}
+
+#[test]
+fn max_in_flight_io_zero_is_invalid() {
+    let result = RuntimeBuilder::new().max_in_flight_io(0).build();
+
+    assert!(matches!(result, Err(folo::io::Error::InvalidOptions(_))));
+}
+
+#[test]
+fn worker_threads_zero_is_invalid() {
+    let result = RuntimeBuilder::new().worker_threads(0).build();
+
+    assert!(matches!(result, Err(folo::io::Error::InvalidOptions(_))));
+}
+
+#[test]
+fn spawning_via_handle_from_foreign_thread() {
+    let folo = RuntimeBuilder::new()
+        .worker_init(folo_testing::init_test_worker)
+        .build()
+        .unwrap();
+
+    // The handle is stored and used on a thread that the runtime knows nothing about.
+    let handle = folo.handle();
+
+    let result = std::thread::spawn(move || {
+        let (tx, rx) = oneshot::channel();
+
+        _ = handle
+            .spawn_on_any(move || async move {
+                // This only works on an async worker thread of the runtime.
+                let local = spawn(async { 42 }).await.unwrap();
+                _ = tx.send(local);
+            })
+            .unwrap();
+
+        rx.recv().unwrap()
+    })
+    .join()
+    .unwrap();
+
+    assert_eq!(result, 42);
+
+    let handle = folo.handle();
+
+    folo.stop();
+
+    // Tasks would never execute once the runtime has been asked to stop.
+    assert!(handle.is_stopping());
+    assert!(handle.spawn_on_any(|| async {}).is_err());
+
+    folo.wait();
+}
+
+#[test]
+fn many_spawns_from_foreign_thread_all_execute() {
+    const TASK_COUNT: usize = 10_000;
+
+    let folo = RuntimeBuilder::new()
+        .worker_init(folo_testing::init_test_worker)
+        .build()
+        .unwrap();
+
+    let handle = folo.handle();
+
+    // Wakeups for tasks spawned in quick succession are coalesced, which must not leave any task
+    // without a wakeup.
+    let results = std::thread::spawn(move || {
+        let join_handles = (0..TASK_COUNT)
+            .map(|i| handle.spawn_on_any(move || async move { i }).unwrap())
+            .collect::<Vec<_>>();
+
+        join_handles
+            .into_iter()
+            .map(|task| futures::executor::block_on(task).unwrap())
+            .collect::<Vec<_>>()
+    })
+    .join()
+    .unwrap();
+
+    assert_eq!(results, (0..TASK_COUNT).collect::<Vec<_>>());
+
+    folo.stop();
+    folo.wait();
+}
+
+#[test]
+fn batched_spawns_from_foreign_thread_all_execute() {
+    // Not a multiple of the batch size, so the last batch is incomplete and never posts a wakeup.
+    const TASK_COUNT: usize = 1_000;
+
+    let folo = RuntimeBuilder::new()
+        .worker_init(folo_testing::init_test_worker)
+        .spawn_wake_batch_size(64)
+        .spawn_flush_interval(Duration::from_millis(5))
+        .build()
+        .unwrap();
+
+    let handle = folo.handle();
+
+    let results = std::thread::spawn(move || {
+        let join_handles = (0..TASK_COUNT)
+            .map(|i| handle.spawn_on_any(move || async move { i }).unwrap())
+            .collect::<Vec<_>>();
+
+        join_handles
+            .into_iter()
+            .map(|task| futures::executor::block_on(task).unwrap())
+            .collect::<Vec<_>>()
+    })
+    .join()
+    .unwrap();
+
+    assert_eq!(results, (0..TASK_COUNT).collect::<Vec<_>>());
+
+    folo.stop();
+    folo.wait();
+}
+
+#[test]
+fn spawn_wake_batch_size_zero_is_invalid() {
+    let result = RuntimeBuilder::new().spawn_wake_batch_size(0).build();
+
+    assert!(matches!(result, Err(folo::io::Error::InvalidOptions(_))));
+}
+
+#[test]
+fn spawn_flush_interval_zero_is_invalid() {
+    let result = RuntimeBuilder::new()
+        .spawn_flush_interval(Duration::ZERO)
+        .build();
+
+    assert!(matches!(result, Err(folo::io::Error::InvalidOptions(_))));
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn high_priority_task_polled_first() {
+    let order = Rc::new(RefCell::new(Vec::new()));
+
+    // All the tasks become ready at the same time, as none of them can start before we yield.
+    let low_tasks = (0..20)
+        .map(|i| {
+            let order = Rc::clone(&order);
+            spawn_with_priority(TaskPriority::Low, async move {
+                order.borrow_mut().push(i);
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let high_task = spawn_with_priority(TaskPriority::High, {
+        let order = Rc::clone(&order);
+        async move {
+            order.borrow_mut().push(-1);
+        }
+    });
+
+    high_task.await.unwrap();
+
+    for task in low_tasks {
+        task.await.unwrap();
+    }
+
+    let order = order.borrow();
+    assert_eq!(order.len(), 21);
+    assert_eq!(order[0], -1);
+    assert!(order[1..].windows(2).all(|pair| pair[0] < pair[1]));
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn yielding_tasks_interleave() {
+    const ITERATIONS: usize = 10;
+
+    let order = Rc::new(RefCell::new(Vec::new()));
+
+    let tasks = (0..2)
+        .map(|id| {
+            let order = Rc::clone(&order);
+            spawn(async move {
+                for _ in 0..ITERATIONS {
+                    order.borrow_mut().push(id);
+                    yield_now().await;
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    let order = order.borrow();
+    assert_eq!(order.len(), 2 * ITERATIONS);
+
+    // Neither task may run all its iterations before the other one gets to start.
+    let first = |id| order.iter().position(|&x| x == id).unwrap();
+    let last = |id| order.iter().rposition(|&x| x == id).unwrap();
+    assert!(first(0) < last(1));
+    assert!(first(1) < last(0));
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn join_all_returns_outputs_in_input_order() {
+    const TASKS: usize = 100;
+
+    let completion_order = Rc::new(RefCell::new(Vec::new()));
+
+    // Later tasks sleep for less time, so they complete before earlier ones.
+    let tasks = (0..TASKS).map(|id| {
+        let completion_order = Rc::clone(&completion_order);
+
+        spawn(async move {
+            folo::time::sleep(Duration::from_millis(((TASKS - id) % 10 * 5) as u64)).await;
+            completion_order.borrow_mut().push(id);
+            id * 2
+        })
+    });
+
+    let results = join_all(tasks).await;
+
+    assert_eq!(results, (0..TASKS).map(|id| Ok(id * 2)).collect::<Vec<_>>());
+
+    let completion_order = completion_order.borrow();
+    assert_eq!(completion_order.len(), TASKS);
+    assert!(completion_order.windows(2).any(|pair| pair[0] > pair[1]));
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn current_task_count_includes_waiting_tasks() {
+    // This includes the current task.
+    let initial = current_task_count();
+    assert!(initial >= 1);
+
+    let (tx, rx) = futures::channel::oneshot::channel::<()>();
+    let rx = rx.shared();
+
+    let tasks = (0..3)
+        .map(|_| {
+            let rx = rx.clone();
+            spawn(async move {
+                _ = rx.await;
+            })
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(current_task_count(), initial + 3);
+
+    // Once started, the tasks remain live while waiting to be woken up.
+    yield_now().await;
+    assert_eq!(current_task_count(), initial + 3);
+
+    tx.send(()).unwrap();
+
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    assert_eq!(current_task_count(), initial);
+}
+
+/// Sets a flag when dropped, to detect that the future of a task has been dropped.
+struct DropFlag(Arc<AtomicBool>);
+
+impl Drop for DropFlag {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Release);
+    }
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn abort_local_task_drops_future() {
+    let dropped = Arc::new(AtomicBool::new(false));
+    let flag = DropFlag(Arc::clone(&dropped));
+
+    // The sender is kept alive, so the task would wait forever if not aborted.
+    let (_tx, rx) = futures::channel::oneshot::channel::<()>();
+
+    let handle = spawn(async move {
+        let _flag = flag;
+        _ = rx.await;
+    });
+
+    // Let the task start waiting.
+    yield_now().await;
+    assert!(!handle.is_finished());
+
+    handle.abort();
+
+    assert_eq!(handle.await, Err(Aborted));
+    assert!(dropped.load(Ordering::Acquire));
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn abort_after_completion_keeps_result() {
+    let handle = spawn(async { 42 });
+
+    while !handle.is_finished() {
+        yield_now().await;
+    }
+
+    handle.abort();
+
+    assert_eq!(handle.await, Ok(42));
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn abort_remote_task_drops_future() {
+    let dropped = Arc::new(AtomicBool::new(false));
+    let flag = DropFlag(Arc::clone(&dropped));
+
+    let handle = spawn_on_any(move || async move {
+        let _flag = flag;
+        futures::future::pending::<()>().await;
+    });
+
+    handle.abort();
+
+    assert_eq!(handle.await, Err(Aborted));
+    assert!(dropped.load(Ordering::Acquire));
+}
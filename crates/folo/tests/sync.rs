@@ -0,0 +1,173 @@
+use folo::{
+    rt::{spawn_on_any, yield_now},
+    sync::{Condvar, Mutex, Semaphore},
+};
+use folo_testing::init_test_worker;
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn condvar_consumer_receives_produced_items() {
+    let shared = Arc::new((Mutex::new(VecDeque::new()), Condvar::new()));
+
+    // The producer may end up on a different worker thread than the consumer.
+    let producer = spawn_on_any({
+        let shared = Arc::clone(&shared);
+
+        move || async move {
+            let (queue, condvar) = &*shared;
+
+            for item in 0..10 {
+                queue.lock().await.push_back(item);
+                condvar.notify_one();
+            }
+        }
+    });
+
+    let (queue, condvar) = &*shared;
+    let mut received = Vec::new();
+
+    while received.len() < 10 {
+        let mut guard = queue.lock().await;
+
+        while guard.is_empty() {
+            guard = condvar.wait(guard).await;
+        }
+
+        received.extend(guard.drain(..));
+    }
+
+    producer.await.unwrap();
+
+    assert_eq!(received, (0..10).collect::<Vec<_>>());
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn mutex_serializes_increments() {
+    const TASKS: usize = 8;
+    const INCREMENTS: usize = 100;
+
+    let counter = Arc::new(Mutex::new(0));
+
+    // The tasks may end up on different worker threads.
+    let tasks = (0..TASKS)
+        .map(|_| {
+            let counter = Arc::clone(&counter);
+
+            spawn_on_any(move || async move {
+                for _ in 0..INCREMENTS {
+                    let mut guard = counter.lock().await;
+                    let value = *guard;
+
+                    // Give the other tasks a chance to interfere while we hold the lock.
+                    yield_now().await;
+
+                    *guard = value + 1;
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    assert_eq!(*counter.lock().await, TASKS * INCREMENTS);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn semaphore_bounds_concurrency() {
+    const PERMITS: usize = 3;
+    const TASKS: usize = 10;
+
+    let semaphore = Arc::new(Semaphore::new(PERMITS));
+    let in_flight = Arc::new(AtomicUsize::new(0));
+    let max_in_flight = Arc::new(AtomicUsize::new(0));
+
+    // The tasks may end up on different worker threads.
+    let tasks = (0..TASKS)
+        .map(|_| {
+            let semaphore = Arc::clone(&semaphore);
+            let in_flight = Arc::clone(&in_flight);
+            let max_in_flight = Arc::clone(&max_in_flight);
+
+            spawn_on_any(move || async move {
+                let _permit = semaphore.acquire().await;
+
+                let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_flight.fetch_max(current, Ordering::SeqCst);
+
+                // Hold the permit for a while, so the excess tasks have to wait for it.
+                for _ in 0..10 {
+                    yield_now().await;
+                }
+
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    assert!(max_in_flight.load(Ordering::SeqCst) <= PERMITS);
+    assert_eq!(semaphore.available_permits(), PERMITS);
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn channel_delivers_values_from_std_thread() {
+    let (tx, rx) = folo::rt::channel();
+
+    let sender = std::thread::spawn(move || {
+        for item in 0..10 {
+            // Give the receiver a moment to start waiting, so it has to be woken up.
+            std::thread::sleep(std::time::Duration::from_millis(5));
+            tx.send(item).unwrap();
+        }
+    });
+
+    let mut received = Vec::new();
+
+    while let Some(item) = rx.recv().await {
+        received.push(item);
+    }
+
+    sender.join().unwrap();
+
+    assert_eq!(received, (0..10).collect::<Vec<_>>());
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn oneshot_delivers_value_from_std_thread() {
+    let (tx, rx) = folo::rt::oneshot::channel();
+
+    let sender = std::thread::spawn(move || {
+        // Give the receiver a moment to start waiting, so it has to be woken up.
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        tx.send(42).unwrap();
+    });
+
+    assert_eq!(rx.await, Ok(42));
+
+    sender.join().unwrap();
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn oneshot_dropped_sender_cancels_receiver() {
+    let (tx, rx) = folo::rt::oneshot::channel::<u32>();
+
+    let sender = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        drop(tx);
+    });
+
+    assert_eq!(rx.await, Err(folo::rt::oneshot::Canceled));
+
+    sender.join().unwrap();
+}
@@ -0,0 +1,95 @@
+use folo::{
+    rt::select,
+    time::{sleep, timeout, Elapsed},
+};
+use folo_testing::init_test_worker;
+use std::{
+    cell::Cell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn sleep_completes_after_duration() {
+    let start = Instant::now();
+
+    sleep(Duration::from_millis(50)).await;
+
+    let elapsed = start.elapsed();
+
+    // The OS may wake us up late, depending on its timer resolution and scheduling, but never early.
+    assert!(
+        elapsed >= Duration::from_millis(50),
+        "woke up after {elapsed:?}"
+    );
+    assert!(
+        elapsed < Duration::from_millis(500),
+        "woke up after {elapsed:?}"
+    );
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn sleep_zero_completes_immediately() {
+    sleep(Duration::ZERO).await;
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn timeout_returns_result_of_fast_future() {
+    let result = timeout(Duration::from_secs(60), async { 42 }).await;
+
+    assert_eq!(result, Ok(42));
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn timeout_drops_slow_future() {
+    let dropped = Rc::new(Cell::new(false));
+
+    let slow = {
+        let guard = DropFlag(Rc::clone(&dropped));
+
+        async move {
+            let _guard = guard;
+            sleep(Duration::from_secs(60)).await;
+        }
+    };
+
+    let result = timeout(Duration::from_millis(10), slow).await;
+
+    assert_eq!(result, Err(Elapsed));
+    assert!(dropped.get());
+}
+
+#[folo::test(worker_init_fn = init_test_worker)]
+async fn select_returns_fast_future_and_drops_slow_one() {
+    let dropped = Rc::new(Cell::new(false));
+
+    let slow = {
+        let guard = DropFlag(Rc::clone(&dropped));
+
+        async move {
+            let _guard = guard;
+            sleep(Duration::from_secs(60)).await;
+            "slow"
+        }
+    };
+
+    let fast = async {
+        sleep(Duration::from_millis(10)).await;
+        "fast"
+    };
+
+    let start = Instant::now();
+    let winner = select((slow, fast)).await;
+
+    assert_eq!(winner, "fast");
+    assert!(dropped.get());
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
+
+struct DropFlag(Rc<Cell<bool>>);
+
+impl Drop for DropFlag {
+    fn drop(&mut self) {
+        self.0.set(true);
+    }
+}
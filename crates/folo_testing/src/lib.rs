@@ -1,3 +1,5 @@
+mod temp_path;
 mod test_setup;
 
-pub use test_setup::*;
\ No newline at end of file
+pub use temp_path::*;
+pub use test_setup::*;
@@ -0,0 +1,7 @@
+use std::path::PathBuf;
+
+// A path in the temporary directory for a test to create a file or directory at. The process ID is
+// part of the path, so test binaries running at the same time do not step on each other's files.
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("folo_test_{}_{name}", std::process::id()))
+}